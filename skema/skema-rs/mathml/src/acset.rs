@@ -2,11 +2,16 @@
 //! JSON-serialized ACSets are the form of model exchange between TA1 and TA2.
 use crate::ast::operator::Operator;
 use crate::ast::{Ci, MathExpression, Mi, Type};
+use crate::parsers::canonical_form::dedup_by_identifier;
 use crate::parsers::first_order_ode::{get_terms, FirstOrderODE, PnTerm};
 use crate::parsers::math_expression_tree::MathExpressionTree;
+use crate::parsers::pde::{SpatialOperator, PDE};
+use crate::validation::AmrSchema;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
 use utoipa;
 use utoipa::ToSchema;
 
@@ -68,6 +73,7 @@ pub struct ACSet {
 // -------------------------------------------------------------------------------------------
 // The following data structs are those requested by TA-4 as an exchange format for the models.
 // the spec in json format can be found here: https://github.com/DARPA-ASKEM/Model-Representations/blob/main/petrinet/petrinet_schema.json
+// and is bundled in schemas/ for validation, see crate::validation.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 pub struct PetriNet {
     pub header: Header,
@@ -85,6 +91,17 @@ pub struct RegNet {
     pub metadata: Option<Metadata>,
 }
 
+// the spec in json format can be found here: https://github.com/DARPA-ASKEM/Model-Representations/blob/main/stockflow/stockflow_schema.json
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+pub struct StockFlow {
+    pub header: Header,
+    pub model: ModelStockFlow,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub semantics: Option<Semantics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
 #[derive(
     Debug,
     Default,
@@ -115,6 +132,14 @@ pub struct ModelRegNet {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<Vec<Parameter>>,
 }
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+pub struct ModelStockFlow {
+    pub stocks: BTreeSet<Stock>,
+    pub flows: BTreeSet<Flow>,
+    pub auxiliaries: BTreeSet<Auxiliary>,
+    pub links: BTreeSet<Link>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 pub struct ModelPetriNet {
     pub states: BTreeSet<State>,
@@ -139,7 +164,11 @@ pub struct ModelPetriNet {
     JsonSchema,
 )]
 pub struct Metadata {
+    #[serde(default)]
     pub placeholder: String, // once we finalize the metadata data struct fill in this data struct
+    /// Warnings about the model, e.g. inconsistent units
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 #[derive(
@@ -199,15 +228,15 @@ pub struct Ode {
     JsonSchema,
 )]
 pub struct Observable {
-    id: String,
+    pub id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<String>,
+    pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    states: Option<Vec<String>>,
+    pub states: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    expression: Option<String>,
+    pub expression: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    expression_mathml: Option<String>,
+    pub expression_mathml: Option<String>,
 }
 
 #[derive(
@@ -258,6 +287,98 @@ pub struct State {
     pub units: Option<Units>,
 }
 
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub struct Stock {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grounding: Option<Grounding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units: Option<Units>,
+}
+
+/// A flow between two stocks, or into or out of the model if it has no upstream or downstream
+/// stock.
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub struct Flow {
+    pub id: String,
+    pub name: String,
+    pub upstream_stock: Option<String>,
+    pub downstream_stock: Option<String>,
+    pub rate_expression: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_expression_mathml: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grounding: Option<Grounding>,
+}
+
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub struct Auxiliary {
+    pub id: String,
+    pub name: String,
+    pub expression: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expression_mathml: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grounding: Option<Grounding>,
+}
+
+/// A stock or auxiliary that the rate of a flow depends on.
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub struct Link {
+    pub id: String,
+    pub source: String,
+    pub target: String,
+}
+
 #[derive(
     Debug,
     Default,
@@ -459,7 +580,7 @@ pub struct Parameter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub value: Option<i64>,
+    pub value: Option<Float>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub grounding: Option<Grounding>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -482,9 +603,9 @@ pub struct Parameter {
     JsonSchema,
 )]
 pub struct Time {
-    id: String,
+    pub id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    units: Option<Units>,
+    pub units: Option<Units>,
 }
 
 #[derive(
@@ -503,7 +624,112 @@ pub struct Time {
 pub struct Distribution {
     #[serde(rename = "type")]
     pub r#type: String,
-    pub parameters: String,
+    pub parameters: DistributionParameters,
+}
+
+impl Distribution {
+    /// Uniform distribution between the bounds.
+    pub fn uniform(minimum: f64, maximum: f64) -> Distribution {
+        Distribution {
+            r#type: "StandardUniform1".to_string(),
+            parameters: DistributionParameters {
+                minimum: Some(minimum.into()),
+                maximum: Some(maximum.into()),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Normal distribution with the mean and standard deviation.
+    pub fn normal(mean: f64, stdev: f64) -> Distribution {
+        Distribution {
+            r#type: "Normal1".to_string(),
+            parameters: DistributionParameters {
+                mean: Some(mean.into()),
+                stdev: Some(stdev.into()),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The distribution truncated to the bounds, e.g. a normal distribution of a rate that is
+    /// positive.
+    pub fn bounded(mut self, minimum: Option<f64>, maximum: Option<f64>) -> Distribution {
+        self.parameters.minimum = minimum.map(Float);
+        self.parameters.maximum = maximum.map(Float);
+        self
+    }
+}
+
+/// Parameters of a distribution: the bounds of a uniform distribution, or the mean and standard
+/// deviation of a normal distribution and its bounds, if any.
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub struct DistributionParameters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<Float>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<Float>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean: Option<Float>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdev: Option<Float>,
+}
+
+/// A floating point number, e.g. the value of a parameter, which is serialized as a JSON number.
+/// Floats are totally ordered by [`f64::total_cmp`], so that the structs containing them can be
+/// compared and kept in sets.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(transparent)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl From<f64> for Float {
+    fn from(value: f64) -> Self {
+        Float(value)
+    }
+}
+
+impl From<Float> for f64 {
+    fn from(value: Float) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 // This is for the routing of mathml for various endpoints to extract the appropriate AMR
@@ -521,8 +747,53 @@ pub struct Distribution {
     JsonSchema,
 )]
 pub struct AMRmathml {
+    /// The AMR subtype: `petrinet`, `regnet` or `stockflow`
     pub model: String,
-    pub mathml: Vec<String>,
+    pub mathml: MathMLEquations,
+    /// Units of states, parameters and the independent variable, e.g. {"S": "persons", "t":
+    /// "days"}, from which the units of the other parameters are inferred and checked
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub units: BTreeMap<String, String>,
+}
+
+/// MathML for a system of equations, given either as a single document (e.g. with one equation
+/// per row of an `<mtable>`) or as an array with one document per equation.
+#[derive(
+    Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize, ToSchema, JsonSchema,
+)]
+#[serde(untagged)]
+pub enum MathMLEquations {
+    System(String),
+    Equations(Vec<String>),
+}
+
+impl Default for MathMLEquations {
+    fn default() -> Self {
+        MathMLEquations::Equations(Vec::new())
+    }
+}
+
+// This is for the routing of LaTeX for various endpoints to extract the appropriate AMR
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub struct AMRlatex {
+    /// The AMR subtype, as for [`AMRmathml`]
+    pub model: String,
+    pub latex: Vec<String>,
+    /// Units of states, parameters and the independent variable, as for [`AMRmathml`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub units: BTreeMap<String, String>,
 }
 
 // -------------------------------------------------------------------------------------------
@@ -567,11 +838,103 @@ pub struct GeneralSemantics {
     pub states: BTreeSet<State>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<Vec<Parameter>>,
+    /// Variables defined by algebraic equations of the model, e.g. N = S + I + R
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observables: Option<Vec<Observable>>,
+    /// Structure of the PDEs of the model, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pdes: Option<Vec<PDESemantics>>,
+}
+
+/// An independent variable of a PDE, with the highest order of the derivatives with respect to it.
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Clone,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub struct IndependentVariable {
+    pub id: String,
+    pub order: u8,
+}
+
+#[derive(
+    Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize, ToSchema, JsonSchema,
+)]
+pub struct PDESemantics {
+    /// The state (field) evolved by the PDE
+    pub state: String,
+    pub time: IndependentVariable,
+    pub spatial: Vec<IndependentVariable>,
+    pub spatial_operators: Vec<SpatialOperator>,
+}
+
+impl From<&PDE> for PDESemantics {
+    fn from(pde: &PDE) -> PDESemantics {
+        PDESemantics {
+            state: pde.lhs_var.to_string(),
+            time: IndependentVariable {
+                id: pde.with_respect_to.to_string(),
+                order: pde.order,
+            },
+            spatial: pde
+                .spatial_vars
+                .iter()
+                .map(|(var, order)| IndependentVariable {
+                    id: var.to_string(),
+                    order: *order,
+                })
+                .collect(),
+            spatial_operators: pde.spatial_operators.clone(),
+        }
+    }
 }
 
 // -------------------------------------------------------------------------------------------
 // This function takes our previous model form, the ACSet and transforms it to the new TA4 exchange format
 // -------------------------------------------------------------------------------------------
+impl From<Vec<FirstOrderODE>> for GeneralizedAMR {
+    fn from(ode_vec: Vec<FirstOrderODE>) -> GeneralizedAMR {
+        GeneralizedAMR::from(
+            ode_vec
+                .iter()
+                .map(FirstOrderODE::to_met)
+                .collect::<Vec<MathExpressionTree>>(),
+        )
+    }
+}
+
+impl From<Vec<PDE>> for GeneralizedAMR {
+    fn from(pde_vec: Vec<PDE>) -> GeneralizedAMR {
+        let mut gamr = GeneralizedAMR::from(pde_vec.iter().map(PDE::to_met).collect::<Vec<_>>());
+
+        // The parameters of the PDEs are not limited to the terms of a petri net
+        let mut param_str_vec: Vec<String> = pde_vec.iter().flat_map(PDE::parameters).collect();
+        dedup_by_identifier(&mut param_str_vec, String::as_str);
+        let parameter_vec = param_str_vec
+            .into_iter()
+            .map(|param| Parameter {
+                id: param.clone(),
+                name: Some(param),
+                ..Default::default()
+            })
+            .collect();
+
+        if let Some(semantics) = gamr.semantics.as_mut() {
+            semantics.parameters = Some(parameter_vec);
+            semantics.pdes = Some(pde_vec.iter().map(PDESemantics::from).collect());
+        }
+        gamr
+    }
+}
+
 impl From<Vec<MathExpressionTree>> for GeneralizedAMR {
     fn from(met_vec: Vec<MathExpressionTree>) -> GeneralizedAMR {
         let mut states_vec = BTreeSet::<State>::new();
@@ -594,8 +957,8 @@ impl From<Vec<MathExpressionTree>> for GeneralizedAMR {
                             states_vec.insert(state.clone());
                             rhs_vec.push(y[1].clone());
                         }
+                        // algebraic equations define observables, see `set_observables`
                         _ => {
-                            println!("Non-differential Equation");
                             rhs_vec.push(y[1].clone());
                         }
                     },
@@ -639,8 +1002,7 @@ impl From<Vec<MathExpressionTree>> for GeneralizedAMR {
         }
 
         // dedup the parameters vector
-        param_str_vec.sort();
-        param_str_vec.dedup();
+        dedup_by_identifier(&mut param_str_vec, String::as_str);
 
         // now to make the parameter vec from the strings
         for param in param_str_vec.iter() {
@@ -675,14 +1037,18 @@ impl From<Vec<MathExpressionTree>> for GeneralizedAMR {
         let semantics = GeneralSemantics {
             states: states_vec,
             parameters: Some(parameter_vec),
+            observables: None,
+            pdes: None,
         };
 
-        GeneralizedAMR {
+        let mut gamr = GeneralizedAMR {
             header,
             met: met_vec.clone(),
             semantics: Some(semantics),
             metadata: None,
-        }
+        };
+        gamr.set_observables();
+        gamr
     }
 }
 
@@ -705,11 +1071,16 @@ impl From<Vec<FirstOrderODE>> for PetriNet {
                 name: ode.lhs_var.to_string().clone(),
                 ..Default::default()
             };
+            // Initial values are named after their states, as for regulatory networks, until
+            // they are given values
+            let initial = format!("{}0", ode.lhs_var);
             let initials = Initial {
                 target: ode.lhs_var.to_string().clone(),
-                ..Default::default()
+                expression: initial.clone(),
+                expression_mathml: MathExpressionTree::Atom(MathExpression::Mi(Mi(initial)))
+                    .to_cmml(),
             };
-            /*let parameters = Parameter {
+            let parameters = Parameter {
                 id: initials.expression.clone(),
                 name: Some(initials.expression.clone()),
                 description: Some(format!(
@@ -717,8 +1088,8 @@ impl From<Vec<FirstOrderODE>> for PetriNet {
                     ode.lhs_var.to_string().clone()
                 )),
                 ..Default::default()
-            };*/
-            //parameter_vec.push(parameters.clone());
+            };
+            parameter_vec.push(parameters.clone());
             initial_vec.push(initials.clone());
             states_vec.insert(states.clone());
             state_string_list.push(ode.lhs_var.to_string().clone()); // used later for transition parsing
@@ -755,28 +1126,6 @@ impl From<Vec<FirstOrderODE>> for PetriNet {
             }
         }
 
-        // first is we need to replace any terms with more than 2 exp_states with subterms, these are simply
-        // terms that need to be distributed (ASSUMPTION, MOST A TRANSTION CAN HAVE IS 2 IN AND 2 OUT)
-        // but first we need to inherit the dynamic state to each sub term
-        /*let mut composite_term_ind = Vec::<usize>::new();
-        let mut sub_terms = Vec::<PnTerm>::new();
-        for (j, t) in terms.clone().iter().enumerate() {
-            if t.exp_states.len() > 2 && t.sub_terms.is_some() {
-                for (i, _sub_t) in t.sub_terms.clone().unwrap().iter().enumerate() {
-                    terms[j].sub_terms.as_mut().unwrap()[i].dyn_state = t.dyn_state.clone();
-                    sub_terms.push(terms[j].sub_terms.as_mut().unwrap()[i].clone());
-                }
-                composite_term_ind.push(j);
-            }
-        }
-        // delete composite terms we are replacing
-        composite_term_ind.sort();
-        for i in composite_term_ind.iter().rev() {
-            terms.remove(*i);
-        }
-        // replace with subterms
-        terms.append(&mut sub_terms);*/
-
         // now for polarity pairs of terms we need to construct the transistions
         let mut paired_term_indices = Vec::<usize>::new();
         let mut transition_pair = Vec::<(PnTerm, PnTerm)>::new();
@@ -795,55 +1144,25 @@ impl From<Vec<FirstOrderODE>> for PetriNet {
             }
         }
 
-        // delete paired terms from list
+        // delete paired terms from list, once even if they are part of several pairs
         paired_term_indices.sort();
+        paired_term_indices.dedup();
         for i in paired_term_indices.iter().rev() {
             terms.remove(*i);
         }
-        // Now we replace unpaired terms with subterms, by their subterms and repeat the process
-
-        // but first we need to inherit the dynamic state to each sub term
-        let mut composite_term_ind = Vec::<usize>::new();
-        let mut sub_terms = Vec::<PnTerm>::new();
-        for (j, t) in terms.clone().iter().enumerate() {
-            if t.sub_terms.is_some() {
-                for (i, _sub_t) in t.sub_terms.clone().unwrap().iter().enumerate() {
-                    terms[j].sub_terms.as_mut().unwrap()[i].dyn_state = t.dyn_state.clone();
-                    sub_terms.push(terms[j].sub_terms.as_mut().unwrap()[i].clone());
-                }
-                composite_term_ind.push(j);
-            }
-        }
 
-        // delete composite terms
-        composite_term_ind.sort();
-        for i in composite_term_ind.iter().rev() {
-            terms.remove(*i);
-        }
-
-        // replace with subterms
-        terms.append(&mut sub_terms);
-
-        // now we attempt to pair again and delete paired terms again
-        let mut paired_term_indices = Vec::<usize>::new();
-        for (i, term1) in terms.clone().iter().enumerate() {
-            for (j, term2) in terms.clone().iter().enumerate() {
-                if term1.polarity != term2.polarity
-                    && term1.parameters == term2.parameters
-                    && term1.polarity
-                    && term1.exp_states == term2.exp_states
-                {
-                    let temp_pair = (term1.clone(), term2.clone());
-                    transition_pair.push(temp_pair);
-                    paired_term_indices.push(i);
-                    paired_term_indices.push(j);
-                }
-            }
-        }
-        paired_term_indices.sort();
-        for i in paired_term_indices.iter().rev() {
-            terms.remove(*i);
-        }
+        // pairs with the same states and equivalent rates describe a single transition
+        let mut pair_keys = HashSet::<String>::new();
+        transition_pair.retain(|(positive, negative)| {
+            let rate = match &positive.math_vec {
+                Some(math) => math.canonical_key(),
+                None => positive.expression.clone(),
+            };
+            pair_keys.insert(format!(
+                "{} {} {}",
+                positive.dyn_state, negative.dyn_state, rate
+            ))
+        });
 
         // now we construct transitions of all paired terms
         for (i, t) in transition_pair.iter().enumerate() {
@@ -938,7 +1257,8 @@ impl From<Vec<FirstOrderODE>> for PetriNet {
                     let transitions = Transition {
                         id: format!("s{}", i),
                         input: Some(input.clone()),
-                        output: None,
+                        // The schema requires outputs, even for sinks
+                        output: Some(vec![]),
                         ..Default::default()
                     };
                     transitions_vec.insert(transitions.clone());
@@ -955,8 +1275,7 @@ impl From<Vec<FirstOrderODE>> for PetriNet {
 
         // trim duplicate parameters and (TODO)remove integer parameters
 
-        parameter_vec.sort();
-        parameter_vec.dedup();
+        dedup_by_identifier(&mut parameter_vec, |param| param.id.as_str());
 
         // now to trim the numbers from the parameters field
         let mut nums = Vec::<usize>::new();
@@ -988,7 +1307,7 @@ impl From<Vec<FirstOrderODE>> for PetriNet {
 
         let header = Header {
             name: "mathml model".to_string(),
-            schema: "https://github.com/DARPA-ASKEM/Model-Representations/blob/main/petrinet/petrinet_schema.json".to_string(),
+            schema: AmrSchema::PetriNet.url().to_string(),
             schema_name: "PetriNet".to_string(),
             description: "This is a model from equations".to_string(),
             model_version: "0.1".to_string(),
@@ -1220,8 +1539,7 @@ impl From<Vec<FirstOrderODE>> for RegNet {
             }
         }
 
-        parameter_vec.sort();
-        parameter_vec.dedup();
+        dedup_by_identifier(&mut parameter_vec, |param| param.id.as_str());
 
         // now to trim the numbers from the parameters field
         let mut nums = Vec::<usize>::new();
@@ -1245,7 +1563,7 @@ impl From<Vec<FirstOrderODE>> for RegNet {
 
         let header = Header {
             name: "Regnet mathml model".to_string(),
-            schema: "https://raw.githubusercontent.com/DARPA-ASKEM/Model-Representations/regnet_v0.1/regnet/regnet_schema.json".to_string(),
+            schema: AmrSchema::RegNet.url().to_string(),
             schema_name: "regnet".to_string(),
             description: "This is a Regnet model from mathml equations".to_string(),
             model_version: "0.1".to_string(),
@@ -1258,3 +1576,158 @@ impl From<Vec<FirstOrderODE>> for RegNet {
         }
     }
 }
+
+/// A rate expression without the outer parentheses of its infix form.
+fn rate_expression(term: &PnTerm) -> String {
+    let infix = term.expression_infix.as_str();
+    infix
+        .strip_prefix('(')
+        .and_then(|infix| infix.strip_suffix(')'))
+        .unwrap_or(infix)
+        .to_string()
+}
+
+// This impl will take a vector of FirstOrderODE and return the stock and flow model for it. Each
+// term of an ODE is an inflow or an outflow of its stock, and an outflow of one stock with the
+// same rate as an inflow of another is a single flow between them. The parameters become
+// auxiliaries, which are linked to the flows whose rates depend on them, as are the stocks.
+impl From<Vec<FirstOrderODE>> for StockFlow {
+    fn from(ode_vec: Vec<FirstOrderODE>) -> StockFlow {
+        let stock_ids: Vec<String> = ode_vec.iter().map(|ode| ode.lhs_var.to_string()).collect();
+        let terms: Vec<PnTerm> = ode_vec
+            .iter()
+            .flat_map(|ode| get_terms(stock_ids.clone(), ode.clone()))
+            .collect();
+        let rate_key = |term: &PnTerm| match &term.math_vec {
+            Some(math) => math.canonical_key(),
+            None => term.expression.clone(),
+        };
+
+        // pair every outflow with an inflow of the same rate into another stock, each inflow at
+        // most once, so that the flows add up to the ODEs
+        let mut paired = vec![false; terms.len()];
+        let mut flow_terms = Vec::<(Option<String>, Option<String>, &PnTerm)>::new();
+        for (i, outflow) in terms.iter().enumerate() {
+            if outflow.polarity {
+                continue;
+            }
+            let inflow = (0..terms.len()).find(|&j| {
+                !paired[j]
+                    && terms[j].polarity
+                    && terms[j].dyn_state != outflow.dyn_state
+                    && rate_key(&terms[j]) == rate_key(outflow)
+            });
+            paired[i] = true;
+            if let Some(j) = inflow {
+                paired[j] = true;
+            }
+            flow_terms.push((
+                Some(outflow.dyn_state.clone()),
+                inflow.map(|j| terms[j].dyn_state.clone()),
+                outflow,
+            ));
+        }
+        for (j, inflow) in terms.iter().enumerate() {
+            if !paired[j] {
+                flow_terms.push((None, Some(inflow.dyn_state.clone()), inflow));
+            }
+        }
+
+        let mut parameter_ids: Vec<String> = terms
+            .iter()
+            .flat_map(|term| term.parameters.iter().cloned())
+            .filter(|param| param.parse::<f32>().is_err())
+            .collect();
+        dedup_by_identifier(&mut parameter_ids, String::as_str);
+
+        let mut flows = BTreeSet::<Flow>::new();
+        let mut links = BTreeSet::<Link>::new();
+        for (i, (upstream_stock, downstream_stock, term)) in flow_terms.into_iter().enumerate() {
+            let flow = Flow {
+                id: format!("f{}", i),
+                name: format!("f{}", i),
+                upstream_stock,
+                downstream_stock,
+                rate_expression: rate_expression(term),
+                rate_expression_mathml: Some(term.expression.clone()),
+                grounding: None,
+            };
+            let mut sources = term.exp_states.clone();
+            sources.dedup();
+            sources.extend(
+                term.parameters
+                    .iter()
+                    .filter(|param| parameter_ids.contains(param))
+                    .cloned(),
+            );
+            for source in sources {
+                links.insert(Link {
+                    id: format!("l{}", links.len()),
+                    source,
+                    target: flow.id.clone(),
+                });
+            }
+            flows.insert(flow);
+        }
+
+        let stocks = stock_ids
+            .iter()
+            .map(|id| Stock {
+                id: id.clone(),
+                name: id.clone(),
+                ..Default::default()
+            })
+            .collect();
+        let auxiliaries = parameter_ids
+            .iter()
+            .map(|id| Auxiliary {
+                id: id.clone(),
+                name: id.clone(),
+                expression: id.clone(),
+                ..Default::default()
+            })
+            .collect();
+        let ode = Ode {
+            initials: Some(
+                stock_ids
+                    .iter()
+                    .map(|id| Initial {
+                        target: id.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            parameters: Some(
+                parameter_ids
+                    .iter()
+                    .map(|id| Parameter {
+                        id: id.clone(),
+                        name: Some(id.clone()),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+
+        let header = Header {
+            name: "mathml model".to_string(),
+            schema: AmrSchema::StockFlow.url().to_string(),
+            schema_name: "stockflow".to_string(),
+            description: "This is a stock and flow model from equations".to_string(),
+            model_version: "0.1".to_string(),
+        };
+
+        StockFlow {
+            header,
+            model: ModelStockFlow {
+                stocks,
+                flows,
+                auxiliaries,
+                links,
+            },
+            semantics: Some(Semantics { ode }),
+            metadata: None,
+        }
+    }
+}
@@ -204,6 +204,29 @@ pub enum MathExpression {
     DownArrow(DownArrow),
     Minimize(Box<MathExpression>, Vec<MathExpression>),
     ExpMath(ExpMath),
+    /// Represents fenced expressions where first argument is the opening fence, second argument is the closing fence and third argument is the fenced elements
+    Mfenced(String, String, Vec<MathExpression>),
+    /// Represents roots where first argument is the base and second argument is the index
+    Mroot(Box<MathExpression>, Box<MathExpression>),
+    /// Represents prescripts and tensor indices where first argument is the base, second argument is the postscripts and third argument is the prescripts.
+    /// Scripts alternate between subscripts and superscripts, with missing scripts (`<none/>`) represented by `MathExpression::None`
+    Mmultiscripts(
+        Box<MathExpression>,
+        Vec<MathExpression>,
+        Vec<MathExpression>,
+    ),
+    /// Represents enclosed expressions where first argument is the notation (e.g. box, radical) and second argument is the enclosed elements
+    Menclose(String, Vec<MathExpression>),
+    /// Represents elements with adjusted spacing around them
+    Mpadded(Vec<MathExpression>),
+    /// Represents invisible elements that only take up space
+    Mphantom(Vec<MathExpression>),
+    /// Represents annotated expressions where first argument is the presentation and second argument is the contents of the annotations
+    Semantics(Box<MathExpression>, Vec<String>),
+    /// Represents tables (e.g. matrices) as rows of cells, each holding a list of elements. Labels of labeled rows are dropped.
+    Mtable(Vec<Vec<Vec<MathExpression>>>),
+    /// Represents piecewise functions (a left brace followed by a table) as the rows of cells of the table, where each row holds a value and the condition under which it holds
+    Piecewise(Vec<Vec<Vec<MathExpression>>>),
     #[default]
     None,
 }
@@ -286,6 +309,43 @@ impl fmt::Display for MathExpression {
                 }
                 Ok(())
             }
+            MathExpression::Mroot(base, index) => write!(f, "root_{{{index}}}({base})"),
+            MathExpression::Mfenced(open, close, elements) => {
+                write!(f, "{open}")?;
+                for (i, e) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", e)?;
+                }
+                write!(f, "{close}")
+            }
+            MathExpression::Mmultiscripts(base, postscripts, prescripts) => {
+                let write_scripts = |f: &mut fmt::Formatter<'_>, scripts: &[MathExpression]| {
+                    for pair in scripts.chunks(2) {
+                        for (script, marker) in pair.iter().zip(["_", "^"]) {
+                            if *script != MathExpression::None {
+                                write!(f, "{marker}{{{script}}}")?;
+                            }
+                        }
+                    }
+                    Ok(())
+                };
+                if !prescripts.is_empty() {
+                    write!(f, "{{}}")?;
+                    write_scripts(f, prescripts)?;
+                }
+                write!(f, "{base}")?;
+                write_scripts(f, postscripts)
+            }
+            MathExpression::Menclose(_, elements) | MathExpression::Mpadded(elements) => {
+                for e in elements {
+                    write!(f, "{}", e)?;
+                }
+                Ok(())
+            }
+            MathExpression::Mphantom(_) => Ok(()),
+            MathExpression::Semantics(presentation, _) => write!(f, "{presentation}"),
             expression => write!(f, "{expression:?}"),
         }
     }
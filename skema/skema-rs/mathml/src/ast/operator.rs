@@ -231,6 +231,37 @@ pub enum Operator {
     Logarithm(Logarithm),
     /// Minimum operator
     Min,
+    /// Matrix, whose arguments are its rows
+    Matrix,
+    /// Row of a matrix, whose arguments are its entries
+    MatrixRow,
+    /// Matrix transpose, e.g. Aᵀ
+    Transpose,
+    /// Matrix inverse, e.g. A⁻¹
+    Inverse,
+    /// Matrix multiplication
+    MatMul,
+    /// Elementwise (Hadamard) product, e.g. A ⊙ B
+    Hadamard,
+    /// Component of a matrix or vector, whose arguments are the matrix followed by the indices,
+    /// e.g. A_{ij}
+    Index,
+    /// Less-than relation
+    Less,
+    /// Less-than-or-equal relation
+    LessEqual,
+    /// Greater-than relation
+    Greater,
+    /// Greater-than-or-equal relation
+    GreaterEqual,
+    /// Inequality relation
+    NotEqual,
+    /// Piecewise function, whose arguments are its pieces followed by an optional otherwise clause
+    Piecewise,
+    /// Piece of a piecewise function, whose arguments are a value and the condition under which it holds
+    Piece,
+    /// Value of a piecewise function when none of the conditions of its pieces hold
+    Otherwise,
     // Catchall for operators we haven't explicitly defined as enum variants yet.
     Other(String),
 }
@@ -312,6 +343,21 @@ impl fmt::Display for Operator {
                 write!(f, "SurfaceInt")
             }
             Operator::Min => write!(f, "Min"),
+            Operator::Matrix => write!(f, "Matrix"),
+            Operator::MatrixRow => write!(f, "Row"),
+            Operator::Transpose => write!(f, "Transpose"),
+            Operator::Inverse => write!(f, "Inverse"),
+            Operator::MatMul => write!(f, "MatMul"),
+            Operator::Hadamard => write!(f, "⊙"),
+            Operator::Index => write!(f, "Index"),
+            Operator::Less => write!(f, "<"),
+            Operator::LessEqual => write!(f, "≤"),
+            Operator::Greater => write!(f, ">"),
+            Operator::GreaterEqual => write!(f, "≥"),
+            Operator::NotEqual => write!(f, "≠"),
+            Operator::Piecewise => write!(f, "Piecewise"),
+            Operator::Piece => write!(f, "Piece"),
+            Operator::Otherwise => write!(f, "Otherwise"),
             Operator::Comma => write!(f, ","),
             Operator::Logarithm(Logarithm { notation }) => match notation {
                 LogarithmNotation::Ln => write!(f, "Ln"),
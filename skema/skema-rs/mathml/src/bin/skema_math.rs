@@ -0,0 +1,162 @@
+//! Program to work with the models extracted from MathML
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use mathml::{
+    acset::PetriNet,
+    codegen::Language,
+    parsers::higher_order_ode::{reduce_to_first_order, HigherOrderODE},
+    simulation::{OdeSystem, SimulationConfig},
+};
+use std::{convert::TryFrom, process::exit};
+
+#[derive(Debug, Clone, ValueEnum, Default)]
+enum ModelFormat {
+    /// A Petri net AMR
+    #[default]
+    Petrinet,
+    /// A JSON array of MathML strings with one ODE each
+    Mathml,
+}
+
+#[derive(Debug, Clone, ValueEnum, Default)]
+enum TrajectoryFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Args, Debug)]
+struct SimulateArgs {
+    /// Path to input file containing the model
+    input: String,
+
+    /// Path to a JSON file with the simulation configuration (time span, step, method,
+    /// parameters and initials)
+    #[arg(long)]
+    config: String,
+
+    /// Input format
+    #[clap(long, value_enum, default_value_t = ModelFormat::Petrinet)]
+    model: ModelFormat,
+
+    /// Output format
+    #[clap(long, value_enum, default_value_t = TrajectoryFormat::Json)]
+    format: TrajectoryFormat,
+}
+
+#[derive(Debug, Clone, ValueEnum, Default)]
+enum CodeLanguage {
+    /// A Python module with `rhs(t, y, params)` for `scipy.integrate.solve_ivp`
+    #[default]
+    Python,
+    /// A Rust function `rhs(t, y, params)`
+    Rust,
+    /// A Julia function `rhs!(du, u, p, t)` for DifferentialEquations.jl
+    Julia,
+}
+
+impl From<&CodeLanguage> for Language {
+    fn from(language: &CodeLanguage) -> Self {
+        match language {
+            CodeLanguage::Python => Language::Python,
+            CodeLanguage::Rust => Language::Rust,
+            CodeLanguage::Julia => Language::Julia,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct CodegenArgs {
+    /// Path to input file containing the model
+    input: String,
+
+    /// Input format
+    #[clap(long, value_enum, default_value_t = ModelFormat::Petrinet)]
+    model: ModelFormat,
+
+    /// Target language
+    #[clap(long, value_enum, default_value_t = CodeLanguage::Python)]
+    language: CodeLanguage,
+
+    /// Print the code together with the order of its states and parameters as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Simulate a model with RK4 or RK45 and print its trajectory
+    Simulate(SimulateArgs),
+    /// Generate the right hand side function of a model in Python, Rust or Julia
+    Codegen(CodegenArgs),
+}
+
+#[derive(Parser, Debug)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn read(path: &str) -> String {
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|err| fail(format!("Unable to read file {path}: {err}")))
+}
+
+/// Print an error and exit with a non-zero status.
+fn fail(message: String) -> ! {
+    eprintln!("{message}");
+    exit(1)
+}
+
+/// Read a model as an ODE system.
+fn read_system(path: &str, model: &ModelFormat) -> OdeSystem {
+    let input = read(path);
+    match model {
+        ModelFormat::Petrinet => {
+            let petri_net: PetriNet = serde_json::from_str(&input)
+                .unwrap_or_else(|err| fail(format!("Invalid Petri net: {err}")));
+            OdeSystem::try_from(&petri_net).unwrap_or_else(|err| fail(err.to_string()))
+        }
+        ModelFormat::Mathml => {
+            let equations: Vec<String> = serde_json::from_str(&input)
+                .unwrap_or_else(|err| fail(format!("Invalid array of MathML strings: {err}")));
+            let odes = equations
+                .iter()
+                .map(|x| x.parse::<HigherOrderODE>())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_else(|err| fail(err.to_string()));
+            OdeSystem::from(reduce_to_first_order(odes).as_slice())
+        }
+    }
+}
+
+fn simulate(args: &SimulateArgs) {
+    let config: SimulationConfig = serde_json::from_str(&read(&args.config))
+        .unwrap_or_else(|err| fail(format!("Invalid simulation configuration: {err}")));
+    let system = read_system(&args.input, &args.model);
+    let trajectory = system
+        .simulate(&config)
+        .unwrap_or_else(|err| fail(err.to_string()));
+    match args.format {
+        TrajectoryFormat::Json => println!("{}", serde_json::to_string(&trajectory).unwrap()),
+        TrajectoryFormat::Csv => print!("{}", trajectory.to_csv(&system.time)),
+    }
+}
+
+fn codegen(args: &CodegenArgs) {
+    let generated = read_system(&args.input, &args.model)
+        .generate((&args.language).into())
+        .unwrap_or_else(|err| fail(err.to_string()));
+    if args.json {
+        println!("{}", serde_json::to_string(&generated).unwrap());
+    } else {
+        print!("{}", generated.code);
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    match &cli.command {
+        Command::Simulate(args) => simulate(args),
+        Command::Codegen(args) => codegen(args),
+    }
+}
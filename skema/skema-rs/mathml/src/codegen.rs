@@ -0,0 +1,274 @@
+//! Generation of executable code from extracted ODE systems, for reproducing a model outside of
+//! SKEMA. Each language gets a right hand side function taking the time, the states and the
+//! parameters, with the states and parameters in the order listed by [`GeneratedCode`]:
+//!
+//! - Python: `rhs(t, y, params)`, ready for `scipy.integrate.solve_ivp`
+//! - Rust: `rhs(t: f64, y: &[f64], params: &[f64]) -> Vec<f64>`
+//! - Julia: `rhs!(du, u, p, t)`, in place as expected by DifferentialEquations.jl
+
+use crate::{
+    parsers::printing::{identifier_name, system_parameters, PrintError, Syntax},
+    simulation::OdeSystem,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Target language of generated code.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Hash, Deserialize, Serialize, ToSchema, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Python,
+    Rust,
+    Julia,
+}
+
+impl Language {
+    fn syntax(self) -> Syntax {
+        match self {
+            Language::Python => Syntax::Python,
+            Language::Rust => Syntax::Rust,
+            Language::Julia => Syntax::Julia,
+        }
+    }
+}
+
+/// Generated source code with the order in which its function expects the states and
+/// parameters.
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize, ToSchema, JsonSchema)]
+pub struct GeneratedCode {
+    pub language: Language,
+    pub code: String,
+    /// The independent variable.
+    pub time: String,
+    /// The states, in the order of the state vector.
+    pub states: Vec<String>,
+    /// The parameters, in the order of the parameter vector.
+    pub parameters: Vec<String>,
+}
+
+/// A list of quoted names, e.g. ["S", "I", "R"].
+fn quoted(names: &[String]) -> String {
+    let names: Vec<String> = names.iter().map(|name| format!("{name:?}")).collect();
+    format!("[{}]", names.join(", "))
+}
+
+impl OdeSystem {
+    /// Generate a right hand side function for the system in the given language. Parameters are
+    /// the identifiers of the derivatives that are neither states nor the independent variable,
+    /// in order of first appearance. Fails if a derivative contains an operator that has no
+    /// counterpart in the language, e.g. a matrix product in Rust.
+    pub fn generate(&self, language: Language) -> Result<GeneratedCode, PrintError> {
+        let syntax = language.syntax();
+        let parameters = system_parameters(&self.states, &self.time, self.derivatives.iter());
+        let name = |name: &String| identifier_name(name, syntax);
+        let names = |names: &[String]| names.iter().map(name).collect::<Vec<_>>();
+        let time = name(&self.time);
+        let derivatives: Vec<String> = self
+            .derivatives
+            .iter()
+            .map(|derivative| derivative.print(syntax))
+            .collect::<Result<_, _>>()?;
+
+        let mut lines = vec![];
+        match language {
+            Language::Python => {
+                lines.push(format!(
+                    "\"\"\"Right hand side of an ODE system in {} for scipy.integrate.solve_ivp.\"\"\"",
+                    self.time
+                ));
+                lines.push(String::new());
+                lines.push("import math".to_string());
+                lines.push(String::new());
+                lines.push("import numpy as np".to_string());
+                lines.push(String::new());
+                lines.push(format!("STATES = {}", quoted(&self.states)));
+                lines.push(format!("PARAMETERS = {}", quoted(&parameters)));
+                lines.push(String::new());
+                lines.push(String::new());
+                lines.push("def rhs(t, y, params):".to_string());
+                if time != "t" {
+                    lines.push(format!("    {time} = t"));
+                }
+                if !self.states.is_empty() {
+                    lines.push(format!("    {}, = y", names(&self.states).join(", ")));
+                }
+                if !parameters.is_empty() {
+                    lines.push(format!("    {}, = params", names(&parameters).join(", ")));
+                }
+                lines.push("    return [".to_string());
+                for derivative in derivatives {
+                    lines.push(format!("        {derivative},"));
+                }
+                lines.push("    ]".to_string());
+            }
+            Language::Rust => {
+                lines.push(format!(
+                    "//! Right hand side of an ODE system in {}.",
+                    self.time
+                ));
+                lines.push(String::new());
+                lines.push(format!(
+                    "pub const STATES: [&str; {}] = {};",
+                    self.states.len(),
+                    quoted(&self.states)
+                ));
+                lines.push(format!(
+                    "pub const PARAMETERS: [&str; {}] = {};",
+                    parameters.len(),
+                    quoted(&parameters)
+                ));
+                lines.push(String::new());
+                lines.push("#[allow(non_snake_case, unused_variables, clippy::all)]".to_string());
+                lines.push(
+                    "pub fn rhs(t: f64, y: &[f64], params: &[f64]) -> Vec<f64> {".to_string(),
+                );
+                if time != "t" {
+                    lines.push(format!("    let {time} = t;"));
+                }
+                for (i, state) in names(&self.states).iter().enumerate() {
+                    lines.push(format!("    let {state} = y[{i}];"));
+                }
+                for (i, parameter) in names(&parameters).iter().enumerate() {
+                    lines.push(format!("    let {parameter} = params[{i}];"));
+                }
+                lines.push("    vec![".to_string());
+                for derivative in derivatives {
+                    lines.push(format!("        {derivative},"));
+                }
+                lines.push("    ]".to_string());
+                lines.push("}".to_string());
+            }
+            Language::Julia => {
+                lines.push(format!(
+                    "# Right hand side of an ODE system in {}.",
+                    self.time
+                ));
+                lines.push(String::new());
+                lines.push(format!("const STATES = {}", quoted(&self.states)));
+                lines.push(format!("const PARAMETERS = {}", quoted(&parameters)));
+                lines.push(String::new());
+                lines.push("function rhs!(du, u, p, t)".to_string());
+                if time != "t" {
+                    lines.push(format!("    {time} = t"));
+                }
+                if !self.states.is_empty() {
+                    lines.push(format!("    {}, = u", names(&self.states).join(", ")));
+                }
+                if !parameters.is_empty() {
+                    lines.push(format!("    {}, = p", names(&parameters).join(", ")));
+                }
+                for (i, derivative) in derivatives.iter().enumerate() {
+                    lines.push(format!("    du[{}] = {}", i + 1, derivative));
+                }
+                lines.push("    return nothing".to_string());
+                lines.push("end".to_string());
+            }
+        }
+        lines.push(String::new());
+
+        Ok(GeneratedCode {
+            language,
+            code: lines.join("\n"),
+            time: self.time.clone(),
+            states: self.states.clone(),
+            parameters,
+        })
+    }
+}
+
+#[cfg(test)]
+fn sir() -> OdeSystem {
+    use crate::parsers::first_order_ode::FirstOrderODE;
+    let odes: Vec<FirstOrderODE> = [
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>",
+    ]
+    .iter()
+    .map(|input| input.parse::<FirstOrderODE>().unwrap())
+    .collect();
+    OdeSystem::from(odes.as_slice())
+}
+
+#[test]
+fn test_python() {
+    let generated = sir().generate(Language::Python).unwrap();
+    assert_eq!(generated.states, vec!["S", "I", "R"]);
+    assert_eq!(generated.parameters, vec!["β", "γ"]);
+    assert_eq!(
+        generated.code,
+        r#""""Right hand side of an ODE system in t for scipy.integrate.solve_ivp."""
+
+import math
+
+import numpy as np
+
+STATES = ["S", "I", "R"]
+PARAMETERS = ["β", "γ"]
+
+
+def rhs(t, y, params):
+    S, I, R, = y
+    β, γ, = params
+    return [
+        -β*S*I,
+        β*S*I - γ*I,
+        γ*I,
+    ]
+"#
+    );
+}
+
+#[test]
+fn test_rust_and_julia() {
+    let generated = sir().generate(Language::Rust).unwrap();
+    assert!(generated
+        .code
+        .contains("pub fn rhs(t: f64, y: &[f64], params: &[f64]) -> Vec<f64> {"));
+    assert!(generated.code.contains("    let γ = params[1];\n"));
+    assert!(generated.code.contains("        β*S*I - γ*I,\n"));
+
+    let generated = sir().generate(Language::Julia).unwrap();
+    assert!(generated
+        .code
+        .contains("function rhs!(du, u, p, t)\n    S, I, R, = u\n"));
+    assert!(generated.code.contains("    du[3] = γ*I\n"));
+}
+
+#[test]
+fn test_petri_net_and_numeric_literals() {
+    use crate::{acset::PetriNet, parsers::math_expression_tree::MathExpressionTree};
+    use std::convert::TryFrom;
+    let petri_net = PetriNet::from(
+        [
+            "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+            "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        ]
+        .iter()
+        .map(|input| input.parse().unwrap())
+        .collect::<Vec<_>>(),
+    );
+    let generated = OdeSystem::try_from(&petri_net)
+        .unwrap()
+        .generate(Language::Python)
+        .unwrap();
+    assert_eq!(generated.states, vec!["I", "S"]);
+    assert_eq!(generated.parameters, vec!["β"]);
+    assert!(generated.code.contains("    I, S, = y\n"));
+
+    let expression = "<math><mn>2</mn><msup><mi>x</mi><mn>3</mn></msup><mo>+</mo><msqrt><mi>x</mi></msqrt></math>"
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    assert_eq!(
+        expression.print(Syntax::Rust).unwrap(),
+        "2.0*x.powi(3) + x.sqrt()"
+    );
+    assert_eq!(
+        expression.print(Syntax::Python).unwrap(),
+        "2*x**3 + math.sqrt(x)"
+    );
+}
@@ -0,0 +1,193 @@
+//! Differences between Petri net AMRs, e.g. between a model extracted from a paper and one
+//! extracted from code. Models are compared in canonical form: states and parameters match
+//! regardless of how their subscripts are written, and transitions match if they have the same
+//! inputs and outputs and equivalent rates, regardless of their ids.
+
+use crate::{
+    acset::{PetriNet, Transition},
+    parsers::{canonical_form::identifier_key, math_expression_tree::MathExpressionTree},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A transition as compared between models, with its states and rate in canonical form.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Deserialize, Serialize, ToSchema, JsonSchema,
+)]
+pub struct TransitionSummary {
+    pub input: Vec<String>,
+    pub output: Vec<String>,
+    /// Canonical key of the rate law, if the model has one for the transition.
+    pub rate: Option<String>,
+}
+
+/// What has to be added to and removed from one model to get another.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize, Serialize, ToSchema, JsonSchema)]
+pub struct ModelDiff {
+    pub added_states: Vec<String>,
+    pub removed_states: Vec<String>,
+    pub added_parameters: Vec<String>,
+    pub removed_parameters: Vec<String>,
+    pub added_transitions: Vec<TransitionSummary>,
+    pub removed_transitions: Vec<TransitionSummary>,
+}
+
+impl ModelDiff {
+    /// Whether the models are equivalent.
+    pub fn is_empty(&self) -> bool {
+        *self == ModelDiff::default()
+    }
+}
+
+/// The elements of `new` that are not in `old`, and those of `old` that are not in `new`,
+/// counting repeated elements.
+fn difference<T: Ord + Clone>(old: &[T], new: &[T]) -> (Vec<T>, Vec<T>) {
+    let mut removed = old.to_vec();
+    let mut added = vec![];
+    for item in new {
+        match removed.iter().position(|other| other == item) {
+            Some(i) => {
+                removed.remove(i);
+            }
+            None => added.push(item.clone()),
+        }
+    }
+    added.sort();
+    removed.sort();
+    (added, removed)
+}
+
+fn rate_key(petri_net: &PetriNet, transition: &Transition) -> Option<String> {
+    let rate = petri_net
+        .semantics
+        .as_ref()?
+        .ode
+        .rates
+        .as_ref()?
+        .iter()
+        .find(|rate| rate.target == transition.id)?;
+    let math = rate
+        .expression_mathml
+        .as_ref()
+        .and_then(|mathml| MathExpressionTree::from_cmml(mathml).ok());
+    Some(match math {
+        Some(math) => math.canonical_key(),
+        None => rate.expression.clone(),
+    })
+}
+
+fn states(states: &Option<Vec<String>>) -> Vec<String> {
+    let mut states: Vec<String> = states
+        .iter()
+        .flatten()
+        .map(|state| identifier_key(state))
+        .collect();
+    states.sort();
+    states
+}
+
+impl PetriNet {
+    fn state_keys(&self) -> Vec<String> {
+        self.model
+            .states
+            .iter()
+            .map(|state| identifier_key(&state.id))
+            .collect()
+    }
+
+    fn parameter_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .semantics
+            .iter()
+            .flat_map(|semantics| semantics.ode.parameters.iter().flatten())
+            .map(|parameter| identifier_key(&parameter.id))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    fn transition_summaries(&self) -> Vec<TransitionSummary> {
+        self.model
+            .transitions
+            .iter()
+            .map(|transition| TransitionSummary {
+                input: states(&transition.input),
+                output: states(&transition.output),
+                rate: rate_key(self, transition),
+            })
+            .collect()
+    }
+
+    /// The changes that turn this model into `other`.
+    pub fn diff(&self, other: &PetriNet) -> ModelDiff {
+        let (added_states, removed_states) = difference(&self.state_keys(), &other.state_keys());
+        let (added_parameters, removed_parameters) =
+            difference(&self.parameter_keys(), &other.parameter_keys());
+        let (added_transitions, removed_transitions) =
+            difference(&self.transition_summaries(), &other.transition_summaries());
+        ModelDiff {
+            added_states,
+            removed_states,
+            added_parameters,
+            removed_parameters,
+            added_transitions,
+            removed_transitions,
+        }
+    }
+}
+
+#[cfg(test)]
+fn sir(infection: &str, recovery: &str) -> PetriNet {
+    use crate::parsers::first_order_ode::FirstOrderODE;
+    let odes: Vec<FirstOrderODE> = [
+        format!("<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo>{infection}</math>"),
+        format!("<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo>{infection}<mo>-</mo>{recovery}</math>"),
+        format!("<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo>{recovery}</math>"),
+    ]
+    .iter()
+    .map(|input| input.parse::<FirstOrderODE>().unwrap())
+    .collect();
+    PetriNet::from(odes)
+}
+
+#[test]
+fn test_equivalent_models() {
+    let paper = sir(
+        "<mi>β</mi><mi>S</mi><mi>I</mi>",
+        "<msub><mi>γ</mi><mi>R</mi></msub><mi>I</mi>",
+    );
+    let code = sir(
+        "<mi>I</mi><mi>S</mi><mi>β</mi>",
+        "<mi>I</mi><msub><mi>γ</mi><mrow><mi>R</mi></mrow></msub>",
+    );
+    assert!(paper.diff(&code).is_empty());
+}
+
+#[test]
+fn test_changed_rate() {
+    let sir_model = sir("<mi>β</mi><mi>S</mi><mi>I</mi>", "<mi>γ</mi><mi>I</mi>");
+    let with_mixing = sir(
+        "<mi>β</mi><mi>κ</mi><mi>S</mi><mi>I</mi>",
+        "<mi>γ</mi><mi>I</mi>",
+    );
+    let diff = sir_model.diff(&with_mixing);
+    assert!(diff.added_states.is_empty());
+    assert!(diff.removed_states.is_empty());
+    assert_eq!(diff.added_parameters, ["κ"]);
+    assert!(diff.removed_parameters.is_empty());
+    assert_eq!(
+        diff.added_transitions,
+        [TransitionSummary {
+            input: vec!["I".to_string(), "S".to_string()],
+            output: vec!["I".to_string(), "I".to_string()],
+            rate: Some("(* I S β κ)".to_string()),
+        }]
+    );
+    assert_eq!(diff.removed_transitions.len(), 1);
+    assert_eq!(
+        diff.removed_transitions[0].rate.as_deref(),
+        Some("(* I S β)")
+    );
+}
@@ -1,7 +1,8 @@
 use crate::ast::{
     Math, MathExpression,
     MathExpression::{
-        Mfrac, Mn, Mo, MoLine, Mover, Mspace, Msqrt, Mstyle, Msub, Msubsup, Msup, Mtext, Munder,
+        Menclose, Mfenced, Mfrac, Mn, Mo, MoLine, Mover, Mpadded, Mphantom, Mroot, Mspace, Msqrt,
+        Mstyle, Msub, Msubsup, Msup, Mtext, Munder, Semantics,
     },
     Mi, Mrow,
 };
@@ -78,6 +79,12 @@ impl MathExpression {
             Mstyle(xs) => add_to_graph_many0(graph, parent_index, "mstyle", xs),
             Mspace(x) => add_to_graph_0(graph, parent_index, x),
             MoLine(x) => add_to_graph_0(graph, parent_index, x),
+            Mroot(x1, x2) => add_to_graph_n!(graph, parent_index, "mroot", x1, x2),
+            Mfenced(_, _, xs) => add_to_graph_many0(graph, parent_index, "mfenced", xs),
+            Menclose(_, xs) => add_to_graph_many0(graph, parent_index, "menclose", xs),
+            Mpadded(xs) => add_to_graph_many0(graph, parent_index, "mpadded", xs),
+            Mphantom(xs) => add_to_graph_many0(graph, parent_index, "mphantom", xs),
+            Semantics(x, _) => add_to_graph_n!(graph, parent_index, "semantics", x),
             _ => {}
         }
     }
@@ -2,9 +2,15 @@
 
 pub mod acset;
 pub mod ast;
+pub mod codegen;
+pub mod diff;
 pub mod expression;
 pub mod graph;
 pub mod mml2pn;
 pub mod normalization;
 pub mod parsers;
 pub mod petri_net;
+pub mod reconstruction;
+pub mod simulation;
+pub mod units;
+pub mod validation;
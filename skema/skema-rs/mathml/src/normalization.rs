@@ -7,6 +7,8 @@ use crate::ast::{
     MathExpression::{Mn, Mo, Msub},
     Mi, Mrow,
 };
+use crate::parsers::generic_mathml::ParseDiagnostic;
+use regex::Regex;
 
 impl MathExpression {
     /// Collapse subscripts
@@ -57,6 +59,126 @@ impl Math {
     }
 }
 
+/// Code points that are rewritten to a canonical form before MathML is parsed. Each entry maps a
+/// character to its replacement.
+///
+/// | Code point | Name                          | Replacement                         |
+/// |------------|-------------------------------|-------------------------------------|
+/// | U+2212     | MINUS SIGN                    | `-`                                 |
+/// | U+FE63     | SMALL HYPHEN-MINUS            | `-`                                 |
+/// | U+FF0D     | FULLWIDTH HYPHEN-MINUS        | `-`                                 |
+/// | U+FF0B     | FULLWIDTH PLUS SIGN           | `+`                                 |
+/// | U+FF1D     | FULLWIDTH EQUALS SIGN         | `=`                                 |
+/// | U+2217     | ASTERISK OPERATOR             | `*`                                 |
+/// | U+2062     | INVISIBLE TIMES               | `*`                                 |
+/// | U+2063     | INVISIBLE SEPARATOR           | `,`                                 |
+/// | U+2064     | INVISIBLE PLUS                | `+`                                 |
+/// | U+00B7     | MIDDLE DOT                    | `*`                                 |
+/// | U+2219     | BULLET OPERATOR               | `*`                                 |
+/// | U+2215     | DIVISION SLASH                | `/`                                 |
+/// | U+2044     | FRACTION SLASH                | `/`                                 |
+/// | U+00A0     | NO-BREAK SPACE                | space                               |
+/// | U+2005     | FOUR-PER-EM SPACE             | space                               |
+/// | U+2009     | THIN SPACE                    | space                               |
+/// | U+200A     | HAIR SPACE                    | space                               |
+/// | U+205F     | MEDIUM MATHEMATICAL SPACE     | space                               |
+/// | U+200B     | ZERO WIDTH SPACE              | removed                             |
+/// | U+FEFF     | ZERO WIDTH NO-BREAK SPACE     | removed                             |
+///
+/// A middle dot or bullet between scalars is a product, as `\cdot` is in LaTeX; after ∇ it still
+/// denotes the divergence. U+22C5 DOT OPERATOR is left in place for dot products of vectors.
+///
+/// U+2061 FUNCTION APPLICATION is left in place, since the parsers use it to recognize
+/// function application.
+pub const SYMBOL_TABLE: &[(char, &str)] = &[
+    ('\u{2212}', "-"),
+    ('\u{FE63}', "-"),
+    ('\u{FF0D}', "-"),
+    ('\u{FF0B}', "+"),
+    ('\u{FF1D}', "="),
+    ('\u{2217}', "*"),
+    ('\u{2062}', "*"),
+    ('\u{2063}', ","),
+    ('\u{2064}', "+"),
+    ('\u{00B7}', "*"),
+    ('\u{2219}', "*"),
+    ('\u{2215}', "/"),
+    ('\u{2044}', "/"),
+    ('\u{00A0}', " "),
+    ('\u{2005}', " "),
+    ('\u{2009}', " "),
+    ('\u{200A}', " "),
+    ('\u{205F}', " "),
+    ('\u{200B}', ""),
+    ('\u{FEFF}', ""),
+];
+
+/// Maps byte offsets in a normalized MathML string back to byte offsets in the original input,
+/// so that parse errors can be reported against what the user actually sent.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct SourceMap(pub(crate) Vec<usize>);
+
+impl SourceMap {
+    /// Rewrite the line and offset of a diagnostic produced on the normalized string so that
+    /// they refer to the original input.
+    pub(crate) fn locate(&self, input: &str, mut diagnostic: ParseDiagnostic) -> ParseDiagnostic {
+        if let Some(offset) = diagnostic.offset {
+            let original = self.0.get(offset).copied().unwrap_or(input.len());
+            diagnostic.offset = Some(original);
+            diagnostic.line = Some(input[..original].matches('\n').count() as u32 + 1);
+        }
+        diagnostic
+    }
+}
+
+/// Push a character to the output, applying `SYMBOL_TABLE`, and record that the resulting bytes
+/// originate at `origin`.
+fn push_normalized(c: char, origin: usize, output: &mut String, map: &mut Vec<usize>) {
+    let start = output.len();
+    match SYMBOL_TABLE.iter().find(|(from, _)| *from == c) {
+        Some((_, to)) => output.push_str(to),
+        None => output.push(c),
+    }
+    map.extend(std::iter::repeat_n(origin, output.len() - start));
+}
+
+/// Normalize a MathML string before parsing, keeping track of where each byte of the output came
+/// from in the input.
+pub(crate) fn normalize_with_source_map(input: &str) -> (String, SourceMap) {
+    let re = Regex::new(r"&(?:#[xX][0-9A-Fa-f]+|#[0-9]+|[A-Za-z][A-Za-z0-9]*);").unwrap();
+    let mut output = String::with_capacity(input.len());
+    let mut map = Vec::<usize>::with_capacity(input.len());
+    let mut last = 0;
+    for m in re.find_iter(input) {
+        for (i, c) in input[last..m.start()].char_indices() {
+            push_normalized(c, last + i, &mut output, &mut map);
+        }
+        let decoded = html_escape::decode_html_entities(m.as_str());
+        // Unknown references and those of XML markup characters stay escaped.
+        if decoded == m.as_str() || decoded.contains(['<', '>', '&', '"', '\'']) {
+            output.push_str(m.as_str());
+            map.extend(std::iter::repeat_n(m.start(), m.len()));
+        } else {
+            for c in decoded.chars() {
+                push_normalized(c, m.start(), &mut output, &mut map);
+            }
+        }
+        last = m.end();
+    }
+    for (i, c) in input[last..].char_indices() {
+        push_normalized(c, last + i, &mut output, &mut map);
+    }
+    (output, SourceMap(map))
+}
+
+/// Normalize a MathML string before parsing:
+/// 1. Decode named, decimal and hexadecimal character references (e.g. `&minus;`, `&#8722;`,
+///    `&#x2212;`), except those of the XML markup characters `<`, `>`, `&`, `"` and `'`.
+/// 2. Rewrite code points according to `SYMBOL_TABLE`.
+pub fn normalize_mathml(input: &str) -> String {
+    normalize_with_source_map(input).0
+}
+
 #[test]
 fn test_get_string_repr() {
     assert_eq!(
@@ -107,3 +229,35 @@ fn test_normalize() {
         ]))
     );
 }
+
+#[test]
+fn test_normalize_mathml() {
+    assert_eq!(
+        normalize_mathml("<mo>&minus;</mo><mo>&#8722;</mo><mo>&#x2212;</mo><mo>\u{2212}</mo>"),
+        "<mo>-</mo><mo>-</mo><mo>-</mo><mo>-</mo>"
+    );
+    assert_eq!(
+        normalize_mathml(
+            "<mi>a</mi><mo>&InvisibleTimes;</mo><mi>b</mi><mo>&middot;</mo><mi>c</mi>"
+        ),
+        "<mi>a</mi><mo>*</mo><mi>b</mi><mo>*</mo><mi>c</mi>"
+    );
+    assert_eq!(
+        normalize_mathml("<mi>sin</mi><mo>&ApplyFunction;</mo><mi>x</mi>"),
+        "<mi>sin</mi><mo>\u{2061}</mo><mi>x</mi>"
+    );
+    assert_eq!(
+        normalize_mathml("<mo>&lt;</mo><mo>&#x3C;</mo><mo>&amp;</mo><mi>&unknown;</mi>"),
+        "<mo>&lt;</mo><mo>&#x3C;</mo><mo>&amp;</mo><mi>&unknown;</mi>"
+    );
+}
+
+#[test]
+fn test_normalize_source_map() {
+    let input = "<mi>&alpha;</mi>\n<mi>x</mi>";
+    let (output, SourceMap(map)) = normalize_with_source_map(input);
+    assert_eq!(output, "<mi>α</mi>\n<mi>x</mi>");
+    assert_eq!(map.len(), output.len());
+    assert_eq!(map[output.find('α').unwrap()], 4);
+    assert_eq!(map[output.find('x').unwrap()], input.find('x').unwrap());
+}
@@ -1,5 +1,20 @@
+pub mod canonical_form;
+pub mod content_mathml;
 pub mod decapodes_serialization;
+pub mod differentiation;
+pub mod equation_system;
+pub mod evaluation;
+pub mod expansion;
 pub mod first_order_ode;
 pub mod generic_mathml;
+pub mod higher_order_ode;
 pub mod interpreted_mathml;
+pub mod latex;
 pub mod math_expression_tree;
+pub mod matrix_algebra;
+pub mod observables;
+pub mod pde;
+pub mod presentation_mathml;
+pub mod printing;
+pub mod simplification;
+pub mod substitution;
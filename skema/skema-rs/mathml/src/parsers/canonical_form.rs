@@ -0,0 +1,239 @@
+//! Canonical form of math expression trees, for comparing expressions up to the order of
+//! commutative operands and the grouping of associative ones, e.g. β*S*I and S*(β*I).
+
+use crate::{
+    ast::{operator::Operator, MathExpression, Mi},
+    parsers::{
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+        substitution::{identifier, key},
+    },
+};
+
+/// The canonical key of an identifier name, equal for names that differ only in how their
+/// subscripts are written, e.g. β_{I} and β_I.
+pub(crate) fn identifier_key(name: &str) -> String {
+    key(name)
+}
+
+/// Sort items by the canonical key of the identifier naming them and keep only the first item
+/// for each key.
+pub(crate) fn dedup_by_identifier<T>(items: &mut Vec<T>, name: impl Fn(&T) -> &str) {
+    items.sort_by_cached_key(|item| identifier_key(name(item)));
+    items.dedup_by(|a, b| identifier_key(name(a)) == identifier_key(name(b)));
+}
+
+fn negate(met: MathExpressionTree) -> MathExpressionTree {
+    match met {
+        Cons(Operator::Subtract, mut args) if args.len() == 1 => args.pop().unwrap(),
+        _ => Cons(Operator::Subtract, vec![met]),
+    }
+}
+
+/// The factors of a term in canonical form: whether it is negated, and the factors of its
+/// numerator and of its denominator.
+fn factors(
+    met: MathExpressionTree,
+    negative: &mut bool,
+    numerator: &mut Vec<MathExpressionTree>,
+    denominator: &mut Vec<MathExpressionTree>,
+) {
+    match met {
+        Cons(Operator::Multiply, args) => {
+            for arg in args {
+                factors(arg, negative, numerator, denominator);
+            }
+        }
+        Cons(Operator::Subtract, mut args) if args.len() == 1 => {
+            *negative = !*negative;
+            factors(args.pop().unwrap(), negative, numerator, denominator);
+        }
+        Cons(Operator::Divide, mut args) if args.len() == 2 => {
+            let divisor = args.pop().unwrap();
+            factors(args.pop().unwrap(), negative, numerator, denominator);
+            factors(divisor, negative, denominator, numerator);
+        }
+        Atom(MathExpression::Mn(ref number)) if number == "1" => {}
+        _ => numerator.push(met),
+    }
+}
+
+/// A product or quotient of terms in canonical form, written as a single division if there are
+/// any factors in the denominator, e.g. β*S*(I/N) and (I*S*β)/N both as (/ (* I S β) N).
+fn product(met: MathExpressionTree) -> MathExpressionTree {
+    let (mut negative, mut numerator, mut denominator) = (false, vec![], vec![]);
+    factors(met, &mut negative, &mut numerator, &mut denominator);
+    let combine = |mut operands: Vec<MathExpressionTree>| {
+        operands.sort_by_cached_key(|operand| operand.to_string());
+        match operands.len() {
+            0 => Atom(MathExpression::Mn("1".to_string())),
+            1 => operands.pop().unwrap(),
+            _ => Cons(Operator::Multiply, operands),
+        }
+    };
+    let numerator = combine(numerator);
+    let combined = if denominator.is_empty() {
+        numerator
+    } else {
+        Cons(Operator::Divide, vec![numerator, combine(denominator)])
+    };
+    if negative {
+        negate(combined)
+    } else {
+        combined
+    }
+}
+
+/// Combine operands that are already in canonical form.
+fn canonical(op: Operator, args: Vec<MathExpressionTree>) -> MathExpressionTree {
+    match op {
+        Operator::Multiply => product(Cons(op, args)),
+        Operator::Divide if args.len() == 2 => product(Cons(op, args)),
+        Operator::Subtract if args.len() == 1 => negate(args.into_iter().next().unwrap()),
+        Operator::Subtract if args.len() == 2 => {
+            let mut args = args.into_iter();
+            let lhs = args.next().unwrap();
+            let rhs = negate(args.next().unwrap());
+            canonical(Operator::Add, vec![lhs, rhs])
+        }
+        Operator::Add | Operator::Hadamard => {
+            let mut operands = vec![];
+            for arg in args {
+                match arg {
+                    Cons(inner, inner_args) if inner == op => operands.extend(inner_args),
+                    _ => operands.push(arg),
+                }
+            }
+            operands.sort_by_cached_key(|operand| operand.to_string());
+            if operands.len() == 1 {
+                operands.pop().unwrap()
+            } else {
+                Cons(op, operands)
+            }
+        }
+        Operator::Min => {
+            let mut args = args;
+            args.sort_by_cached_key(|arg| arg.to_string());
+            Cons(op, args)
+        }
+        _ => Cons(op, args),
+    }
+}
+
+impl MathExpressionTree {
+    /// The expression with associative operators flattened, the operands of commutative
+    /// operators sorted, subtractions written as additions of negated terms, negations pulled
+    /// out of products, products and quotients combined into a single division and identifiers
+    /// renamed to their canonical keys.
+    pub fn canonicalize(&self) -> MathExpressionTree {
+        match self {
+            Cons(op, args) => canonical(
+                op.clone(),
+                args.iter().map(|arg| arg.canonicalize()).collect(),
+            ),
+            Atom(atom) => match identifier(atom) {
+                Some(name) if identifier_key(&name) != name => {
+                    Atom(MathExpression::Mi(Mi(identifier_key(&name))))
+                }
+                _ => self.clone(),
+            },
+        }
+    }
+
+    /// A key that is equal for equivalent expressions, suitable for hashing and deduplication.
+    pub fn canonical_key(&self) -> String {
+        self.canonicalize().to_string()
+    }
+
+    /// Whether two expressions are equal up to the order of commutative operands, the grouping
+    /// of associative ones and the placement of negations.
+    pub fn equivalent(&self, other: &MathExpressionTree) -> bool {
+        self.canonical_key() == other.canonical_key()
+    }
+}
+
+#[cfg(test)]
+fn met(input: &str) -> MathExpressionTree {
+    format!("<math>{input}</math>")
+        .parse::<MathExpressionTree>()
+        .unwrap()
+}
+
+#[test]
+fn test_commutative_products_and_sums() {
+    let a = met("<mi>β</mi><mi>S</mi><mi>I</mi>");
+    let b = met("<mi>S</mi><mo>*</mo><mo>(</mo><mi>I</mi><mi>β</mi><mo>)</mo>");
+    assert_ne!(a, b);
+    assert!(a.equivalent(&b));
+    assert_eq!(a.canonical_key(), "(* I S β)");
+    assert!(
+        met("<mi>a</mi><mo>+</mo><mi>b</mi>").equivalent(&met("<mi>b</mi><mo>+</mo><mi>a</mi>"))
+    );
+    assert!(
+        !met("<mi>a</mi><mo>-</mo><mi>b</mi>").equivalent(&met("<mi>b</mi><mo>-</mo><mi>a</mi>"))
+    );
+    assert!(!met("<mfrac><mi>a</mi><mi>b</mi></mfrac>")
+        .equivalent(&met("<mfrac><mi>b</mi><mi>a</mi></mfrac>")));
+}
+
+#[test]
+fn test_negations() {
+    // As extracted from code, with a unary minus on the parameter.
+    let code = MathExpressionTree::from_cmml(
+        "<apply><plus/>
+            <apply><times/><apply><minus/><ci>β</ci></apply><ci>S</ci><ci>I</ci></apply>
+            <apply><times/><ci>γ</ci><ci>I</ci></apply>
+        </apply>",
+    )
+    .unwrap();
+    let paper = met("<mi>γ</mi><mi>I</mi><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi>");
+    assert!(code.equivalent(&paper));
+    assert_eq!(paper.canonical_key(), "(+ (* I γ) (- (* I S β)))");
+}
+
+#[test]
+fn test_equivalent_odes() {
+    use crate::parsers::first_order_ode::FirstOrderODE;
+    let a = "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap();
+    let b = "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>I</mi><mi>S</mi><mi>β</mi></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap();
+    assert!(a.equivalent(&b));
+
+    // Frequency-dependent incidence, with the division by the population written either way
+    let c = "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>/</mo><mi>N</mi></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap();
+    let d = "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mfrac><mrow><mi>I</mi><mi>S</mi><mi>β</mi></mrow><mi>N</mi></mfrac></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap();
+    assert!(c.equivalent(&d));
+    assert!(!a.equivalent(&c));
+    assert_eq!(d.rhs.canonical_key(), "(- (/ (* I S β) N))");
+}
+
+#[test]
+fn test_petri_net_with_reordered_factors() {
+    use crate::{acset::PetriNet, parsers::first_order_ode::FirstOrderODE};
+    let petri_net = |infection: &str| {
+        let odes: Vec<FirstOrderODE> = [
+            "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>κ</mi><mi>S</mi><mi>I</mi></math>".to_string(),
+            format!("<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo>{infection}<mo>-</mo><mi>γ</mi><mi>I</mi></math>"),
+            "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>".to_string(),
+        ]
+        .iter()
+        .map(|input| input.parse::<FirstOrderODE>().unwrap())
+        .collect();
+        PetriNet::from(odes)
+            .model
+            .transitions
+            .iter()
+            .map(|t| (t.input.clone(), t.output.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        petri_net("<mi>κ</mi><mi>β</mi><mi>I</mi><mi>S</mi>"),
+        petri_net("<mi>β</mi><mi>κ</mi><mi>S</mi><mi>I</mi>")
+    );
+}
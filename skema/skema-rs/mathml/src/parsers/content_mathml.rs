@@ -0,0 +1,484 @@
+//! Parser for content MathML (https://www.w3.org/TR/MathML3/chapter4.html) into
+//! MathExpressionTree objects. This is the inverse of `MathExpressionTree::to_cmml`.
+use crate::{
+    ast::{
+        operator::{
+            Derivative, DerivativeNotation, Gradient, Logarithm, LogarithmNotation, Operator,
+        },
+        Ci, MathExpression, Mi, Type,
+    },
+    normalization::normalize_with_source_map,
+    parsers::{
+        generic_mathml::{
+            attribute, elem0, etag, stag, tag_parser, ws, xml_declaration, IResult,
+            ParseDiagnostic, ParseError, Span,
+        },
+        math_expression_tree::MathExpressionTree,
+        matrix_algebra::matrix_product,
+    },
+};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_until},
+    character::complete::multispace0,
+    combinator::{all_consuming, map, opt, value},
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, tuple},
+};
+
+/// Empty content elements such as `<plus/>`, which are used as operators.
+fn empty_element<'a>(name: &'static str) -> impl FnMut(Span<'a>) -> IResult<'a, ()> {
+    value(
+        (),
+        ws(tuple((
+            tag("<"),
+            tag(name),
+            many0(attribute),
+            multispace0,
+            tag("/>"),
+        ))),
+    )
+}
+
+/// Numbers
+pub fn cn(input: Span) -> IResult<MathExpression> {
+    let (s, element) = elem0!("cn")(input)?;
+    Ok((s, MathExpression::Mn(element.trim().to_string())))
+}
+
+/// Identifiers
+pub fn ci(input: Span) -> IResult<Mi> {
+    let (s, element) = elem0!("ci")(input)?;
+    Ok((s, Mi(element.trim().to_string())))
+}
+
+/// Bound variables, with an optional degree, e.g. `<bvar><ci>t</ci><degree><cn>2</cn></degree></bvar>`.
+/// The variable may be given either as a `<ci>` element or as bare text (as emitted by
+/// `to_cmml`).
+fn bvar(input: Span) -> IResult<(Mi, Option<u8>)> {
+    let (s, _) = stag!("bvar")(input)?;
+    let (s, var) = alt((
+        ci,
+        map(ws(take_till1(|c| c == '<')), |x: Span| {
+            Mi(x.trim().to_string())
+        }),
+    ))(s)?;
+    let (s, degree) = opt(tag_parser!("degree", cn))(s)?;
+    let degree = match degree {
+        Some(MathExpression::Mn(n)) => match n.parse::<u8>() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                return Err(nom::Err::Failure(ParseError::new(
+                    format!("Unsupported derivative degree '{n}'"),
+                    input,
+                )))
+            }
+        },
+        _ => None,
+    };
+    let (s, _) = etag!("bvar")(s)?;
+    Ok((s, (var, degree)))
+}
+
+/// Derivatives: `<diff/>` and `<partialdiff/>` followed by a bound variable, optionally preceded
+/// by a `<list>` with the index of the variable among the arguments of the function.
+fn derivative(input: Span) -> IResult<Operator> {
+    let (s, (notation, index, (var, degree))) = tuple((
+        alt((
+            value(DerivativeNotation::LeibnizTotal, empty_element("diff")),
+            value(
+                DerivativeNotation::LeibnizPartialStandard,
+                empty_element("partialdiff"),
+            ),
+        )),
+        opt(tag_parser!("list", cn)),
+        bvar,
+    ))(input)?;
+    let var_index = match index {
+        Some(MathExpression::Mn(n)) => match n.parse::<u8>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                return Err(nom::Err::Failure(ParseError::new(
+                    format!("Unsupported derivative variable index '{n}'"),
+                    input,
+                )))
+            }
+        },
+        _ => 1,
+    };
+    let bound_var = Ci::new(
+        Some(Type::Real),
+        Box::new(MathExpression::Mi(var)),
+        None,
+        None,
+    );
+    Ok((
+        s,
+        Operator::Derivative(Derivative::new(
+            degree.unwrap_or(1),
+            var_index,
+            bound_var,
+            notation,
+        )),
+    ))
+}
+
+/// Gradient, with an optional subscript given as a bound variable.
+fn gradient(input: Span) -> IResult<Operator> {
+    let (s, (_, subscript)) = pair(empty_element("grad"), opt(bvar))(input)?;
+    let subscript = subscript.map(|(var, _)| Box::new(MathExpression::Mi(var)));
+    Ok((s, Operator::Gradient(Gradient::new(subscript))))
+}
+
+/// Logarithms, with an optional `<logbase>` qualifier.
+fn logarithm(input: Span) -> IResult<Operator> {
+    let (s, notation) = alt((
+        value(LogarithmNotation::Ln, empty_element("ln")),
+        map(
+            preceded(
+                empty_element("log"),
+                opt(tag_parser!(
+                    "logbase",
+                    alt((cn, map(ci, MathExpression::Mi)))
+                )),
+            ),
+            |base| match base {
+                Some(base) => LogarithmNotation::LogBase(Box::new(base)),
+                None => LogarithmNotation::Log,
+            },
+        ),
+    ))(input)?;
+    Ok((s, Operator::Logarithm(Logarithm::new(notation))))
+}
+
+/// Operators that do not take qualifiers.
+fn simple_operator(input: Span) -> IResult<Operator> {
+    alt((
+        alt((
+            value(Operator::Add, empty_element("plus")),
+            value(Operator::Subtract, empty_element("minus")),
+            value(Operator::Multiply, empty_element("times")),
+            value(Operator::Equals, empty_element("eq")),
+            value(Operator::Divide, empty_element("divide")),
+            value(Operator::Power, empty_element("power")),
+            value(Operator::Exp, empty_element("exp")),
+            value(Operator::Abs, empty_element("abs")),
+            value(Operator::Div, empty_element("divergence")),
+            value(Operator::Sqrt, empty_element("root")),
+            value(Operator::Factorial, empty_element("factorial")),
+            value(Operator::Dot, empty_element("scalarproduct")),
+            value(Operator::Cross, empty_element("vectorproduct")),
+            value(Operator::Laplacian, empty_element("laplacian")),
+            value(Operator::Mean, empty_element("mean")),
+            value(Operator::Min, empty_element("min")),
+        )),
+        alt((
+            value(Operator::Sin, empty_element("sin")),
+            value(Operator::Cos, empty_element("cos")),
+            value(Operator::Tan, empty_element("tan")),
+            value(Operator::Sec, empty_element("sec")),
+            value(Operator::Csc, empty_element("csc")),
+            value(Operator::Cot, empty_element("cot")),
+            value(Operator::Arcsin, empty_element("arcsin")),
+            value(Operator::Arccos, empty_element("arccos")),
+            value(Operator::Arctan, empty_element("arctan")),
+            value(Operator::Arcsec, empty_element("arcsec")),
+            value(Operator::Arccsc, empty_element("arccsc")),
+            value(Operator::Arccot, empty_element("arccot")),
+            value(Operator::Less, empty_element("lt")),
+            value(Operator::LessEqual, empty_element("leq")),
+            value(Operator::Greater, empty_element("gt")),
+            value(Operator::GreaterEqual, empty_element("geq")),
+            value(Operator::NotEqual, empty_element("neq")),
+            value(Operator::Transpose, empty_element("transpose")),
+            value(Operator::Inverse, empty_element("inverse")),
+            value(Operator::Index, empty_element("selector")),
+            value(
+                Operator::Hadamard,
+                tag_parser!("csymbol", ws(tag("hadamard"))),
+            ),
+        )),
+    ))(input)
+}
+
+/// The operator in the first position of an `<apply>` element.
+pub fn operator(input: Span) -> IResult<Operator> {
+    alt((simple_operator, derivative, gradient, logarithm))(input).map_err(|err| match err {
+        nom::Err::Error(_) => nom::Err::Failure(ParseError::new(
+            "Unsupported content MathML operator".to_string(),
+            input,
+        )),
+        err => err,
+    })
+}
+
+/// Function application, e.g. `<apply><plus/><ci>x</ci><ci>y</ci></apply>`
+pub fn apply(input: Span) -> IResult<MathExpressionTree> {
+    let (s, (_, op, args, _)) =
+        tuple((stag!("apply"), operator, many1(expression), etag!("apply")))(input)?;
+    Ok((s, matrix_product(op, args)))
+}
+
+/// Matrices, e.g. `<matrix><matrixrow><cn>1</cn></matrixrow></matrix>`
+pub fn matrix(input: Span) -> IResult<MathExpressionTree> {
+    map(
+        tag_parser!(
+            "matrix",
+            many1(map(
+                tag_parser!("matrixrow", many1(expression)),
+                |entries| MathExpressionTree::Cons(Operator::MatrixRow, entries)
+            ))
+        ),
+        |rows| MathExpressionTree::Cons(Operator::Matrix, rows),
+    )(input)
+}
+
+/// Piecewise functions, e.g. `<piecewise><piece><ci>a</ci><apply><lt/>...</apply></piece><otherwise><ci>b</ci></otherwise></piecewise>`
+pub fn piecewise(input: Span) -> IResult<MathExpressionTree> {
+    map(
+        tag_parser!(
+            "piecewise",
+            pair(
+                many0(map(
+                    tag_parser!("piece", pair(expression, expression)),
+                    |(value, condition)| {
+                        MathExpressionTree::Cons(Operator::Piece, vec![value, condition])
+                    }
+                )),
+                opt(map(tag_parser!("otherwise", expression), |value| {
+                    MathExpressionTree::Cons(Operator::Otherwise, vec![value])
+                }))
+            )
+        ),
+        |(mut pieces, otherwise)| {
+            pieces.extend(otherwise);
+            MathExpressionTree::Cons(Operator::Piecewise, pieces)
+        },
+    )(input)
+}
+
+/// Content MathML expressions.
+pub fn expression(input: Span) -> IResult<MathExpressionTree> {
+    alt((
+        apply,
+        matrix,
+        piecewise,
+        map(ci, |x| {
+            MathExpressionTree::Atom(MathExpression::Ci(Ci::new(
+                None,
+                Box::new(MathExpression::Mi(x)),
+                None,
+                None,
+            )))
+        }),
+        map(cn, MathExpressionTree::Atom),
+    ))(input)
+}
+
+/// A content MathML expression, optionally wrapped in a `<math>` element.
+pub fn content_math(input: Span) -> IResult<MathExpressionTree> {
+    preceded(
+        opt(xml_declaration),
+        alt((tag_parser!("math", expression), expression)),
+    )(input)
+}
+
+impl MathExpressionTree {
+    /// Construct a MathExpressionTree from a content MathML string.
+    pub fn from_cmml(input: &str) -> Result<Self, ParseDiagnostic> {
+        let (normalized, source_map) = normalize_with_source_map(input);
+        let (_, met) = all_consuming(content_math)(normalized.as_str().into())
+            .map_err(|err| source_map.locate(input, ParseDiagnostic::from(err)))?;
+        Ok(met)
+    }
+}
+
+#[cfg(test)]
+use crate::parsers::first_order_ode::FirstOrderODE;
+
+#[cfg(test)]
+fn test_round_trip(met: MathExpressionTree) {
+    let cmml = met.to_cmml();
+    let parsed = MathExpressionTree::from_cmml(&cmml).unwrap();
+    assert_eq!(parsed, met);
+    assert_eq!(parsed.to_cmml(), cmml);
+}
+
+#[test]
+fn test_content_mathml_atoms() {
+    let met = MathExpressionTree::from_cmml("<ci> x </ci>").unwrap();
+    assert_eq!(
+        met,
+        MathExpressionTree::Atom(MathExpression::Ci(Ci::new(
+            None,
+            Box::new(MathExpression::Mi(Mi("x".to_string()))),
+            None,
+            None
+        )))
+    );
+    let met = MathExpressionTree::from_cmml("<cn type=\"integer\">2</cn>").unwrap();
+    assert_eq!(
+        met,
+        MathExpressionTree::Atom(MathExpression::Mn("2".to_string()))
+    );
+}
+
+#[test]
+fn test_content_mathml_apply() {
+    let input = "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">
+        <apply>
+            <minus/>
+            <apply><times/><ci>β</ci><ci>S</ci><ci>I</ci></apply>
+            <apply><power/><ci>x</ci><cn>2</cn></apply>
+        </apply>
+    </math>";
+    let met = MathExpressionTree::from_cmml(input).unwrap();
+    assert_eq!(met.to_string(), "(- (* β S I) (^ x 2))");
+}
+
+#[test]
+fn test_content_mathml_derivative_with_ci_bvar() {
+    let input = "<apply><eq/>
+        <apply><partialdiff/><bvar><ci>x</ci><degree><cn>2</cn></degree></bvar><ci>u</ci></apply>
+        <cn>0</cn>
+    </apply>";
+    let met = MathExpressionTree::from_cmml(input).unwrap();
+    match met {
+        MathExpressionTree::Cons(Operator::Equals, args) => match &args[0] {
+            MathExpressionTree::Cons(Operator::Derivative(d), _) => {
+                assert_eq!(d.order, 2);
+                assert_eq!(d.bound_var.to_string(), "x");
+                assert_eq!(d.notation, DerivativeNotation::LeibnizPartialStandard);
+            }
+            _ => panic!("Expected a derivative"),
+        },
+        _ => panic!("Expected an equation"),
+    }
+}
+
+#[test]
+fn test_content_mathml_unsupported_operator() {
+    let err = MathExpressionTree::from_cmml("<apply><foo/><ci>x</ci></apply>").unwrap_err();
+    assert_eq!(err.message, "Unsupported content MathML operator");
+    assert_eq!(err.offset, Some(7));
+    assert_eq!(err.token, Some("<foo/>".to_string()));
+}
+
+#[test]
+fn test_content_mathml_round_trip_operators() {
+    let x = || {
+        MathExpressionTree::Atom(MathExpression::Ci(Ci::new(
+            None,
+            Box::new(MathExpression::Mi(Mi("x".to_string()))),
+            None,
+            None,
+        )))
+    };
+    let two = || MathExpressionTree::Atom(MathExpression::Mn("2".to_string()));
+    let t = Ci::new(
+        Some(Type::Real),
+        Box::new(MathExpression::Mi(Mi("t".to_string()))),
+        None,
+        None,
+    );
+    let operators = vec![
+        Operator::Add,
+        Operator::Subtract,
+        Operator::Multiply,
+        Operator::Equals,
+        Operator::Divide,
+        Operator::Power,
+        Operator::Exp,
+        Operator::Abs,
+        Operator::Div,
+        Operator::Sin,
+        Operator::Cos,
+        Operator::Tan,
+        Operator::Sec,
+        Operator::Csc,
+        Operator::Cot,
+        Operator::Arcsin,
+        Operator::Arccos,
+        Operator::Arctan,
+        Operator::Arcsec,
+        Operator::Arccsc,
+        Operator::Arccot,
+        Operator::Sqrt,
+        Operator::Factorial,
+        Operator::Dot,
+        Operator::Cross,
+        Operator::Laplacian,
+        Operator::Mean,
+        Operator::Min,
+        Operator::Gradient(Gradient::new(None)),
+        Operator::Gradient(Gradient::new(Some(Box::new(MathExpression::Mi(Mi(
+            "x".to_string()
+        )))))),
+        Operator::Logarithm(Logarithm::new(LogarithmNotation::Ln)),
+        Operator::Logarithm(Logarithm::new(LogarithmNotation::Log)),
+        Operator::Logarithm(Logarithm::new(LogarithmNotation::LogBase(Box::new(
+            MathExpression::Mn("10".to_string()),
+        )))),
+        Operator::Derivative(Derivative::new(
+            1,
+            1,
+            t.clone(),
+            DerivativeNotation::LeibnizTotal,
+        )),
+        Operator::Derivative(Derivative::new(
+            1,
+            1,
+            t.clone(),
+            DerivativeNotation::LeibnizPartialStandard,
+        )),
+        Operator::Derivative(Derivative::new(
+            3,
+            1,
+            t.clone(),
+            DerivativeNotation::LeibnizTotal,
+        )),
+        Operator::Derivative(Derivative::new(
+            1,
+            2,
+            t.clone(),
+            DerivativeNotation::LeibnizPartialStandard,
+        )),
+        Operator::Derivative(Derivative::new(2, 3, t, DerivativeNotation::LeibnizTotal)),
+    ];
+    for op in operators {
+        test_round_trip(MathExpressionTree::Cons(op.clone(), vec![x()]));
+        test_round_trip(MathExpressionTree::Cons(op, vec![x(), two()]));
+    }
+}
+
+#[test]
+fn test_content_mathml_round_trip_presentation_mathml() {
+    let inputs = [
+        "<math><mi>x</mi><mo>+</mo><mi>y</mi></math>",
+        "<math><mo>−</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>/</mo><mi>N</mi></math>",
+        "<math><msup><mi>e</mi><mrow><mo>−</mo><mi>k</mi><mi>t</mi></mrow></msup><mo>−</mo><mn>2.5</mn><mi>x</mi></math>",
+        "<math><mo>|</mo><mi>x</mi><mo>|</mo><mo>=</mo><msqrt><msup><mi>x</mi><mn>2</mn></msup></msqrt></math>",
+    ];
+    for input in inputs {
+        let met = input.parse::<MathExpressionTree>().unwrap();
+        test_round_trip(met);
+    }
+}
+
+#[test]
+fn test_content_mathml_round_trip_first_order_ode() {
+    let input = "<math>
+        <mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo><mo>−</mo><mi>β</mi><mi>I</mi><mfrac><mi>S</mi><mi>N</mi></mfrac>
+        </math>";
+    let ode = input.parse::<FirstOrderODE>().unwrap();
+    let cmml = ode.to_cmml();
+    let met = MathExpressionTree::from_cmml(&cmml).unwrap();
+    assert_eq!(met.to_cmml(), cmml);
+    match met {
+        MathExpressionTree::Cons(Operator::Equals, args) => {
+            assert_eq!(args[1].to_string(), ode.rhs.to_string());
+        }
+        _ => panic!("Expected an equation"),
+    }
+}
@@ -4,6 +4,7 @@ use crate::ast::{
     MathExpression,
 };
 use crate::parsers::math_expression_tree::MathExpressionTree;
+use crate::parsers::pde::PDE;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -363,6 +364,29 @@ pub fn to_wiring_diagram(input: &MathExpressionTree) -> WiringDiagram {
     }
 }
 
+/// The field evolved by a PDE is a 0-form; the types of the other variables are left to be
+/// inferred.
+impl From<&PDE> for WiringDiagram {
+    fn from(pde: &PDE) -> WiringDiagram {
+        let mut wiring_diagram = to_wiring_diagram(&pde.to_met());
+        let field = pde.lhs_var.content.to_string();
+        for variable in wiring_diagram.Var.iter_mut() {
+            if variable.name == field {
+                variable.r#type = Type::Form0;
+            }
+        }
+        wiring_diagram
+    }
+}
+
+impl From<&[PDE]> for DecapodesCollection {
+    fn from(pdes: &[PDE]) -> DecapodesCollection {
+        DecapodesCollection {
+            decapodes: pdes.iter().map(WiringDiagram::from).collect(),
+        }
+    }
+}
+
 pub fn to_decapodes_json(input: WiringDiagram) -> String {
     serde_json::to_string(&input).unwrap()
     //Ok(json_wiring_diagram)
@@ -529,3 +553,17 @@ fn test_serialize_from_image_3_2() {
     let json = to_decapodes_json(wiring_diagram);
     assert_eq!(json, "{\"Var\":[{\"type\":\"infer\",\"name\":\"mult_1\"},{\"type\":\"infer\",\"name\":\"mult_2\"},{\"type\":\"infer\",\"name\":\"•1\"},{\"type\":\"Literal\",\"name\":\"2\"},{\"type\":\"infer\",\"name\":\"sum_1\"},{\"type\":\"infer\",\"name\":\"n\"},{\"type\":\"infer\",\"name\":\"A\"},{\"type\":\"infer\",\"name\":\"•2\"},{\"type\":\"infer\",\"name\":\"mult_3\"},{\"type\":\"infer\",\"name\":\"ρ\"},{\"type\":\"infer\",\"name\":\"g\"},{\"type\":\"infer\",\"name\":\"Γ\"}],\"Op1\":[],\"Op2\":[{\"proj1\":4,\"proj2\":5,\"res\":3,\"op2\":\"/\"},{\"proj1\":3,\"proj2\":7,\"res\":2,\"op2\":\"*\"},{\"proj1\":10,\"proj2\":11,\"res\":9,\"op2\":\"*\"},{\"proj1\":9,\"proj2\":6,\"res\":8,\"op2\":\"^\"},{\"proj1\":2,\"proj2\":8,\"res\":1,\"op2\":\"*\"}],\"Σ\":[{\"sum\":5}],\"Summand\":[{\"summand\":6,\"summation\":1},{\"summand\":4,\"summation\":1}]}");
 }
+
+#[test]
+fn test_serialize_pde() {
+    let input = "
+    <math>
+        <mfrac><mrow><mi>∂</mi><mi>H</mi></mrow><mrow><mi>∂</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo>
+        <mo>∇</mo><mo>⋅</mo><mo>(</mo><mi>Γ</mi><mo>∇</mo><mi>H</mi><mo>)</mo>
+    </math>
+    ";
+    let pde = input.parse::<PDE>().unwrap();
+    let json = to_decapodes_json(WiringDiagram::from(&pde));
+    assert_eq!(json, "{\"Var\":[{\"type\":\"infer\",\"name\":\"•1\"},{\"type\":\"infer\",\"name\":\"mult_1\"},{\"type\":\"infer\",\"name\":\"Γ\"},{\"type\":\"infer\",\"name\":\"•2\"},{\"type\":\"Form0\",\"name\":\"H\"},{\"type\":\"infer\",\"name\":\"•3\"}],\"Op1\":[{\"src\":5,\"tgt\":4,\"op1\":\"Grad\"},{\"src\":2,\"tgt\":1,\"op1\":\"Div\"},{\"src\":5,\"tgt\":6,\"op1\":\"PD(1,t)\"}],\"Op2\":[{\"proj1\":3,\"proj2\":4,\"res\":2,\"op2\":\"*\"}],\"Σ\":[],\"Summand\":[]}");
+}
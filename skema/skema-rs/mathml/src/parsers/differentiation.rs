@@ -0,0 +1,355 @@
+//! Symbolic differentiation of math expression trees, e.g. for building the Jacobians,
+//! linearizations and sensitivity equations of extracted ODE systems.
+
+use crate::{
+    ast::{
+        operator::{Derivative, DerivativeNotation, Logarithm, LogarithmNotation, Operator},
+        Ci, MathExpression, Mi,
+    },
+    parsers::{
+        first_order_ode::FirstOrderODE,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+    },
+};
+
+fn number(n: &str) -> MathExpressionTree {
+    Atom(MathExpression::Mn(n.to_string()))
+}
+
+fn apply(op: Operator, args: Vec<MathExpressionTree>) -> MathExpressionTree {
+    Cons(op, args)
+}
+
+fn mul(lhs: MathExpressionTree, rhs: MathExpressionTree) -> MathExpressionTree {
+    apply(Operator::Multiply, vec![lhs, rhs])
+}
+
+fn div(lhs: MathExpressionTree, rhs: MathExpressionTree) -> MathExpressionTree {
+    apply(Operator::Divide, vec![lhs, rhs])
+}
+
+fn neg(met: MathExpressionTree) -> MathExpressionTree {
+    apply(Operator::Subtract, vec![met])
+}
+
+fn square(met: MathExpressionTree) -> MathExpressionTree {
+    apply(Operator::Power, vec![met, number("2")])
+}
+
+fn ln(met: MathExpressionTree) -> MathExpressionTree {
+    apply(
+        Operator::Logarithm(Logarithm::new(LogarithmNotation::Ln)),
+        vec![met],
+    )
+}
+
+/// Whether the identifier is the given variable or is declared to be a function of it.
+fn depends_on(ci: &Ci, var: &str) -> bool {
+    ci.content.to_string() == var
+        || ci
+            .func_of
+            .iter()
+            .flatten()
+            .any(|arg| arg.to_string() == var)
+}
+
+impl MathExpressionTree {
+    /// Whether the expression depends on the variable.
+    fn contains_var(&self, var: &str) -> bool {
+        match self {
+            Atom(MathExpression::Ci(x)) => depends_on(x, var),
+            Atom(MathExpression::Mi(Mi(x))) => x == var,
+            Atom(_) => false,
+            Cons(_, args) => args.iter().any(|arg| arg.contains_var(var)),
+        }
+    }
+
+    /// The partial derivative of the expression with respect to a variable. Expressions that
+    /// cannot be differentiated symbolically are wrapped in a `Derivative` operator.
+    pub fn differentiate(&self, var: &Ci) -> MathExpressionTree {
+        self.derivative(var).simplify()
+    }
+
+    /// The unsimplified derivative of the expression.
+    fn derivative(&self, var: &Ci) -> MathExpressionTree {
+        let name = var.content.to_string();
+        if !self.contains_var(&name) {
+            return number("0");
+        }
+        let unknown = || {
+            apply(
+                Operator::Derivative(Derivative::new(
+                    1,
+                    1,
+                    var.clone(),
+                    DerivativeNotation::LeibnizPartialStandard,
+                )),
+                vec![self.clone()],
+            )
+        };
+        let args = match self {
+            Atom(MathExpression::Ci(x)) if x.content.to_string() == name => return number("1"),
+            Atom(MathExpression::Mi(_)) => return number("1"),
+            Atom(_) => return unknown(),
+            Cons(_, args) => args,
+        };
+        // The derivatives of a function of a single argument, by the chain rule.
+        let chain = |outer: MathExpressionTree| mul(outer, args[0].derivative(var));
+        let f = || args[0].clone();
+        match (self, args.len()) {
+            (Cons(Operator::Add, _), _) => apply(
+                Operator::Add,
+                args.iter().map(|arg| arg.derivative(var)).collect(),
+            ),
+            (Cons(Operator::Subtract, _), 1 | 2) => apply(
+                Operator::Subtract,
+                args.iter().map(|arg| arg.derivative(var)).collect(),
+            ),
+            (Cons(Operator::Multiply, _), _) => apply(
+                Operator::Add,
+                (0..args.len())
+                    .map(|i| {
+                        let mut factors = args.clone();
+                        factors[i] = args[i].derivative(var);
+                        apply(Operator::Multiply, factors)
+                    })
+                    .collect(),
+            ),
+            (Cons(Operator::Divide, _), 2) => div(
+                apply(
+                    Operator::Subtract,
+                    vec![
+                        mul(args[0].derivative(var), args[1].clone()),
+                        mul(args[0].clone(), args[1].derivative(var)),
+                    ],
+                ),
+                square(args[1].clone()),
+            ),
+            (Cons(Operator::Power, _), 2) => {
+                let (base, exponent) = (&args[0], &args[1]);
+                if !exponent.contains_var(&name) {
+                    let reduced = apply(Operator::Subtract, vec![exponent.clone(), number("1")]);
+                    mul(
+                        mul(
+                            exponent.clone(),
+                            apply(Operator::Power, vec![base.clone(), reduced]),
+                        ),
+                        base.derivative(var),
+                    )
+                } else {
+                    // d(f^g) = f^g (g' ln f + g f'/f)
+                    mul(
+                        self.clone(),
+                        apply(
+                            Operator::Add,
+                            vec![
+                                mul(exponent.derivative(var), ln(base.clone())),
+                                div(mul(exponent.clone(), base.derivative(var)), base.clone()),
+                            ],
+                        ),
+                    )
+                }
+            }
+            (Cons(Operator::Exp, _), 1) => chain(self.clone()),
+            (Cons(Operator::Logarithm(Logarithm { notation }), _), 1) => {
+                let base = match notation {
+                    LogarithmNotation::Ln => None,
+                    LogarithmNotation::Log => Some(number("10")),
+                    LogarithmNotation::LogBase(base) => Some(Atom(*base.clone())),
+                };
+                match base {
+                    None => chain(div(number("1"), f())),
+                    Some(base) => chain(div(number("1"), mul(f(), ln(base)))),
+                }
+            }
+            (Cons(Operator::Sin, _), 1) => chain(apply(Operator::Cos, vec![f()])),
+            (Cons(Operator::Cos, _), 1) => chain(neg(apply(Operator::Sin, vec![f()]))),
+            (Cons(Operator::Tan, _), 1) => chain(square(apply(Operator::Sec, vec![f()]))),
+            (Cons(Operator::Sec, _), 1) => chain(mul(
+                apply(Operator::Sec, vec![f()]),
+                apply(Operator::Tan, vec![f()]),
+            )),
+            (Cons(Operator::Csc, _), 1) => chain(neg(mul(
+                apply(Operator::Csc, vec![f()]),
+                apply(Operator::Cot, vec![f()]),
+            ))),
+            (Cons(Operator::Cot, _), 1) => chain(neg(square(apply(Operator::Csc, vec![f()])))),
+            (Cons(Operator::Arcsin | Operator::Arccos, _), 1) => {
+                let derivative = div(
+                    number("1"),
+                    apply(
+                        Operator::Sqrt,
+                        vec![apply(Operator::Subtract, vec![number("1"), square(f())])],
+                    ),
+                );
+                chain(if let Cons(Operator::Arccos, _) = self {
+                    neg(derivative)
+                } else {
+                    derivative
+                })
+            }
+            (Cons(Operator::Arctan | Operator::Arccot, _), 1) => {
+                let derivative = div(
+                    number("1"),
+                    apply(Operator::Add, vec![number("1"), square(f())]),
+                );
+                chain(if let Cons(Operator::Arccot, _) = self {
+                    neg(derivative)
+                } else {
+                    derivative
+                })
+            }
+            (Cons(Operator::Arcsec | Operator::Arccsc, _), 1) => {
+                let derivative = div(
+                    number("1"),
+                    mul(
+                        apply(Operator::Abs, vec![f()]),
+                        apply(
+                            Operator::Sqrt,
+                            vec![apply(Operator::Subtract, vec![square(f()), number("1")])],
+                        ),
+                    ),
+                );
+                chain(if let Cons(Operator::Arccsc, _) = self {
+                    neg(derivative)
+                } else {
+                    derivative
+                })
+            }
+            (Cons(Operator::Sqrt, _), 1) => chain(div(
+                number("1"),
+                mul(number("2"), apply(Operator::Sqrt, vec![f()])),
+            )),
+            (Cons(Operator::Abs, _), 1) => chain(div(f(), apply(Operator::Abs, vec![f()]))),
+            _ => unknown(),
+        }
+    }
+}
+
+/// The Jacobian matrix of the RHS of a system of first order ODEs with respect to its states.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Jacobian {
+    /// The states of the system, in the order of the rows and columns of the matrix.
+    pub states: Vec<Ci>,
+    /// The entries of the matrix, where entry (i, j) is the partial derivative of the RHS of
+    /// the i-th ODE with respect to the j-th state.
+    pub entries: Vec<Vec<MathExpressionTree>>,
+}
+
+impl Jacobian {
+    /// The Jacobian as a matrix expression.
+    pub fn to_met(&self) -> MathExpressionTree {
+        Cons(
+            Operator::Matrix,
+            self.entries
+                .iter()
+                .map(|row| Cons(Operator::MatrixRow, row.clone()))
+                .collect(),
+        )
+    }
+}
+
+impl From<&[FirstOrderODE]> for Jacobian {
+    fn from(odes: &[FirstOrderODE]) -> Self {
+        let states: Vec<Ci> = odes.iter().map(|ode| ode.lhs_var.clone()).collect();
+        let entries = odes
+            .iter()
+            .map(|ode| {
+                states
+                    .iter()
+                    .map(|state| ode.rhs.differentiate(state))
+                    .collect()
+            })
+            .collect();
+        Jacobian { states, entries }
+    }
+}
+
+impl From<&Vec<FirstOrderODE>> for Jacobian {
+    fn from(odes: &Vec<FirstOrderODE>) -> Self {
+        Jacobian::from(odes.as_slice())
+    }
+}
+
+#[cfg(test)]
+fn derivative(input: &str, var: &str) -> String {
+    let var = Ci::new(
+        None,
+        Box::new(MathExpression::Mi(Mi(var.to_string()))),
+        None,
+        None,
+    );
+    input
+        .parse::<MathExpressionTree>()
+        .unwrap()
+        .differentiate(&var)
+        .to_string()
+}
+
+#[test]
+fn test_differentiate_arithmetic() {
+    assert_eq!(
+        derivative(
+            "<math><mn>3</mn><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><mi>a</mi><mi>x</mi><mo>-</mo><mi>b</mi></math>",
+            "x"
+        ),
+        "(+ a (* 6 x))"
+    );
+    assert_eq!(
+        derivative("<math><mfrac><mn>1</mn><mi>x</mi></mfrac></math>", "x"),
+        "(- (/ 1 (^ x 2)))"
+    );
+    assert_eq!(
+        derivative("<math><msup><mi>a</mi><mi>x</mi></msup></math>", "x"),
+        "(* (Ln a) (^ a x))"
+    );
+}
+
+#[test]
+fn test_differentiate_functions() {
+    assert_eq!(
+        derivative(
+            "<math><msup><mi>e</mi><mrow><mo>-</mo><mi>k</mi><mi>t</mi></mrow></msup></math>",
+            "t"
+        ),
+        "(- (* (exp (- (* k t))) k))"
+    );
+    assert_eq!(
+        derivative(
+            "<math><mrow><mi>sin</mi><mo>(</mo><mi>ω</mi><mi>t</mi><mo>)</mo></mrow></math>",
+            "t"
+        ),
+        "(* (Cos (* t ω)) ω)"
+    );
+    assert_eq!(
+        derivative("<math><msqrt><mi>x</mi></msqrt></math>", "x"),
+        "(/ 1 (* 2 (√ x)))"
+    );
+}
+
+#[test]
+fn test_differentiate_unknown() {
+    // Derivatives of spatial operators are left unevaluated.
+    assert_eq!(
+        derivative("<math><mi>a</mi><mo>+</mo><mo>∇</mo><mi>x</mi></math>", "x"),
+        "(PD(1, x) (Grad x))"
+    );
+}
+
+#[test]
+fn test_jacobian() {
+    let odes: Vec<FirstOrderODE> = [
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi></math>",
+    ]
+    .iter()
+    .map(|input| input.parse::<FirstOrderODE>().unwrap())
+    .collect();
+    let jacobian = Jacobian::from(&odes);
+    let states: Vec<String> = jacobian.states.iter().map(|s| s.to_string()).collect();
+    assert_eq!(states, vec!["S", "I"]);
+    assert_eq!(
+        jacobian.to_met().to_string(),
+        "(Matrix (Row (- (* I β)) (- (* S β))) (Row (* I β) (- (* S β) γ)))"
+    );
+}
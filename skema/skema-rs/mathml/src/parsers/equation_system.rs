@@ -0,0 +1,420 @@
+//! Splitting of MathML documents that hold a whole system of equations (an `<mtable>`, such as
+//! the output of a LaTeX `aligned` environment, or equations separated by line breaks) into one
+//! standalone `<math>` document per equation.
+
+use crate::{
+    normalization::SourceMap,
+    parsers::{
+        generic_mathml::{
+            alignment_marker, attribute, etag, mtable_rows, stag, ws, xml_declaration, IResult,
+            ParseDiagnostic, ParseError, Span,
+        },
+        interpreted_mathml::math_expression,
+    },
+};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_until},
+    character::complete::multispace0,
+    combinator::{all_consuming, opt, peek, recognize, value},
+    multi::many0,
+    sequence::{delimited, tuple},
+};
+use regex::Regex;
+use std::str::FromStr;
+
+#[cfg(test)]
+use crate::parsers::{first_order_ode::FirstOrderODE, math_expression_tree::MathExpressionTree};
+
+/// A single equation extracted from a system document.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Equation<'a> {
+    /// The system document that the equation was extracted from.
+    source: &'a str,
+    /// A standalone `<math>` document containing only this equation.
+    pub mathml: String,
+    source_map: SourceMap,
+}
+
+impl<'a> Equation<'a> {
+    /// Parse the equation, reporting the location of any error relative to the system document
+    /// rather than to `self.mathml`.
+    pub fn parse<T>(&self) -> Result<T, ParseDiagnostic>
+    where
+        T: FromStr<Err = ParseDiagnostic>,
+    {
+        self.mathml
+            .parse::<T>()
+            .map_err(|err| self.source_map.locate(self.source, err))
+    }
+}
+
+/// A piece of an equation: its byte offset in the system document, and its text.
+type Fragment<'a> = (usize, &'a str);
+
+fn fragment(span: Span<'_>) -> Fragment<'_> {
+    (span.location_offset(), span.fragment())
+}
+
+/// An `&` left behind from a LaTeX alignment environment, which `assemble` drops.
+fn ampersand(input: Span) -> IResult<()> {
+    value(
+        (),
+        ws(delimited(
+            stag!("mo"),
+            ws(alt((tag("&amp;"), tag("&")))),
+            etag!("mo"),
+        )),
+    )(input)
+}
+
+/// A comma or period, e.g. at the end of a row of a system, which `assemble` drops there.
+fn punctuation(input: Span) -> IResult<()> {
+    value(
+        (),
+        ws(delimited(
+            stag!("mo"),
+            ws(alt((tag(","), tag(".")))),
+            etag!("mo"),
+        )),
+    )(input)
+}
+
+/// Contents of a table cell, which are parsed as an equation once the cell is split off. An
+/// element that cannot be part of an equation is reported where it occurs, rather than as a
+/// malformed table.
+fn cell(input: Span) -> IResult<Span> {
+    let (s, contents) = recognize(many0(alt((
+        alignment_marker,
+        ampersand,
+        punctuation,
+        value((), math_expression),
+    ))))(input)?;
+    if peek(etag!("mtd"))(s).is_err() {
+        let (s, _) = multispace0(s)?;
+        return Err(nom::Err::Failure(ParseError::new(
+            "Unexpected element in table cell".to_string(),
+            s,
+        )));
+    }
+    Ok((s, contents))
+}
+
+/// The opening of a document whose equations are the rows of a table, up to the table, which
+/// may be wrapped in `<mrow>` or `<mstyle>` elements.
+fn table_start(input: Span) -> IResult<()> {
+    value(
+        (),
+        tuple((
+            opt(xml_declaration),
+            stag!("math"),
+            many0(alt((stag!("mrow"), stag!("mstyle")))),
+            peek(stag!("mtable")),
+        )),
+    )(input)
+}
+
+/// The closing of a document after the table holding its equations.
+fn table_end(input: Span) -> IResult<()> {
+    value(
+        (),
+        tuple((many0(alt((etag!("mrow"), etag!("mstyle")))), etag!("math"))),
+    )(input)
+}
+
+/// The rows of a document whose equations are the rows of a table, or `None` if the document is
+/// not such a table. A table with anything beside it within `<math>`, such as a matrix or the
+/// cases of a piecewise function, is part of a single equation instead.
+fn table_document(input: Span) -> Result<Option<Vec<Vec<Span>>>, ParseDiagnostic> {
+    let input = match table_start(input) {
+        Ok((input, ())) => input,
+        Err(_) => return Ok(None),
+    };
+    let (rest, rows) = mtable_rows(cell)(input).map_err(ParseDiagnostic::from)?;
+    if all_consuming(table_end)(rest).is_err() {
+        return Ok(None);
+    }
+    Ok(Some(
+        rows.into_iter()
+            .map(|cells| cells.into_iter().flatten().collect())
+            .collect(),
+    ))
+}
+
+/// A document whose equations are separated by `<mspace linebreak="newline"/>`, returning the
+/// contents of its `<math>` element.
+fn line_document(input: Span) -> IResult<Span> {
+    delimited(
+        tuple((opt(xml_declaration), stag!("math"))),
+        take_until("</math>"),
+        etag!("math"),
+    )(input)
+}
+
+/// Split the contents of a `<math>` element at forced line breaks.
+fn split_lines(body: Fragment) -> Vec<Vec<Fragment>> {
+    let linebreak =
+        Regex::new(r#"<mspace[^>]*linebreak\s*=\s*["'](?:indenting)?newline["'][^>]*/>"#).unwrap();
+    let (offset, text) = body;
+    let mut lines = Vec::new();
+    let mut last = 0;
+    for m in linebreak.find_iter(text) {
+        lines.push(vec![(offset + last, &text[last..m.start()])]);
+        last = m.end();
+    }
+    lines.push(vec![(offset + last, &text[last..])]);
+    lines
+}
+
+/// Returns true if a piece of MathML only contains an equation number such as `(1)` or `(2a)`.
+fn is_equation_number(mathml: &str) -> bool {
+    let tags = Regex::new(r"<[^>]*>").unwrap();
+    let number = Regex::new(r"^\(\s*[0-9]+[a-z]?\s*\)$").unwrap();
+    number.is_match(tags.replace_all(mathml, "").trim())
+}
+
+/// Assemble the cells of a row into a standalone equation, dropping alignment markup and any
+/// trailing equation number. Returns `None` for rows without content.
+fn assemble<'a>(source: &'a str, mut cells: Vec<Fragment>) -> Option<Equation<'a>> {
+    if cells.len() > 1
+        && cells
+            .last()
+            .is_some_and(|(_, text)| is_equation_number(text))
+    {
+        cells.pop();
+    }
+
+    // Alignment points carry no mathematical meaning, and neither does `&` (which some
+    // converters leave behind from LaTeX alignment environments).
+    let alignment = Regex::new(
+        r#"<maligngroup[^>]*/>|<malignmark[^>]*/>|<mo(?:\s[^>]*)?>\s*&(?:amp;)?\s*</mo>"#,
+    )
+    .unwrap();
+    let mut body = String::new();
+    let mut offsets = Vec::<usize>::new();
+    let mut push = |offset: usize, text: &str| {
+        body.push_str(text);
+        offsets.extend(offset..offset + text.len());
+    };
+    for (offset, text) in cells {
+        let mut last = 0;
+        for m in alignment.find_iter(text) {
+            push(offset + last, &text[last..m.start()]);
+            last = m.end();
+        }
+        push(offset + last, &text[last..]);
+    }
+
+    // Trailing equation numbers written inline, e.g. `<mtext>(1)</mtext>`
+    let trailing_number =
+        Regex::new(r"<mtext[^>]*>\s*\(\s*[0-9]+[a-z]?\s*\)\s*</mtext>\s*$").unwrap();
+    let mut end = trailing_number
+        .find(&body)
+        .map_or(body.len(), |m| m.start());
+    // Punctuation ending the equation, e.g. `dS/dt = -βSI,`
+    let trailing_punctuation = Regex::new(r"<mo(?:\s[^>]*)?>\s*[,.]\s*</mo>\s*$").unwrap();
+    end = trailing_punctuation
+        .find(&body[..end])
+        .map_or(end, |m| m.start());
+    end = body[..end].trim_end().len();
+    let start = body[..end].len() - body[..end].trim_start().len();
+    if start == end {
+        return None;
+    }
+
+    let mathml = format!("<math>{}</math>", &body[start..end]);
+    let mut source_map = vec![offsets[start]; "<math>".len()];
+    source_map.extend_from_slice(&offsets[start..end]);
+    source_map.extend(std::iter::repeat_n(offsets[end - 1] + 1, "</math>".len()));
+    Some(Equation {
+        source,
+        mathml,
+        source_map: SourceMap(source_map),
+    })
+}
+
+/// Split a MathML document containing a system of equations into its individual equations.
+/// Equations are either the rows of an `<mtable>` directly within `<math>` (including
+/// `<mlabeledtr>` rows, whose labels are dropped) or separated by
+/// `<mspace linebreak="newline"/>`. A document with a single equation, which may itself contain
+/// tables, yields a single equation.
+pub fn split_system(input: &str) -> Result<Vec<Equation<'_>>, ParseDiagnostic> {
+    let rows = match table_document(input.into())? {
+        Some(rows) => rows
+            .into_iter()
+            .map(|cells| cells.into_iter().map(fragment).collect())
+            .collect(),
+        None => {
+            let (_, body) =
+                all_consuming(line_document)(input.into()).map_err(ParseDiagnostic::from)?;
+            split_lines(fragment(body))
+        }
+    };
+    Ok(rows
+        .into_iter()
+        .filter_map(|cells| assemble(input, cells))
+        .collect())
+}
+
+/// Parse every equation in a MathML document containing a system of equations, e.g. as
+/// `Vec<MathExpressionTree>` or `Vec<FirstOrderODE>`.
+pub fn parse_system<T>(input: &str) -> Result<Vec<T>, ParseDiagnostic>
+where
+    T: FromStr<Err = ParseDiagnostic>,
+{
+    split_system(input)?
+        .iter()
+        .map(|equation| equation.parse::<T>())
+        .collect()
+}
+
+#[cfg(test)]
+const SIR_EQUATIONS: [&str; 3] = [
+    "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>−</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+    "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>−</mo><mi>γ</mi><mi>I</mi></math>",
+    "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>",
+];
+
+#[test]
+fn test_mtable_system() {
+    let input = r#"<math display="block">
+  <mrow>
+    <mtable columnalign="right left">
+      <mtr>
+        <mtd><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac></mtd>
+        <mtd><maligngroup/><mo>=</mo><mo>−</mo><mi>β</mi><mi>S</mi><mi>I</mi></mtd>
+        <mtd><mtext>(1)</mtext></mtd>
+      </mtr>
+      <mlabeledtr>
+        <mtd><mtext>(2)</mtext></mtd>
+        <mtd><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac></mtd>
+        <mtd><mo>&amp;</mo><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>−</mo><mi>γ</mi><mi>I</mi></mtd>
+      </mlabeledtr>
+      <mtr>
+        <mtd><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac></mtd>
+        <mtd><malignmark edge="left"/><mo>=</mo><mi>γ</mi><mi>I</mi></mtd>
+        <mtd><mo>(</mo><mn>3</mn><mo>)</mo></mtd>
+      </mtr>
+      <mtr><mtd/></mtr>
+    </mtable>
+  </mrow>
+</math>"#;
+    let expected: Vec<FirstOrderODE> = SIR_EQUATIONS.iter().map(|x| x.parse().unwrap()).collect();
+    assert_eq!(parse_system::<FirstOrderODE>(input).unwrap(), expected);
+
+    let expected: Vec<MathExpressionTree> =
+        SIR_EQUATIONS.iter().map(|x| x.parse().unwrap()).collect();
+    assert_eq!(parse_system::<MathExpressionTree>(input).unwrap(), expected);
+}
+
+#[test]
+fn test_linebreak_system() {
+    let input = format!(
+        "<math>{}<mspace linebreak=\"newline\"/>{}<mspace linebreak=\"newline\"/>{}<mtext>(3)</mtext></math>",
+        SIR_EQUATIONS[0].trim_start_matches("<math>").trim_end_matches("</math>"),
+        SIR_EQUATIONS[1].trim_start_matches("<math>").trim_end_matches("</math>"),
+        SIR_EQUATIONS[2].trim_start_matches("<math>").trim_end_matches("</math>"),
+    );
+    let equations = split_system(&input).unwrap();
+    let mathml: Vec<&str> = equations.iter().map(|x| x.mathml.as_str()).collect();
+    assert_eq!(mathml, SIR_EQUATIONS);
+
+    // A single equation is a system of one
+    assert_eq!(split_system(SIR_EQUATIONS[0]).unwrap().len(), 1);
+}
+
+#[test]
+fn test_system_diagnostic_location() {
+    let input = "<math><mtable>
+<mtr><mtd><mover><mi>x</mi><mo>˙</mo></mover><mo>=</mo><mi>y</mi></mtd></mtr>
+<mtr><mtd><mover><mi>y</mi><mo>˙</mo></mover><mo>=</mo><mo>(</mo><mi>x</mi></mtd></mtr>
+</mtable></math>";
+    let equations = split_system(input).unwrap();
+    assert!(equations[0].parse::<FirstOrderODE>().is_ok());
+    let diagnostic = equations[1].parse::<FirstOrderODE>().unwrap_err();
+    assert_eq!(diagnostic.line, Some(3));
+    let offset = diagnostic.offset.unwrap();
+    assert!(
+        input[offset..].starts_with("<mo>(</mo>"),
+        "{}",
+        &input[offset..]
+    );
+}
+
+#[test]
+fn test_punctuated_system() {
+    let row = |equation: &str, punctuation: &str| {
+        format!(
+            "<mtr><mtd>{}<mo>{punctuation}</mo></mtd></mtr>",
+            equation
+                .trim_start_matches("<math>")
+                .trim_end_matches("</math>")
+        )
+    };
+    let input = format!(
+        "<math><mtable>{}{}{}</mtable></math>",
+        row(SIR_EQUATIONS[0], ","),
+        row(SIR_EQUATIONS[1], ","),
+        row(SIR_EQUATIONS[2], "."),
+    );
+    let equations = split_system(&input).unwrap();
+    let mathml: Vec<&str> = equations.iter().map(|x| x.mathml.as_str()).collect();
+    assert_eq!(mathml, SIR_EQUATIONS);
+
+    // Elements that cannot be part of an equation are reported where they occur
+    let input = "<math><mtable><mtr><mtd><mi>x</mi><mo>=</mo><mi>y</mi><mo>;</mo></mtd></mtr></mtable></math>";
+    let diagnostic = split_system(input).unwrap_err();
+    assert_eq!(diagnostic.offset, input.find("<mo>;</mo>"));
+}
+
+#[test]
+fn test_system_with_tables_in_equations() {
+    // A piecewise function is a single equation, not a system
+    let piecewise = "<math><mi>β</mi><mo>=</mo><mrow><mo>{</mo><mtable><mtr><mtd><msub><mi>β</mi><mn>1</mn></msub></mtd><mtd><mtext>if </mtext><mi>t</mi><mo>&lt;</mo><msub><mi>t</mi><mn>0</mn></msub></mtd></mtr><mtr><mtd><msub><mi>β</mi><mn>2</mn></msub></mtd><mtd><mtext>otherwise</mtext></mtd></mtr></mtable></mrow></math>";
+    let equations = split_system(piecewise).unwrap();
+    assert_eq!(equations.len(), 1);
+    assert_eq!(
+        equations[0].parse::<MathExpressionTree>().unwrap(),
+        piecewise.parse::<MathExpressionTree>().unwrap()
+    );
+
+    // Rows of a system with matrices on their right hand sides are kept whole
+    let matrix = "<mrow><mo>[</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr><mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr></mtable><mo>]</mo></mrow>";
+    let input = format!(
+        "<math><mtable><mtr><mtd><mi>A</mi></mtd><mtd><mo>=</mo>{matrix}</mtd></mtr><mtr><mtd><mi>B</mi></mtd><mtd><mo>=</mo>{matrix}<mi>A</mi></mtd></mtr></mtable></math>"
+    );
+    let equations = split_system(&input).unwrap();
+    let mathml: Vec<&str> = equations.iter().map(|x| x.mathml.as_str()).collect();
+    assert_eq!(
+        mathml,
+        [
+            format!("<math><mi>A</mi><mo>=</mo>{matrix}</math>"),
+            format!("<math><mi>B</mi><mo>=</mo>{matrix}<mi>A</mi></math>"),
+        ]
+    );
+    assert!(parse_system::<MathExpressionTree>(&input).is_ok());
+}
+
+#[test]
+fn test_malformed_table() {
+    let diagnostic =
+        split_system("<math><mtable><mtr><mtd><mi>x</mi></mtr></mtable></math>").unwrap_err();
+    assert!(diagnostic.offset.is_some());
+}
+
+#[test]
+fn test_amr_mathml_accepts_system_or_array() {
+    use crate::acset::{AMRmathml, MathMLEquations};
+    let system: AMRmathml =
+        serde_json::from_str(r#"{"model": "petrinet", "mathml": "<math></math>"}"#).unwrap();
+    assert_eq!(
+        system.mathml,
+        MathMLEquations::System("<math></math>".to_string())
+    );
+    let array: AMRmathml =
+        serde_json::from_str(r#"{"model": "petrinet", "mathml": ["<math></math>"]}"#).unwrap();
+    assert_eq!(
+        array.mathml,
+        MathMLEquations::Equations(vec!["<math></math>".to_string()])
+    );
+}
@@ -0,0 +1,429 @@
+//! Numerical evaluation of math expression trees, e.g. for checking that equations extracted from
+//! code and from papers agree.
+
+use crate::{
+    ast::{
+        operator::{Logarithm, LogarithmNotation, Operator},
+        MathExpression, Mi,
+    },
+    parsers::math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+};
+use std::{
+    collections::HashMap,
+    f64::consts::{FRAC_PI_2, PI},
+    fmt,
+};
+
+/// Error raised when an expression cannot be evaluated numerically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// An identifier without a value.
+    UnboundIdentifier(String),
+    /// An operator or expression without a numeric interpretation, e.g. a gradient.
+    NonNumeric(String),
+    /// An operator applied to an unexpected number of arguments.
+    Arity { operator: String, arguments: usize },
+    /// An operator applied outside of its domain, e.g. the factorial of a negative number.
+    Domain(String),
+    /// A numeric literal that cannot be read as a number.
+    InvalidNumber(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::UnboundIdentifier(x) => write!(f, "No value was given for `{x}`"),
+            EvalError::NonNumeric(x) => write!(f, "`{x}` cannot be evaluated numerically"),
+            EvalError::Arity {
+                operator,
+                arguments,
+            } => write!(
+                f,
+                "`{operator}` cannot be applied to {arguments} argument(s)"
+            ),
+            EvalError::Domain(x) => write!(f, "{x}"),
+            EvalError::InvalidNumber(x) => write!(f, "`{x}` is not a valid number"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// An expression compiled into a closure over the values of its variables, in the order in which
+/// the variables were given to [`MathExpressionTree::compile`].
+pub type CompiledExpression = Box<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+/// Numeric functions of a single argument.
+fn unary(op: &Operator) -> Option<fn(f64) -> f64> {
+    let f: fn(f64) -> f64 = match op {
+        Operator::Exp => f64::exp,
+        Operator::Logarithm(Logarithm {
+            notation: LogarithmNotation::Ln,
+        }) => f64::ln,
+        Operator::Logarithm(Logarithm {
+            notation: LogarithmNotation::Log,
+        }) => f64::log10,
+        Operator::Sin => f64::sin,
+        Operator::Cos => f64::cos,
+        Operator::Tan => f64::tan,
+        Operator::Sec => |x| 1.0 / x.cos(),
+        Operator::Csc => |x| 1.0 / x.sin(),
+        Operator::Cot => |x| 1.0 / x.tan(),
+        Operator::Arcsin => f64::asin,
+        Operator::Arccos => f64::acos,
+        Operator::Arctan => f64::atan,
+        Operator::Arcsec => |x| (1.0 / x).acos(),
+        Operator::Arccsc => |x| (1.0 / x).asin(),
+        Operator::Arccot => |x| FRAC_PI_2 - x.atan(),
+        Operator::Sqrt => f64::sqrt,
+        Operator::Abs => f64::abs,
+        _ => return None,
+    };
+    Some(f)
+}
+
+fn truth(holds: bool) -> f64 {
+    if holds {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Numeric functions of two arguments. Relations evaluate to 1 if they hold and 0 otherwise.
+fn binary(op: &Operator) -> Option<fn(f64, f64) -> f64> {
+    let f: fn(f64, f64) -> f64 = match op {
+        Operator::Divide => |x, y| x / y,
+        Operator::Power => f64::powf,
+        Operator::Equals => |x, y| truth(x == y),
+        Operator::NotEqual => |x, y| truth(x != y),
+        Operator::Less => |x, y| truth(x < y),
+        Operator::LessEqual => |x, y| truth(x <= y),
+        Operator::Greater => |x, y| truth(x > y),
+        Operator::GreaterEqual => |x, y| truth(x >= y),
+        _ => return None,
+    };
+    Some(f)
+}
+
+/// The factorial of a non-negative integer, which overflows to infinity beyond 170!.
+fn factorial(x: f64) -> Option<f64> {
+    (x >= 0.0 && x.fract() == 0.0).then(|| (1..=x.min(171.0) as u64).map(|i| i as f64).product())
+}
+
+fn expect_arguments(op: &Operator, args: &[MathExpressionTree], n: usize) -> Result<(), EvalError> {
+    if args.len() == n {
+        Ok(())
+    } else {
+        Err(EvalError::Arity {
+            operator: op.to_string(),
+            arguments: args.len(),
+        })
+    }
+}
+
+/// The name of an identifier, or None for other atoms.
+fn identifier(atom: &MathExpression) -> Option<String> {
+    match atom {
+        MathExpression::Ci(x) => Some(x.content.to_string()),
+        MathExpression::Mi(Mi(x)) => Some(x.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// The value of an identifier that is a well-known mathematical constant.
+fn constant(name: &str) -> Option<f64> {
+    match name {
+        "π" | "pi" => Some(PI),
+        _ => None,
+    }
+}
+
+fn literal(atom: &MathExpression) -> Result<f64, EvalError> {
+    match atom {
+        MathExpression::Mn(x) => x
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| EvalError::InvalidNumber(x.clone())),
+        _ => Err(EvalError::NonNumeric(atom.to_string())),
+    }
+}
+
+impl MathExpressionTree {
+    /// Evaluate the expression, given the values of its identifiers. The constant π is
+    /// recognized when it is not given a value.
+    pub fn eval(&self, env: &HashMap<String, f64>) -> Result<f64, EvalError> {
+        let (op, args) = match self {
+            Atom(atom) => {
+                return match identifier(atom) {
+                    Some(name) => env
+                        .get(&name)
+                        .copied()
+                        .or_else(|| constant(&name))
+                        .ok_or(EvalError::UnboundIdentifier(name)),
+                    None => literal(atom),
+                }
+            }
+            Cons(op, args) => (op, args),
+        };
+        match op {
+            Operator::Add => args.iter().map(|arg| arg.eval(env)).sum(),
+            Operator::Multiply => args.iter().map(|arg| arg.eval(env)).product(),
+            Operator::Subtract if args.len() == 1 => Ok(-args[0].eval(env)?),
+            Operator::Subtract => {
+                expect_arguments(op, args, 2)?;
+                Ok(args[0].eval(env)? - args[1].eval(env)?)
+            }
+            Operator::Min if !args.is_empty() => args
+                .iter()
+                .map(|arg| arg.eval(env))
+                .try_fold(f64::INFINITY, |min, x| Ok(min.min(x?))),
+            Operator::Factorial => {
+                expect_arguments(op, args, 1)?;
+                let x = args[0].eval(env)?;
+                factorial(x).ok_or_else(|| {
+                    EvalError::Domain(format!(
+                        "The factorial of {x} is undefined; it requires a non-negative integer"
+                    ))
+                })
+            }
+            Operator::Logarithm(Logarithm {
+                notation: LogarithmNotation::LogBase(base),
+            }) => {
+                expect_arguments(op, args, 1)?;
+                Ok(args[0].eval(env)?.ln() / Atom(*base.clone()).eval(env)?.ln())
+            }
+            Operator::Piecewise => {
+                for piece in args {
+                    match piece {
+                        Cons(Operator::Piece, piece_args) if piece_args.len() == 2 => {
+                            if piece_args[1].eval(env)? != 0.0 {
+                                return piece_args[0].eval(env);
+                            }
+                        }
+                        Cons(Operator::Otherwise, value) if value.len() == 1 => {
+                            return value[0].eval(env)
+                        }
+                        _ => return Err(EvalError::NonNumeric(piece.to_string())),
+                    }
+                }
+                Err(EvalError::Domain(format!(
+                    "None of the conditions of {self} hold"
+                )))
+            }
+            _ => {
+                if let Some(f) = unary(op) {
+                    expect_arguments(op, args, 1)?;
+                    Ok(f(args[0].eval(env)?))
+                } else if let Some(f) = binary(op) {
+                    expect_arguments(op, args, 2)?;
+                    Ok(f(args[0].eval(env)?, args[1].eval(env)?))
+                } else {
+                    Err(EvalError::NonNumeric(op.to_string()))
+                }
+            }
+        }
+    }
+
+    /// Compile the expression into a closure over the values of the given variables, for fast
+    /// repeated evaluation. Unbound identifiers and non-numeric operators are reported at compile
+    /// time; operators applied outside of their domain evaluate to NaN.
+    pub fn compile(&self, variables: &[String]) -> Result<CompiledExpression, EvalError> {
+        let (op, args) = match self {
+            Atom(atom) => {
+                return match identifier(atom) {
+                    Some(name) => match variables.iter().position(|var| *var == name) {
+                        Some(i) => Ok(Box::new(move |values: &[f64]| values[i])),
+                        None => match constant(&name) {
+                            Some(x) => Ok(Box::new(move |_: &[f64]| x)),
+                            None => Err(EvalError::UnboundIdentifier(name)),
+                        },
+                    },
+                    None => {
+                        let x = literal(atom)?;
+                        Ok(Box::new(move |_: &[f64]| x))
+                    }
+                }
+            }
+            Cons(op, args) => (op, args),
+        };
+        match op {
+            Operator::Piecewise => {
+                let mut pieces = vec![];
+                let mut otherwise = None;
+                for piece in args {
+                    match piece {
+                        Cons(Operator::Piece, piece_args) if piece_args.len() == 2 => {
+                            pieces.push((
+                                piece_args[0].compile(variables)?,
+                                piece_args[1].compile(variables)?,
+                            ))
+                        }
+                        Cons(Operator::Otherwise, value) if value.len() == 1 => {
+                            otherwise = Some(value[0].compile(variables)?)
+                        }
+                        _ => return Err(EvalError::NonNumeric(piece.to_string())),
+                    }
+                }
+                return Ok(Box::new(move |values| {
+                    pieces
+                        .iter()
+                        .find(|(_, condition)| condition(values) != 0.0)
+                        .map(|(value, _)| value(values))
+                        .or_else(|| otherwise.as_ref().map(|value| value(values)))
+                        .unwrap_or(f64::NAN)
+                }));
+            }
+            Operator::Logarithm(Logarithm {
+                notation: LogarithmNotation::LogBase(base),
+            }) => {
+                expect_arguments(op, args, 1)?;
+                let (x, base) = (
+                    args[0].compile(variables)?,
+                    Atom(*base.clone()).compile(variables)?,
+                );
+                return Ok(Box::new(move |values| x(values).ln() / base(values).ln()));
+            }
+            _ => {}
+        }
+        let mut compiled = args
+            .iter()
+            .map(|arg| arg.compile(variables))
+            .collect::<Result<Vec<CompiledExpression>, EvalError>>()?
+            .into_iter();
+        let mut next = || compiled.next().unwrap();
+        Ok(match op {
+            Operator::Add => {
+                let terms: Vec<CompiledExpression> = (0..args.len()).map(|_| next()).collect();
+                Box::new(move |values| terms.iter().map(|term| term(values)).sum())
+            }
+            Operator::Multiply => {
+                let factors: Vec<CompiledExpression> = (0..args.len()).map(|_| next()).collect();
+                Box::new(move |values| factors.iter().map(|factor| factor(values)).product())
+            }
+            Operator::Subtract if args.len() == 1 => {
+                let x = next();
+                Box::new(move |values| -x(values))
+            }
+            Operator::Subtract => {
+                expect_arguments(op, args, 2)?;
+                let (x, y) = (next(), next());
+                Box::new(move |values| x(values) - y(values))
+            }
+            Operator::Min if !args.is_empty() => {
+                let xs: Vec<CompiledExpression> = (0..args.len()).map(|_| next()).collect();
+                Box::new(move |values| xs.iter().map(|x| x(values)).fold(f64::INFINITY, f64::min))
+            }
+            Operator::Factorial => {
+                expect_arguments(op, args, 1)?;
+                let x = next();
+                Box::new(move |values| factorial(x(values)).unwrap_or(f64::NAN))
+            }
+            _ => {
+                if let Some(f) = unary(op) {
+                    expect_arguments(op, args, 1)?;
+                    let x = next();
+                    Box::new(move |values| f(x(values)))
+                } else if let Some(f) = binary(op) {
+                    expect_arguments(op, args, 2)?;
+                    let (x, y) = (next(), next());
+                    Box::new(move |values| f(x(values), y(values)))
+                } else {
+                    return Err(EvalError::NonNumeric(op.to_string()));
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+fn env(bindings: &[(&str, f64)]) -> HashMap<String, f64> {
+    bindings
+        .iter()
+        .map(|(name, value)| (name.to_string(), *value))
+        .collect()
+}
+
+#[test]
+fn test_eval_arithmetic() {
+    let met = "<math><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>+</mo><mfrac><msup><mi>I</mi><mn>2</mn></msup><mn>4</mn></mfrac></math>"
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    let bindings = env(&[("β", 0.5), ("S", 10.0), ("I", 2.0)]);
+    assert_eq!(met.eval(&bindings), Ok(-9.0));
+    let variables: Vec<String> = vec!["β".to_string(), "S".to_string(), "I".to_string()];
+    let compiled = met.compile(&variables).unwrap();
+    assert_eq!(compiled(&[0.5, 10.0, 2.0]), -9.0);
+    assert_eq!(compiled(&[0.0, 10.0, 4.0]), 4.0);
+
+    // A middle dot is a product, as `\cdot` is in LaTeX
+    let met = "<math><mi>β</mi><mo>&middot;</mo><mi>S</mi><mo>·</mo><mi>I</mi></math>"
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    assert_eq!(met.eval(&bindings), Ok(10.0));
+    assert_eq!(
+        MathExpressionTree::from_latex("\\beta \\cdot S \\cdot I").unwrap(),
+        met
+    );
+}
+
+#[test]
+fn test_eval_functions() {
+    let cmml = "<apply><plus/>
+        <apply><exp/><cn>0</cn></apply>
+        <apply><sin/><apply><divide/><ci>π</ci><cn>2</cn></apply></apply>
+        <apply><abs/><apply><minus/><cn>3</cn></apply></apply>
+        <apply><root/><cn>16</cn></apply>
+        <apply><factorial/><cn>4</cn></apply>
+        <apply><min/><ci>x</ci><cn>2</cn></apply>
+    </apply>";
+    let met = MathExpressionTree::from_cmml(cmml).unwrap();
+    assert_eq!(
+        met.eval(&env(&[("x", -1.0)])),
+        Ok(1.0 + 1.0 + 3.0 + 4.0 + 24.0 - 1.0)
+    );
+    let compiled = met.compile(&["x".to_string()]).unwrap();
+    assert_eq!(compiled(&[5.0]), 1.0 + 1.0 + 3.0 + 4.0 + 24.0 + 2.0);
+}
+
+#[test]
+fn test_eval_piecewise() {
+    let met = "
+    <math>
+        <mrow><mo>{</mo><mtable>
+            <mtr><mtd><mn>0</mn></mtd><mtd><mi>x</mi><mo>≤</mo><mn>0</mn></mtd></mtr>
+            <mtr><mtd><mi>x</mi></mtd><mtd><mtext>otherwise</mtext></mtd></mtr>
+        </mtable></mrow>
+    </math>"
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    assert_eq!(met.eval(&env(&[("x", -2.0)])), Ok(0.0));
+    assert_eq!(met.eval(&env(&[("x", 3.0)])), Ok(3.0));
+    let compiled = met.compile(&["x".to_string()]).unwrap();
+    assert_eq!(compiled(&[-2.0]), 0.0);
+    assert_eq!(compiled(&[3.0]), 3.0);
+}
+
+#[test]
+fn test_eval_errors() {
+    let met = "<math><mi>a</mi><mo>+</mo><mi>b</mi></math>"
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    assert_eq!(
+        met.eval(&env(&[("a", 1.0)])),
+        Err(EvalError::UnboundIdentifier("b".to_string()))
+    );
+    assert!(met.compile(&["a".to_string()]).is_err());
+    let met = "<math><mo>∇</mo><mi>u</mi></math>"
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    assert!(matches!(
+        met.eval(&env(&[("u", 1.0)])),
+        Err(EvalError::NonNumeric(_))
+    ));
+    let met = MathExpressionTree::from_cmml("<apply><factorial/><cn>-1</cn></apply>").unwrap();
+    assert!(matches!(
+        met.eval(&HashMap::new()),
+        Err(EvalError::Domain(_))
+    ));
+}
@@ -0,0 +1,218 @@
+//! Expansion of math expression trees into flat sums of signed monomials, by distributing
+//! products and integer powers over sums. This is the form that Petri net and RegNet
+//! construction reads transitions from: each monomial of an ODE's RHS is one term.
+
+use crate::{
+    ast::{operator::Operator, MathExpression},
+    parsers::{
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+        simplification::{collect, number, number_expression, Number, Term},
+    },
+};
+
+/// Powers of sums are only multiplied out up to this exponent, and kept as opaque factors above.
+const MAX_EXPANDED_POWER: i64 = 32;
+
+/// A numeric coefficient times a product of powers of factors. The factors are identifiers or
+/// opaque subexpressions that are not polynomial, e.g. exp(-k*t), a piecewise function or the
+/// sum in a denominator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monomial {
+    /// Exact fraction, unless the expression contains floating point numbers.
+    pub coefficient: Number,
+    /// Pairs of (base, exponent), in the order in which the bases first appear.
+    pub factors: Vec<(MathExpressionTree, MathExpressionTree)>,
+}
+
+/// Flat product, so that monomials read as written, e.g. β*S*I.
+fn flat_product(mut factors: Vec<MathExpressionTree>) -> MathExpressionTree {
+    match factors.len() {
+        0 => Atom(MathExpression::Mn("1".to_string())),
+        1 => factors.pop().unwrap(),
+        _ => Cons(Operator::Multiply, factors),
+    }
+}
+
+impl Monomial {
+    pub fn is_negative(&self) -> bool {
+        self.coefficient.is_negative()
+    }
+
+    /// The monomial without its sign, e.g. the rate of the transition it describes.
+    pub fn magnitude(&self) -> MathExpressionTree {
+        self.term().to_expression(flat_product).1
+    }
+
+    /// The monomial as an expression, with a unary minus if it is negative.
+    pub fn to_expression(&self) -> MathExpressionTree {
+        match self.term().to_expression(flat_product) {
+            (true, magnitude) => Cons(Operator::Subtract, vec![magnitude]),
+            (false, magnitude) => magnitude,
+        }
+    }
+
+    fn term(&self) -> Term {
+        Term {
+            coefficient: self.coefficient,
+            factors: self.factors.clone(),
+        }
+    }
+}
+
+impl From<Term> for Monomial {
+    fn from(term: Term) -> Monomial {
+        Monomial {
+            coefficient: term.coefficient,
+            factors: term.factors,
+        }
+    }
+}
+
+/// Multiply every term of one sum with every term of another.
+fn distribute(lhs: Vec<Term>, rhs: &[Term]) -> Vec<Term> {
+    let mut terms = vec![];
+    for a in lhs.iter() {
+        for b in rhs.iter() {
+            terms.push(a.clone().mul(b.clone()));
+        }
+    }
+    collect(terms)
+}
+
+fn opaque(base: &MathExpressionTree, exponent: MathExpressionTree) -> Vec<Term> {
+    vec![Term::factor(base.clone(), exponent)]
+}
+
+/// The terms of a power, distributed over the base if the exponent is an integer.
+fn power_terms(base: &MathExpressionTree, exponent: &MathExpressionTree) -> Vec<Term> {
+    let base_terms = terms(base);
+    let integer = number(exponent).and_then(Number::as_integer);
+    match (base_terms.as_slice(), integer) {
+        ([term], Some(n)) => match term.clone().powi(n) {
+            Some(term) => vec![term],
+            None => opaque(base, exponent.clone()),
+        },
+        (_, Some(n)) if (0..=MAX_EXPANDED_POWER).contains(&n) => (0..n)
+            .fold(vec![Term::constant(Number::Rational(1, 1))], |acc, _| {
+                distribute(acc, &base_terms)
+            }),
+        // Non-integer powers of a single identifier stay monomials, e.g. S^(1/2).
+        ([term], None) if term.coefficient.is_one() && term.factors.len() == 1 => {
+            let (base, e) = term.factors[0].clone();
+            vec![Term::factor(
+                base,
+                Cons(Operator::Multiply, vec![e, exponent.clone()]).simplify(),
+            )]
+        }
+        _ => opaque(base, exponent.clone()),
+    }
+}
+
+/// The terms of an expression, with products and integer powers distributed over sums.
+fn terms(met: &MathExpressionTree) -> Vec<Term> {
+    if let Some(n) = number(met) {
+        return vec![Term::constant(n)];
+    }
+    let one = || Atom(MathExpression::Mn("1".to_string()));
+    match met {
+        Cons(Operator::Add, args) => collect(args.iter().flat_map(terms).collect()),
+        Cons(Operator::Subtract, args) if args.len() == 1 => {
+            terms(&args[0]).into_iter().map(Term::neg).collect()
+        }
+        Cons(Operator::Subtract, args) if args.len() == 2 => {
+            let mut all_terms = terms(&args[0]);
+            all_terms.extend(terms(&args[1]).into_iter().map(Term::neg));
+            collect(all_terms)
+        }
+        Cons(Operator::Multiply, args) => args
+            .iter()
+            .fold(vec![Term::constant(Number::Rational(1, 1))], |acc, arg| {
+                distribute(acc, &terms(arg))
+            }),
+        Cons(Operator::Divide, args) if args.len() == 2 => {
+            let denominator = match terms(&args[1]).as_slice() {
+                [term] => term.clone().powi(-1),
+                _ => None,
+            };
+            let denominator = denominator.unwrap_or_else(|| {
+                Term::factor(args[1].clone(), number_expression(Number::Rational(-1, 1)))
+            });
+            distribute(terms(&args[0]), &[denominator])
+        }
+        Cons(Operator::Power, args) if args.len() == 2 => power_terms(&args[0], &args[1]),
+        _ => vec![Term::factor(met.clone(), one())],
+    }
+}
+
+impl MathExpressionTree {
+    /// The monomials of the expanded expression, in the order in which they first appear. Like
+    /// monomials are added up and monomials that cancel out are dropped.
+    pub fn monomials(&self) -> Vec<Monomial> {
+        terms(self).into_iter().map(Monomial::from).collect()
+    }
+
+    /// Expand the expression into a flat sum of signed monomials, e.g. β*S*(I+R)/N into
+    /// β*S*I/N + β*S*R/N. Factors that are not polynomial are kept as they are.
+    pub fn expand(&self) -> MathExpressionTree {
+        let mut monomials: Vec<MathExpressionTree> = self
+            .monomials()
+            .iter()
+            .map(Monomial::to_expression)
+            .collect();
+        match monomials.len() {
+            0 => Atom(MathExpression::Mn("0".to_string())),
+            1 => monomials.pop().unwrap(),
+            _ => Cons(Operator::Add, monomials),
+        }
+    }
+}
+
+#[cfg(test)]
+fn expanded(input: &str) -> String {
+    format!("<math>{input}</math>")
+        .parse::<MathExpressionTree>()
+        .unwrap()
+        .expand()
+        .to_string()
+}
+
+#[test]
+fn test_distribution() {
+    assert_eq!(
+        expanded("<mi>β</mi><mi>S</mi><mo>(</mo><mi>I</mi><mo>+</mo><mi>R</mi><mo>)</mo>"),
+        "(+ (* β S I) (* β S R))"
+    );
+    assert_eq!(
+        expanded("<mo>(</mo><mi>a</mi><mo>+</mo><mi>b</mi><mo>)</mo><mo>(</mo><mi>a</mi><mo>-</mo><mi>b</mi><mo>)</mo>"),
+        "(+ (^ a 2) (- (^ b 2)))"
+    );
+    assert_eq!(
+        expanded("<msup><mrow><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo></mrow><mn>2</mn></msup>"),
+        "(+ (^ x 2) (* 2 x) 1)"
+    );
+    assert_eq!(expanded("<mi>x</mi><mo>-</mo><mi>x</mi>"), "0");
+}
+
+#[test]
+fn test_rational_coefficients_and_opaque_factors() {
+    assert_eq!(
+        expanded("<mfrac><mrow><mi>β</mi><mo>(</mo><mi>S</mi><mo>+</mo><mi>I</mi><mo>)</mo></mrow><mn>2</mn></mfrac>"),
+        "(+ (/ (* β S) 2) (/ (* β I) 2))"
+    );
+    assert_eq!(
+        expanded("<mfrac><mrow><mi>β</mi><mi>S</mi><mi>I</mi></mrow><mrow><mi>S</mi><mo>+</mo><mi>I</mi></mrow></mfrac>"),
+        "(/ (* β S I) (+ S I))"
+    );
+    // Non-integer exponents no longer need to be distributed.
+    assert_eq!(
+        expanded("<mi>k</mi><msup><mi>S</mi><mrow><mn>1</mn><mo>/</mo><mn>2</mn></mrow></msup>"),
+        "(* k (^ S (/ 1 2)))"
+    );
+    let monomials = "<math><mo>-</mo><mrow><mi>exp</mi><mo>(</mo><mo>-</mo><mi>k</mi><mi>t</mi><mo>)</mo></mrow><mo>(</mo><mi>x</mi><mo>+</mo><mi>y</mi><mo>)</mo></math>"
+    .parse::<MathExpressionTree>()
+    .unwrap()
+    .monomials();
+    assert_eq!(monomials.len(), 2);
+    assert!(monomials.iter().all(Monomial::is_negative));
+    assert_eq!(monomials[0].factors.len(), 2);
+}
@@ -1,4 +1,4 @@
-use crate::ast::operator::Operator::{Add, Divide, Multiply, Power, Subtract};
+use crate::ast::operator::Operator::{Divide, Multiply};
 use crate::parsers::math_expression_tree::MathExpressionTree::Atom;
 use crate::parsers::math_expression_tree::MathExpressionTree::Cons;
 use crate::{
@@ -6,8 +6,12 @@ use crate::{
         operator::{Derivative, Operator},
         Ci, MathExpression, Type,
     },
+    normalization::normalize_with_source_map,
     parsers::{
-        generic_mathml::{attribute, equals, etag, stag, ws, IResult, Span},
+        expansion::Monomial,
+        generic_mathml::{
+            attribute, equals, etag, stag, ws, IResult, ParseDiagnostic, ParseError, Span,
+        },
         interpreted_mathml::{
             ci_univariate_with_bounds, ci_univariate_without_bounds, ci_unknown_with_bounds,
             ci_unknown_without_bounds, first_order_dderivative_leibniz_notation,
@@ -28,7 +32,7 @@ use nom::{
     multi::{many0, many1},
     sequence::{delimited, tuple},
 };
-use std::convert::TryInto;
+use std::convert::TryFrom;
 
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -59,6 +63,7 @@ pub fn first_order_ode(input: Span) -> IResult<FirstOrderODE> {
     let (s, _) = stag!("math")(input)?;
 
     // Recognize LHS derivative
+    let lhs_start = s;
     let (s, (derivative, ci)) = alt((
         first_order_derivative_leibniz_notation,
         newtonian_derivative,
@@ -66,6 +71,12 @@ pub fn first_order_ode(input: Span) -> IResult<FirstOrderODE> {
         first_order_partial_derivative_partial_func,
         first_order_dderivative_leibniz_notation,
     ))(s)?;
+    if derivative.order != 1 {
+        return Err(nom::Err::Error(ParseError::new(
+            "Expected a first order derivative".to_string(),
+            lhs_start,
+        )));
+    }
     //let ci = binding.content;
     //let parenthesized = ci.func_of.clone();
     let mut parenthesized: Vec<Ci> = Vec::new();
@@ -83,6 +94,7 @@ pub fn first_order_ode(input: Span) -> IResult<FirstOrderODE> {
 
     // Recognize equals sign
     let (s, _) = delimited(stag!("mo"), equals, etag!("mo"))(s)?;
+    let rhs_start = s;
 
     // Recognize other tokens
     let (s, remaining_tokens) = many1(alt((
@@ -124,19 +136,27 @@ pub fn first_order_ode(input: Span) -> IResult<FirstOrderODE> {
         map(operator, MathExpression::Mo),
         math_expression,
     )))(s)?;
+    let rhs = MathExpressionTree::try_from(remaining_tokens).map_err(|err| {
+        let mut parse_error = ParseError::new(err.message, rhs_start);
+        if let Some(token) = err.token {
+            parse_error = parse_error.with_token(token);
+        }
+        nom::Err::Failure(parse_error)
+    })?;
     let (s, _) = etag!("math")(s)?;
 
     let ode = FirstOrderODE {
         lhs_var: ci,
         func_of: parenthesized,
         with_respect_to: bvar,
-        rhs: MathExpressionTree::from(remaining_tokens),
+        rhs,
     };
 
     Ok((s, ode))
 }
 impl FirstOrderODE {
-    pub fn to_cmml(&self) -> String {
+    /// The ODE as an equation whose LHS is the derivative of the LHS variable.
+    pub fn to_met(&self) -> MathExpressionTree {
         let lhs_expression_tree = MathExpressionTree::Cons(
             Operator::Derivative(Derivative::new(
                 1,
@@ -148,21 +168,66 @@ impl FirstOrderODE {
                 self.lhs_var.clone(),
             ))],
         );
-        let combined = MathExpressionTree::Cons(
+        MathExpressionTree::Cons(
             Operator::Equals,
             vec![lhs_expression_tree, self.rhs.clone()],
-        );
-        combined.to_cmml()
+        )
+    }
+
+    pub fn to_cmml(&self) -> String {
+        self.to_met().to_cmml()
+    }
+
+    /// Whether two ODEs are the same up to the order of commutative operands in their RHS.
+    pub fn equivalent(&self, other: &FirstOrderODE) -> bool {
+        self.lhs_var.to_string() == other.lhs_var.to_string()
+            && self.with_respect_to.to_string() == other.with_respect_to.to_string()
+            && self.rhs.equivalent(&other.rhs)
     }
 }
 
 impl FromStr for FirstOrderODE {
-    type Err = String;
+    type Err = ParseDiagnostic;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        first_order_ode(s.into())
+        let (normalized, source_map) = normalize_with_source_map(s);
+        first_order_ode(normalized.as_str().into())
             .map(|(_, ode)| ode)
-            .map_err(|err| err.to_string())
+            .map_err(|err| source_map.locate(s, ParseDiagnostic::from(err)))
+    }
+}
+
+impl TryFrom<MathExpressionTree> for FirstOrderODE {
+    type Error = ParseDiagnostic;
+
+    /// Interpret an equation of the form d(lhs_var)/d(with_respect_to) = rhs as a first order ODE.
+    fn try_from(met: MathExpressionTree) -> Result<Self, Self::Error> {
+        if let Cons(Operator::Equals, mut args) = met {
+            if args.len() == 2 {
+                let rhs = args.pop().unwrap();
+                if let Some(Cons(
+                    Operator::Derivative(Derivative {
+                        order: 1,
+                        bound_var,
+                        ..
+                    }),
+                    mut lhs,
+                )) = args.pop()
+                {
+                    if let (1, Some(Atom(MathExpression::Ci(lhs_var)))) = (lhs.len(), lhs.pop()) {
+                        return Ok(FirstOrderODE {
+                            func_of: lhs_var.func_of.clone().unwrap_or_default(),
+                            lhs_var,
+                            with_respect_to: bound_var,
+                            rhs,
+                        });
+                    }
+                }
+            }
+        }
+        Err(ParseDiagnostic::new(
+            "Expected a first order ODE of the form dx/dt = f(x)".to_string(),
+        ))
     }
 }
 
@@ -200,948 +265,87 @@ pub struct PnTerm {
     pub expression: String,             // content mathml for the expression
     pub expression_infix: String,
     pub parameters: Vec<String>,        // list of parameters in term
-    pub sub_terms: Option<Vec<PnTerm>>, // This is to handle when we need to distribute or not for terms.
-    pub math_vec: Option<MathExpressionTree>, // This is to allows for easy distribution using our current frame work
+    pub math_vec: Option<MathExpressionTree>, // the rate expression of the term, without its sign
 }
 
-// refactored
-// this function takes in one ode equations and returns a vector of the terms in it
+// this function takes in one ode equations and returns a vector of the terms in it, one for each
+// monomial of its expanded RHS. The RHS is simplified first, so that equations written
+// differently (e.g. extracted from code or from papers) give the same terms.
 pub fn get_terms(sys_states: Vec<String>, ode: FirstOrderODE) -> Vec<PnTerm> {
-    let mut terms = Vec::<PnTerm>::new();
-    let _exp_states = Vec::<String>::new();
-    let _parameters = Vec::<String>::new();
-
     let dyn_state = ode.lhs_var.to_string();
-
-    match ode.rhs {
-        Cons(ref x, ref y) => match &x {
-            Multiply => {
-                let mut temp_term = get_terms_mult(sys_states, y.clone());
-                temp_term.dyn_state = dyn_state;
-                terms.push(temp_term);
-            }
-            Divide => {
-                let mut temp_term = get_term_div(sys_states, y.clone());
-                temp_term.dyn_state = dyn_state;
-                terms.push(temp_term);
-            }
-            Add => {
-                let temp_terms = get_terms_add(sys_states, y.clone());
-                for term in temp_terms.iter() {
-                    let mut t_term = term.clone();
-                    t_term.dyn_state = dyn_state.clone();
-                    terms.push(t_term.clone());
-                }
-            }
-            Subtract => {
-                let temp_terms = get_terms_sub(sys_states, y.clone());
-                for term in temp_terms.iter() {
-                    let mut t_term = term.clone();
-                    t_term.dyn_state = dyn_state.clone();
-                    terms.push(t_term.clone());
-                }
-            }
-            Power => {
-                let mut temp_term = get_term_power(sys_states, y.clone());
-                temp_term.dyn_state = dyn_state;
-                terms.push(temp_term);
-            }
-            _ => {
-                println!("Warning unsupported case");
-            }
-        },
-        Atom(ref x) => {
-            // also need to construct a partial term here to handle distribution of just a parameter
-            let mut is_state = false;
-            for state in sys_states.iter() {
-                if x.to_string() == *state {
-                    is_state = true;
-                }
-            }
-            if is_state {
-                let temp_term = PnTerm {
-                    dyn_state: "temp".to_string(),
-                    exp_states: [x.to_string().clone()].to_vec(),
-                    polarity: true,
-                    expression: "".to_string(),
-                    expression_infix: "".to_string(),
-                    parameters: Vec::<String>::new(),
-                    sub_terms: None,
-                    math_vec: None,
-                };
-                terms.push(temp_term.clone());
-            } else {
-                let temp_term = PnTerm {
-                    dyn_state: "temp".to_string(),
-                    exp_states: Vec::<String>::new(),
-                    polarity: true,
-                    expression: "".to_string(),
-                    expression_infix: "".to_string(),
-                    parameters: [x.to_string().clone()].to_vec(),
-                    sub_terms: None,
-                    math_vec: None,
-                };
-                terms.push(temp_term.clone());
-            }
-        }
-    }
-    terms
+    // The independent variable only enters rate expressions through the conditions of piecewise
+    // functions, and is not a parameter.
+    let time = ode.with_respect_to.to_string();
+    ode.rhs
+        .simplify()
+        .monomials()
+        .iter()
+        .map(|monomial| {
+            let mut term = get_term(&sys_states, monomial);
+            term.dyn_state = dyn_state.clone();
+            term.parameters.retain(|parameter| *parameter != time);
+            term
+        })
+        .collect()
 }
 
-pub fn get_term_power(sys_states: Vec<String>, eq: Vec<MathExpressionTree>) -> PnTerm {
-    let mut variables = Vec::<String>::new();
+// this takes in a monomial and returns the PnTerm for it. States raised to a positive integer
+// power enter the term that many times, states inside other factors (e.g. a denominator or a
+// piecewise function) once.
+pub fn get_term(sys_states: &[String], monomial: &Monomial) -> PnTerm {
     let mut exp_states = Vec::<String>::new();
-    let mut polarity = true;
-    let mut power = 0;
-    // assume power arguments are only length 2.
-    power = eq[1].to_string().parse::<i32>().unwrap();
-
-    // this walks the tree and composes a vector of all variable and polarity changes
-    for (i, obj) in eq.iter().enumerate() {
-        match obj {
-            Cons(x, y) => {
-                match &x {
-                    Subtract => {
-                        if y.len() == 1 {
-                            polarity = false;
-                            variables.push(y[0].to_string());
-                        } else {
-                            for var in y.iter() {
-                                variables.push(var.to_string().clone());
-                            }
-                        }
-                    }
-                    Multiply => {
-                        // call mult function to get a partial term
-                        let mut temp_term = get_term_mult(sys_states.clone(), y.clone());
-
-                        // parse term polarity
-                        polarity = temp_term.polarity;
-
-                        // parse term parameters and expression states
-                        // need to do both to populate both later
-                        variables.append(&mut temp_term.parameters);
-                        let mut j = 0;
-                        while j < power {
-                            variables.append(&mut temp_term.exp_states);
-                            j += 1;
-                        }
-                    }
-                    Add => {
-                        if y.len() == 1 {
-                            // really should need to support unary addition, but oh well
-                            variables.push(y[0].to_string());
-                        } else {
-                            for var in y.iter() {
-                                variables.push(var.to_string().clone());
-                            }
-                        }
-                    }
-                    _ => {
-                        println!("Not expected operation inside Power")
-                    }
-                }
+    let mut variables = Vec::<String>::new();
+    for (base, exponent) in monomial.factors.iter() {
+        let name = base.to_string();
+        match exponent.to_string().parse::<usize>() {
+            Ok(power) if matches!(base, Atom(_)) && sys_states.contains(&name) => {
+                exp_states.extend(std::iter::repeat_n(name, power));
             }
-            Atom(x) => {
-                // need to add expression state a number of times equal to the power
-                if i == 0 {
-                    let mut j = 0;
-                    while j < power {
-                        variables.push(x.to_string());
-                        j += 1;
-                    }
-                } else {
-                    variables.push(x.to_string());
-                }
+            _ => {
+                collect_identifiers(base, &mut variables);
+                collect_identifiers(exponent, &mut variables);
             }
         }
     }
-
-    // this compiles the vector of expression states for the term
-    let mut ind = Vec::<usize>::new();
-    for (i, var) in variables.iter().enumerate() {
-        for sys_var in sys_states.iter() {
-            if var == sys_var {
-                exp_states.push(var.clone());
-                ind.push(i);
-            }
+    for var in variables.iter() {
+        if sys_states.contains(var) && !exp_states.contains(var) {
+            exp_states.push(var.clone());
         }
     }
-
-    // this removes the expression states from the variable vector
-    for i in ind.iter().rev() {
-        variables.remove(*i);
-    }
-
-    // now to dedup variables and exp_states
+    variables.retain(|var| !sys_states.contains(var));
     variables.sort();
     variables.dedup();
     exp_states.sort();
-    //exp_states.dedup();
-
-    PnTerm {
-        dyn_state: "temp".to_string(),
-        exp_states,
-        polarity,
-        expression: MathExpressionTree::Cons(Multiply, eq.clone()).to_cmml(),
-        expression_infix: MathExpressionTree::Cons(Multiply, eq).to_infix_expression(),
-        parameters: variables,
-        sub_terms: None,
-        math_vec: None,
-    }
-}
-
-// this takes in the arguments of a closer to root level add operator and returns the PnTerms for it's subgraphs
-// we do expect at most multiplication, subtraction, division, or addition
-pub fn get_terms_add(sys_states: Vec<String>, eq: Vec<MathExpressionTree>) -> Vec<PnTerm> {
-    let mut terms = Vec::<PnTerm>::new();
-
-    /* found multiple terms */
-
-    for arg in eq.iter() {
-        match &arg {
-            Cons(x1, ref y1) => match x1 {
-                Multiply => {
-                    let mut temp_term = get_terms_mult(sys_states.clone(), y1.clone());
-                    temp_term.math_vec = Some(arg.clone());
-                    terms.push(temp_term);
-                }
-                Divide => {
-                    let mut temp_term = get_term_div(sys_states.clone(), y1.clone());
-                    temp_term.math_vec = Some(arg.clone());
-                    terms.push(temp_term);
-                }
-                Subtract => {
-                    let temp_terms = get_terms_sub(sys_states.clone(), y1.clone());
-                    for term in temp_terms.iter() {
-                        terms.push(term.clone());
-                    }
-                }
-                Add => {
-                    let temp_terms = get_terms_add(sys_states.clone(), y1.clone());
-                    for term in temp_terms.iter() {
-                        terms.push(term.clone());
-                    }
-                }
-                Power => {
-                    let mut temp_term = get_term_power(sys_states.clone(), y1.clone());
-                    temp_term.math_vec = Some(arg.clone());
-                    terms.push(temp_term);
-                }
-                _ => {
-                    println!("Error unsupported operation")
-                }
-            },
-            Atom(ref x) => {
-                // also need to construct a partial term here to handle distribution of just a parameter
-                let mut is_state = false;
-                for state in sys_states.iter() {
-                    if x.to_string() == *state {
-                        is_state = true;
-                    }
-                }
-                if is_state {
-                    let temp_term = PnTerm {
-                        dyn_state: "temp".to_string(),
-                        exp_states: [x.to_string().clone()].to_vec(),
-                        polarity: true,
-                        expression: MathExpressionTree::Cons(Add, [arg.clone()].to_vec()).to_cmml(),
-                        expression_infix: MathExpressionTree::Cons(Add, [arg.clone()].to_vec()).to_infix_expression(),
-                        parameters: Vec::<String>::new(),
-                        sub_terms: None,
-                        math_vec: Some(arg.clone()),
-                    };
-                    terms.push(temp_term.clone());
-                } else {
-                    let temp_term = PnTerm {
-                        dyn_state: "temp".to_string(),
-                        exp_states: Vec::<String>::new(),
-                        polarity: true,
-                        expression: MathExpressionTree::Cons(Add, [arg.clone()].to_vec()).to_cmml(),
-                        expression_infix: MathExpressionTree::Cons(Add, [arg.clone()].to_vec()).to_infix_expression(),
-                        parameters: [x.to_string().clone()].to_vec(),
-                        sub_terms: None,
-                        math_vec: Some(arg.clone()),
-                    };
-                    terms.push(temp_term.clone());
-                }
-            }
-        }
-    }
-    terms
-}
-
-// this takes in the arguments of a closer to root level sub operator and returns the PnTerms for it's subgraphs
-// we do expect at most multiplication, subtraction, division, or addition
-pub fn get_terms_sub(sys_states: Vec<String>, eq: Vec<MathExpressionTree>) -> Vec<PnTerm> {
-    let mut terms = Vec::<PnTerm>::new();
-
-    /* found multiple terms */
-    /* similar to get_terms_add, but need to swap polarity on term from second arg
-    and handle unary subtraction too */
-
-    let arg_len = eq.len();
-
-    // if unary subtraction
-    if arg_len == 1 {
-        match &eq[0] {
-            Cons(x1, ref y1) => match x1 {
-                Multiply => {
-                    let mut temp_term = get_terms_mult(sys_states, y1.clone());
-                    temp_term.polarity = !temp_term.polarity;
-                    if temp_term.sub_terms.is_some() {
-                        for (i, sub_term) in temp_term.sub_terms.clone().unwrap().iter().enumerate()
-                        {
-                            temp_term.sub_terms.as_mut().unwrap()[i].polarity = !sub_term.polarity;
-                        }
-                    }
-                    temp_term.math_vec = Some(eq[0].clone());
-                    terms.push(temp_term);
-                }
-                Divide => {
-                    let mut temp_term = get_term_div(sys_states, y1.clone());
-                    temp_term.polarity = !temp_term.polarity;
-                    if temp_term.sub_terms.is_some() {
-                        for (i, sub_term) in temp_term.sub_terms.clone().unwrap().iter().enumerate()
-                        {
-                            temp_term.sub_terms.as_mut().unwrap()[i].polarity = !sub_term.polarity;
-                        }
-                    }
-                    temp_term.math_vec = Some(eq[0].clone());
-                    terms.push(temp_term);
-                }
-                Subtract => {
-                    let temp_terms = get_terms_sub(sys_states, y1.clone());
-                    for term in temp_terms.iter() {
-                        // swap polarity of temp term
-                        let mut t_term = term.clone();
-                        t_term.polarity = !t_term.polarity;
-                        if t_term.sub_terms.is_some() {
-                            for (i, sub_term) in
-                                t_term.sub_terms.clone().unwrap().iter().enumerate()
-                            {
-                                t_term.sub_terms.as_mut().unwrap()[i].polarity = !sub_term.polarity;
-                            }
-                        }
-                        terms.push(t_term.clone());
-                    }
-                }
-                Add => {
-                    let temp_terms = get_terms_add(sys_states, y1.clone());
-                    for term in temp_terms.iter() {
-                        // swap polarity of temp term
-                        let mut t_term = term.clone();
-                        t_term.polarity = !t_term.polarity;
-                        if t_term.sub_terms.is_some() {
-                            for (i, sub_term) in
-                                t_term.sub_terms.clone().unwrap().iter().enumerate()
-                            {
-                                t_term.sub_terms.as_mut().unwrap()[i].polarity = !sub_term.polarity;
-                            }
-                        }
-                        terms.push(t_term.clone());
-                    }
-                }
-                Power => {
-                    let mut temp_term = get_term_power(sys_states, y1.clone());
-                    temp_term.polarity = !temp_term.polarity;
-                    if temp_term.sub_terms.is_some() {
-                        for (i, sub_term) in temp_term.sub_terms.clone().unwrap().iter().enumerate()
-                        {
-                            temp_term.sub_terms.as_mut().unwrap()[i].polarity = !sub_term.polarity;
-                        }
-                    }
-                    temp_term.math_vec = Some(eq[0].clone());
-                    terms.push(temp_term);
-                }
-                _ => {
-                    println!("Not valid term for PN")
-                }
-            },
-            Atom(ref x1) => {
-                // is either only a state or only a parameter
-                let mut expression_term = false;
-                for state in sys_states.iter() {
-                    if *state == x1.to_string() {
-                        expression_term = true;
-                    }
-                }
-
-                if expression_term {
-                    let temp_term = PnTerm {
-                        dyn_state: "temp".to_string(),
-                        exp_states: [x1.to_string()].to_vec(),
-                        polarity: false,
-                        expression: MathExpressionTree::Cons(Subtract, [eq[0].clone()].to_vec())
-                            .to_cmml(),
-                        expression_infix: MathExpressionTree::Cons(Subtract, [eq[0].clone()].to_vec()).to_infix_expression(),
-                        parameters: Vec::<String>::new(),
-                        sub_terms: None,
-                        math_vec: Some(eq[0].clone()),
-                    };
-                    terms.push(temp_term);
-                } else {
-                    let temp_term = PnTerm {
-                        dyn_state: "temp".to_string(),
-                        exp_states: Vec::<String>::new(),
-                        polarity: false,
-                        expression: MathExpressionTree::Cons(Subtract, [eq[0].clone()].to_vec())
-                            .to_cmml(),
-                        expression_infix: MathExpressionTree::Cons(Subtract, [eq[0].clone()].to_vec()).to_infix_expression(),
-                        parameters: [x1.to_string()].to_vec(),
-                        sub_terms: None,
-                        math_vec: Some(eq[0].clone()),
-                    };
-                    terms.push(temp_term);
-                }
-            }
-        }
-    } else {
-        // need to treat second term with polarity swap
-        for (i, arg) in eq.iter().enumerate() {
-            match &arg {
-                Cons(x1, ref y1) => match x1 {
-                    Multiply => {
-                        let mut temp_term = get_terms_mult(sys_states.clone(), y1.clone());
-                        if i == 1 {
-                            // swap polarity of temp term
-                            temp_term.polarity = !temp_term.polarity;
-                            if temp_term.sub_terms.is_some() {
-                                for (i, sub_term) in
-                                    temp_term.sub_terms.clone().unwrap().iter().enumerate()
-                                {
-                                    temp_term.sub_terms.as_mut().unwrap()[i].polarity =
-                                        !sub_term.polarity;
-                                }
-                            }
-                            temp_term.math_vec = Some(arg.clone());
-                            terms.push(temp_term);
-                        } else {
-                            temp_term.math_vec = Some(arg.clone());
-                            terms.push(temp_term);
-                        }
-                    }
-                    Divide => {
-                        let mut temp_term = get_term_div(sys_states.clone(), y1.clone());
-                        if i == 1 {
-                            // swap polarity of temp term
-                            temp_term.polarity = !temp_term.polarity;
-                            if temp_term.sub_terms.is_some() {
-                                for (i, sub_term) in
-                                    temp_term.sub_terms.clone().unwrap().iter().enumerate()
-                                {
-                                    temp_term.sub_terms.as_mut().unwrap()[i].polarity =
-                                        !sub_term.polarity;
-                                }
-                            }
-                            temp_term.math_vec = Some(arg.clone());
-                            terms.push(temp_term);
-                        } else {
-                            temp_term.math_vec = Some(arg.clone());
-                            terms.push(temp_term);
-                        }
-                    }
-                    Subtract => {
-                        let temp_terms = get_terms_sub(sys_states.clone(), y1.clone());
-                        for term in temp_terms.iter() {
-                            let mut t_term = term.clone();
-                            if i == 1 {
-                                // swap polarity of temp term
-                                t_term.polarity = !t_term.polarity;
-                                if t_term.sub_terms.is_some() {
-                                    for (i, sub_term) in
-                                        t_term.sub_terms.clone().unwrap().iter().enumerate()
-                                    {
-                                        t_term.sub_terms.as_mut().unwrap()[i].polarity =
-                                            !sub_term.polarity;
-                                    }
-                                }
-                                terms.push(t_term.clone());
-                            } else {
-                                terms.push(t_term.clone());
-                            }
-                        }
-                    }
-                    Add => {
-                        let temp_terms = get_terms_add(sys_states.clone(), y1.clone());
-                        for term in temp_terms.iter() {
-                            let mut t_term = term.clone();
-                            if i == 1 {
-                                // swap polarity of temp term
-                                t_term.polarity = !t_term.polarity;
-                                if t_term.sub_terms.is_some() {
-                                    for (i, sub_term) in
-                                        t_term.sub_terms.clone().unwrap().iter().enumerate()
-                                    {
-                                        t_term.sub_terms.as_mut().unwrap()[i].polarity =
-                                            !sub_term.polarity;
-                                    }
-                                }
-                                terms.push(t_term.clone());
-                            } else {
-                                terms.push(t_term.clone());
-                            }
-                        }
-                    }
-                    Power => {
-                        let mut temp_term = get_term_power(sys_states.clone(), y1.clone());
-                        if i == 1 {
-                            // swap polarity of temp term
-                            temp_term.polarity = !temp_term.polarity;
-                            if temp_term.sub_terms.is_some() {
-                                for (i, sub_term) in
-                                    temp_term.sub_terms.clone().unwrap().iter().enumerate()
-                                {
-                                    temp_term.sub_terms.as_mut().unwrap()[i].polarity =
-                                        !sub_term.polarity;
-                                }
-                            }
-                            temp_term.math_vec = Some(arg.clone());
-                            terms.push(temp_term);
-                        } else {
-                            temp_term.math_vec = Some(arg.clone());
-                            terms.push(temp_term);
-                        }
-                    }
-                    _ => {
-                        println!("Error unsupported operation")
-                    }
-                },
-                Atom(ref x) => {
-                    // also need to construct a partial term here to handle distribution of just a parameter
-                    let mut is_state = false;
-                    let mut polarity = true;
-                    let mut expression =
-                        MathExpressionTree::Cons(Add, [arg.clone()].to_vec()).to_cmml();
-                    let mut expression_infix = MathExpressionTree::Cons(Add, [arg.clone()].to_vec()).to_infix_expression();
-                    if i == 1 {
-                        polarity = false;
-                        expression =
-                            MathExpressionTree::Cons(Subtract, [arg.clone()].to_vec()).to_cmml();
-                        expression_infix = MathExpressionTree::Cons(Subtract, [arg.clone()].to_vec()).to_infix_expression()
-                    }
-                    for state in sys_states.iter() {
-                        if x.to_string() == *state {
-                            is_state = true;
-                        }
-                    }
-                    if is_state {
-                        let temp_term = PnTerm {
-                            dyn_state: "temp".to_string(),
-                            exp_states: [x.to_string().clone()].to_vec(),
-                            polarity,
-                            expression,
-                            expression_infix,
-                            parameters: Vec::<String>::new(),
-                            sub_terms: None,
-                            math_vec: Some(arg.clone()),
-                        };
-                        terms.push(temp_term.clone());
-                    } else {
-                        let temp_term = PnTerm {
-                            dyn_state: "temp".to_string(),
-                            exp_states: Vec::<String>::new(),
-                            polarity,
-                            expression,
-                            expression_infix,
-                            parameters: [x.to_string().clone()].to_vec(),
-                            sub_terms: None,
-                            math_vec: Some(arg.clone()),
-                        };
-                        terms.push(temp_term.clone());
-                    }
-                }
-            }
-        }
-    }
-    terms
-}
-
-// this takes in the arguments of a div operator and returns the PnTerm for it
-// we do expect at most multiplication, subtraction, or addition
-pub fn get_term_div(sys_states: Vec<String>, eq: Vec<MathExpressionTree>) -> PnTerm {
-    let mut variables = Vec::<String>::new();
-    let mut exp_states = Vec::<String>::new();
-    let mut polarity = true;
-
-    // this walks the tree and composes a vector of all variable and polarity changes
-    for obj in eq.iter() {
-        match obj {
-            Cons(x, y) => {
-                match &x {
-                    Subtract => {
-                        if y.len() == 1 {
-                            polarity = false;
-                            variables.push(y[0].to_string());
-                        } else {
-                            for var in y.iter() {
-                                variables.push(var.to_string().clone());
-                            }
-                        }
-                    }
-                    Multiply => {
-                        // call mult function to get a partial term
-                        let mut temp_term = get_term_mult(sys_states.clone(), y.clone());
-
-                        // parse term polarity
-                        polarity = temp_term.polarity;
-
-                        // parse term parameters and expression states
-                        // need to do both to populate both later
-                        variables.append(&mut temp_term.parameters);
-                        variables.append(&mut temp_term.exp_states);
-                    }
-                    Add => {
-                        if y.len() == 1 {
-                            // really should need to support unary addition, but oh well
-                            variables.push(y[0].to_string());
-                        } else {
-                            for var in y.iter() {
-                                variables.push(var.to_string().clone());
-                            }
-                        }
-                    }
-                    Power => {
-                        // call mult function to get a partial term
-                        let mut temp_term = get_term_power(sys_states.clone(), y.clone());
-
-                        // parse term polarity
-                        polarity = temp_term.polarity;
-
-                        // parse term parameters and expression states
-                        // need to do both to populate both later
-                        variables.append(&mut temp_term.parameters);
-                        variables.append(&mut temp_term.exp_states);
-                    }
-                    _ => {
-                        println!("Not expected operation inside Multiply")
-                    }
-                }
-            }
-            Atom(x) => variables.push(x.to_string()),
-        }
-    }
-
-    // this compiles the vector of expression states for the term
-    let mut ind = Vec::<usize>::new();
-    for (i, var) in variables.iter().enumerate() {
-        for sys_var in sys_states.iter() {
-            if var == sys_var {
-                exp_states.push(var.clone());
-                ind.push(i);
-            }
-        }
-    }
-
-    // this removes the expression states from the variable vector
-    for i in ind.iter().rev() {
-        variables.remove(*i);
-    }
 
-    // now to dedup variables and exp_states
-    variables.sort();
-    variables.dedup();
-    exp_states.sort();
-    //exp_states.dedup();
+    // rate expressions are written without their outer parentheses, which the infix form only
+    // has for arithmetic
+    let magnitude = monomial.magnitude();
+    let expression_infix = match magnitude {
+        Cons(Multiply | Divide, _) => magnitude.to_infix_expression(),
+        _ => format!("({})", magnitude.to_infix_expression()),
+    };
 
     PnTerm {
         dyn_state: "temp".to_string(),
         exp_states,
-        polarity,
-        expression: MathExpressionTree::Cons(Multiply, eq.clone()).to_cmml(),
-        expression_infix: MathExpressionTree::Cons(Multiply, eq).to_infix_expression(),
+        polarity: !monomial.is_negative(),
+        expression: magnitude.to_cmml(),
+        expression_infix,
         parameters: variables,
-        sub_terms: None,
-        math_vec: None,
+        math_vec: Some(magnitude),
     }
 }
 
-// When we hit a multiplication inference is needed on if it is a complicated single transition
-// or if we need to distribute over the multiplication and thus get multiple terms out. We will collect
-// both here for now and leave the inference to the PN construction phase since only then can we infer
-pub fn get_terms_mult(sys_states: Vec<String>, eq: Vec<MathExpressionTree>) -> PnTerm {
-    let mut variables = Vec::<String>::new();
-    let mut exp_states = Vec::<String>::new();
-    let mut polarity = true;
-    let mut terms = Vec::<PnTerm>::new();
-    let mut arg_terms = Vec::<(i32, PnTerm)>::new();
-
-    // determine if we need to distribute or if simply mult we can just make term from
-    let mut distribution = false;
-    for arg in eq.iter() {
-        if let Cons(x1, y1) = arg {
-            if *x1 != Power && *x1 != Divide && !(*x1 == Subtract && y1.len() == 1) {
-                distribution = true;
-            }
-        }
-    }
-
-    if !distribution {
-        // simple mult, simple term
-        get_term_mult(sys_states, eq)
-    } else {
-        for (i, arg) in eq.iter().enumerate() {
-            match &arg {
-                Cons(x1, ref y1) => match x1 {
-                    Multiply => {
-                        // this actually shouldn't be reachable if mults are flattened properly
-                        let mut temp_term = get_terms_mult(sys_states.clone(), y1.clone());
-                        temp_term.math_vec = Some(arg.clone());
-                        arg_terms.push((i.try_into().unwrap(), temp_term.clone()));
-                        // we now need to parse the term to constuct the large full term
-                        variables.append(&mut temp_term.parameters.clone());
-                        exp_states.append(&mut temp_term.exp_states.clone());
-                    }
-                    Divide => {
-                        let mut temp_term = get_term_div(sys_states.clone(), y1.clone());
-                        temp_term.math_vec = Some(arg.clone());
-                        arg_terms.push((i.try_into().unwrap(), temp_term.clone()));
-                        // we now need to parse the term to constuct the large full term
-                        variables.append(&mut temp_term.parameters.clone());
-                        exp_states.append(&mut temp_term.exp_states.clone());
-                    }
-                    Subtract => {
-                        let temp_terms = get_terms_sub(sys_states.clone(), y1.clone());
-                        // if there is a unary subtraction (single term) we do get a polarity flip
-                        if temp_terms.len() == 1 {
-                            polarity = !polarity;
-                        }
-                        for term in temp_terms.iter() {
-                            arg_terms.push((i.try_into().unwrap(), term.clone()));
-                            // we now need to parse the term to constuct the large full term
-                            variables.append(&mut term.parameters.clone());
-                            exp_states.append(&mut term.exp_states.clone());
-                        }
-                    }
-                    Add => {
-                        let temp_terms = get_terms_add(sys_states.clone(), y1.clone());
-                        for term in temp_terms.iter() {
-                            arg_terms.push((i.try_into().unwrap(), term.clone()));
-                            // we now need to parse the term to constuct the large full term
-                            variables.append(&mut term.parameters.clone());
-                            exp_states.append(&mut term.exp_states.clone());
-                        }
-                    }
-                    Power => {
-                        let mut temp_term = get_term_power(sys_states.clone(), y1.clone());
-                        temp_term.math_vec = Some(arg.clone());
-                        arg_terms.push((i.try_into().unwrap(), temp_term.clone()));
-                        // we now need to parse the term to constuct the large full term
-                        variables.append(&mut temp_term.parameters.clone());
-                        exp_states.append(&mut temp_term.exp_states.clone());
-                    }
-                    _ => {
-                        println!("Error unsupported operation")
-                    }
-                },
-                Atom(ref x) => {
-                    variables.push(x.to_string());
-                    // also need to construct a partial term here to handle distribution of just a parameter
-                    let mut is_state = false;
-                    for state in sys_states.iter() {
-                        if x.to_string() == *state {
-                            is_state = true;
-                        }
-                    }
-                    if is_state {
-                        let temp_term = PnTerm {
-                            dyn_state: "temp".to_string(),
-                            exp_states: [x.to_string().clone()].to_vec(),
-                            polarity: true,
-                            expression: "temp".to_string(),
-                            expression_infix: "temp".to_string(),
-                            parameters: Vec::<String>::new(),
-                            sub_terms: None,
-                            math_vec: Some(arg.clone()),
-                        };
-                        arg_terms.push((i.try_into().unwrap(), temp_term.clone()));
-                    } else {
-                        let temp_term = PnTerm {
-                            dyn_state: "temp".to_string(),
-                            exp_states: Vec::<String>::new(),
-                            polarity: true,
-                            expression: "temp".to_string(),
-                            expression_infix: "temp".to_string(),
-                            parameters: [x.to_string().clone()].to_vec(),
-                            sub_terms: None,
-                            math_vec: Some(arg.clone()),
-                        };
-                        arg_terms.push((i.try_into().unwrap(), temp_term.clone()));
-                    }
-                }
-            }
-        }
-
-        // now to construct the sub terms vector, this will involve distributing the multiplication and
-        // filling in the vector itself
-        let mut lhs_vec = Vec::<PnTerm>::new();
-        let mut rhs_vec = Vec::<PnTerm>::new();
-
-        for arg in arg_terms.iter() {
-            println!("arg_term: {:?}", arg.clone());
-            if arg.0 == 0 {
-                lhs_vec.push(arg.1.clone());
-            } else {
-                rhs_vec.push(arg.1.clone());
-            }
-        }
-
-        for lhs_term in lhs_vec.iter() {
-            for rhs_term in rhs_vec.iter() {
-                // construct distributed term
-                let temp_term = Cons(
-                    Multiply,
-                    [
-                        lhs_term.math_vec.clone().unwrap(),
-                        rhs_term.math_vec.clone().unwrap(),
-                    ]
-                    .to_vec(),
-                );
-                let flat_temp_term = flatten_mults(temp_term.clone());
-                let mut arg_vec = Vec::<MathExpressionTree>::new();
-                if let Cons(Multiply, y) = flat_temp_term {
-                    arg_vec.append(&mut y.clone());
-                }
-                let mut sub_term = get_term_mult(sys_states.clone(), arg_vec.clone());
-                if lhs_term.polarity != rhs_term.polarity {
-                    sub_term.polarity = !sub_term.polarity;
-                }
-                terms.push(sub_term.clone());
-            }
-        }
-
-        // now for cleaning up the big term
-        // this compiles the vector of expression states for the term
-        let mut ind = Vec::<usize>::new();
-        for (i, var) in variables.iter().enumerate() {
-            for sys_var in sys_states.iter() {
-                if var == sys_var {
-                    exp_states.push(var.clone());
-                    ind.push(i);
-                }
+// this collects the identifiers (but not the numbers) of an expression
+fn collect_identifiers(eq: &MathExpressionTree, variables: &mut Vec<String>) {
+    match eq {
+        Atom(MathExpression::Mn(_)) => {}
+        Atom(x) => variables.push(x.to_string()),
+        Cons(_, args) => {
+            for arg in args {
+                collect_identifiers(arg, variables);
             }
         }
-
-        // this removes the expression states from the variable vector
-        for i in ind.iter().rev() {
-            variables.remove(*i);
-        }
-
-        // now to dedup variables and exp_states
-        variables.sort();
-        variables.dedup();
-        exp_states.sort();
-        //exp_states.dedup();
-
-        PnTerm {
-            dyn_state: "temp".to_string(),
-            exp_states,
-            polarity,
-            expression: MathExpressionTree::Cons(Multiply, eq.clone()).to_cmml(),
-            expression_infix: MathExpressionTree::Cons(Multiply, eq).to_infix_expression(),
-            parameters: variables,
-            sub_terms: Some(terms),
-            math_vec: None,
-        }
-    }
-}
-
-// this takes in the arguments of a multiply operator and returns the PnTerm for it
-// we do expect at most division, subtraction, or addition
-pub fn get_term_mult(sys_states: Vec<String>, eq: Vec<MathExpressionTree>) -> PnTerm {
-    let mut variables = Vec::<String>::new();
-    let mut exp_states = Vec::<String>::new();
-    let mut polarity = true;
-
-    // this walks the tree and composes a vector of all variable and polarity changes
-    for obj in eq.iter() {
-        match obj {
-            Cons(x, y) => {
-                match &x {
-                    Subtract => {
-                        if y.len() == 1 {
-                            polarity = false;
-                            variables.push(y[0].to_string());
-                        } else {
-                            for var in y.iter() {
-                                variables.push(var.to_string().clone());
-                            }
-                        }
-                    }
-                    Divide => {
-                        // call mult function to get a partial term
-                        let mut temp_term = get_term_div(sys_states.clone(), y.clone());
-
-                        // parse term polarity
-                        polarity = temp_term.polarity;
-
-                        // parse term parameters and expression states
-                        // need to do both to populate both later
-                        variables.append(&mut temp_term.parameters);
-                        variables.append(&mut temp_term.exp_states);
-                    }
-                    Add => {
-                        if y.len() == 1 {
-                            // really should need to support unary addition, but oh well
-                            variables.push(y[0].to_string());
-                        } else {
-                            for var in y.iter() {
-                                variables.push(var.to_string().clone());
-                            }
-                        }
-                    }
-                    Power => {
-                        // call mult function to get a partial term
-                        let mut temp_term = get_term_power(sys_states.clone(), y.clone());
-
-                        // parse term polarity
-                        polarity = temp_term.polarity;
-
-                        // parse term parameters and expression states
-                        // need to do both to populate both later
-                        variables.append(&mut temp_term.parameters);
-                        variables.append(&mut temp_term.exp_states);
-                    }
-                    _ => {
-                        println!("Not expected operation inside Multiply")
-                    }
-                }
-            }
-            Atom(x) => variables.push(x.to_string()),
-        }
-    }
-
-    // this compiles the vector of expression states for the term
-    let mut ind = Vec::<usize>::new();
-    for (i, var) in variables.iter().enumerate() {
-        for sys_var in sys_states.iter() {
-            if var == sys_var {
-                exp_states.push(var.clone());
-                ind.push(i);
-            }
-        }
-    }
-
-    // this removes the expression states from the variable vector
-    for i in ind.iter().rev() {
-        variables.remove(*i);
-    }
-
-    // now to dedup variables and exp_states
-    variables.sort();
-    variables.dedup();
-    exp_states.sort();
-    //exp_states.dedup();
-
-    PnTerm {
-        dyn_state: "temp".to_string(),
-        exp_states,
-        polarity,
-        expression: MathExpressionTree::Cons(Multiply, eq.clone()).to_cmml(),
-        expression_infix: MathExpressionTree::Cons(Multiply, eq).to_infix_expression(),
-        parameters: variables,
-        sub_terms: None,
-        math_vec: None,
     }
 }
 
@@ -1415,3 +619,178 @@ fn test_msub_derivative() {
         ),
     );
 }
+
+#[test]
+fn test_first_order_ode_parse_error() {
+    let input = "<math>
+        <mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo><mo>)</mo><mi>β</mi>
+        </math>";
+    let err = input.parse::<FirstOrderODE>().unwrap_err();
+    assert_eq!(err.line, Some(3));
+    assert_eq!(err.token, Some(")".to_string()));
+}
+
+#[test]
+fn test_piecewise_rate() {
+    let input = "
+    <math>
+        <mfrac>
+        <mrow><mi>d</mi><mi>S</mi></mrow>
+        <mrow><mi>d</mi><mi>t</mi></mrow>
+        </mfrac>
+        <mo>=</mo>
+        <mo>-</mo>
+        <mrow>
+            <mo>{</mo>
+            <mtable>
+                <mtr><mtd><msub><mi>β</mi><mn>1</mn></msub></mtd><mtd><mi>t</mi><mo>&lt;</mo><msub><mi>t</mi><mn>0</mn></msub></mtd></mtr>
+                <mtr><mtd><msub><mi>β</mi><mn>2</mn></msub></mtd><mtd><mtext>otherwise</mtext></mtd></mtr>
+            </mtable>
+        </mrow>
+        <mi>S</mi><mi>I</mi>
+    </math>
+    ";
+    let ode = input.parse::<FirstOrderODE>().unwrap();
+    let terms = get_terms(vec!["S".to_string(), "I".to_string()], ode);
+    assert_eq!(terms.len(), 1);
+    assert_eq!(terms[0].exp_states, vec!["I", "S"]);
+    assert_eq!(terms[0].parameters, vec!["t_{0}", "β_{1}", "β_{2}"]);
+    assert!(terms[0].expression.contains("<piecewise>"));
+    assert!(terms[0].expression_infix.contains("Piecewise"));
+}
+
+#[test]
+fn test_nested_rhs_terms() {
+    use crate::acset::PetriNet;
+    let ode = |lhs: &str, rhs: &str| {
+        format!("<math><mfrac><mrow><mi>d</mi><mi>{lhs}</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo>{rhs}</math>")
+            .parse::<FirstOrderODE>()
+            .unwrap()
+    };
+    // Infection by both symptomatic (I) and asymptomatic (A) individuals.
+    let infection = "<mi>β</mi><mi>S</mi><mo>(</mo><mi>I</mi><mo>+</mo><mi>A</mi><mo>)</mo>";
+    let odes = vec![
+        ode("S", &format!("<mo>-</mo>{infection}")),
+        ode("I", &format!("{infection}<mo>-</mo><mi>γ</mi><mi>I</mi>")),
+        ode("A", "<mn>0</mn>"),
+        ode("R", "<mi>γ</mi><mi>I</mi>"),
+    ];
+    let states = ["S", "I", "A", "R"].map(String::from).to_vec();
+    let terms = get_terms(states, odes[1].clone());
+    assert_eq!(terms.len(), 3);
+    // The RHS is simplified, which orders the factors of each term canonically
+    assert_eq!(terms[0].exp_states, vec!["A", "S"]);
+    assert_eq!(terms[0].expression_infix, "(A*S*β)");
+    assert_eq!(terms[1].exp_states, vec!["I", "S"]);
+    assert!(!terms[2].polarity);
+
+    let transitions = PetriNet::from(odes)
+        .model
+        .transitions
+        .iter()
+        .map(|t| (t.input.clone(), t.output.clone()))
+        .collect::<Vec<_>>();
+    let transition = |input: [&str; 2], output: [&str; 2]| {
+        (
+            Some(input.iter().map(|x| x.to_string()).collect()),
+            Some(output.iter().map(|x| x.to_string()).collect()),
+        )
+    };
+    assert_eq!(transitions.len(), 3);
+    assert!(transitions.contains(&transition(["I", "S"], ["I", "I"])));
+    assert!(transitions.contains(&transition(["A", "S"], ["I", "A"])));
+}
+
+#[test]
+fn test_simplified_terms() {
+    use crate::acset::PetriNet;
+    let ode = |lhs: &str, rhs: &str| {
+        format!("<math><mfrac><mrow><mi>d</mi><mi>{lhs}</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo>{rhs}</math>")
+            .parse::<FirstOrderODE>()
+            .unwrap()
+    };
+    // SIR as written in a paper, and as extracted from code with nested products, unary minus
+    // and identities
+    let paper = vec![
+        ode("S", "<mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi>"),
+        ode(
+            "I",
+            "<mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi>",
+        ),
+        ode("R", "<mi>γ</mi><mi>I</mi>"),
+    ];
+    let code = vec![
+        ode("S", "<mo>-</mo><mo>(</mo><mi>β</mi><mo>*</mo><mo>(</mo><mi>S</mi><mo>*</mo><mi>I</mi><mo>)</mo><mo>)</mo>"),
+        ode("I", "<mn>1</mn><mo>*</mo><mi>I</mi><mo>*</mo><mo>(</mo><mi>S</mi><mo>*</mo><mi>β</mi><mo>)</mo><mo>+</mo><mo>-</mo><mo>(</mo><mi>I</mi><mo>*</mo><mi>γ</mi><mo>)</mo>"),
+        ode("R", "<mi>I</mi><mo>*</mo><mi>γ</mi><mo>+</mo><mn>0</mn>"),
+    ];
+    assert_eq!(PetriNet::from(paper), PetriNet::from(code));
+}
+
+#[test]
+fn test_non_integer_exponent_term() {
+    let ode = "<math><mfrac><mrow><mi>d</mi><mi>x</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>k</mi><msup><mi>x</mi><mrow><mn>1</mn><mo>/</mo><mn>2</mn></mrow></msup></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap();
+    let terms = get_terms(vec!["x".to_string()], ode);
+    assert_eq!(terms.len(), 1);
+    assert_eq!(terms[0].exp_states, vec!["x"]);
+    assert_eq!(terms[0].parameters, vec!["k"]);
+    assert!(!terms[0].polarity);
+}
+
+#[test]
+fn test_stock_flow() {
+    use crate::acset::StockFlow;
+    let ode = |lhs: &str, rhs: &str| {
+        format!("<math><mfrac><mrow><mi>d</mi><mi>{lhs}</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo>{rhs}</math>")
+            .parse::<FirstOrderODE>()
+            .unwrap()
+    };
+    let odes = vec![
+        ode("S", "<mi>μ</mi><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi>"),
+        ode(
+            "I",
+            "<mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi>",
+        ),
+        ode("R", "<mi>γ</mi><mi>I</mi><mo>-</mo><mi>δ</mi><mi>R</mi>"),
+    ];
+    let model = StockFlow::from(odes).model;
+    let flows = model
+        .flows
+        .iter()
+        .map(|f| {
+            (
+                f.upstream_stock.as_deref(),
+                f.downstream_stock.as_deref(),
+                f.rate_expression.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(flows.len(), 4);
+    assert!(flows.contains(&(Some("S"), Some("I"), "I*S*β")));
+    assert!(flows.contains(&(Some("I"), Some("R"), "I*γ")));
+    assert!(flows.contains(&(Some("R"), None, "R*δ")));
+    assert!(flows.contains(&(None, Some("S"), "μ")));
+
+    let infection = model
+        .flows
+        .iter()
+        .find(|f| f.upstream_stock.as_deref() == Some("S"))
+        .unwrap();
+    let mut sources = model
+        .links
+        .iter()
+        .filter(|l| l.target == infection.id)
+        .map(|l| l.source.as_str())
+        .collect::<Vec<_>>();
+    sources.sort();
+    assert_eq!(sources, vec!["I", "S", "β"]);
+    let auxiliaries = model
+        .auxiliaries
+        .iter()
+        .map(|a| a.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(auxiliaries, vec!["β", "γ", "δ", "μ"]);
+}
@@ -6,19 +6,23 @@ use crate::ast::{
     },
     Mi, Mrow,
 };
+use crate::normalization::normalize_mathml;
 
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_until},
     character::complete::{alphanumeric1, multispace0, not_line_ending},
-    combinator::{cut, map, map_parser, opt, peek, recognize, value},
+    combinator::{cut, map, map_parser, not, opt, peek, recognize, value},
     multi::many0,
     sequence::{delimited, pair, preceded, separated_pair, tuple},
 };
 use nom::{character::complete::char as nom_char, error::context};
 
 use nom_locate::LocatedSpan;
-use std::str::FromStr;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use utoipa::ToSchema;
 
 pub type Span<'a> = LocatedSpan<&'a str>;
 
@@ -26,18 +30,34 @@ pub type Span<'a> = LocatedSpan<&'a str>;
 pub struct ParseError<'a> {
     span: Span<'a>,
     message: String,
+    token: Option<String>,
 }
 
 /// We implement the ParseError trait here to support the Span type.
 impl<'a> ParseError<'a> {
     pub fn new(message: String, span: Span<'a>) -> Self {
-        Self { message, span }
+        Self {
+            message,
+            span,
+            token: None,
+        }
+    }
+
+    /// Attach the offending token to the error, for errors where it is not simply the input at
+    /// the error location (e.g. errors raised after tokenization).
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
     }
 
     pub fn span(&self) -> &Span {
         &self.span
     }
 
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
     pub fn line(&self) -> u32 {
         self.span().location_line()
     }
@@ -46,11 +66,92 @@ impl<'a> ParseError<'a> {
         self.span().location_offset()
     }
 
+    /// The offending token: either the one explicitly attached to the error, or the first
+    /// element (up to and including the first '>') of the remaining input.
+    pub fn token(&self) -> Option<String> {
+        if self.token.is_some() {
+            return self.token.clone();
+        }
+        let fragment = self.span.fragment().trim_start();
+        fragment
+            .split_inclusive('>')
+            .next()
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_string())
+    }
+
     pub fn append_message(&mut self, msg: &str) {
         self.message.push_str(&format!("\nERROR: {}", msg));
     }
 }
 
+/// An owned, serializable description of a parse failure. Unlike `ParseError`, this does not
+/// borrow from the input, so it can be returned from `FromStr` implementations and sent back to
+/// clients of the REST API.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Deserialize, Serialize, ToSchema, JsonSchema)]
+pub struct ParseDiagnostic {
+    /// Human-readable description of the failure
+    pub message: String,
+    /// 1-based line of the input at which the failure occurred, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    /// Byte offset into the input at which the failure occurred, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    /// The offending token, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl ParseDiagnostic {
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let (Some(line), Some(offset)) = (self.line, self.offset) {
+            write!(f, " (line {line}, offset {offset})")?;
+        }
+        if let Some(token) = &self.token {
+            write!(f, " at `{token}`")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseDiagnostic {}
+
+impl<'a> From<ParseError<'a>> for ParseDiagnostic {
+    fn from(err: ParseError<'a>) -> Self {
+        Self {
+            message: err.message().to_string(),
+            line: Some(err.line()),
+            offset: Some(err.offset()),
+            token: err.token(),
+        }
+    }
+}
+
+impl<'a> From<nom::Err<ParseError<'a>>> for ParseDiagnostic {
+    fn from(err: nom::Err<ParseError<'a>>) -> Self {
+        match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.into(),
+            nom::Err::Incomplete(_) => Self::new("Incomplete input".to_string()),
+        }
+    }
+}
+
 /// Further trait implementation for Span
 impl<'a> nom::error::ParseError<Span<'a>> for ParseError<'a> {
     fn from_error_kind(input: Span<'a>, kind: nom::error::ErrorKind) -> Self {
@@ -196,7 +297,12 @@ pub fn subtract(input: Span) -> IResult<Operator> {
 pub fn multiply(input: Span) -> IResult<Operator> {
     let (s, op) = value(
         Operator::Multiply,
-        alt((ws(tag("*")), ws(tag("&#x2217;")), ws(tag("∗")))),
+        alt((
+            ws(tag("*")),
+            ws(tag("&#x2217;")),
+            ws(tag("∗")),
+            ws(tag("\u{2062}")),
+        )),
     )(input)?;
     Ok((s, op))
 }
@@ -214,6 +320,31 @@ pub fn equals(input: Span) -> IResult<Operator> {
     Ok((s, op))
 }
 
+pub fn less(input: Span) -> IResult<Operator> {
+    let (s, op) = value(Operator::Less, alt((ws(tag("&lt;")), ws(tag("<")))))(input)?;
+    Ok((s, op))
+}
+
+pub fn less_equal(input: Span) -> IResult<Operator> {
+    let (s, op) = value(Operator::LessEqual, alt((ws(tag("≤")), ws(tag("⩽")))))(input)?;
+    Ok((s, op))
+}
+
+pub fn greater(input: Span) -> IResult<Operator> {
+    let (s, op) = value(Operator::Greater, alt((ws(tag("&gt;")), ws(tag(">")))))(input)?;
+    Ok((s, op))
+}
+
+pub fn greater_equal(input: Span) -> IResult<Operator> {
+    let (s, op) = value(Operator::GreaterEqual, alt((ws(tag("≥")), ws(tag("⩾")))))(input)?;
+    Ok((s, op))
+}
+
+pub fn not_equal(input: Span) -> IResult<Operator> {
+    let (s, op) = value(Operator::NotEqual, ws(tag("≠")))(input)?;
+    Ok((s, op))
+}
+
 pub fn lparen(input: Span) -> IResult<Operator> {
     let (s, op) = value(Operator::Lparen, alt((ws(tag("(")), ws(tag("[")))))(input)?;
     Ok((s, op))
@@ -239,6 +370,19 @@ pub fn cross(input: Span) -> IResult<Operator> {
     Ok((s, op))
 }
 
+pub fn hadamard(input: Span) -> IResult<Operator> {
+    let (s, op) = value(Operator::Hadamard, alt((ws(tag("⊙")), ws(tag("&#x2299;")))))(input)?;
+    Ok((s, op))
+}
+
+pub fn transpose(input: Span) -> IResult<Operator> {
+    let (s, op) = value(
+        Operator::Transpose,
+        alt((ws(tag("⊤")), ws(tag("&#x22A4;")), ws(tag("&#x22a4;")))),
+    )(input)?;
+    Ok((s, op))
+}
+
 pub fn minimum(input: Span) -> IResult<Operator> {
     let (s, _x) = ws(delimited(stag!("mi"), ws(tag("min")), etag!("mi")))(input)?;
     let (s, _op) = ws(alt((
@@ -267,6 +411,13 @@ pub fn operator(input: Span) -> IResult<Operator> {
         divide,
         dot,
         cross,
+        hadamard,
+        transpose,
+        less_equal,
+        less,
+        greater_equal,
+        greater,
+        not_equal,
         operator_other,
     ))(input)?;
     Ok((s, op))
@@ -352,6 +503,239 @@ fn mstyle(input: Span) -> IResult<MathExpression> {
     Ok((s, Mstyle(elements)))
 }
 
+/// A parser for the children of the layout elements below. The layout element parsers take the
+/// parser for their children as an argument so that they can be shared with the interpreted_mathml
+/// module, which has its own `math_expression` parser.
+pub type ExpressionParser = for<'a> fn(Span<'a>) -> IResult<'a, MathExpression>;
+
+/// Returns the value of the attribute `name` in a list of attributes, if present.
+fn attribute_value(attributes: &[(&str, &str)], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Fenced expressions, e.g. `<mfenced open="[" close="]">`. The fences default to parentheses.
+pub fn mfenced<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    map(
+        ws(tuple((
+            stag!("mfenced"),
+            many0(expression),
+            etag!("mfenced"),
+        ))),
+        |((_, _, attributes, _), elements, _)| {
+            MathExpression::Mfenced(
+                attribute_value(&attributes, "open").unwrap_or_else(|| "(".to_string()),
+                attribute_value(&attributes, "close").unwrap_or_else(|| ")".to_string()),
+                elements,
+            )
+        },
+    )
+}
+
+/// Roots with an index, e.g. cube roots
+pub fn mroot<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    map(
+        tag_parser!("mroot", pair(expression, expression)),
+        |(base, index)| MathExpression::Mroot(Box::new(base), Box::new(index)),
+    )
+}
+
+/// Placeholder for a missing script in `<mmultiscripts>`
+fn none(input: Span) -> IResult<MathExpression> {
+    value(
+        MathExpression::None,
+        ws(tuple((tag("<none"), multispace0, tag("/>")))),
+    )(input)
+}
+
+/// Prescripts and tensor indices, e.g. `<mmultiscripts><mi>R</mi><mi>i</mi><none/></mmultiscripts>`
+pub fn mmultiscripts<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    map(
+        tag_parser!(
+            "mmultiscripts",
+            tuple((
+                expression,
+                many0(alt((none, expression))),
+                opt(preceded(
+                    ws(tuple((tag("<mprescripts"), multispace0, tag("/>")))),
+                    many0(alt((none, expression))),
+                )),
+            ))
+        ),
+        |(base, postscripts, prescripts)| {
+            MathExpression::Mmultiscripts(
+                Box::new(base),
+                postscripts,
+                prescripts.unwrap_or_default(),
+            )
+        },
+    )
+}
+
+/// Enclosed expressions, e.g. `<menclose notation="box">`. The notation defaults to `longdiv`.
+pub fn menclose<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    map(
+        ws(tuple((
+            stag!("menclose"),
+            many0(expression),
+            etag!("menclose"),
+        ))),
+        |((_, _, attributes, _), elements, _)| {
+            MathExpression::Menclose(
+                attribute_value(&attributes, "notation").unwrap_or_else(|| "longdiv".to_string()),
+                elements,
+            )
+        },
+    )
+}
+
+/// Padded expressions
+pub fn mpadded<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    map(
+        tag_parser!("mpadded", many0(expression)),
+        MathExpression::Mpadded,
+    )
+}
+
+/// Invisible expressions
+pub fn mphantom<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    map(
+        tag_parser!("mphantom", many0(expression)),
+        MathExpression::Mphantom,
+    )
+}
+
+/// Annotations of a `<semantics>` element, returning their (unparsed) contents.
+fn annotation(input: Span) -> IResult<String> {
+    map(
+        alt((
+            delimited(
+                stag!("annotation-xml"),
+                take_until("</annotation-xml>"),
+                etag!("annotation-xml"),
+            ),
+            delimited(
+                stag!("annotation"),
+                take_until("</annotation>"),
+                etag!("annotation"),
+            ),
+        )),
+        |contents| contents.trim().to_string(),
+    )(input)
+}
+
+/// Annotated expressions, e.g. the LaTeX source of an expression attached by LaTeXML
+pub fn semantics<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    map(
+        tag_parser!(
+            "semantics",
+            pair(
+                many0(preceded(not(ws(tag("<annotation"))), expression)),
+                many0(annotation),
+            )
+        ),
+        |(mut presentation, annotations)| {
+            let presentation = if presentation.len() == 1 {
+                presentation.remove(0)
+            } else {
+                MathExpression::Mrow(Mrow(presentation))
+            };
+            MathExpression::Semantics(Box::new(presentation), annotations)
+        },
+    )
+}
+
+/// Alignment markers within table cells, which carry no meaning
+pub(crate) fn alignment_marker(input: Span) -> IResult<()> {
+    value(
+        (),
+        ws(tuple((
+            alt((tag("<maligngroup"), tag("<malignmark"))),
+            many0(attribute),
+            multispace0,
+            tag("/>"),
+        ))),
+    )(input)
+}
+
+/// Table cell, with its contents parsed by `contents`. Empty `<mtd/>` cells are `None`.
+fn mtd<'a, O, F>(contents: F) -> impl FnMut(Span<'a>) -> IResult<'a, Option<O>>
+where
+    O: Clone + 'a,
+    F: FnMut(Span<'a>) -> IResult<'a, O> + 'a,
+{
+    alt((
+        value(
+            None,
+            ws(tuple((
+                tag("<mtd"),
+                many0(attribute),
+                multispace0,
+                tag("/>"),
+            ))),
+        ),
+        map(tag_parser!("mtd", contents), Some),
+    ))
+}
+
+/// Rows of a table, with the contents of its cells parsed by `contents`. The labels of
+/// `<mlabeledtr>` rows are dropped.
+pub(crate) fn mtable_rows<'a, O, F>(
+    contents: F,
+) -> impl FnMut(Span<'a>) -> IResult<'a, Vec<Vec<Option<O>>>>
+where
+    O: Clone + 'a,
+    F: FnMut(Span<'a>) -> IResult<'a, O> + Clone + 'a,
+{
+    tag_parser!(
+        "mtable",
+        many0(alt((
+            tag_parser!("mtr", many0(mtd(contents.clone()))),
+            map(tag_parser!("mlabeledtr", many0(mtd(contents))), |cells| {
+                cells.into_iter().skip(1).collect()
+            },),
+        )))
+    )
+}
+
+/// Tables, e.g. matrices. Empty cells and alignment markers are dropped.
+pub fn mtable<'a>(
+    expression: ExpressionParser,
+) -> impl FnMut(Span<'a>) -> IResult<'a, MathExpression> {
+    let cell = move |input| {
+        map(
+            many0(alt((
+                map(alignment_marker, |_| None),
+                map(expression, Some),
+            ))),
+            |elements| elements.into_iter().flatten().collect::<Vec<_>>(),
+        )(input)
+    };
+    map(mtable_rows(cell), |rows| {
+        MathExpression::Mtable(
+            rows.into_iter()
+                .map(|row| row.into_iter().map(Option::unwrap_or_default).collect())
+                .collect(),
+        )
+    })
+}
+
 // function for xml
 pub fn xml_declaration(input: Span) -> IResult<()> {
     let (s, _contents) = ws(delimited(tag("<?"), take_until("?>"), tag("?>")))(input)?;
@@ -411,6 +795,24 @@ pub fn math_expression(input: Span) -> IResult<MathExpression> {
             "mstyle" => context("FAILED TO PARSE <mstyle>", cut(ws(mstyle)))(input),
             "mspace" => context("FAILED TO PARSE <mspace>", cut(ws(mspace)))(input),
             "mo" => context("FAILED TO PARSE <mo>", cut(ws(alt((mo, mo_line)))))(input),
+            "mfenced" => context("FAILED TO PARSE <mfenced>", cut(mfenced(math_expression)))(input),
+            "mroot" => context("FAILED TO PARSE <mroot>", cut(mroot(math_expression)))(input),
+            "mmultiscripts" => context(
+                "FAILED TO PARSE <mmultiscripts>",
+                cut(mmultiscripts(math_expression)),
+            )(input),
+            "menclose" => {
+                context("FAILED TO PARSE <menclose>", cut(menclose(math_expression)))(input)
+            }
+            "mpadded" => context("FAILED TO PARSE <mpadded>", cut(mpadded(math_expression)))(input),
+            "mphantom" => {
+                context("FAILED TO PARSE <mphantom>", cut(mphantom(math_expression)))(input)
+            }
+            "semantics" => context(
+                "FAILED TO PARSE <semantics>",
+                cut(semantics(math_expression)),
+            )(input),
+            "mtable" => context("FAILED TO PARSE <mtable>", cut(mtable(math_expression)))(input),
             _ => {
                 println!("Something went wrong. We grabbed a {} tag", tag_name);
                 context("SOMETHING WENT WRONG. WE SHOULDN'T BE HERE.", cut(mn))(input)
@@ -436,7 +838,8 @@ impl FromStr for Math {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        math(s.into())
+        let normalized = normalize_mathml(s);
+        math(normalized.as_str().into())
             .map(|(_, math)| math)
             .map_err(|err| err.to_string())
     }
@@ -599,9 +1002,9 @@ fn test_mathml_parser() {
                     Box::new(Mo(Operator::Other("sup".to_string()))),
                     Box::new(MathExpression::Mrow(Mrow(vec![
                         Mn("0".to_string()),
-                        Mo(Operator::Other("≤".to_string())),
+                        Mo(Operator::LessEqual),
                         MathExpression::Mi(Mi("t".to_string())),
-                        Mo(Operator::Other("≤".to_string())),
+                        Mo(Operator::LessEqual),
                         Msub(
                             Box::new(MathExpression::Mi(Mi("T".to_string()))),
                             Box::new(Mn("0".to_string())),
@@ -638,14 +1041,85 @@ fn test_mathml_parser() {
                         ),
                     ]))),
                 ),
-                Mo(Operator::Other("≤".to_string())),
+                Mo(Operator::LessEqual),
                 MathExpression::Mi(Mi("C".to_string())),
             ],
         },
     )
 }
 
+#[test]
+fn test_layout_elements() {
+    test_parser(
+        "<mfenced open=\"[\" close=\"]\"><mi>x</mi><mi>y</mi></mfenced>",
+        math_expression,
+        MathExpression::Mfenced(
+            "[".to_string(),
+            "]".to_string(),
+            vec![
+                MathExpression::Mi(Mi("x".to_string())),
+                MathExpression::Mi(Mi("y".to_string())),
+            ],
+        ),
+    );
+    test_parser(
+        "<mroot><mi>x</mi><mn>3</mn></mroot>",
+        math_expression,
+        MathExpression::Mroot(
+            Box::new(MathExpression::Mi(Mi("x".to_string()))),
+            Box::new(Mn("3".to_string())),
+        ),
+    );
+    test_parser(
+        "<mmultiscripts><mi>R</mi><mi>i</mi><none/><mprescripts/><none/><mn>2</mn></mmultiscripts>",
+        math_expression,
+        MathExpression::Mmultiscripts(
+            Box::new(MathExpression::Mi(Mi("R".to_string()))),
+            vec![
+                MathExpression::Mi(Mi("i".to_string())),
+                MathExpression::None,
+            ],
+            vec![MathExpression::None, Mn("2".to_string())],
+        ),
+    );
+    test_parser(
+        "<menclose><mi>x</mi></menclose>",
+        math_expression,
+        MathExpression::Menclose(
+            "longdiv".to_string(),
+            vec![MathExpression::Mi(Mi("x".to_string()))],
+        ),
+    );
+    test_parser(
+        "<mpadded><mi>x</mi></mpadded>",
+        math_expression,
+        MathExpression::Mpadded(vec![MathExpression::Mi(Mi("x".to_string()))]),
+    );
+    test_parser(
+        "<mphantom><mi>x</mi></mphantom>",
+        math_expression,
+        MathExpression::Mphantom(vec![MathExpression::Mi(Mi("x".to_string()))]),
+    );
+    test_parser(
+        "<semantics><mi>x</mi><annotation-xml encoding=\"MathML-Content\"><ci>x</ci></annotation-xml><annotation encoding=\"application/x-tex\">x</annotation></semantics>",
+        math_expression,
+        MathExpression::Semantics(
+            Box::new(MathExpression::Mi(Mi("x".to_string()))),
+            vec!["<ci>x</ci>".to_string(), "x".to_string()],
+        ),
+    );
+    test_parser(
+        "<mtable><mtr><mtd><mi>a</mi></mtd><mtd/></mtr><mlabeledtr><mtd><mtext>(1)</mtext></mtd><mtd><malignmark/><mi>b</mi></mtd></mlabeledtr></mtable>",
+        math_expression,
+        MathExpression::Mtable(vec![
+            vec![vec![MathExpression::Mi(Mi("a".to_string()))], vec![]],
+            vec![vec![MathExpression::Mi(Mi("b".to_string()))]],
+        ]),
+    );
+}
+
 // Exporting macros
+pub(crate) use elem0;
 pub(crate) use elem2;
 pub(crate) use elem_many0;
 pub(crate) use etag;
@@ -0,0 +1,273 @@
+//! Ordinary differential equations of arbitrary order, e.g. d²x/dt² = -kx, and their reduction to
+//! equivalent systems of first order ODEs that can be used to construct AMRs.
+
+use crate::{
+    ast::{
+        operator::{Derivative, Operator},
+        Ci, MathExpression, Mi, Type,
+    },
+    parsers::{
+        first_order_ode::FirstOrderODE,
+        generic_mathml::ParseDiagnostic,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+    },
+};
+use derive_new::new;
+use std::{collections::HashMap, convert::TryFrom, str::FromStr};
+
+/// Ordinary differential equation with a single derivative of arbitrary order on the LHS.
+#[derive(Debug, Ord, PartialOrd, PartialEq, Eq, Clone, Hash, new)]
+pub struct HigherOrderODE {
+    /// The variable/univariate function on the LHS of the equation that is being differentiated.
+    pub lhs_var: Ci,
+
+    pub func_of: Vec<Ci>,
+
+    pub with_respect_to: Ci,
+
+    /// The order of the derivative on the LHS.
+    pub order: u8,
+
+    /// An expression tree corresponding to the RHS of the ODE.
+    pub rhs: MathExpressionTree,
+}
+
+impl From<FirstOrderODE> for HigherOrderODE {
+    fn from(ode: FirstOrderODE) -> Self {
+        HigherOrderODE {
+            lhs_var: ode.lhs_var,
+            func_of: ode.func_of,
+            with_respect_to: ode.with_respect_to,
+            order: 1,
+            rhs: ode.rhs,
+        }
+    }
+}
+
+impl TryFrom<MathExpressionTree> for HigherOrderODE {
+    type Error = ParseDiagnostic;
+
+    /// Interpret an equation of the form dⁿ(lhs_var)/d(with_respect_to)ⁿ = rhs as an ODE.
+    fn try_from(met: MathExpressionTree) -> Result<Self, Self::Error> {
+        if let Cons(Operator::Equals, mut args) = met {
+            if args.len() == 2 {
+                let rhs = args.pop().unwrap();
+                if let Some(Cons(
+                    Operator::Derivative(Derivative {
+                        order, bound_var, ..
+                    }),
+                    mut lhs,
+                )) = args.pop()
+                {
+                    if let (1, Some(Atom(MathExpression::Ci(lhs_var)))) = (lhs.len(), lhs.pop()) {
+                        // Newtonian derivatives without arguments leave the independent variable
+                        // implicit.
+                        let with_respect_to = if bound_var.to_string().is_empty() {
+                            Ci::new(
+                                Some(Type::Real),
+                                Box::new(MathExpression::Mi(Mi("t".to_string()))),
+                                None,
+                                None,
+                            )
+                        } else {
+                            bound_var
+                        };
+                        return Ok(HigherOrderODE {
+                            func_of: lhs_var.func_of.clone().unwrap_or_default(),
+                            lhs_var,
+                            with_respect_to,
+                            order,
+                            rhs,
+                        });
+                    }
+                }
+            }
+        }
+        Err(ParseDiagnostic::new(
+            "Expected an ODE of the form dⁿx/dtⁿ = f(x)".to_string(),
+        ))
+    }
+}
+
+impl FromStr for HigherOrderODE {
+    type Err = ParseDiagnostic;
+
+    /// First order ODEs are parsed exactly as `FirstOrderODE` parses them; other equations are
+    /// parsed as math expression trees and then interpreted as ODEs.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<FirstOrderODE>() {
+            Ok(ode) => Ok(ode.into()),
+            Err(err) => s
+                .parse::<MathExpressionTree>()
+                .ok()
+                .and_then(|met| HigherOrderODE::try_from(met).ok())
+                .ok_or(err),
+        }
+    }
+}
+
+impl HigherOrderODE {
+    /// Name of the auxiliary state holding the derivative of the given order of the LHS
+    /// variable, e.g. `dx_dt` and `d2x_dt2` for the first and second derivatives of x.
+    pub fn auxiliary_name(&self, order: u8) -> String {
+        let (x, t) = (self.lhs_var.to_string(), self.with_respect_to.to_string());
+        if order == 1 {
+            format!("d{x}_d{t}")
+        } else {
+            format!("d{order}{x}_d{t}{order}")
+        }
+    }
+
+    /// The state holding the derivative of the given order of the LHS variable.
+    fn state(&self, order: u8) -> Ci {
+        if order == 0 {
+            self.lhs_var.clone()
+        } else {
+            Ci::new(
+                Some(Type::Function),
+                Box::new(MathExpression::Mi(Mi(self.auxiliary_name(order)))),
+                self.lhs_var.func_of.clone(),
+                None,
+            )
+        }
+    }
+}
+
+/// Replace derivatives of the LHS variables of a system by the states holding them.
+fn replace_derivatives(
+    met: MathExpressionTree,
+    odes: &HashMap<String, &HigherOrderODE>,
+) -> MathExpressionTree {
+    match met {
+        Cons(Operator::Derivative(derivative), args) => {
+            if let [Atom(MathExpression::Ci(x))] = args.as_slice() {
+                if let Some(ode) = odes.get(&x.to_string()) {
+                    if derivative.order < ode.order
+                        && derivative.bound_var.to_string() == ode.with_respect_to.to_string()
+                    {
+                        return Atom(MathExpression::Ci(ode.state(derivative.order)));
+                    }
+                }
+            }
+            Cons(
+                Operator::Derivative(derivative),
+                args.into_iter()
+                    .map(|arg| replace_derivatives(arg, odes))
+                    .collect(),
+            )
+        }
+        Cons(op, args) => Cons(
+            op,
+            args.into_iter()
+                .map(|arg| replace_derivatives(arg, odes))
+                .collect(),
+        ),
+        atom => atom,
+    }
+}
+
+/// Rewrite a system of ODEs of arbitrary order into an equivalent first order system.
+///
+/// An equation dⁿx/dtⁿ = f with n > 1 introduces auxiliary states for the derivatives of x up to
+/// order n - 1 (see `HigherOrderODE::auxiliary_name`), related by dx/dt = dx_dt,
+/// d(dx_dt)/dt = d2x_dt2, ..., and d(dⁿ⁻¹x_dtⁿ⁻¹)/dt = f. Derivatives of those orders in the
+/// right hand sides of the system are replaced by the auxiliary states. First order equations are
+/// passed through unchanged, apart from that replacement.
+pub fn reduce_to_first_order(odes: Vec<HigherOrderODE>) -> Vec<FirstOrderODE> {
+    let by_state: HashMap<String, &HigherOrderODE> = odes
+        .iter()
+        .map(|ode| (ode.lhs_var.to_string(), ode))
+        .collect();
+    let mut system = Vec::new();
+    for ode in odes.iter() {
+        for order in 0..ode.order.saturating_sub(1) {
+            system.push(FirstOrderODE::new(
+                ode.state(order),
+                ode.func_of.clone(),
+                ode.with_respect_to.clone(),
+                Atom(MathExpression::Ci(ode.state(order + 1))),
+            ));
+        }
+        system.push(FirstOrderODE::new(
+            ode.state(ode.order.saturating_sub(1)),
+            ode.func_of.clone(),
+            ode.with_respect_to.clone(),
+            replace_derivatives(ode.rhs.clone(), &by_state),
+        ));
+    }
+    system
+}
+
+#[test]
+fn test_second_order_ode() {
+    let input = "
+    <math>
+        <mfrac>
+            <mrow><msup><mi>d</mi><mn>2</mn></msup><mi>x</mi></mrow>
+            <mrow><mi>d</mi><msup><mi>t</mi><mn>2</mn></msup></mrow>
+        </mfrac>
+        <mo>=</mo>
+        <mo>-</mo><mi>k</mi><mi>x</mi>
+        <mo>-</mo><mi>c</mi>
+        <mfrac><mrow><mi>d</mi><mi>x</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>
+    </math>
+    ";
+    let ode = input.parse::<HigherOrderODE>().unwrap();
+    assert_eq!(ode.order, 2);
+    assert_eq!(ode.lhs_var.to_string(), "x");
+    assert_eq!(ode.with_respect_to.to_string(), "t");
+
+    let system = reduce_to_first_order(vec![ode]);
+    let system: Vec<String> = system
+        .iter()
+        .map(|ode| format!("d{}/d{} = {}", ode.lhs_var, ode.with_respect_to, ode.rhs))
+        .collect();
+    assert_eq!(
+        system,
+        vec!["dx/dt = dx_dt", "ddx_dt/dt = (- (* (- k) x) (* c dx_dt))"]
+    );
+}
+
+#[test]
+fn test_newtonian_third_order_ode() {
+    let input = "<math><mover><mi>y</mi><mo>˙˙˙</mo></mover><mo>=</mo><mi>y</mi></math>";
+    let ode = input.parse::<HigherOrderODE>().unwrap();
+    assert_eq!(ode.order, 3);
+    let system = reduce_to_first_order(vec![ode]);
+    let lhs: Vec<String> = system.iter().map(|ode| ode.lhs_var.to_string()).collect();
+    assert_eq!(lhs, vec!["y", "dy_dt", "d2y_dt2"]);
+    assert_eq!(system[2].rhs.to_string(), "y");
+}
+
+#[test]
+fn test_first_order_ode_is_unchanged() {
+    let input = "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>";
+    let system = reduce_to_first_order(vec![input.parse::<HigherOrderODE>().unwrap()]);
+    assert_eq!(system, vec![input.parse::<FirstOrderODE>().unwrap()]);
+}
+
+#[test]
+fn test_reduced_system_to_amr() {
+    use crate::acset::{GeneralizedAMR, PetriNet};
+    let input = "
+    <math>
+        <mfrac>
+            <mrow><msup><mi>d</mi><mn>2</mn></msup><mi>x</mi></mrow>
+            <mrow><mi>d</mi><msup><mi>t</mi><mn>2</mn></msup></mrow>
+        </mfrac>
+        <mo>=</mo>
+        <mo>-</mo><mi>k</mi><mi>x</mi>
+    </math>
+    ";
+    let system = reduce_to_first_order(vec![input.parse::<HigherOrderODE>().unwrap()]);
+    let petrinet = PetriNet::from(system.clone());
+    let states: Vec<String> = petrinet
+        .model
+        .states
+        .iter()
+        .map(|state| state.id.clone())
+        .collect();
+    assert_eq!(states, vec!["dx_dt", "x"]);
+    let gamr = GeneralizedAMR::from(system);
+    assert_eq!(gamr.met.len(), 2);
+}
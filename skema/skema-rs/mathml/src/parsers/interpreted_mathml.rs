@@ -14,9 +14,11 @@ use crate::{
         Mrow, SummationMath, Type, VectorNotation,
     },
     parsers::generic_mathml::{
-        add, attribute, cross, divide, dot, elem_many0, equals, etag, lparen, mean, mi, mn, msub,
-        msubsup, mtext, multiply, rparen, stag, subtract, tag_parser, ws, xml_declaration, IResult,
-        ParseError, Span,
+        add, attribute, cross, divide, dot, elem_many0, equals, etag, greater, greater_equal,
+        hadamard, less, less_equal, lparen, mean, menclose, mfenced, mi, mmultiscripts, mn,
+        mpadded, mphantom, mroot, msub, msubsup, mtable, mtext, multiply, not_equal, rparen,
+        semantics, stag, subtract, tag_parser, transpose, ws, xml_declaration, IResult, ParseError,
+        Span,
     },
 };
 
@@ -25,7 +27,7 @@ use nom::sequence::terminated;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    combinator::{map, opt, value},
+    combinator::{map, map_opt, opt, value, verify},
     multi::{many0, many1, separated_list1},
     sequence::{delimited, pair, preceded, tuple},
 };
@@ -36,7 +38,23 @@ pub fn operator(input: Span) -> IResult<Operator> {
     let (s, op) = ws(delimited(
         stag!("mo"),
         alt((
-            add, subtract, multiply, divide, equals, lparen, rparen, mean, dot, cross,
+            add,
+            subtract,
+            multiply,
+            divide,
+            equals,
+            lparen,
+            rparen,
+            mean,
+            dot,
+            cross,
+            hadamard,
+            transpose,
+            less_equal,
+            less,
+            greater_equal,
+            greater,
+            not_equal,
         )),
         etag!("mo"),
     ))(input)?;
@@ -993,6 +1011,81 @@ pub fn second_order_partial_derivative_leibniz_notation(input: Span) -> IResult<
     )))
 }
 
+/// Parse the order of a higher order derivative, e.g. the 2 in d²x/dt².
+fn derivative_order(input: Span) -> IResult<u8> {
+    map_opt(mn, |order| match order {
+        MathExpression::Mn(n) => n.parse::<u8>().ok().filter(|n| *n >= 2),
+        _ => None,
+    })(input)
+}
+
+/// Parse an ordinary derivative of order two or higher written in Leibniz notation.
+/// Example: d²x/dt², with the denominator written as either d t² or (d t)².
+pub fn higher_order_derivative_leibniz_notation(input: Span) -> IResult<(Derivative, Ci)> {
+    let (s, (_, _, numerator_order)) = tuple((
+        stag!("mfrac"),
+        stag!("mrow"),
+        tag_parser!("msup", preceded(d, derivative_order)),
+    ))(input)?;
+    let (s, func) = ws(alt((
+        ci_univariate_func,
+        map(
+            ci_unknown,
+            |Ci {
+                 content, func_of, ..
+             }| {
+                Ci {
+                    r#type: Some(Type::Function),
+                    content,
+                    func_of,
+                    notation: None,
+                }
+            },
+        ),
+        ci_subscript_func,
+    )))(s)?;
+    let (s, (with_respect_to, denominator_order)) = delimited(
+        pair(etag!("mrow"), stag!("mrow")),
+        alt((
+            preceded(d, tag_parser!("msup", pair(mi, derivative_order))),
+            tag_parser!(
+                "msup",
+                pair(tag_parser!("mrow", preceded(d, mi)), derivative_order)
+            ),
+        )),
+        pair(etag!("mrow"), etag!("mfrac")),
+    )(s)?;
+    if numerator_order != denominator_order {
+        return Err(nom::Err::Error(ParseError::new(
+            "Orders of the numerator and denominator of the derivative do not match".to_string(),
+            input,
+        )));
+    }
+    let var_index = func
+        .func_of
+        .iter()
+        .flatten()
+        .position(|bvar| *bvar.content == MathExpression::Mi(with_respect_to.clone()))
+        .map_or(1, |i| i + 1) as u8;
+    Ok((
+        s,
+        (
+            Derivative::new(
+                numerator_order,
+                var_index,
+                Ci::new(
+                    Some(Type::Real),
+                    Box::new(MathExpression::Mi(with_respect_to)),
+                    None,
+                    None,
+                ),
+                DerivativeNotation::LeibnizTotal,
+            ),
+            func,
+        ),
+    ))
+}
+
 pub fn newtonian_derivative(input: Span) -> IResult<(Derivative, Ci)> {
     // Get number of dots to recognize the order of the derivative
     let n_dots = delimited(
@@ -1002,6 +1095,7 @@ pub fn newtonian_derivative(input: Span) -> IResult<(Derivative, Ci)> {
                 x.len() as u8
             }),
             value(1_u8, tag("&#x02D9;")),
+            value(2_u8, tag("¨")),
         )),
         etag!("mo"),
     );
@@ -1146,9 +1240,13 @@ pub fn absolute(input: Span) -> IResult<MathExpression> {
     Ok((s, components))
 }
 
-/// Example: Divergence
+/// Example: Divergence. A middle dot is normalized to `*`, so ∇ followed by it is a divergence
+/// as well.
 pub fn div(input: Span) -> IResult<Operator> {
-    let (s, _op) = ws(pair(gradient, ws(delimited(stag!("mo"), dot, etag!("mo")))))(input)?;
+    let (s, _op) = ws(pair(
+        gradient,
+        ws(delimited(stag!("mo"), alt((dot, multiply)), etag!("mo"))),
+    ))(input)?;
     let div = Operator::Div;
     Ok((s, div))
 }
@@ -1626,6 +1724,127 @@ pub fn natural_log(input: Span) -> IResult<Operator> {
     Ok((s, operator))
 }
 
+/// Functions that become prefix operators when applied with the invisible function application
+/// operator (U+2061).
+fn applied_function_operator(name: &str) -> Option<Operator> {
+    match name {
+        "sin" => Some(Operator::Sin),
+        "cos" => Some(Operator::Cos),
+        "tan" => Some(Operator::Tan),
+        "sec" => Some(Operator::Sec),
+        "csc" => Some(Operator::Csc),
+        "cot" => Some(Operator::Cot),
+        "arcsin" => Some(Operator::Arcsin),
+        "arccos" => Some(Operator::Arccos),
+        "arctan" => Some(Operator::Arctan),
+        "exp" => Some(Operator::Exp),
+        "ln" => Some(Operator::Logarithm(Logarithm::new(LogarithmNotation::Ln))),
+        "log" => Some(Operator::Logarithm(Logarithm::new(LogarithmNotation::Log))),
+        _ => None,
+    }
+}
+
+/// Parenthesized function arguments, which may contain commas and nested parentheses.
+fn parenthesized_arguments(input: Span) -> IResult<Vec<MathExpression>> {
+    let (s, (arguments, _)) = preceded(
+        ws(delimited(stag!("mo"), lparen, etag!("mo"))),
+        many_till(
+            alt((
+                map(parenthesized_arguments, |xs| MathExpression::Mrow(Mrow(xs))),
+                value(
+                    MathExpression::Mo(Operator::Comma),
+                    ws(delimited(stag!("mo"), ws(tag(",")), etag!("mo"))),
+                ),
+                math_expression,
+            )),
+            ws(delimited(stag!("mo"), rparen, etag!("mo"))),
+        ),
+    )(input)?;
+    Ok((s, arguments))
+}
+
+/// Collect the arguments of a function application if they are all identifiers, e.g. (t, x).
+fn identifier_arguments(arguments: &[MathExpression], identifiers: &mut Vec<Ci>) -> bool {
+    arguments.iter().all(|argument| match argument {
+        MathExpression::Ci(Ci {
+            content,
+            func_of: None,
+            ..
+        }) if matches!(**content, MathExpression::Mi(_)) => {
+            identifiers.push(Ci::new(Some(Type::Real), content.clone(), None, None));
+            true
+        }
+        MathExpression::Mo(Operator::Comma | Operator::Lparen | Operator::Rparen) => true,
+        MathExpression::Mrow(Mrow(xs)) => identifier_arguments(xs, identifiers),
+        _ => false,
+    })
+}
+
+/// Function application made explicit with the invisible function application operator, e.g.
+/// `sin⁡x` or `f⁡(t, x)`. Known functions become prefix operators, functions of identifiers become
+/// content identifiers of type function, and anything else is left as a juxtaposition.
+pub fn function_application(input: Span) -> IResult<MathExpression> {
+    let (s, (Mi(name), _, arguments)) = tuple((
+        // Minimization has a dedicated parser
+        verify(mi, |Mi(name)| name != "min"),
+        ws(delimited(stag!("mo"), ws(tag("\u{2061}")), etag!("mo"))),
+        alt((parenthesized_arguments, map(math_expression, |x| vec![x]))),
+    ))(input)?;
+    if let Some(operator) = applied_function_operator(&name) {
+        return Ok((
+            s,
+            MathExpression::Mrow(Mrow(vec![
+                MathExpression::Mo(operator),
+                MathExpression::Mrow(Mrow(arguments)),
+            ])),
+        ));
+    }
+    let mut identifiers = Vec::new();
+    let function = if identifier_arguments(&arguments, &mut identifiers) && !identifiers.is_empty()
+    {
+        MathExpression::Ci(Ci::new(
+            Some(Type::Function),
+            Box::new(MathExpression::Mi(Mi(name))),
+            Some(identifiers),
+            None,
+        ))
+    } else {
+        MathExpression::Mrow(Mrow(vec![
+            MathExpression::Ci(Ci::new(
+                Some(Type::Function),
+                Box::new(MathExpression::Mi(Mi(name))),
+                None,
+                None,
+            )),
+            MathExpression::Mrow(Mrow(arguments)),
+        ]))
+    };
+    Ok((s, function))
+}
+
+/// Piecewise functions, written as a left brace followed by a table of cases, either as
+/// `<mo>{</mo><mtable>...</mtable>` or as `<mfenced open="{" close=""><mtable>...</mtable></mfenced>`.
+pub fn piecewise(input: Span) -> IResult<MathExpression> {
+    map_opt(
+        alt((
+            preceded(
+                ws(delimited(stag!("mo"), ws(tag("{")), etag!("mo"))),
+                mtable(math_expression),
+            ),
+            map_opt(mfenced(math_expression), |fenced| match fenced {
+                MathExpression::Mfenced(open, _, mut xs) if open == "{" && xs.len() == 1 => {
+                    xs.pop()
+                }
+                _ => None,
+            }),
+        )),
+        |cases| match cases {
+            MathExpression::Mtable(rows) => Some(MathExpression::Piecewise(rows)),
+            _ => None,
+        },
+    )(input)
+}
+
 /// Msubsup to content indentifiers
 pub fn msubsubsup_to_content(input: Span) -> IResult<MathExpression> {
     let (s, x) = ws(msubsup)(input)?;
@@ -1645,6 +1864,8 @@ pub fn msub_to_content(input: Span) -> IResult<MathExpression> {
 pub fn math_expression(input: Span) -> IResult<MathExpression> {
     ws(alt((
         alt((
+            function_application,
+            piecewise,
             map(
                 integral_with_many_math_expression_integrand,
                 |(operator, comp, var)| {
@@ -1922,6 +2143,38 @@ pub fn math_expression(input: Span) -> IResult<MathExpression> {
                     })
                 },
             ),
+            map(
+                higher_order_derivative_leibniz_notation,
+                |(
+                    Derivative {
+                        order,
+                        var_index,
+                        bound_var,
+                        notation,
+                    },
+                    Ci {
+                        r#type,
+                        content,
+                        func_of,
+                        notation: vector_notation,
+                    },
+                )| {
+                    MathExpression::Differential(Differential {
+                        diff: Box::new(MathExpression::Mo(Operator::Derivative(Derivative {
+                            order,
+                            var_index,
+                            bound_var,
+                            notation,
+                        }))),
+                        func: Box::new(MathExpression::Ci(Ci {
+                            r#type,
+                            content,
+                            func_of,
+                            notation: vector_notation,
+                        })),
+                    })
+                },
+            ),
             map(
                 newtonian_derivative,
                 |(
@@ -2110,6 +2363,14 @@ pub fn math_expression(input: Span) -> IResult<MathExpression> {
             mfrac,
             mtext,
             over_term,
+            mfenced(math_expression),
+            mroot(math_expression),
+            mmultiscripts(math_expression),
+            menclose(math_expression),
+            mpadded(math_expression),
+            mphantom(math_expression),
+            semantics(math_expression),
+            mtable(math_expression),
         )),
         map(mrow, MathExpression::Mrow),
         msubsubsup_to_content,
@@ -0,0 +1,958 @@
+//! Parser for LaTeX equations. LaTeX is parsed into the same MathExpression representation that
+//! the interpreted presentation MathML parsers produce, so that the resulting expressions can be
+//! converted to MathExpressionTree objects by the Pratt parser, without going through MathML.
+use crate::{
+    ast::{
+        operator::{
+            Derivative, DerivativeNotation, Gradient, Int, Logarithm, LogarithmNotation, Operator,
+            Summation,
+        },
+        Ci, Differential, Integral, Math, MathExpression, Mi, Mrow, SummationMath, Type,
+        VectorNotation,
+    },
+    parsers::{
+        first_order_ode::FirstOrderODE,
+        generic_mathml::{IResult, ParseDiagnostic, ParseError, Span},
+        math_expression_tree::MathExpressionTree,
+    },
+};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{alpha1, char, digit1, multispace1, satisfy},
+    combinator::{map, not, opt, recognize, value, verify},
+    multi::{many0, many_till, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+};
+use std::convert::TryFrom;
+
+/// Control words that denote identifiers (mostly Greek letters), along with the Unicode
+/// characters used for them in presentation MathML.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("varpi", "ϖ"),
+    ("rho", "ρ"),
+    ("varrho", "ϱ"),
+    ("sigma", "σ"),
+    ("varsigma", "ς"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("ell", "ℓ"),
+    ("infty", "∞"),
+];
+
+/// Control words for functions that are applied as prefix operators.
+const FUNCTIONS: &[(&str, Operator)] = &[
+    ("sin", Operator::Sin),
+    ("cos", Operator::Cos),
+    ("tan", Operator::Tan),
+    ("sec", Operator::Sec),
+    ("csc", Operator::Csc),
+    ("cot", Operator::Cot),
+    ("arcsin", Operator::Arcsin),
+    ("arccos", Operator::Arccos),
+    ("arctan", Operator::Arctan),
+    ("exp", Operator::Exp),
+    ("min", Operator::Min),
+];
+
+/// Whitespace, spacing commands and delimiter sizing commands, none of which carry meaning.
+fn space(input: Span) -> IResult<()> {
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value(
+                (),
+                alt((
+                    tag("\\qquad"),
+                    tag("\\quad"),
+                    tag("\\,"),
+                    tag("\\;"),
+                    tag("\\:"),
+                    tag("\\!"),
+                    tag("\\ "),
+                    tag("~"),
+                )),
+            ),
+            value(
+                (),
+                terminated(
+                    alt((
+                        tag("\\left"),
+                        tag("\\right"),
+                        tag("\\bigl"),
+                        tag("\\bigr"),
+                        tag("\\Bigl"),
+                        tag("\\Bigr"),
+                        tag("\\big"),
+                        tag("\\Big"),
+                    )),
+                    not(satisfy(|c| c.is_ascii_alphabetic())),
+                ),
+            ),
+        ))),
+    )(input)
+}
+
+/// A combinator that takes a parser `inner` and produces a parser that also consumes leading and
+/// trailing LaTeX whitespace, returning the output of `inner`.
+fn lx<'a, F, O>(inner: F) -> impl FnMut(Span<'a>) -> IResult<O>
+where
+    F: FnMut(Span<'a>) -> IResult<O> + 'a,
+{
+    delimited(space, inner, space)
+}
+
+/// Control words, e.g. `\frac`
+fn command(input: Span) -> IResult<Span> {
+    preceded(char('\\'), alpha1)(input)
+}
+
+/// A specific control word
+fn named<'a>(name: &'static str) -> impl FnMut(Span<'a>) -> IResult<'a, ()> {
+    lx(value(
+        (),
+        verify(command, move |c: &Span| *c.fragment() == name),
+    ))
+}
+
+/// A braced group, e.g. `{x+y}`
+fn braced<'a, F, O>(inner: F) -> impl FnMut(Span<'a>) -> IResult<O>
+where
+    F: FnMut(Span<'a>) -> IResult<O> + 'a,
+{
+    delimited(lx(char('{')), inner, lx(char('}')))
+}
+
+/// Convert a sequence of expressions to a single one, wrapping it in an Mrow if needed.
+fn row(mut elements: Vec<MathExpression>) -> MathExpression {
+    if elements.len() == 1 {
+        elements.remove(0)
+    } else {
+        MathExpression::Mrow(Mrow(elements))
+    }
+}
+
+/// Convert a sequence of expressions to the generic (uninterpreted) form used by the
+/// presentation MathML parsers for subscripts, e.g. `<msub><mi>S</mi><mi>h</mi></msub>`.
+fn generic(elements: Vec<MathExpression>) -> MathExpression {
+    let elements: Vec<MathExpression> = elements
+        .into_iter()
+        .map(|x| match x {
+            MathExpression::Ci(Ci {
+                r#type: None,
+                content,
+                func_of: None,
+                notation: None,
+            }) => *content,
+            x => x,
+        })
+        .collect();
+    row(elements)
+}
+
+/// Identifiers without an interpretation: `\alpha`, `\infty`, etc.
+fn symbol(input: Span) -> IResult<String> {
+    let (s, c) = lx(command)(input)?;
+    match SYMBOLS.iter().find(|(name, _)| name == c.fragment()) {
+        Some((_, unicode)) => Ok((s, unicode.to_string())),
+        None => Err(nom::Err::Error(ParseError::new(
+            format!("Unknown symbol '\\{}'", c.fragment()),
+            input,
+        ))),
+    }
+}
+
+/// Names: single letters, symbols, and multi-letter names given with e.g. `\mathrm{...}`.
+fn name(input: Span) -> IResult<String> {
+    lx(alt((
+        map(satisfy(|c| c.is_alphabetic()), |c| c.to_string()),
+        symbol,
+        map(
+            preceded(
+                alt((
+                    named("mathrm"),
+                    named("text"),
+                    named("textrm"),
+                    named("textit"),
+                    named("mathit"),
+                    named("operatorname"),
+                )),
+                braced(take_while1(|c: char| c != '}')),
+            ),
+            |x: Span| x.trim().to_string(),
+        ),
+    )))(input)
+}
+
+/// The argument of a subscript or superscript: either a braced group or a single token.
+fn script_argument(input: Span) -> IResult<Vec<MathExpression>> {
+    alt((
+        braced(expressions),
+        map(lx(satisfy(|c| c.is_ascii_digit())), |c| {
+            vec![MathExpression::Mn(c.to_string())]
+        }),
+        map(name, |x| vec![identifier(x)]),
+    ))(input)
+}
+
+/// Subscripts, e.g. `_h` in `S_h`
+fn subscript(input: Span) -> IResult<MathExpression> {
+    map(preceded(lx(char('_')), script_argument), generic)(input)
+}
+
+/// Superscripts, e.g. `^{2}` in `x^{2}`
+fn superscript(input: Span) -> IResult<MathExpression> {
+    map(preceded(lx(char('^')), script_argument), row)(input)
+}
+
+/// An identifier with no further information about it.
+fn identifier(name: String) -> MathExpression {
+    MathExpression::Ci(Ci::new(
+        None,
+        Box::new(MathExpression::Mi(Mi(name))),
+        None,
+        None,
+    ))
+}
+
+/// Attach a subscript to an expression.
+fn with_subscript(base: MathExpression, sub: MathExpression) -> MathExpression {
+    let content = match base {
+        MathExpression::Ci(ci) => ci.content,
+        base => Box::new(base),
+    };
+    MathExpression::Ci(Ci::new(
+        None,
+        Box::new(MathExpression::Msub(content, Box::new(sub))),
+        None,
+        None,
+    ))
+}
+
+/// Attach optional subscripts and superscripts (in either order) to an expression.
+fn scripted<'a, F>(mut inner: F) -> impl FnMut(Span<'a>) -> IResult<MathExpression>
+where
+    F: FnMut(Span<'a>) -> IResult<MathExpression> + 'a,
+{
+    move |input| {
+        let (s, base) = inner(input)?;
+        let (s, sub) = opt(subscript)(s)?;
+        let (s, sup) = opt(superscript)(s)?;
+        let (s, sub) = match sub {
+            Some(sub) => (s, Some(sub)),
+            None => opt(subscript)(s)?,
+        };
+        let base = match sub {
+            Some(sub) => with_subscript(base, sub),
+            None => base,
+        };
+        match sup {
+            Some(sup) => Ok((s, MathExpression::Msup(Box::new(base), Box::new(sup)))),
+            None => Ok((s, base)),
+        }
+    }
+}
+
+/// The variables in a function application, e.g. `(t)` in `S(t)`.
+fn bound_variables(input: Span) -> IResult<Vec<Ci>> {
+    map(
+        delimited(
+            lx(char('(')),
+            separated_list1(lx(char(',')), name),
+            lx(char(')')),
+        ),
+        |vars| {
+            vars.into_iter()
+                .map(|x| {
+                    Ci::new(
+                        Some(Type::Real),
+                        Box::new(MathExpression::Mi(Mi(x))),
+                        None,
+                        None,
+                    )
+                })
+                .collect()
+        },
+    )(input)
+}
+
+/// Identifiers, which are treated as univariate functions if they are followed by their bound
+/// variables, e.g. `S(t)`.
+fn ci(input: Span) -> IResult<MathExpression> {
+    let (s, base) = map(name, identifier)(input)?;
+    let (s, sub) = opt(subscript)(s)?;
+    let base = match sub {
+        Some(sub) => with_subscript(base, sub),
+        None => base,
+    };
+    let (s, func_of) = opt(bound_variables)(s)?;
+    let base = match (base, func_of) {
+        (MathExpression::Ci(ci), Some(func_of)) => {
+            MathExpression::Ci(Ci::new(Some(Type::Real), ci.content, Some(func_of), None))
+        }
+        (base, _) => base,
+    };
+    let (s, sup) = opt(superscript)(s)?;
+    match sup {
+        Some(sup) => Ok((s, MathExpression::Msup(Box::new(base), Box::new(sup)))),
+        None => Ok((s, base)),
+    }
+}
+
+/// Vectors, e.g. `\vec{v}` or `\mathbf{v}`
+fn vector(input: Span) -> IResult<MathExpression> {
+    let (s, (notation, x)) = pair(
+        alt((
+            value(VectorNotation::Arrow, named("vec")),
+            value(VectorNotation::Bold, named("mathbf")),
+            value(VectorNotation::Bold, named("boldsymbol")),
+        )),
+        braced(name),
+    )(input)?;
+    Ok((
+        s,
+        MathExpression::Ci(Ci::new(
+            Some(Type::Real),
+            Box::new(MathExpression::Mi(Mi(x))),
+            None,
+            Some(notation),
+        )),
+    ))
+}
+
+/// The function being differentiated, e.g. `S` or `S(t)` in `\frac{dS(t)}{dt}`.
+fn differentiated_function(input: Span) -> IResult<Ci> {
+    let (s, x) = name(input)?;
+    let (s, sub) = opt(subscript)(s)?;
+    let content = match sub {
+        Some(sub) => MathExpression::Msub(Box::new(MathExpression::Mi(Mi(x))), Box::new(sub)),
+        None => MathExpression::Mi(Mi(x)),
+    };
+    let (s, func_of) = opt(bound_variables)(s)?;
+    Ok((
+        s,
+        Ci::new(Some(Type::Function), Box::new(content), func_of, None),
+    ))
+}
+
+/// Construct a derivative of `func` with respect to `var`.
+fn derivative(order: u8, var: String, func: &Ci, notation: DerivativeNotation) -> MathExpression {
+    let var_index = func
+        .func_of
+        .as_ref()
+        .and_then(|func_of| {
+            func_of
+                .iter()
+                .position(|x| *x.content == MathExpression::Mi(Mi(var.clone())))
+        })
+        .unwrap_or(0);
+    let bound_var = Ci::new(
+        Some(Type::Real),
+        Box::new(MathExpression::Mi(Mi(var))),
+        None,
+        None,
+    );
+    MathExpression::Mo(Operator::Derivative(Derivative::new(
+        order,
+        (var_index + 1) as u8,
+        bound_var,
+        notation,
+    )))
+}
+
+/// `d` as used in Leibniz notation and for integration variables.
+fn d(input: Span) -> IResult<()> {
+    lx(alt((
+        value((), char('d')),
+        value(
+            (),
+            preceded(
+                alt((named("mathrm"), named("text"), named("operatorname"))),
+                braced(lx(char('d'))),
+            ),
+        ),
+    )))(input)
+}
+
+/// The order of a derivative, e.g. `^2` in `d^2`
+fn derivative_order(input: Span) -> IResult<u8> {
+    map(
+        opt(preceded(
+            lx(char('^')),
+            alt((
+                braced(lx(digit1)),
+                lx(recognize(satisfy(|c| c.is_ascii_digit()))),
+            )),
+        )),
+        |order: Option<Span>| order.map_or(1, |x| x.fragment().parse::<u8>().unwrap_or(1)),
+    )(input)
+}
+
+/// Derivatives in Leibniz notation, e.g. `\frac{dS}{dt}`, `\frac{\partial^2 u}{\partial x^2}`
+/// or `\frac{d}{dt}(...)`.
+fn leibniz_derivative(input: Span) -> IResult<MathExpression> {
+    let (s, _) = alt((named("frac"), named("dfrac"), named("tfrac")))(input)?;
+    let (s, (notation, order)) = preceded(
+        lx(char('{')),
+        alt((
+            map(preceded(d, derivative_order), |order| {
+                (DerivativeNotation::LeibnizTotal, order)
+            }),
+            map(preceded(named("partial"), derivative_order), |order| {
+                (DerivativeNotation::LeibnizPartialStandard, order)
+            }),
+        )),
+    )(s)?;
+    let (s, func) = terminated(opt(differentiated_function), lx(char('}')))(s)?;
+    let total = notation == DerivativeNotation::LeibnizTotal;
+    let differential = move |input| {
+        if total {
+            d(input)
+        } else {
+            named("partial")(input)
+        }
+    };
+    let (s, var) = braced(terminated(preceded(differential, name), derivative_order))(s)?;
+    match func {
+        Some(func) => Ok((
+            s,
+            MathExpression::Differential(Differential::new(
+                Box::new(derivative(order, var, &func, notation)),
+                Box::new(MathExpression::Ci(func)),
+            )),
+        )),
+        None => {
+            let (s, operand) = expression(s)?;
+            let func = Ci::new(None, Box::new(MathExpression::None), None, None);
+            Ok((
+                s,
+                MathExpression::Differential(Differential::new(
+                    Box::new(derivative(order, var, &func, notation)),
+                    Box::new(operand),
+                )),
+            ))
+        }
+    }
+}
+
+/// Partial derivatives in compact notation, e.g. `\partial_t S`
+fn compact_partial_derivative(input: Span) -> IResult<MathExpression> {
+    let (s, (_, var, func)) = tuple((
+        named("partial"),
+        preceded(lx(char('_')), alt((braced(name), name))),
+        differentiated_function,
+    ))(input)?;
+    Ok((
+        s,
+        MathExpression::Differential(Differential::new(
+            Box::new(derivative(
+                1,
+                var,
+                &func,
+                DerivativeNotation::LeibnizPartialCompact,
+            )),
+            Box::new(MathExpression::Ci(func)),
+        )),
+    ))
+}
+
+/// Derivatives in Newton's notation, e.g. `\dot{S}` or `\ddot{x}(t)`
+fn newtonian_derivative(input: Span) -> IResult<MathExpression> {
+    let (s, order) = alt((
+        value(1, named("dot")),
+        value(2, named("ddot")),
+        value(3, named("dddot")),
+    ))(input)?;
+    let (s, mut func) = braced(differentiated_function)(s)?;
+    let (s, func_of) = opt(bound_variables)(s)?;
+    if func_of.is_some() {
+        func.func_of = func_of;
+    }
+    let var = match &func.func_of {
+        Some(func_of) if !func_of.is_empty() => func_of[0].content.to_string(),
+        _ => "t".to_string(),
+    };
+    Ok((
+        s,
+        MathExpression::Differential(Differential::new(
+            Box::new(derivative(order, var, &func, DerivativeNotation::Newton)),
+            Box::new(MathExpression::Ci(func)),
+        )),
+    ))
+}
+
+/// Forms of the nabla operator: gradient `\nabla`, `\nabla_x`, divergence `\nabla \cdot` and
+/// Laplacian `\nabla^2`. As in the presentation MathML parsers, the operator is grouped with the
+/// expression it applies to, so that a preceding coefficient multiplies the whole term.
+fn nabla(input: Span) -> IResult<MathExpression> {
+    let (s, _) = named("nabla")(input)?;
+    let (s, op) = alt((
+        value(
+            Operator::Laplacian,
+            preceded(lx(char('^')), alt((braced(lx(char('2'))), lx(char('2'))))),
+        ),
+        map(preceded(lx(char('_')), alt((braced(name), name))), |x| {
+            Operator::Gradient(Gradient::new(Some(Box::new(MathExpression::Mi(Mi(x))))))
+        }),
+        value(Operator::Div, named("cdot")),
+        value(Operator::Gradient(Gradient::new(None)), space),
+    ))(s)?;
+    let (s, operand) = opt(verify(expression, |x| !matches!(x, MathExpression::Mo(_))))(s)?;
+    let op = Box::new(MathExpression::Mo(op));
+    Ok((
+        s,
+        match operand {
+            Some(operand) => MathExpression::Differential(Differential::new(op, Box::new(operand))),
+            None => *op,
+        },
+    ))
+}
+
+/// Summations, e.g. `\sum_{i=1}^{N} x_i`. As in the presentation MathML parsers, the rest of the
+/// expression (up to an equals sign) is taken to be the summand.
+fn summation(input: Span) -> IResult<MathExpression> {
+    let bound = |c| {
+        map(preceded(lx(char(c)), script_argument), |x| {
+            Box::new(MathExpression::Mrow(Mrow(x)))
+        })
+    };
+    let (s, _) = named("sum")(input)?;
+    let (s, lower) = opt(bound('_'))(s)?;
+    let (s, upper) = opt(bound('^'))(s)?;
+    let (s, lower) = match lower {
+        Some(lower) => (s, Some(lower)),
+        None => opt(bound('_'))(s)?,
+    };
+    let (s, summand) = many0(verify(expression, |x| {
+        *x != MathExpression::Mo(Operator::Equals)
+    }))(s)?;
+    Ok((
+        s,
+        MathExpression::SummationMath(SummationMath::new(
+            Box::new(MathExpression::Mo(Operator::Summation(Summation::new(
+                lower, upper,
+            )))),
+            Box::new(MathExpression::Mrow(Mrow(summand))),
+        )),
+    ))
+}
+
+/// Integrals, e.g. `\int_a^b x^2 \, dx`
+fn integral(input: Span) -> IResult<MathExpression> {
+    let limit = |c| {
+        map(preceded(lx(char(c)), script_argument), |x| {
+            Box::new(generic(x))
+        })
+    };
+    let (s, _) = named("int")(input)?;
+    let (s, lower) = opt(limit('_'))(s)?;
+    let (s, upper) = opt(limit('^'))(s)?;
+    let (s, lower) = match lower {
+        Some(lower) => (s, Some(lower)),
+        None => opt(limit('_'))(s)?,
+    };
+    let (s, (integrand, (_, var))) = many_till(expression, pair(d, name))(s)?;
+    let var = identifier(var);
+    Ok((
+        s,
+        MathExpression::Integral(Integral::new(
+            Box::new(MathExpression::Mo(Operator::Int(Int::new(
+                lower,
+                upper,
+                Box::new(var.clone()),
+            )))),
+            Box::new(MathExpression::Mrow(Mrow(integrand))),
+            Box::new(var),
+        )),
+    ))
+}
+
+/// Functions applied as prefix operators, e.g. `\sin`, `\ln` and `\log_{10}`
+fn function(input: Span) -> IResult<MathExpression> {
+    let (s, c) = lx(command)(input)?;
+    let op = match *c.fragment() {
+        "ln" => Operator::Logarithm(Logarithm::new(LogarithmNotation::Ln)),
+        "log" => {
+            let (s, base) = opt(subscript)(s)?;
+            let notation = match base {
+                Some(base) => LogarithmNotation::LogBase(Box::new(base)),
+                None => LogarithmNotation::Log,
+            };
+            return Ok((
+                s,
+                MathExpression::Mo(Operator::Logarithm(Logarithm::new(notation))),
+            ));
+        }
+        name => match FUNCTIONS.iter().find(|(f, _)| *f == name) {
+            Some((_, op)) => op.clone(),
+            None => {
+                return Err(nom::Err::Error(ParseError::new(
+                    format!("Unknown function '\\{name}'"),
+                    input,
+                )))
+            }
+        },
+    };
+    Ok((s, MathExpression::Mo(op)))
+}
+
+/// Square roots, e.g. `\sqrt{x}`
+fn sqrt(input: Span) -> IResult<MathExpression> {
+    map(preceded(named("sqrt"), braced(expressions)), |x| {
+        MathExpression::Msqrt(Box::new(MathExpression::Mrow(Mrow(x))))
+    })(input)
+}
+
+/// Plain fractions, e.g. `\frac{a}{b}`
+fn frac(input: Span) -> IResult<MathExpression> {
+    map(
+        preceded(
+            alt((named("frac"), named("dfrac"), named("tfrac"))),
+            pair(braced(expressions), braced(expressions)),
+        ),
+        |(numerator, denominator)| {
+            MathExpression::Mfrac(Box::new(row(numerator)), Box::new(row(denominator)))
+        },
+    )(input)
+}
+
+/// Parenthesized expressions, e.g. `(a+b)` or `\left[ a+b \right]`
+fn parenthesized(input: Span) -> IResult<MathExpression> {
+    map(
+        delimited(
+            lx(alt((tag("("), tag("["), tag("\\{")))),
+            expressions,
+            lx(alt((tag(")"), tag("]"), tag("\\}")))),
+        ),
+        |x| MathExpression::Mrow(Mrow(x)),
+    )(input)
+}
+
+/// Absolute values, e.g. `|x|`
+fn absolute(input: Span) -> IResult<MathExpression> {
+    map(
+        delimited(
+            lx(alt((tag("|"), tag("\\lvert"), tag("\\vert")))),
+            expressions,
+            lx(alt((tag("|"), tag("\\rvert"), tag("\\vert")))),
+        ),
+        |x| {
+            MathExpression::Absolute(
+                Box::new(MathExpression::Mo(Operator::Abs)),
+                Box::new(MathExpression::Mrow(Mrow(x))),
+            )
+        },
+    )(input)
+}
+
+/// Numbers, e.g. `2` or `0.25`
+fn number(input: Span) -> IResult<MathExpression> {
+    map(
+        lx(recognize(pair(digit1, opt(pair(char('.'), digit1))))),
+        |x: Span| MathExpression::Mn(x.fragment().to_string()),
+    )(input)
+}
+
+/// Binary and unary operators
+fn operator(input: Span) -> IResult<MathExpression> {
+    map(
+        lx(alt((
+            value(Operator::Add, char('+')),
+            value(Operator::Subtract, char('-')),
+            value(Operator::Equals, char('=')),
+            value(Operator::Multiply, char('*')),
+            value(Operator::Divide, char('/')),
+            value(Operator::Multiply, named("cdot")),
+            value(Operator::Multiply, named("times")),
+            value(Operator::Multiply, named("ast")),
+            value(Operator::Divide, named("div")),
+        ))),
+        MathExpression::Mo,
+    )(input)
+}
+
+/// Control words that are not recognized are reported as errors rather than silently dropped.
+fn unsupported_command(input: Span) -> IResult<MathExpression> {
+    let (s, _) = space(input)?;
+    let (_, c) = command(s)?;
+    Err(nom::Err::Failure(
+        ParseError::new(format!("Unsupported LaTeX command '\\{}'", c.fragment()), s)
+            .with_token(format!("\\{}", c.fragment())),
+    ))
+}
+
+/// A single LaTeX expression, e.g. an identifier, operator or fraction.
+pub fn expression(input: Span) -> IResult<MathExpression> {
+    alt((
+        alt((
+            leibniz_derivative,
+            compact_partial_derivative,
+            newtonian_derivative,
+            nabla,
+            summation,
+            integral,
+            frac,
+        )),
+        operator,
+        scripted(sqrt),
+        scripted(vector),
+        function,
+        scripted(parenthesized),
+        scripted(absolute),
+        scripted(map(braced(expressions), row)),
+        scripted(number),
+        ci,
+        unsupported_command,
+    ))(input)
+}
+
+/// A sequence of LaTeX expressions.
+pub fn expressions(input: Span) -> IResult<Vec<MathExpression>> {
+    many0(expression)(input)
+}
+
+/// The first token of the input, for error reporting.
+fn first_token(input: &Span) -> Option<String> {
+    let fragment = input.fragment();
+    match command(*input) {
+        Ok((_, c)) => Some(format!("\\{}", c.fragment())),
+        Err(_) => fragment.chars().next().map(|c| c.to_string()),
+    }
+}
+
+/// A LaTeX equation, optionally surrounded by math mode delimiters, e.g. `$...$`. A trailing
+/// comma or period is ignored.
+pub fn latex_math(input: Span) -> IResult<Math> {
+    let (s, delimiter) = lx(opt(alt((
+        value("$$", tag("$$")),
+        value("$", tag("$")),
+        value("\\]", tag("\\[")),
+        value("\\)", tag("\\(")),
+    ))))(input)?;
+    let (s, content) = expressions(s)?;
+    let (s, _) = opt(lx(alt((tag(","), tag("."), tag("\\\\")))))(s)?;
+    let (s, _) = match delimiter {
+        Some(delimiter) => lx(tag(delimiter))(s)?,
+        None => (s, s),
+    };
+    if !s.fragment().is_empty() {
+        let mut error = ParseError::new("Unable to parse LaTeX".to_string(), s);
+        if let Some(token) = first_token(&s) {
+            error = error.with_token(token);
+        }
+        return Err(nom::Err::Failure(error));
+    }
+    Ok((s, Math { content }))
+}
+
+impl MathExpressionTree {
+    /// Construct a MathExpressionTree from a LaTeX string.
+    pub fn from_latex(input: &str) -> Result<Self, ParseDiagnostic> {
+        let (_, math) = latex_math(input.into()).map_err(ParseDiagnostic::from)?;
+        MathExpressionTree::try_from(math)
+    }
+}
+
+impl FirstOrderODE {
+    /// Construct a FirstOrderODE from a LaTeX string.
+    pub fn from_latex(input: &str) -> Result<Self, ParseDiagnostic> {
+        FirstOrderODE::try_from(MathExpressionTree::from_latex(input)?)
+    }
+}
+
+#[cfg(test)]
+fn test_same_as_mathml(latex: &str, mathml: &str) {
+    let from_latex = MathExpressionTree::from_latex(latex).unwrap();
+    let from_mathml = mathml.parse::<MathExpressionTree>().unwrap();
+    assert_eq!(from_latex.to_string(), from_mathml.to_string());
+    assert_eq!(from_latex.to_cmml(), from_mathml.to_cmml());
+}
+
+#[test]
+fn test_latex_arithmetic() {
+    let met = MathExpressionTree::from_latex("\\alpha x^{2} + \\frac{b}{c} - 3.5").unwrap();
+    assert_eq!(met.to_string(), "(+ (* α (^ x 2)) (- (/ b c) 3.5))");
+    test_same_as_mathml(
+        "\\alpha x^{2} + \\frac{b}{c}",
+        "<math><mi>α</mi><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><mfrac><mi>b</mi><mi>c</mi></mfrac></math>",
+    );
+}
+
+#[test]
+fn test_latex_leibniz_derivative() {
+    test_same_as_mathml(
+        "\\frac{dS}{dt} = -\\beta S I",
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo><mo>−</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+    );
+    let met = MathExpressionTree::from_latex(
+        "\\frac{\\mathrm{d} S(t)}{\\mathrm{d}t} = -\\beta S(t) I(t)",
+    )
+    .unwrap();
+    assert_eq!(met.to_string(), "(= (D(1, t) S) (* (* (- β) S) I))");
+}
+
+#[test]
+fn test_latex_partial_derivatives() {
+    let met = MathExpressionTree::from_latex("\\frac{\\partial^2 u}{\\partial x^2}").unwrap();
+    match met {
+        MathExpressionTree::Cons(Operator::Derivative(d), _) => {
+            assert_eq!(d.order, 2);
+            assert_eq!(d.notation, DerivativeNotation::LeibnizPartialStandard);
+        }
+        _ => panic!("Expected a derivative"),
+    }
+    let met = MathExpressionTree::from_latex("\\partial_{t} u = \\nabla^2 u").unwrap();
+    match met {
+        MathExpressionTree::Cons(Operator::Equals, args) => {
+            match &args[0] {
+                MathExpressionTree::Cons(Operator::Derivative(d), _) => {
+                    assert_eq!(d.notation, DerivativeNotation::LeibnizPartialCompact);
+                }
+                _ => panic!("Expected a derivative"),
+            }
+            assert_eq!(args[1].to_string(), "(Laplacian u)");
+        }
+        _ => panic!("Expected an equation"),
+    }
+}
+
+#[test]
+fn test_latex_newtonian_derivative() {
+    let ode = FirstOrderODE::from_latex("\\dot{S} = -\\beta S I").unwrap();
+    assert_eq!(ode.lhs_var.to_string(), "S");
+    assert_eq!(ode.with_respect_to.to_string(), "t");
+    assert_eq!(ode.rhs.to_string(), "(* (* (- β) S) I)");
+}
+
+#[test]
+fn test_latex_subscripts_and_greek_letters() {
+    test_same_as_mathml(
+        "\\beta_{h} S_h \\epsilon",
+        "<math><msub><mi>β</mi><mi>h</mi></msub><msub><mi>S</mi><mi>h</mi></msub><mi>ϵ</mi></math>",
+    );
+    let met = MathExpressionTree::from_latex("e^{-k t}").unwrap();
+    assert_eq!(met.to_string(), "(exp (* (- k) t))");
+}
+
+#[test]
+fn test_latex_nabla() {
+    let met = MathExpressionTree::from_latex("\\nabla \\cdot \\vec{v}").unwrap();
+    assert_eq!(met.to_string(), "(Div v)");
+    let met = MathExpressionTree::from_latex("\\nabla_{x} \\phi").unwrap();
+    assert_eq!(met.to_string(), "(Grad_x ϕ)");
+    test_same_as_mathml(
+        "\\nabla \\cdot v",
+        "<math><mo>∇</mo><mo>&middot;</mo><mi>v</mi></math>",
+    );
+    test_same_as_mathml(
+        "D \\nabla^2 u",
+        "<math><mi>D</mi><msup><mo>∇</mo><mn>2</mn></msup><mi>u</mi></math>",
+    );
+    test_same_as_mathml(
+        "k \\nabla u + f",
+        "<math><mi>k</mi><mo>∇</mo><mi>u</mi><mo>+</mo><mi>f</mi></math>",
+    );
+    let met =
+        MathExpressionTree::from_latex("\\frac{\\partial u}{\\partial t} = D \\nabla^2 u").unwrap();
+    assert_eq!(met.to_string(), "(= (PD(1, t) u) (* D (Laplacian u)))");
+}
+
+#[test]
+fn test_latex_summation_and_integral() {
+    let met = MathExpressionTree::from_latex("\\sum_{i=1}^{N} x_i").unwrap();
+    match met {
+        MathExpressionTree::Cons(Operator::Summation(s), args) => {
+            assert!(s.lower_bound.is_some() && s.upper_bound.is_some());
+            assert_eq!(args[0].to_string(), "x_{i}");
+        }
+        _ => panic!("Expected a summation"),
+    }
+    let met = MathExpressionTree::from_latex("\\int_{0}^{T} x^2 \\, dx").unwrap();
+    match met {
+        MathExpressionTree::Cons(Operator::Int(int), args) => {
+            assert_eq!(int.integration_variable.to_string(), "x");
+            assert_eq!(args[0].to_string(), "(^ x 2)");
+        }
+        _ => panic!("Expected an integral"),
+    }
+}
+
+#[test]
+fn test_latex_parse_errors() {
+    let err = MathExpressionTree::from_latex("x + \\foo{y}").unwrap_err();
+    assert_eq!(err.message, "Unsupported LaTeX command '\\foo'");
+    assert_eq!(err.offset, Some(4));
+    assert_eq!(err.token, Some("\\foo".to_string()));
+
+    let err = MathExpressionTree::from_latex("x + (y").unwrap_err();
+    assert_eq!(err.offset, Some(4));
+    assert_eq!(err.token, Some("(".to_string()));
+}
+
+#[test]
+fn test_latex_petrinet_same_as_mathml() {
+    use crate::acset::PetriNet;
+    use crate::parsers::first_order_ode::flatten_mults;
+
+    let latex = [
+        "\\frac{dS}{dt} = -\\beta I S",
+        "\\frac{dI}{dt} = \\beta I S - \\gamma I",
+        "\\frac{dR}{dt} = \\gamma I",
+    ];
+    let mathml = [
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>−</mo><mi>β</mi><mi>I</mi><mi>S</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>I</mi><mi>S</mi><mo>−</mo><mi>γ</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>",
+    ];
+    let flattened = |mut ode: FirstOrderODE| {
+        ode.rhs = flatten_mults(ode.rhs.clone());
+        ode
+    };
+    let from_latex: Vec<FirstOrderODE> = latex
+        .iter()
+        .map(|x| flattened(FirstOrderODE::from_latex(x).unwrap()))
+        .collect();
+    let from_mathml: Vec<FirstOrderODE> = mathml
+        .iter()
+        .map(|x| flattened(x.parse::<FirstOrderODE>().unwrap()))
+        .collect();
+    assert_eq!(
+        serde_json::to_value(PetriNet::from(from_latex)).unwrap(),
+        serde_json::to_value(PetriNet::from(from_mathml)).unwrap()
+    );
+}
@@ -7,16 +7,19 @@ use crate::{
             Derivative, DerivativeNotation, Gradient, Hat, Int, Logarithm, LogarithmNotation,
             Operator, Summation,
         },
-        Math, MathExpression, Mi, Mrow,
+        Ci, Math, MathExpression, Mi, Mrow, Type,
+    },
+    normalization::{normalize_mathml, normalize_with_source_map, SourceMap},
+    parsers::{
+        generic_mathml::ParseDiagnostic, interpreted_mathml::interpreted_math,
+        matrix_algebra::matrix_product,
     },
-    parsers::interpreted_mathml::interpreted_math,
 };
 use derive_new::new;
-use nom::error::Error;
 use regex::Regex;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{fmt, str::FromStr};
+use std::{convert::TryFrom, fmt, str::FromStr};
 use utoipa::ToSchema;
 
 #[cfg(test)]
@@ -293,6 +296,11 @@ fn is_unary_operator(op: &Operator) -> bool {
             | Operator::Arccsc
             | Operator::Arccot
             | Operator::Mean
+            | Operator::Piecewise
+            | Operator::Matrix
+            | Operator::Transpose
+            | Operator::Inverse
+            | Operator::Index
     )
 }
 
@@ -504,6 +512,25 @@ fn process_math_expression(expr: &MathExpression, expression: &mut String) {
         MathExpression::Mo(Operator::Other(x)) => {
             expression.push_str(x);
         }
+        MathExpression::Mmultiscripts(base, postscripts, prescripts) => {
+            let process_scripts = |scripts: &[MathExpression], expression: &mut String| {
+                for pair in scripts.chunks(2) {
+                    for (script, marker) in pair.iter().zip(["_{", "^{"]) {
+                        if *script != MathExpression::None {
+                            expression.push_str(marker);
+                            process_math_expression(script, expression);
+                            expression.push('}');
+                        }
+                    }
+                }
+            };
+            if !prescripts.is_empty() {
+                expression.push_str("{}");
+                process_scripts(prescripts, expression);
+            }
+            process_math_expression(base, expression);
+            process_scripts(postscripts, expression);
+        }
         t => panic!("Unhandled MathExpression: {:?}", t),
     }
 }
@@ -528,6 +555,41 @@ impl MathExpressionTree {
                 }
                 t => panic!("Unhandled MathExpression: {:?}", t),
             },
+            MathExpressionTree::Cons(Operator::Matrix, rows) => {
+                content_mathml.push_str("<matrix>");
+                for row in rows {
+                    content_mathml.push_str(&row.to_cmml());
+                }
+                content_mathml.push_str("</matrix>");
+            }
+            MathExpressionTree::Cons(Operator::MatrixRow, entries) => {
+                content_mathml.push_str("<matrixrow>");
+                for entry in entries {
+                    content_mathml.push_str(&entry.to_cmml());
+                }
+                content_mathml.push_str("</matrixrow>");
+            }
+            MathExpressionTree::Cons(Operator::Piecewise, pieces) => {
+                content_mathml.push_str("<piecewise>");
+                for piece in pieces {
+                    content_mathml.push_str(&piece.to_cmml());
+                }
+                content_mathml.push_str("</piecewise>");
+            }
+            MathExpressionTree::Cons(Operator::Piece, args) => {
+                content_mathml.push_str("<piece>");
+                for arg in args {
+                    content_mathml.push_str(&arg.to_cmml());
+                }
+                content_mathml.push_str("</piece>");
+            }
+            MathExpressionTree::Cons(Operator::Otherwise, args) => {
+                content_mathml.push_str("<otherwise>");
+                for arg in args {
+                    content_mathml.push_str(&arg.to_cmml());
+                }
+                content_mathml.push_str("</otherwise>");
+            }
             MathExpressionTree::Cons(head, rest) => {
                 content_mathml.push_str("<apply>");
                 match head {
@@ -535,6 +597,11 @@ impl MathExpressionTree {
                     Operator::Subtract => content_mathml.push_str("<minus/>"),
                     Operator::Multiply => content_mathml.push_str("<times/>"),
                     Operator::Equals => content_mathml.push_str("<eq/>"),
+                    Operator::Less => content_mathml.push_str("<lt/>"),
+                    Operator::LessEqual => content_mathml.push_str("<leq/>"),
+                    Operator::Greater => content_mathml.push_str("<gt/>"),
+                    Operator::GreaterEqual => content_mathml.push_str("<geq/>"),
+                    Operator::NotEqual => content_mathml.push_str("<neq/>"),
                     Operator::Divide => content_mathml.push_str("<divide/>"),
                     Operator::Power => content_mathml.push_str("<power/>"),
                     Operator::Exp => content_mathml.push_str("<exp/>"),
@@ -542,26 +609,74 @@ impl MathExpressionTree {
                     Operator::Gradient(x) => match &x.subscript {
                         Some(sub) => {
                             content_mathml.push_str("<grad/>");
-                            content_mathml.push_str(&format!("<bvar>{}</bar>", sub));
+                            content_mathml.push_str(&format!("<bvar>{}</bvar>", sub));
                         }
                         None => content_mathml.push_str("<grad/>"),
                     },
                     Operator::Div => content_mathml.push_str("<divergence/>"),
                     Operator::Cos => content_mathml.push_str("<cos/>"),
                     Operator::Sin => content_mathml.push_str("<sin/>"),
+                    Operator::Tan => content_mathml.push_str("<tan/>"),
+                    Operator::Sec => content_mathml.push_str("<sec/>"),
+                    Operator::Csc => content_mathml.push_str("<csc/>"),
+                    Operator::Cot => content_mathml.push_str("<cot/>"),
+                    Operator::Arcsin => content_mathml.push_str("<arcsin/>"),
+                    Operator::Arccos => content_mathml.push_str("<arccos/>"),
+                    Operator::Arctan => content_mathml.push_str("<arctan/>"),
+                    Operator::Arcsec => content_mathml.push_str("<arcsec/>"),
+                    Operator::Arccsc => content_mathml.push_str("<arccsc/>"),
+                    Operator::Arccot => content_mathml.push_str("<arccot/>"),
+                    Operator::Sqrt => content_mathml.push_str("<root/>"),
+                    Operator::Factorial => content_mathml.push_str("<factorial/>"),
+                    Operator::Dot => content_mathml.push_str("<scalarproduct/>"),
+                    Operator::Cross => content_mathml.push_str("<vectorproduct/>"),
+                    Operator::Laplacian => content_mathml.push_str("<laplacian/>"),
+                    Operator::Mean => content_mathml.push_str("<mean/>"),
+                    Operator::Min => content_mathml.push_str("<min/>"),
+                    Operator::Transpose => content_mathml.push_str("<transpose/>"),
+                    Operator::Inverse => content_mathml.push_str("<inverse/>"),
+                    Operator::MatMul => content_mathml.push_str("<times/>"),
+                    Operator::Hadamard => content_mathml.push_str("<csymbol>hadamard</csymbol>"),
+                    Operator::Index => content_mathml.push_str("<selector/>"),
+                    Operator::Logarithm(x) => match &x.notation {
+                        LogarithmNotation::Ln => content_mathml.push_str("<ln/>"),
+                        LogarithmNotation::Log => content_mathml.push_str("<log/>"),
+                        LogarithmNotation::LogBase(base) => {
+                            content_mathml.push_str("<log/>");
+                            content_mathml.push_str(&format!(
+                                "<logbase>{}</logbase>",
+                                MathExpressionTree::Atom(*base.clone()).to_cmml()
+                            ));
+                        }
+                    },
                     Operator::Derivative(Derivative {
                         order,
                         var_index,
                         bound_var,
                         notation,
-                    }) if (*order, *var_index) == (1_u8, 1_u8) => {
-                        if *notation == DerivativeNotation::LeibnizTotal {
-                            content_mathml.push_str("<diff/>");
-                            content_mathml.push_str(&format!("<bvar>{}</bar>", bound_var));
-                        } else if *notation == DerivativeNotation::LeibnizPartialStandard {
-                            content_mathml.push_str("<partialdiff/>");
-                            content_mathml.push_str(&format!("<bvar>{}</bar>", bound_var));
+                    }) => {
+                        let bvar = if *order == 1_u8 {
+                            format!("<bvar>{}</bvar>", bound_var)
+                        } else {
+                            format!(
+                                "<bvar>{}<degree><cn>{}</cn></degree></bvar>",
+                                bound_var, order
+                            )
+                        };
+                        match notation {
+                            DerivativeNotation::LeibnizPartialStandard
+                            | DerivativeNotation::LeibnizPartialCompact => {
+                                content_mathml.push_str("<partialdiff/>")
+                            }
+                            _ => content_mathml.push_str("<diff/>"),
+                        }
+                        // The index of the bound variable among the function's arguments, as
+                        // in the `<list>` form of `<partialdiff/>`.
+                        if *var_index != 1_u8 {
+                            content_mathml
+                                .push_str(&format!("<list><cn>{}</cn></list>", var_index));
                         }
+                        content_mathml.push_str(&bvar);
                     }
                     _ => {}
                 }
@@ -598,15 +713,63 @@ impl MathExpressionTree {
                 t => panic!("Unhandled MathExpression: {:?}", t),
             },
 
+            MathExpressionTree::Cons(Operator::Piecewise, pieces) => {
+                let pieces: Vec<String> = pieces.iter().map(|p| p.to_infix_expression()).collect();
+                expression.push_str(&format!("Piecewise({})", pieces.join(", ")));
+            }
+            MathExpressionTree::Cons(Operator::Piece, args) => {
+                expression.push_str(&format!(
+                    "({}, {})",
+                    args[0].to_infix_expression(),
+                    args[1].to_infix_expression()
+                ));
+            }
+            MathExpressionTree::Cons(Operator::Otherwise, args) => {
+                expression.push_str(&format!("({}, True)", args[0].to_infix_expression()));
+            }
+            MathExpressionTree::Cons(Operator::Matrix, rows) => {
+                let rows: Vec<String> = rows.iter().map(|r| r.to_infix_expression()).collect();
+                expression.push_str(&format!("Matrix([{}])", rows.join(", ")));
+            }
+            MathExpressionTree::Cons(Operator::MatrixRow, entries) => {
+                let entries: Vec<String> =
+                    entries.iter().map(|e| e.to_infix_expression()).collect();
+                expression.push_str(&format!("[{}]", entries.join(", ")));
+            }
+            MathExpressionTree::Cons(Operator::Transpose, args) => {
+                expression.push_str(&format!("{}.T", args[0].to_infix_expression()));
+            }
+            MathExpressionTree::Cons(Operator::Inverse, args) => {
+                expression.push_str(&format!("{}**(-1)", args[0].to_infix_expression()));
+            }
+            MathExpressionTree::Cons(Operator::Hadamard, args) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_infix_expression()).collect();
+                expression.push_str(&format!("hadamard_product({})", args.join(", ")));
+            }
+            MathExpressionTree::Cons(Operator::Index, args) => {
+                let indices: Vec<String> =
+                    args[1..].iter().map(|a| a.to_infix_expression()).collect();
+                expression.push_str(&format!(
+                    "{}[{}]",
+                    args[0].to_infix_expression(),
+                    indices.join(", ")
+                ));
+            }
             MathExpressionTree::Cons(head, rest) => {
                 let mut operation = String::new();
                 match head {
                     Operator::Add => operation.push('+'),
                     Operator::Subtract => operation.push('-'),
                     Operator::Multiply => operation.push('*'),
+                    Operator::MatMul => operation.push('@'),
                     Operator::Equals => operation.push('='),
                     Operator::Divide => operation.push('/'),
                     Operator::Exp => operation.push_str("exp"),
+                    Operator::Less => operation.push('<'),
+                    Operator::LessEqual => operation.push_str("<="),
+                    Operator::Greater => operation.push('>'),
+                    Operator::GreaterEqual => operation.push_str(">="),
+                    Operator::NotEqual => operation.push_str("!="),
                     _ => {}
                 }
                 let mut component = Vec::new();
@@ -681,6 +844,22 @@ impl MathExpressionTree {
                         expression.push('=');
                         expression.push_str(&rest[1].to_latex().to_string());
                     }
+                    Operator::Less
+                    | Operator::LessEqual
+                    | Operator::Greater
+                    | Operator::GreaterEqual
+                    | Operator::NotEqual => {
+                        let relation = match head {
+                            Operator::Less => "<",
+                            Operator::LessEqual => "\\leq ",
+                            Operator::Greater => ">",
+                            Operator::GreaterEqual => "\\geq ",
+                            _ => "\\neq ",
+                        };
+                        expression.push_str(&rest[0].to_latex().to_string());
+                        expression.push_str(relation);
+                        expression.push_str(&rest[1].to_latex().to_string());
+                    }
                     Operator::Divide => {
                         expression.push_str(&format!("\\frac{{{}}}", &rest[0].to_latex()));
                         expression.push_str(&format!("{{{}}}", &rest[1].to_latex()));
@@ -953,6 +1132,66 @@ impl MathExpressionTree {
                         expression.push(',');
                         process_atoms_cons_parentheses(&mut expression, &rest[1]);
                     }
+                    Operator::Matrix => {
+                        let rows: Vec<String> = rest.iter().map(|r| r.to_latex()).collect();
+                        expression.push_str(&format!(
+                            "\\begin{{bmatrix}}{}\\end{{bmatrix}}",
+                            rows.join(" \\\\ ")
+                        ));
+                    }
+                    Operator::MatrixRow => {
+                        let entries: Vec<String> = rest.iter().map(|r| r.to_latex()).collect();
+                        expression.push_str(&entries.join(" & "));
+                    }
+                    Operator::Transpose => {
+                        process_atoms_cons_parentheses(&mut expression, &rest[0]);
+                        expression.push_str("^{\\top}");
+                    }
+                    Operator::Inverse => {
+                        process_atoms_cons_parentheses(&mut expression, &rest[0]);
+                        expression.push_str("^{-1}");
+                    }
+                    Operator::MatMul => {
+                        for (index, r) in rest.iter().enumerate() {
+                            if index > 0 {
+                                expression.push(' ');
+                            }
+                            // Matrix multiplication is associative
+                            if let MathExpressionTree::Cons(Operator::MatMul, _) = r {
+                                expression.push_str(&r.to_latex());
+                            } else {
+                                process_atoms_cons_parentheses(&mut expression, r);
+                            }
+                        }
+                    }
+                    Operator::Hadamard => {
+                        process_atoms_cons_parentheses(&mut expression, &rest[0]);
+                        expression.push_str(" \\odot ");
+                        process_atoms_cons_parentheses(&mut expression, &rest[1]);
+                    }
+                    Operator::Index => {
+                        process_atoms_cons_parentheses(&mut expression, &rest[0]);
+                        let indices: Vec<String> = rest[1..].iter().map(|r| r.to_latex()).collect();
+                        expression.push_str(&format!("_{{{}}}", indices.join(",")));
+                    }
+                    Operator::Piecewise => {
+                        let pieces: Vec<String> = rest.iter().map(|r| r.to_latex()).collect();
+                        expression.push_str(&format!(
+                            "\\begin{{cases}}{}\\end{{cases}}",
+                            pieces.join(" \\\\ ")
+                        ));
+                    }
+                    Operator::Piece => {
+                        expression.push_str(&format!(
+                            "{} & {}",
+                            rest[0].to_latex(),
+                            rest[1].to_latex()
+                        ));
+                    }
+                    Operator::Otherwise => {
+                        expression
+                            .push_str(&format!("{} & \\text{{otherwise}}", rest[0].to_latex()));
+                    }
                     _ => {
                         expression = "".to_string();
                         return "Contain unsupported operators.".to_string();
@@ -972,6 +1211,21 @@ pub enum Token {
     Eof,
 }
 
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Atom(atom) => write!(f, "{atom}"),
+            Token::Op(op) => write!(f, "{op}"),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Construct the error reported by the Pratt parser when it encounters an unexpected token.
+fn unexpected_token(message: &str, token: &Token) -> ParseDiagnostic {
+    ParseDiagnostic::new(format!("{message}: {token}")).with_token(token.to_string())
+}
+
 /// Lexer for the Pratt parsing algorithm.
 #[derive(Debug)]
 struct Lexer {
@@ -1070,6 +1324,15 @@ impl MathExpression {
                 denominator.flatten(tokens);
                 tokens.push(MathExpression::Mo(Operator::Rparen));
             }
+            // Transposes and inverses of matrices are postfix operators
+            MathExpression::Msup(base, superscript)
+                if matrix_superscript(base, superscript).is_some() =>
+            {
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                base.flatten(tokens);
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+                tokens.extend(matrix_superscript(base, superscript).map(MathExpression::Mo));
+            }
             // Insert implicit `exponential` and `power` operators
             MathExpression::Msup(base, superscript) => {
                 if let MathExpression::Ci(x) = &**base {
@@ -1164,6 +1427,75 @@ impl MathExpression {
                 x.integrand.flatten(tokens);
                 tokens.push(MathExpression::Mo(Operator::Rparen));
             }
+            // Fences become parentheses (or an absolute value), with the fenced elements separated
+            // by commas.
+            MathExpression::Mfenced(open, close, elements) => {
+                let absolute = open == "|" && close == "|";
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                if absolute {
+                    tokens.push(MathExpression::Mo(Operator::Abs));
+                    tokens.push(MathExpression::Mo(Operator::Lparen));
+                }
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        tokens.push(MathExpression::Mo(Operator::Comma));
+                    }
+                    element.flatten(tokens);
+                }
+                if absolute {
+                    tokens.push(MathExpression::Mo(Operator::Rparen));
+                }
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+            }
+            // The n-th root of x becomes x^(1/n)
+            MathExpression::Mroot(base, index) => {
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                base.flatten(tokens);
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+                tokens.push(MathExpression::Mo(Operator::Power));
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                tokens.push(MathExpression::Mn("1".to_string()));
+                tokens.push(MathExpression::Mo(Operator::Divide));
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                index.flatten(tokens);
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+            }
+            // Enclosures are only meaningful for radicals; otherwise they group their contents.
+            MathExpression::Menclose(notation, elements) => {
+                let radical = notation.split_whitespace().any(|x| x == "radical");
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                if radical {
+                    tokens.push(MathExpression::Mo(Operator::Sqrt));
+                    tokens.push(MathExpression::Mo(Operator::Lparen));
+                }
+                for element in elements {
+                    element.flatten(tokens);
+                }
+                if radical {
+                    tokens.push(MathExpression::Mo(Operator::Rparen));
+                }
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+            }
+            MathExpression::Mpadded(elements) => {
+                tokens.push(MathExpression::Mo(Operator::Lparen));
+                for element in elements {
+                    element.flatten(tokens);
+                }
+                tokens.push(MathExpression::Mo(Operator::Rparen));
+            }
+            // Phantoms are invisible, so they are dropped.
+            MathExpression::Mphantom(_) => {}
+            MathExpression::Semantics(presentation, _) => presentation.flatten(tokens),
+            // Scripted identifiers such as tensor components are atoms.
+            MathExpression::Mmultiscripts(..) => tokens.push(MathExpression::Ci(Ci::new(
+                Some(Type::Real),
+                Box::new(self.clone()),
+                None,
+                None,
+            ))),
             t => tokens.push(t.clone()),
         }
     }
@@ -1186,8 +1518,12 @@ impl Lexer {
                     // Handle left parenthesis operator '('
                     MathExpression::Mo(Operator::Lparen) => {
                         // Check last element of the accumulator.
-                        if let Some(MathExpression::Mo(_)) = acc.last() {
-                            // If the last element is an Mo, noop.
+                        if let Some(MathExpression::Mo(op)) = acc.last() {
+                            // If the last element is an Mo, noop, unless it is a postfix matrix
+                            // operator.
+                            if matches!(op, Operator::Transpose | Operator::Inverse) {
+                                acc.push(&MathExpression::Mo(Operator::Multiply));
+                            }
                         } else {
                             acc.push(&MathExpression::Mo(Operator::Multiply));
                         }
@@ -1205,9 +1541,11 @@ impl Lexer {
                     t => {
                         let last_token = acc.last().unwrap();
                         match last_token {
-                            MathExpression::Mo(Operator::Rparen) => {
-                                // If the last item in the accumulator is a right parenthesis ')',
-                                // insert a multiplication operator
+                            MathExpression::Mo(
+                                Operator::Rparen | Operator::Transpose | Operator::Inverse,
+                            ) => {
+                                // If the last item in the accumulator is a right parenthesis ')'
+                                // or a postfix matrix operator, insert a multiplication operator
                                 acc.push(&MathExpression::Mo(Operator::Multiply));
                             }
                             MathExpression::Mo(_) => {
@@ -1310,48 +1648,85 @@ fn flatten_min_op(mut expr: MathExpressionTree) -> MathExpressionTree {
 }
 
 /// Construct a MathExpressionTree from a vector of MathExpression structs.
-fn expr(input: Vec<MathExpression>) -> MathExpressionTree {
+fn expr(input: Vec<MathExpression>) -> Result<MathExpressionTree, ParseDiagnostic> {
     let mut lexer = Lexer::new(input);
     insert_multiple_between_paren(&mut lexer);
-    let mut result: MathExpressionTree = expr_bp(&mut lexer, 0);
-    let mut result = flatten_min_op(result.clone());
+    let result: MathExpressionTree = expr_bp(&mut lexer, 0)?;
+    let mut result = flatten_min_op(result);
     let mut math_vec: Vec<MathExpressionTree> = vec![];
     while lexer.next() != Token::Eof {
-        let math_result = expr_bp(&mut lexer, 0);
-        math_vec.push(math_result.clone());
+        let math_result = expr_bp(&mut lexer, 0)?;
+        math_vec.push(math_result);
     }
 
     if !math_vec.is_empty() {
         result = MathExpressionTree::Cons(Operator::Multiply, math_vec);
     }
 
-    result
+    Ok(result)
 }
 
-impl From<Vec<MathExpression>> for MathExpressionTree {
-    fn from(input: Vec<MathExpression>) -> Self {
+impl TryFrom<Vec<MathExpression>> for MathExpressionTree {
+    type Error = ParseDiagnostic;
+
+    fn try_from(input: Vec<MathExpression>) -> Result<Self, Self::Error> {
         expr(input)
     }
 }
 
-impl From<Math> for MathExpressionTree {
-    fn from(input: Math) -> Self {
+impl TryFrom<Math> for MathExpressionTree {
+    type Error = ParseDiagnostic;
+
+    fn try_from(input: Math) -> Result<Self, Self::Error> {
         expr(input.content)
     }
 }
 
 impl FromStr for MathExpressionTree {
-    type Err = Error<String>;
+    type Err = ParseDiagnostic;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let modified_input1 = replace_unicode_with_symbols(input);
-        let modified_input2 = preprocess_mathml_for_to_latex(&modified_input1).to_string();
-        let modified_input3: &str = &modified_input2;
-        let (_, math) = interpreted_math(modified_input3.into()).unwrap();
-        Ok(MathExpressionTree::from(math))
+        let (modified_input, source_map) = preprocess_with_source_map(input);
+        let (_, math) = interpreted_math(modified_input.as_str().into())
+            .map_err(|err| source_map.locate(input, ParseDiagnostic::from(err)))?;
+        MathExpressionTree::try_from(math)
     }
 }
 
+/// Applies `replace_unicode_with_symbols` followed by `preprocess_mathml_for_to_latex`, while
+/// keeping track of where each byte of the output came from in the input.
+fn preprocess_with_source_map(input: &str) -> (String, SourceMap) {
+    let (stage1, SourceMap(map1)) = normalize_with_source_map(input);
+
+    // Remove newlines
+    let mut stage2 = String::new();
+    let mut map2 = Vec::<usize>::new();
+    for (i, c) in stage1.char_indices() {
+        if c != '\n' {
+            stage2.push(c);
+            map2.extend(std::iter::repeat_n(map1[i], c.len_utf8()));
+        }
+    }
+
+    // Remove whitespace between elements
+    let re = Regex::new(r">\s*<").unwrap();
+    let mut output = String::new();
+    let mut map = Vec::<usize>::new();
+    let mut last = 0;
+    for m in re.find_iter(&stage2) {
+        output.push_str(&stage2[last..m.start()]);
+        map.extend_from_slice(&map2[last..m.start()]);
+        output.push_str("><");
+        map.push(map2[m.start()]);
+        map.push(map2[m.end() - 1]);
+        last = m.end();
+    }
+    output.push_str(&stage2[last..]);
+    map.extend_from_slice(&map2[last..]);
+
+    (output, SourceMap(map))
+}
+
 /// Inserts an `Operator::Multiply` token between adjacent `Operator::Lparen` and `Operator::Rparen` tokens in the given Lexer.
 fn insert_multiple_between_paren(lexer: &mut Lexer) {
     let mut new_tokens = Vec::new();
@@ -1370,27 +1745,152 @@ fn insert_multiple_between_paren(lexer: &mut Lexer) {
     lexer.tokens = new_tokens;
 }
 
+/// Construct a matrix from the rows of a table. Empty cells are zeros.
+fn matrix(rows: Vec<Vec<Vec<MathExpression>>>) -> Result<MathExpressionTree, ParseDiagnostic> {
+    let rows = rows
+        .into_iter()
+        .map(|row| {
+            let entries = row
+                .into_iter()
+                .map(|cell| {
+                    if cell.is_empty() {
+                        Ok(MathExpressionTree::Atom(MathExpression::Mn(
+                            "0".to_string(),
+                        )))
+                    } else {
+                        expr(cell)
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(MathExpressionTree::Cons(Operator::MatrixRow, entries))
+        })
+        .collect::<Result<Vec<_>, ParseDiagnostic>>()?;
+    Ok(MathExpressionTree::Cons(Operator::Matrix, rows))
+}
+
+/// The name of an identifier, looking through redundant rows.
+fn identifier_name(expression: &MathExpression) -> Option<&str> {
+    match expression {
+        MathExpression::Mi(Mi(name)) => Some(name),
+        MathExpression::Ci(Ci { content, .. }) => identifier_name(content),
+        MathExpression::Mrow(Mrow(elements)) if elements.len() == 1 => {
+            identifier_name(&elements[0])
+        }
+        _ => None,
+    }
+}
+
+/// Whether an expression is a matrix: a table (possibly between brackets), or an identifier with
+/// a matrix or vector type or notation.
+fn is_matrix(expression: &MathExpression) -> bool {
+    match expression {
+        MathExpression::Mtable(_) => true,
+        MathExpression::Ci(Ci {
+            r#type: Some(Type::Matrix | Type::Vector),
+            ..
+        })
+        | MathExpression::Ci(Ci {
+            notation: Some(_), ..
+        }) => true,
+        MathExpression::Mrow(Mrow(elements)) | MathExpression::Mfenced(_, _, elements) => {
+            let mut contents = elements.iter().filter(|element| {
+                !matches!(
+                    element,
+                    MathExpression::Mo(Operator::Lparen | Operator::Rparen)
+                )
+            });
+            matches!((contents.next(), contents.next()), (Some(x), None) if is_matrix(x))
+        }
+        _ => false,
+    }
+}
+
+/// The matrix operator written as a superscript, if any: `⊤` is always a transpose, and so is `T`
+/// on a matrix or a single upper case letter (e.g. Aᵀ), while `-1` on a matrix is an inverse.
+fn matrix_superscript(base: &MathExpression, superscript: &MathExpression) -> Option<Operator> {
+    let uppercase_letter = identifier_name(base)
+        .is_some_and(|name| name.len() == 1 && name.chars().all(|c| c.is_ascii_uppercase()));
+    match superscript {
+        MathExpression::Mo(Operator::Transpose) => Some(Operator::Transpose),
+        _ if identifier_name(superscript) == Some("⊤") => Some(Operator::Transpose),
+        _ if identifier_name(superscript) == Some("T") && (uppercase_letter || is_matrix(base)) => {
+            Some(Operator::Transpose)
+        }
+        MathExpression::Mrow(Mrow(elements)) if is_matrix(base) => match elements.as_slice() {
+            [MathExpression::Mo(Operator::Subtract), MathExpression::Mn(one)] if one == "1" => {
+                Some(Operator::Inverse)
+            }
+            [element] => matrix_superscript(base, element),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Construct a piecewise function from the rows of a table of cases. Each row holds a value and a
+/// condition, either in separate cells or in one cell separated by text such as "if". Text in
+/// conditions is dropped, and a row without a condition (e.g. "otherwise") is the fallback value.
+fn piecewise(rows: Vec<Vec<Vec<MathExpression>>>) -> Result<MathExpressionTree, ParseDiagnostic> {
+    let mut pieces = Vec::new();
+    let mut otherwise = None;
+    for row in rows {
+        let mut cells = row.into_iter().filter(|cell| !cell.is_empty());
+        let mut value = cells.next().unwrap_or_default();
+        let mut condition: Vec<MathExpression> = cells.flatten().collect();
+        if condition.is_empty() {
+            if let Some(i) = value
+                .iter()
+                .position(|x| matches!(x, MathExpression::Mtext(_)))
+            {
+                condition = value.split_off(i);
+            }
+        }
+        condition.retain(|x| !matches!(x, MathExpression::Mtext(_)));
+        let value = expr(value)?;
+        if condition.is_empty() {
+            otherwise = Some(MathExpressionTree::Cons(Operator::Otherwise, vec![value]));
+        } else {
+            pieces.push(MathExpressionTree::Cons(
+                Operator::Piece,
+                vec![value, expr(condition)?],
+            ));
+        }
+    }
+    pieces.extend(otherwise);
+    Ok(MathExpressionTree::Cons(Operator::Piecewise, pieces))
+}
+
 /// The Pratt parsing algorithm for constructing an S-expression representing an equation.
-fn expr_bp(lexer: &mut Lexer, min_bp: u8) -> MathExpressionTree {
+fn expr_bp(lexer: &mut Lexer, min_bp: u8) -> Result<MathExpressionTree, ParseDiagnostic> {
     let mut lhs = match lexer.next() {
+        Token::Atom(MathExpression::Mtable(rows)) => matrix(rows)?,
+        Token::Atom(MathExpression::Piecewise(rows)) => piecewise(rows)?,
         Token::Atom(it) => MathExpressionTree::Atom(it),
         Token::Op(Operator::Lparen) => {
-            let lhs = expr_bp(lexer, 0);
-            assert_eq!(lexer.next(), Token::Op(Operator::Rparen));
+            let lhs = expr_bp(lexer, 0)?;
+            match lexer.next() {
+                Token::Op(Operator::Rparen) => {}
+                t => {
+                    return Err(unexpected_token(
+                        "Expected a closing parenthesis, found",
+                        &t,
+                    ))
+                }
+            }
             lhs
         }
         Token::Op(op) => {
-            let ((), r_bp) = prefix_binding_power(&op);
-            let rhs = expr_bp(lexer, r_bp);
+            let ((), r_bp) = prefix_binding_power(&op)?;
+            let rhs = expr_bp(lexer, r_bp)?;
             MathExpressionTree::Cons(op, vec![rhs])
         }
-        t => panic!("bad token: {:?}", t),
+        t => return Err(unexpected_token("Unexpected token", &t)),
     };
     loop {
         let op = match lexer.peek() {
             Token::Eof => break,
             Token::Op(op) => op,
-            t => panic!("bad token: {:?}", t),
+            t => return Err(unexpected_token("Expected an operator, found", &t)),
         };
         if let Some((l_bp, ())) = postfix_binding_power(&op) {
             if l_bp < min_bp {
@@ -1400,30 +1900,36 @@ fn expr_bp(lexer: &mut Lexer, min_bp: u8) -> MathExpressionTree {
             lhs = MathExpressionTree::Cons(op, vec![lhs]);
             continue;
         }
-        if let Some((l_bp, r_bp)) = infix_binding_power(&op) {
+        if let Some((l_bp, r_bp)) = infix_binding_power(&op)? {
             if l_bp < min_bp {
                 break;
             }
             lexer.next();
             lhs = {
-                let rhs = expr_bp(lexer, r_bp);
-                MathExpressionTree::Cons(op, vec![lhs, rhs])
+                let rhs = expr_bp(lexer, r_bp)?;
+                matrix_product(op, vec![lhs, rhs])
             };
             continue;
         }
         break;
     }
-    lhs
+    Ok(lhs)
 }
 
 /// Table of binding powers for prefix operators.
-fn prefix_binding_power(op: &Operator) -> ((), u8) {
-    match op {
+fn prefix_binding_power(op: &Operator) -> Result<((), u8), ParseDiagnostic> {
+    let res = match op {
         Operator::Add | Operator::Subtract => ((), 9),
         Operator::Exp => ((), 21),
         Operator::Cos => ((), 21),
         Operator::Sin => ((), 21),
         Operator::Tan => ((), 21),
+        Operator::Sec => ((), 21),
+        Operator::Csc => ((), 21),
+        Operator::Cot => ((), 21),
+        Operator::Arcsin => ((), 21),
+        Operator::Arccos => ((), 21),
+        Operator::Arctan => ((), 21),
         Operator::Mean => ((), 25),
         Operator::SurfaceInt => ((), 25),
         Operator::Gradient(Gradient { .. }) => ((), 25),
@@ -1437,14 +1943,21 @@ fn prefix_binding_power(op: &Operator) -> ((), u8) {
         Operator::Hat(Hat { .. }) => ((), 25),
         Operator::Int(Int { .. }) => ((), 25),
         Operator::Logarithm(Logarithm { .. }) => ((), 25),
-        _ => panic!("Bad operator: {:?}", op),
-    }
+        _ => {
+            return Err(unexpected_token(
+                "Operator cannot be used in prefix position",
+                &Token::Op(op.clone()),
+            ))
+        }
+    };
+    Ok(res)
 }
 
 /// Table of binding powers for postfix operators.
 fn postfix_binding_power(op: &Operator) -> Option<(u8, ())> {
     let res = match op {
         Operator::Factorial => (11, ()),
+        Operator::Transpose | Operator::Inverse => (17, ()),
         //Operator::DownArrow => (11, ()),
         _ => return None,
     };
@@ -1452,12 +1965,18 @@ fn postfix_binding_power(op: &Operator) -> Option<(u8, ())> {
 }
 
 /// Table of binding powers for infix operators.
-fn infix_binding_power(op: &Operator) -> Option<(u8, u8)> {
+fn infix_binding_power(op: &Operator) -> Result<Option<(u8, u8)>, ParseDiagnostic> {
     let res = match op {
         Operator::Equals => (1, 2),
+        Operator::Less
+        | Operator::LessEqual
+        | Operator::Greater
+        | Operator::GreaterEqual
+        | Operator::NotEqual => (1, 2),
         Operator::Add => (3, 4),
         Operator::Subtract => (5, 6),
         Operator::Multiply => (7, 8),
+        Operator::Hadamard => (7, 8),
         Operator::Divide => (9, 10),
         Operator::Compose => (14, 13),
         Operator::Power => (16, 15),
@@ -1465,31 +1984,21 @@ fn infix_binding_power(op: &Operator) -> Option<(u8, u8)> {
         Operator::Cross => (18, 17),
         Operator::Comma => (14, 13),
         Operator::Min => (14, 13),
-        Operator::Other(op) => panic!("Unhandled operator: {}!", op),
-        _ => return None,
+        Operator::Other(_) => {
+            return Err(unexpected_token(
+                "Unhandled operator",
+                &Token::Op(op.clone()),
+            ))
+        }
+        _ => return Ok(None),
     };
-    Some(res)
+    Ok(Some(res))
 }
 
-/// Replaces Unicode representations in the input string with their corresponding symbols.
+/// Replaces character references and Unicode variants in the input string with their
+/// corresponding symbols. See `normalize_mathml`.
 pub fn replace_unicode_with_symbols(input: &str) -> String {
-    // Define a regex pattern to match Unicode representations
-    let re = Regex::new(r#"&#x([0-9A-Fa-f]+);"#).unwrap();
-
-    // Use replace_all to replace Unicode representations with corresponding symbols
-    let replaced_str = re.replace_all(input, |captures: &regex::Captures| {
-        // captures[0] contains the entire match, captures[1] contains the hexadecimal code
-        let hex_code = &captures[1];
-        // Convert hexadecimal code to u32 and then to char
-        let unicode_char = u32::from_str_radix(hex_code, 16)
-            .ok()
-            .and_then(std::char::from_u32);
-
-        // Replace with the Unicode character if conversion is successful, otherwise keep the original
-        unicode_char.map_or_else(|| captures[0].to_string(), |c| c.to_string())
-    });
-
-    replaced_str.to_string()
+    normalize_mathml(input)
 }
 
 /// Preprocesses a MathML string for conversion to LaTeX format.
@@ -1594,7 +2103,7 @@ fn test_to_content_mathml_example2() {
         ";
     let ode = input.parse::<FirstOrderODE>().unwrap();
     let cmml = ode.to_cmml();
-    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bar><ci>S</ci></apply><apply><times/><apply><times/><apply><minus/><ci>β</ci></apply><ci>S</ci></apply><ci>I</ci></apply></apply>");
+    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bvar><ci>S</ci></apply><apply><times/><apply><times/><apply><minus/><ci>β</ci></apply><ci>S</ci></apply><ci>I</ci></apply></apply>");
 }
 
 #[test]
@@ -1620,7 +2129,7 @@ fn test_content_hackathon2_scenario1_eq1() {
         with_respect_to: _,
         rhs: _,
     } = first_order_ode(input.into()).unwrap().1;
-    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bar><ci>S</ci></apply><apply><times/><apply><times/><apply><minus/><ci>β</ci></apply><ci>I</ci></apply><apply><divide/><ci>S</ci><ci>N</ci></apply></apply></apply>");
+    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bvar><ci>S</ci></apply><apply><times/><apply><times/><apply><minus/><ci>β</ci></apply><ci>I</ci></apply><apply><divide/><ci>S</ci><ci>N</ci></apply></apply></apply>");
 }
 
 #[test]
@@ -1644,7 +2153,7 @@ fn test_content_hackathon2_scenario1_eq2() {
     let s_exp = exp.to_string();
     assert_eq!(s_exp, "(= (D(1, t) E) (- (* (* β I) (/ S N)) (* δ E)))");
     let cmml = ode.to_cmml();
-    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bar><ci>E</ci></apply><apply><minus/><apply><times/><apply><times/><ci>β</ci><ci>I</ci></apply><apply><divide/><ci>S</ci><ci>N</ci></apply></apply><apply><times/><ci>δ</ci><ci>E</ci></apply></apply></apply>");
+    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bvar><ci>E</ci></apply><apply><minus/><apply><times/><apply><times/><ci>β</ci><ci>I</ci></apply><apply><divide/><ci>S</ci><ci>N</ci></apply></apply><apply><times/><ci>δ</ci><ci>E</ci></apply></apply></apply>");
 }
 
 #[test]
@@ -1666,7 +2175,7 @@ fn test_content_hackathon2_scenario1_eq3() {
     let ode = input.parse::<FirstOrderODE>().unwrap();
     let cmml = ode.to_cmml();
     println!("cmml={:?}", cmml);
-    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bar><ci>I</ci></apply><apply><minus/><apply><minus/><apply><times/><ci>δ</ci><ci>E</ci></apply><apply><times/><apply><times/><apply><minus/><cn>1</cn><ci>α</ci></apply><ci>γ</ci></apply><ci>I</ci></apply></apply><apply><times/><apply><times/><ci>α</ci><ci>ρ</ci></apply><ci>I</ci></apply></apply></apply>");
+    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bvar><ci>I</ci></apply><apply><minus/><apply><minus/><apply><times/><ci>δ</ci><ci>E</ci></apply><apply><times/><apply><times/><apply><minus/><cn>1</cn><ci>α</ci></apply><ci>γ</ci></apply><ci>I</ci></apply></apply><apply><times/><apply><times/><ci>α</ci><ci>ρ</ci></apply><ci>I</ci></apply></apply></apply>");
 }
 
 #[test]
@@ -1683,7 +2192,7 @@ fn test_content_hackathon2_scenario1_eq4() {
     ";
     let ode = input.parse::<FirstOrderODE>().unwrap();
     let cmml = ode.to_cmml();
-    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bar><ci>R</ci></apply><apply><times/><apply><times/><apply><minus/><cn>1</cn><ci>α</ci></apply><ci>γ</ci></apply><ci>I</ci></apply></apply>");
+    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bvar><ci>R</ci></apply><apply><times/><apply><times/><apply><minus/><cn>1</cn><ci>α</ci></apply><ci>γ</ci></apply><ci>I</ci></apply></apply>");
 }
 
 #[test]
@@ -1702,7 +2211,7 @@ fn test_content_hackathon2_scenario1_eq5() {
     ";
     let ode = input.parse::<FirstOrderODE>().unwrap();
     let cmml = ode.to_cmml();
-    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bar><ci>D</ci></apply><apply><times/><apply><times/><ci>α</ci><ci>ρ</ci></apply><ci>I</ci></apply></apply>");
+    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bvar><ci>D</ci></apply><apply><times/><apply><times/><ci>α</ci><ci>ρ</ci></apply><ci>I</ci></apply></apply>");
 }
 
 #[test]
@@ -1725,7 +2234,7 @@ fn test_content_hackathon2_scenario1_eq6() {
     ";
     let ode = input.parse::<FirstOrderODE>().unwrap();
     let cmml = ode.to_cmml();
-    assert_eq!(cmml,"<apply><eq/><apply><diff/><bvar>t</bar><ci>S</ci></apply><apply><plus/><apply><times/><apply><times/><apply><minus/><ci>β</ci></apply><ci>I</ci></apply><apply><divide/><ci>S</ci><ci>N</ci></apply></apply><apply><times/><ci>ϵ</ci><ci>R</ci></apply></apply></apply>");
+    assert_eq!(cmml,"<apply><eq/><apply><diff/><bvar>t</bvar><ci>S</ci></apply><apply><plus/><apply><times/><apply><times/><apply><minus/><ci>β</ci></apply><ci>I</ci></apply><apply><divide/><ci>S</ci><ci>N</ci></apply></apply><apply><times/><ci>ϵ</ci><ci>R</ci></apply></apply></apply>");
 }
 
 #[test]
@@ -1745,7 +2254,7 @@ fn test_content_hackathon2_scenario1_eq7() {
     ";
     let ode = input.parse::<FirstOrderODE>().unwrap();
     let cmml = ode.to_cmml();
-    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bar><ci>R</ci></apply><apply><minus/><apply><times/><apply><times/><apply><minus/><cn>1</cn><ci>α</ci></apply><ci>γ</ci></apply><ci>I</ci></apply><apply><times/><ci>ϵ</ci><ci>R</ci></apply></apply></apply>");
+    assert_eq!(cmml, "<apply><eq/><apply><diff/><bvar>t</bvar><ci>R</ci></apply><apply><minus/><apply><times/><apply><times/><apply><minus/><cn>1</cn><ci>α</ci></apply><ci>γ</ci></apply><ci>I</ci></apply><apply><times/><ci>ϵ</ci><ci>R</ci></apply></apply></apply>");
 }
 
 #[test]
@@ -3880,3 +4389,152 @@ fn test_new_eval_test() {
     assert_eq!(s_exp, "(= (D(1, t) s_{c}) (- (* α r_{c}) (* s_{c} (+ (+ (+ (+ (* β_{cc}^{Dc} i_{c}) (* β_{cc}^{Aero} i_{c})) (* β_{cW}^{Aero} i_{W})) (* β_{cW}^{Dc} i_{W})) (* β_{Hc}^{Aero} i_{H})))))");
     assert_eq!(exp.to_latex(), "\\frac{d s_{c}}{dt}=\\alpha*r_{c}-s_{c}*(\\beta_{cc}^{Dc}*i_{c}+\\beta_{cc}^{Aero}*i_{c}+\\beta_{cW}^{Aero}*i_{W}+\\beta_{cW}^{Dc}*i_{W}+\\beta_{Hc}^{Aero}*i_{H})");
 }
+
+#[test]
+fn test_parse_error_reports_location_in_original_input() {
+    let input = "<math>
+    <mi>x</mi>
+    <mo>&#x2208;</mo>
+    <mi>y</mi>
+</math>";
+    let err = input.parse::<MathExpressionTree>().unwrap_err();
+    assert_eq!(err.line, Some(3));
+    assert_eq!(err.offset, input.find("<mo>"));
+    assert_eq!(err.token, Some("<mo>".to_string()));
+}
+
+#[test]
+fn test_parse_error_location_after_non_ascii_input() {
+    // the minus sign and the asterisk operator are rewritten to ASCII before parsing, and the
+    // Greek letter is multi-byte, yet the error is reported against the input as sent
+    let input = "<math>
+    <mi>β</mi><mo>\u{2212}</mo><mi>x</mi><mo>\u{2217}</mo><mi>y</mi>
+    <mo>&#x2208;</mo>
+    <mi>z</mi>
+</math>";
+    let err = input.parse::<MathExpressionTree>().unwrap_err();
+    assert_eq!(err.line, Some(3));
+    assert_eq!(err.offset, input.find("<mo>&#x2208;"));
+}
+
+#[test]
+fn test_unbalanced_parenthesis_is_an_error() {
+    let input = "<math><mo>(</mo><mi>x</mi><mo>+</mo><mi>y</mi></math>";
+    let err = input.parse::<MathExpressionTree>().unwrap_err();
+    assert_eq!(err.token, Some("end of input".to_string()));
+}
+
+#[test]
+fn test_layout_elements() {
+    let cases = [
+        ("<math><mroot><mi>x</mi><mn>3</mn></mroot></math>", "(^ x (/ 1 3))"),
+        (
+            "<math><mi>a</mi><mfenced><mrow><mi>b</mi><mo>+</mo><mi>c</mi></mrow></mfenced></math>",
+            "(* a (+ b c))",
+        ),
+        (
+            "<math><mfenced open=\"|\" close=\"|\"><mi>x</mi></mfenced></math>",
+            "(Abs x)",
+        ),
+        (
+            "<math><menclose notation=\"radical\"><mi>x</mi></menclose><mo>+</mo><menclose notation=\"box\"><mi>y</mi></menclose></math>",
+            "(+ (√ x) y)",
+        ),
+        (
+            "<math><mi>x</mi><mphantom><mo>+</mo></mphantom><mo>+</mo><mpadded><mi>y</mi></mpadded></math>",
+            "(+ x y)",
+        ),
+        (
+            "<math><semantics><mrow><mi>x</mi><mo>+</mo><mi>y</mi></mrow><annotation encoding=\"application/x-tex\">x+y</annotation></semantics></math>",
+            "(+ x y)",
+        ),
+        (
+            "<math><mmultiscripts><mi>R</mi><mi>i</mi><none/><none/><mi>j</mi></mmultiscripts><mo>+</mo><mmultiscripts><mi>C</mi><mprescripts/><mn>6</mn><mn>14</mn></mmultiscripts></math>",
+            "(+ R_{i}^{j} {}_{6}^{14}C)",
+        ),
+    ];
+    for (input, expected) in cases {
+        let met = input.parse::<MathExpressionTree>().unwrap();
+        assert_eq!(met.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_mtable_matrix() {
+    let input = "<math><mrow><mo>(</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi><mo>+</mo><mn>1</mn></mtd></mtr><mtr><mtd/><mtd><maligngroup/><mi>d</mi></mtd></mtr></mtable><mo>)</mo></mrow></math>";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    assert_eq!(met.to_string(), "(Matrix (Row a (+ b 1)) (Row 0 d))");
+    assert_eq!(
+        met.to_latex(),
+        "\\begin{bmatrix}a & b+1 \\\\ 0 & d\\end{bmatrix}"
+    );
+    assert_eq!(MathExpressionTree::from_cmml(&met.to_cmml()).unwrap(), met);
+}
+
+#[test]
+fn test_invisible_operators() {
+    for (input, expected) in [
+        (
+            "<math><mi>a</mi><mo>&InvisibleTimes;</mo><mi>b</mi></math>",
+            "(* a b)",
+        ),
+        (
+            "<math><mi>a</mi><mo>&#x2062;</mo><mi>b</mi><mo>&minus;</mo><mi>c</mi></math>",
+            "(- (* a b) c)",
+        ),
+        (
+            "<math><mi>β</mi><mo>&#x2062;</mo><mi>sin</mi><mo>&ApplyFunction;</mo><mrow><mo>(</mo><mi>x</mi><mo>+</mo><mi>y</mi><mo>)</mo></mrow></math>",
+            "(* β (Sin (+ x y)))",
+        ),
+        (
+            "<math><mi>cos</mi><mo>&#x2061;</mo><mi>θ</mi></math>",
+            "(Cos θ)",
+        ),
+        (
+            "<math><mi>ln</mi><mo>&af;</mo><mo>(</mo><mi>x</mi><mo>)</mo></math>",
+            "(Ln x)",
+        ),
+        (
+            "<math><mi>S</mi><mo>&ApplyFunction;</mo><mo>(</mo><mi>t</mi><mo>,</mo><mi>x</mi><mo>)</mo><mo>&#x2062;</mo><mi>I</mi></math>",
+            "(* S I)",
+        ),
+    ] {
+        let met = input.parse::<MathExpressionTree>().unwrap();
+        assert_eq!(met.to_string(), expected, "{input}");
+    }
+}
+
+#[test]
+fn test_piecewise() {
+    let input = "<math><mi>β</mi><mo>=</mo><mrow><mo>{</mo><mtable><mtr><mtd><msub><mi>β</mi><mn>1</mn></msub></mtd><mtd><mtext>if </mtext><mi>t</mi><mo>&lt;</mo><msub><mi>t</mi><mn>0</mn></msub></mtd></mtr><mtr><mtd><msub><mi>β</mi><mn>2</mn></msub></mtd><mtd><mtext>otherwise</mtext></mtd></mtr></mtable></mrow></math>";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    assert_eq!(
+        met.to_string(),
+        "(= β (Piecewise (Piece β_{1} (< t t_{0})) (Otherwise β_{2})))"
+    );
+    assert_eq!(
+        met.to_latex(),
+        "\\beta=\\begin{cases}\\beta_{1} & t<t_{0} \\\\ \\beta_{2} & \\text{otherwise}\\end{cases}"
+    );
+    let MathExpressionTree::Cons(_, args) = &met else {
+        panic!("Expected an equation");
+    };
+    assert_eq!(
+        args[1].to_infix_expression(),
+        "Piecewise((β_{1}, (t<t_{0})), (β_{2}, True))"
+    );
+    assert_eq!(
+        args[1].to_cmml(),
+        "<piecewise><piece><ci>β_{1}</ci><apply><lt/><ci>t</ci><ci>t_{0}</ci></apply></piece><otherwise><ci>β_{2}</ci></otherwise></piecewise>"
+    );
+    let parsed = MathExpressionTree::from_cmml(&met.to_cmml()).unwrap();
+    assert_eq!(parsed.to_cmml(), met.to_cmml());
+
+    // Value and condition in the same cell, and a fenced table of cases
+    let input = "<math><mfenced open=\"{\" close=\"\"><mtable><mtr><mtd><mn>0</mn><mtext>for</mtext><mi>x</mi><mo>≤</mo><mn>0</mn></mtd></mtr><mtr><mtd><mi>x</mi><mtext>else</mtext></mtd></mtr></mtable></mfenced></math>";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    assert_eq!(
+        met.to_string(),
+        "(Piecewise (Piece 0 (≤ x 0)) (Otherwise x))"
+    );
+}
@@ -0,0 +1,503 @@
+//! Matrix and vector algebra on math expression trees, e.g. to expand a linear compartmental model
+//! written as dx/dt = A x into one equation per state.
+
+use crate::{
+    ast::{operator::Operator, Ci, MathExpression, Type},
+    parsers::{
+        generic_mathml::ParseDiagnostic,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+    },
+};
+
+/// The entries of a matrix, row by row.
+type Entries = Vec<Vec<MathExpressionTree>>;
+
+/// Whether an expression is known to evaluate to a matrix (or vector): a matrix literal, the
+/// result of a matrix operation, or an identifier with a matrix or vector type or notation.
+pub(crate) fn is_matrix_valued(met: &MathExpressionTree) -> bool {
+    matches!(
+        met,
+        Cons(
+            Operator::Matrix
+                | Operator::Transpose
+                | Operator::Inverse
+                | Operator::MatMul
+                | Operator::Hadamard,
+            _
+        ) | Atom(MathExpression::Ci(Ci {
+            r#type: Some(Type::Matrix | Type::Vector),
+            ..
+        })) | Atom(MathExpression::Ci(Ci {
+            notation: Some(_),
+            ..
+        }))
+    )
+}
+
+/// Construct an operation, where products of matrices are matrix multiplications.
+pub(crate) fn matrix_product(op: Operator, args: Vec<MathExpressionTree>) -> MathExpressionTree {
+    if op == Operator::Multiply && args.len() > 1 && args.iter().all(is_matrix_valued) {
+        Cons(Operator::MatMul, args)
+    } else {
+        Cons(op, args)
+    }
+}
+
+fn is_number(met: &MathExpressionTree, number: &str) -> bool {
+    matches!(met, Atom(MathExpression::Mn(x)) if x == number)
+}
+
+fn zero() -> MathExpressionTree {
+    Atom(MathExpression::Mn("0".to_string()))
+}
+
+/// Product of two entries, omitting factors of one and terms multiplied by zero.
+fn product(a: MathExpressionTree, b: MathExpressionTree) -> MathExpressionTree {
+    if is_number(&a, "0") || is_number(&b, "0") {
+        zero()
+    } else if is_number(&a, "1") {
+        b
+    } else if is_number(&b, "1") {
+        a
+    } else {
+        Cons(Operator::Multiply, vec![a, b])
+    }
+}
+
+/// Sum of entries, omitting zero terms.
+fn sum(terms: Vec<MathExpressionTree>) -> MathExpressionTree {
+    let mut terms: Vec<MathExpressionTree> =
+        terms.into_iter().filter(|x| !is_number(x, "0")).collect();
+    match terms.len() {
+        0 => zero(),
+        1 => terms.pop().unwrap(),
+        _ => Cons(Operator::Add, terms),
+    }
+}
+
+fn shape(entries: &Entries) -> (usize, usize) {
+    (entries.len(), entries.first().map_or(0, Vec::len))
+}
+
+fn shape_error(op: &Operator, a: &Entries, b: &Entries) -> ParseDiagnostic {
+    let ((m, n), (p, q)) = (shape(a), shape(b));
+    ParseDiagnostic::new(format!(
+        "Incompatible matrix shapes for {op}: {m}×{n} and {p}×{q}"
+    ))
+}
+
+/// Combine the entries of matrices of the same shape.
+fn elementwise(
+    op: &Operator,
+    matrices: Vec<Entries>,
+    combine: impl Fn(Vec<MathExpressionTree>) -> MathExpressionTree,
+) -> Result<Entries, ParseDiagnostic> {
+    let (m, n) = shape(&matrices[0]);
+    if let Some(other) = matrices.iter().find(|x| shape(x) != (m, n)) {
+        return Err(shape_error(op, &matrices[0], other));
+    }
+    Ok((0..m)
+        .map(|i| {
+            (0..n)
+                .map(|j| combine(matrices.iter().map(|x| x[i][j].clone()).collect()))
+                .collect()
+        })
+        .collect())
+}
+
+/// The components A_{ij} of a symbolic square matrix of the given size.
+fn components(matrix: &MathExpressionTree, size: usize) -> Entries {
+    let index = |i: usize| Atom(MathExpression::Mn(i.to_string()));
+    (1..=size)
+        .map(|i| {
+            (1..=size)
+                .map(|j| Cons(Operator::Index, vec![matrix.clone(), index(i), index(j)]))
+                .collect()
+        })
+        .collect()
+}
+
+fn matmul(a: &Entries, b: &Entries) -> Result<Entries, ParseDiagnostic> {
+    let ((m, n), (p, q)) = (shape(a), shape(b));
+    if n != p {
+        return Err(shape_error(&Operator::MatMul, a, b));
+    }
+    Ok((0..m)
+        .map(|i| {
+            (0..q)
+                .map(|j| {
+                    sum((0..n)
+                        .map(|k| product(a[i][k].clone(), b[k][j].clone()))
+                        .collect())
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Multiply a sequence of factors, of which at least one is a matrix with known entries. Symbolic
+/// matrices are assumed to be square, and scalars scale the result.
+fn multiply(factors: &[MathExpressionTree]) -> Result<Option<Entries>, ParseDiagnostic> {
+    let evaluated = factors.iter().map(entries).collect::<Result<Vec<_>, _>>()?;
+    if evaluated.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    let mut scalars = Vec::new();
+    let mut result: Option<Entries> = None;
+    let mut pending_symbolic: Option<&MathExpressionTree> = None;
+    for (factor, entries) in factors.iter().zip(evaluated) {
+        let entries = match entries {
+            Some(entries) => entries,
+            None if is_matrix_valued(factor) => match &result {
+                Some(left) => components(factor, shape(left).1),
+                None => {
+                    pending_symbolic = Some(factor);
+                    continue;
+                }
+            },
+            None => {
+                scalars.push(factor.clone());
+                continue;
+            }
+        };
+        let entries = match pending_symbolic.take() {
+            Some(symbolic) => matmul(&components(symbolic, shape(&entries).0), &entries)?,
+            None => entries,
+        };
+        result = Some(match result {
+            Some(left) => matmul(&left, &entries)?,
+            None => entries,
+        });
+    }
+    let result = result.unwrap();
+    Ok(Some(
+        result
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|entry| {
+                        scalars
+                            .iter()
+                            .rev()
+                            .fold(entry, |entry, scalar| product(scalar.clone(), entry))
+                    })
+                    .collect()
+            })
+            .collect(),
+    ))
+}
+
+/// Inverse of a matrix with known entries, which is only supported up to 2×2 matrices.
+fn inverse(a: &Entries) -> Result<Entries, ParseDiagnostic> {
+    let divide = |x: MathExpressionTree, y: MathExpressionTree| Cons(Operator::Divide, vec![x, y]);
+    match shape(a) {
+        (1, 1) => Ok(vec![vec![divide(
+            Atom(MathExpression::Mn("1".to_string())),
+            a[0][0].clone(),
+        )]]),
+        (2, 2) => {
+            let determinant = Cons(
+                Operator::Subtract,
+                vec![
+                    product(a[0][0].clone(), a[1][1].clone()),
+                    product(a[0][1].clone(), a[1][0].clone()),
+                ],
+            );
+            let negate = |x: &MathExpressionTree| Cons(Operator::Subtract, vec![x.clone()]);
+            Ok(vec![
+                vec![
+                    divide(a[1][1].clone(), determinant.clone()),
+                    divide(negate(&a[0][1]), determinant.clone()),
+                ],
+                vec![
+                    divide(negate(&a[1][0]), determinant.clone()),
+                    divide(a[0][0].clone(), determinant),
+                ],
+            ])
+        }
+        (m, n) => Err(ParseDiagnostic::new(format!(
+            "Cannot invert a {m}×{n} matrix; only square matrices up to 2×2 are supported"
+        ))),
+    }
+}
+
+/// The entries of an expression that evaluates to a matrix with known entries, or `None` if the
+/// expression is a scalar (or a symbolic matrix, whose entries depend on its context).
+fn entries(met: &MathExpressionTree) -> Result<Option<Entries>, ParseDiagnostic> {
+    let (op, args) = match met {
+        Atom(_) => return Ok(None),
+        Cons(op, args) => (op, args),
+    };
+    let required = |arg: &MathExpressionTree| {
+        entries(arg)?.ok_or_else(|| {
+            ParseDiagnostic::new(format!(
+                "The entries of {arg} are needed to expand {op}, but are unknown"
+            ))
+        })
+    };
+    match op {
+        Operator::Matrix => {
+            let rows: Entries = args
+                .iter()
+                .map(|row| match row {
+                    Cons(Operator::MatrixRow, row) => row.clone(),
+                    row => vec![row.clone()],
+                })
+                .collect();
+            // the operations index entries by the shape, which is that of the first row
+            let n = shape(&rows).1;
+            if let Some((i, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != n) {
+                return Err(ParseDiagnostic::new(format!(
+                    "Ragged matrix: row {} has {} entries, but row 1 has {n}",
+                    i + 1,
+                    row.len()
+                )));
+            }
+            Ok(Some(rows))
+        }
+        Operator::Transpose => {
+            let a = required(&args[0])?;
+            let (m, n) = shape(&a);
+            Ok(Some(
+                (0..n)
+                    .map(|j| (0..m).map(|i| a[i][j].clone()).collect())
+                    .collect(),
+            ))
+        }
+        Operator::Inverse => Ok(Some(inverse(&required(&args[0])?)?)),
+        Operator::MatMul | Operator::Multiply => multiply(args),
+        Operator::Hadamard => {
+            let matrices = args.iter().map(required).collect::<Result<Vec<_>, _>>()?;
+            elementwise(op, matrices, |x| {
+                x.into_iter().reduce(product).unwrap_or_else(zero)
+            })
+            .map(Some)
+        }
+        _ => {
+            let evaluated = args.iter().map(entries).collect::<Result<Vec<_>, _>>()?;
+            if evaluated.iter().all(Option::is_none) {
+                return Ok(None);
+            }
+            match (op, evaluated.as_slice()) {
+                // Operations applied to each entry, e.g. the derivatives of the states of a system
+                (Operator::Subtract | Operator::Derivative(_), [Some(a)]) => {
+                    elementwise(op, vec![a.clone()], |x| Cons(op.clone(), x)).map(Some)
+                }
+                (Operator::Add | Operator::Subtract, _)
+                    if evaluated.iter().all(Option::is_some) =>
+                {
+                    let matrices = evaluated.into_iter().flatten().collect();
+                    elementwise(op, matrices, |x| Cons(op.clone(), x)).map(Some)
+                }
+                (Operator::Divide, [Some(a), None]) => elementwise(op, vec![a.clone()], |x| {
+                    Cons(op.clone(), vec![x[0].clone(), args[1].clone()])
+                })
+                .map(Some),
+                _ => Err(ParseDiagnostic::new(format!(
+                    "Unsupported matrix operation: {op}"
+                ))),
+            }
+        }
+    }
+}
+
+impl MathExpressionTree {
+    /// Expand an equation between matrices or vectors with known entries into one equation per
+    /// entry, e.g. a linear compartmental model [dS/dt; dI/dt] = [[-β, 0], [β, -γ]] [S; I] into
+    /// dS/dt = -β S and dI/dt = β S - γ I. Other equations are returned unchanged.
+    pub fn expand_matrix_equation(&self) -> Result<Vec<MathExpressionTree>, ParseDiagnostic> {
+        if let Cons(Operator::Equals, args) = self {
+            if let [lhs, rhs] = args.as_slice() {
+                match (entries(lhs)?, entries(rhs)?) {
+                    (None, None) => {}
+                    (Some(lhs), Some(rhs)) if shape(&lhs) == shape(&rhs) => {
+                        return Ok(lhs
+                            .into_iter()
+                            .flatten()
+                            .zip(rhs.into_iter().flatten())
+                            .map(|(lhs, rhs)| Cons(Operator::Equals, vec![lhs, rhs]))
+                            .collect());
+                    }
+                    (Some(lhs), Some(rhs)) => {
+                        return Err(shape_error(&Operator::Equals, &lhs, &rhs))
+                    }
+                    _ => {
+                        return Err(ParseDiagnostic::new(
+                            "Expected matrices with known entries on both sides of the equation"
+                                .to_string(),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(vec![self.clone()])
+    }
+}
+
+#[test]
+fn test_matrix_operators() {
+    let input = "
+    <math>
+        <mi>y</mi>
+        <mo>=</mo>
+        <msup><mi>A</mi><mi>T</mi></msup>
+        <msup>
+            <mrow><mo>[</mo><mtable>
+                <mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr>
+                <mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr>
+            </mtable><mo>]</mo></mrow>
+            <mrow><mo>-</mo><mn>1</mn></mrow>
+        </msup>
+        <mrow><mo>[</mo><mtable>
+            <mtr><mtd><mi>u</mi></mtd></mtr>
+            <mtr><mtd><mi>v</mi></mtd></mtr>
+        </mtable><mo>]</mo></mrow>
+        <mo>+</mo>
+        <mi>B</mi><mo>⊙</mo><msup><mi>C</mi><mo>⊤</mo></msup>
+    </math>
+    ";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    assert_eq!(
+        met.to_string(),
+        "(= y (+ (MatMul (MatMul (Transpose A) (Inverse (Matrix (Row a b) (Row c d)))) (Matrix (Row u) (Row v))) (⊙ B (Transpose C))))"
+    );
+    assert_eq!(
+        met.to_latex(),
+        "y=A^{\\top} \\begin{bmatrix}a & b \\\\ c & d\\end{bmatrix}^{-1} \\begin{bmatrix}u \\\\ v\\end{bmatrix}+B \\odot C^{\\top}"
+    );
+    assert_eq!(MathExpressionTree::from_cmml(&met.to_cmml()).unwrap(), met);
+
+    // T is an exponent on anything other than a matrix or a single upper case letter
+    let input = "<math><msup><mi>x</mi><mi>T</mi></msup></math>";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    assert_eq!(met.to_string(), "(^ x T)");
+}
+
+#[test]
+fn test_expand_linear_compartmental_model() {
+    let input = "
+    <math>
+        <mrow><mo>[</mo><mtable>
+            <mtr><mtd><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac></mtd></mtr>
+            <mtr><mtd><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac></mtd></mtr>
+        </mtable><mo>]</mo></mrow>
+        <mo>=</mo>
+        <mrow><mo>[</mo><mtable>
+            <mtr><mtd><mo>-</mo><mi>β</mi></mtd><mtd><mn>0</mn></mtd></mtr>
+            <mtr><mtd><mi>β</mi></mtd><mtd><mo>-</mo><mi>γ</mi></mtd></mtr>
+        </mtable><mo>]</mo></mrow>
+        <mrow><mo>[</mo><mtable>
+            <mtr><mtd><mi>S</mi></mtd></mtr>
+            <mtr><mtd><mi>I</mi></mtd></mtr>
+        </mtable><mo>]</mo></mrow>
+    </math>
+    ";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    let equations: Vec<String> = met
+        .expand_matrix_equation()
+        .unwrap()
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    assert_eq!(
+        equations,
+        vec![
+            "(= (D(1, t) S) (* (- β) S))",
+            "(= (D(1, t) I) (+ (* β S) (* (- γ) I)))"
+        ]
+    );
+
+    // The expanded equations are ordinary ODEs
+    use std::convert::TryFrom;
+    let odes = met
+        .expand_matrix_equation()
+        .unwrap()
+        .into_iter()
+        .map(crate::parsers::higher_order_ode::HigherOrderODE::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(odes.len(), 2);
+}
+
+#[test]
+fn test_expand_symbolic_and_derived_matrices() {
+    let symbol = |name: &str, r#type| {
+        Atom(MathExpression::Ci(Ci::new(
+            r#type,
+            Box::new(MathExpression::Mi(crate::ast::Mi(name.to_string()))),
+            None,
+            None,
+        )))
+    };
+    let vector = Cons(
+        Operator::Matrix,
+        vec![
+            Cons(Operator::MatrixRow, vec![symbol("x", None)]),
+            Cons(Operator::MatrixRow, vec![symbol("y", None)]),
+        ],
+    );
+    let equation = Cons(
+        Operator::Equals,
+        vec![
+            vector.clone(),
+            Cons(
+                Operator::Multiply,
+                vec![
+                    symbol("k", None),
+                    Cons(
+                        Operator::MatMul,
+                        vec![symbol("A", Some(Type::Matrix)), vector],
+                    ),
+                ],
+            ),
+        ],
+    );
+    let equations: Vec<String> = equation
+        .expand_matrix_equation()
+        .unwrap()
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    assert_eq!(
+        equations,
+        vec![
+            "(= x (* k (+ (* (Index A 1 1) x) (* (Index A 1 2) y))))",
+            "(= y (* k (+ (* (Index A 2 1) x) (* (Index A 2 2) y))))"
+        ]
+    );
+
+    let input = "<math><mi>z</mi><mo>=</mo><msup><mrow><mo>(</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr><mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr></mtable><mo>)</mo></mrow><mi>T</mi></msup></math>";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    assert!(met.expand_matrix_equation().is_err());
+    if let Cons(Operator::Equals, args) = met {
+        let transpose = entries(&args[1]).unwrap().unwrap();
+        assert_eq!(transpose[0][1].to_string(), "c");
+    }
+}
+
+#[test]
+fn test_ragged_matrix() {
+    let input = "
+    <math>
+        <mrow><mo>[</mo><mtable>
+            <mtr><mtd><mi>x</mi></mtd></mtr>
+            <mtr><mtd><mi>y</mi></mtd></mtr>
+        </mtable><mo>]</mo></mrow>
+        <mo>=</mo>
+        <mrow><mo>[</mo><mtable>
+            <mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr>
+            <mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd><mtd><mi>e</mi></mtd></mtr>
+        </mtable><mo>]</mo></mrow>
+        <mrow><mo>[</mo><mtable>
+            <mtr><mtd><mi>x</mi></mtd></mtr>
+            <mtr><mtd><mi>y</mi></mtd></mtr>
+        </mtable><mo>]</mo></mrow>
+    </math>
+    ";
+    let met = input.parse::<MathExpressionTree>().unwrap();
+    let err = met.expand_matrix_equation().unwrap_err();
+    assert_eq!(
+        err.message,
+        "Ragged matrix: row 2 has 3 entries, but row 1 has 2"
+    );
+}
@@ -0,0 +1,618 @@
+//! Algebraic equations of a model, e.g. N = S + I + R or incidence = βSI/N, which define a
+//! variable in terms of the states and parameters instead of giving its derivative. They are
+//! represented as observables in AMRs, and can be substituted into the rates of the ODEs.
+//!
+//! Equations with a constant right hand side, e.g. β = 0.3, give the values of parameters
+//! instead, and initial conditions, e.g. S(0) = 990, the initial values of states.
+
+use crate::{
+    acset::{GeneralizedAMR, Initial, Observable, Parameter, PetriNet, RegNet, StockFlow},
+    ast::{operator::Operator, MathExpression},
+    parsers::{
+        first_order_ode::{flatten_mults, FirstOrderODE},
+        generic_mathml::ParseDiagnostic,
+        higher_order_ode::HigherOrderODE,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+        substitution::{identifier, identifiers, key},
+    },
+};
+use std::{collections::HashMap, convert::TryFrom, str::FromStr};
+
+/// Algebraic equation defining a variable, e.g. N = S + I + R.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Definition {
+    /// The variable on the LHS of the equation.
+    pub lhs_var: String,
+    /// Whether the equation gives the initial value of the variable, e.g. S(0) = 990, instead of
+    /// defining it.
+    pub initial: bool,
+    /// An expression tree corresponding to the RHS of the equation.
+    pub rhs: MathExpressionTree,
+}
+
+impl TryFrom<MathExpressionTree> for Definition {
+    type Error = ParseDiagnostic;
+
+    /// Interpret an equation of the form x = rhs as the definition of x, and one of the form
+    /// x(0) = rhs as the initial value of x.
+    fn try_from(met: MathExpressionTree) -> Result<Self, Self::Error> {
+        if let Cons(Operator::Equals, mut args) = met {
+            if args.len() == 2 {
+                let rhs = args.pop().unwrap();
+                let lhs = args.pop().unwrap();
+                let (lhs_var, initial) = match &lhs {
+                    Atom(atom) => (identifier(atom), false),
+                    // x(0) is parsed as the product of x and 0
+                    Cons(Operator::Multiply, factors) => match factors.as_slice() {
+                        [Atom(atom), Atom(MathExpression::Mn(zero))] if zero == "0" => {
+                            (identifier(atom), true)
+                        }
+                        _ => (None, false),
+                    },
+                    _ => (None, false),
+                };
+                if let Some(lhs_var) = lhs_var {
+                    return Ok(Definition {
+                        lhs_var,
+                        initial,
+                        rhs,
+                    });
+                }
+            }
+        }
+        Err(ParseDiagnostic::new(
+            "Expected a definition of the form x = f(y)".to_string(),
+        ))
+    }
+}
+
+impl FromStr for Definition {
+    type Err = ParseDiagnostic;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Definition::try_from(s.parse::<MathExpressionTree>()?)
+    }
+}
+
+impl Definition {
+    /// The value of the RHS, if it is constant, e.g. for β = 0.3 or k = 1/5.
+    pub fn value(&self) -> Option<f64> {
+        self.rhs
+            .eval(&HashMap::new())
+            .ok()
+            .filter(|value| value.is_finite())
+    }
+
+    /// Whether the equation defines an observable, rather than giving the value of a parameter
+    /// or the initial value of a state.
+    pub fn is_observable(&self) -> bool {
+        !self.initial && self.value().is_none()
+    }
+
+    /// The observable defined by the equation, which depends on the given states.
+    pub fn to_observable(&self, states: &[String]) -> Observable {
+        let mut names = vec![];
+        identifiers(&self.rhs, &mut names);
+        let states = states
+            .iter()
+            .filter(|state| names.iter().any(|name| key(name) == key(state)))
+            .cloned()
+            .collect();
+        Observable {
+            id: self.lhs_var.clone(),
+            name: Some(self.lhs_var.clone()),
+            states: Some(states),
+            expression: Some(self.rhs.to_infix_expression()),
+            expression_mathml: Some(self.rhs.to_cmml()),
+        }
+    }
+}
+
+/// An equation of a model: either an ODE or an algebraic equation.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum ModelEquation {
+    Ode(HigherOrderODE),
+    Definition(Definition),
+}
+
+impl FromStr for ModelEquation {
+    type Err = ParseDiagnostic;
+
+    /// Equations are parsed as ODEs if possible, and otherwise as definitions. The diagnostic of
+    /// an equation that is neither is the one for ODEs.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<HigherOrderODE>() {
+            Ok(ode) => Ok(ModelEquation::Ode(ode)),
+            Err(err) => s
+                .parse::<Definition>()
+                .map(ModelEquation::Definition)
+                .map_err(|_| err),
+        }
+    }
+}
+
+/// Split the equations of a model into its ODEs and its definitions.
+pub fn partition(equations: Vec<ModelEquation>) -> (Vec<HigherOrderODE>, Vec<Definition>) {
+    let mut odes = vec![];
+    let mut definitions = vec![];
+    for equation in equations {
+        match equation {
+            ModelEquation::Ode(ode) => odes.push(ode),
+            ModelEquation::Definition(definition) => definitions.push(definition),
+        }
+    }
+    (odes, definitions)
+}
+
+/// Substitute the definitions into an expression. Definitions may refer to each other in any
+/// order, so they are substituted once per definition, which resolves any chain of them.
+fn inline(met: &MathExpressionTree, definitions: &[Definition]) -> MathExpressionTree {
+    let observables: Vec<&Definition> = definitions
+        .iter()
+        .filter(|definition| definition.is_observable())
+        .collect();
+    let mut met = met.clone();
+    for _ in observables.iter() {
+        for definition in observables.iter() {
+            met = met.substitute(&definition.lhs_var, &definition.rhs);
+        }
+    }
+    met
+}
+
+/// Substitute the definitions of observables into the right hand sides of the ODEs, e.g.
+/// N = S + I + R into dS/dt = -βSI/N.
+pub fn inline_definitions(
+    odes: &[FirstOrderODE],
+    definitions: &[Definition],
+) -> Vec<FirstOrderODE> {
+    odes.iter()
+        .map(|ode| FirstOrderODE {
+            rhs: flatten_mults(inline(&ode.rhs, definitions)),
+            ..ode.clone()
+        })
+        .collect()
+}
+
+/// The observables defined by the definitions, and the identifiers in their expressions and in
+/// the initial values that are neither states nor observables, which are parameters of the
+/// model.
+fn observables(definitions: &[Definition], states: &[String]) -> (Vec<Observable>, Vec<String>) {
+    let observables: Vec<Observable> = definitions
+        .iter()
+        .filter(|definition| definition.is_observable())
+        .map(|definition| definition.to_observable(states))
+        .collect();
+    let mut parameters = vec![];
+    for definition in definitions {
+        identifiers(&definition.rhs, &mut parameters);
+    }
+    parameters.retain(|name| {
+        !states.iter().any(|state| key(state) == key(name))
+            && !observables
+                .iter()
+                .any(|observable| key(&observable.id) == key(name))
+    });
+    (observables, parameters)
+}
+
+/// Set the values of the parameters given by the definitions with a constant RHS, adding the
+/// parameters that are missing.
+fn set_parameter_values(parameters: &mut Vec<Parameter>, definitions: &[Definition]) {
+    for definition in definitions.iter().filter(|definition| !definition.initial) {
+        if let Some(value) = definition.value() {
+            match parameters
+                .iter_mut()
+                .find(|parameter| key(&parameter.id) == key(&definition.lhs_var))
+            {
+                Some(parameter) => parameter.value = Some(value.into()),
+                None => parameters.push(Parameter {
+                    id: definition.lhs_var.clone(),
+                    name: Some(definition.lhs_var.clone()),
+                    value: Some(value.into()),
+                    ..Default::default()
+                }),
+            }
+        }
+    }
+}
+
+/// The initial values of states given by the initial conditions, as infix expressions and
+/// content MathML.
+fn initial_values(definitions: &[Definition]) -> Vec<Initial> {
+    definitions
+        .iter()
+        .filter(|definition| definition.initial)
+        .map(|definition| Initial {
+            target: definition.lhs_var.clone(),
+            expression: definition.rhs.to_infix_expression(),
+            expression_mathml: definition.rhs.to_cmml(),
+        })
+        .collect()
+}
+
+/// Replace the initials of the states that have an initial condition. Parameters without a value
+/// that only named a replaced initial, e.g. `S0`, are removed.
+fn set_initials(
+    initials: &mut Vec<Initial>,
+    parameters: &mut Vec<Parameter>,
+    definitions: &[Definition],
+) {
+    for initial in initial_values(definitions) {
+        match initials
+            .iter_mut()
+            .find(|existing| key(&existing.target) == key(&initial.target))
+        {
+            Some(existing) => {
+                let replaced = std::mem::replace(existing, initial);
+                if replaced.expression == format!("{}0", replaced.target) {
+                    parameters.retain(|parameter| {
+                        parameter.value.is_some() || key(&parameter.id) != key(&replaced.expression)
+                    });
+                }
+            }
+            None => initials.push(initial),
+        }
+    }
+}
+
+/// Add the parameters to a list of parameters, except for those already in it, and remove the
+/// observables from it.
+fn merge_parameters(parameters: &mut Vec<Parameter>, new: Vec<String>, observables: &[Observable]) {
+    parameters.retain(|parameter| {
+        !observables
+            .iter()
+            .any(|observable| key(&observable.id) == key(&parameter.id))
+    });
+    for name in new {
+        if !parameters
+            .iter()
+            .any(|parameter| key(&parameter.id) == key(&name))
+        {
+            parameters.push(Parameter {
+                id: name.clone(),
+                name: Some(name),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+impl PetriNet {
+    /// Construct a Petri net from ODEs and definitions of observables, which are either
+    /// substituted into the rates of the transitions, or referred to by name in the rates.
+    /// Either way, the definitions are included as the observables of the ODE semantics. Values
+    /// of parameters and initial conditions among the definitions are set as well.
+    pub fn with_observables(
+        odes: Vec<FirstOrderODE>,
+        definitions: &[Definition],
+        inline: bool,
+    ) -> PetriNet {
+        let states: Vec<String> = odes.iter().map(|ode| ode.lhs_var.to_string()).collect();
+        let mut petri_net = if inline {
+            PetriNet::from(inline_definitions(&odes, definitions))
+        } else {
+            PetriNet::from(odes)
+        };
+        if definitions.is_empty() {
+            return petri_net;
+        }
+        let (observables, parameters) = observables(definitions, &states);
+        if let Some(semantics) = petri_net.semantics.as_mut() {
+            merge_parameters(
+                semantics.ode.parameters.get_or_insert_with(Vec::new),
+                parameters,
+                &observables,
+            );
+            if !observables.is_empty() {
+                semantics.ode.observables = Some(observables);
+            }
+        }
+        petri_net.set_values(definitions);
+        petri_net
+    }
+
+    /// Set the values of the parameters and the initial values of the states given by the
+    /// definitions, e.g. β = 0.3 and S(0) = 990.
+    pub fn set_values(&mut self, definitions: &[Definition]) {
+        if let Some(semantics) = self.semantics.as_mut() {
+            let parameters = semantics.ode.parameters.get_or_insert_with(Vec::new);
+            set_parameter_values(parameters, definitions);
+            set_initials(
+                semantics.ode.initials.get_or_insert_with(Vec::new),
+                parameters,
+                definitions,
+            );
+        }
+    }
+}
+
+impl RegNet {
+    /// Set the values of the parameters and the initial values of the vertices given by the
+    /// definitions, e.g. β = 0.3 and S(0) = 990.
+    pub fn set_values(&mut self, definitions: &[Definition]) {
+        set_parameter_values(
+            self.model.parameters.get_or_insert_with(Vec::new),
+            definitions,
+        );
+        let initials = initial_values(definitions);
+        self.model.vertices = std::mem::take(&mut self.model.vertices)
+            .into_iter()
+            .map(|mut vertex| {
+                if let Some(initial) = initials
+                    .iter()
+                    .find(|initial| key(&initial.target) == key(&vertex.id))
+                {
+                    vertex.initial = Some(initial.expression.clone());
+                }
+                vertex
+            })
+            .collect();
+    }
+}
+
+impl StockFlow {
+    /// Set the values of the parameters and the initial values of the stocks given by the
+    /// definitions, e.g. β = 0.3 and S(0) = 990.
+    pub fn set_values(&mut self, definitions: &[Definition]) {
+        if let Some(semantics) = self.semantics.as_mut() {
+            let parameters = semantics.ode.parameters.get_or_insert_with(Vec::new);
+            set_parameter_values(parameters, definitions);
+            set_initials(
+                semantics.ode.initials.get_or_insert_with(Vec::new),
+                parameters,
+                definitions,
+            );
+        }
+    }
+}
+
+/// The definitions among the equations of a generalized AMR.
+fn definitions(met: &[MathExpressionTree]) -> Vec<Definition> {
+    met.iter()
+        .filter_map(|equation| Definition::try_from(equation.clone()).ok())
+        .collect()
+}
+
+impl GeneralizedAMR {
+    /// Set the observables from the definitions among the equations, whose variables are then
+    /// not parameters, and the values of the parameters from the equations with a constant RHS.
+    pub(crate) fn set_observables(&mut self) {
+        let definitions = definitions(&self.met);
+        if let Some(semantics) = self.semantics.as_mut() {
+            if definitions.is_empty() {
+                semantics.observables = None;
+                return;
+            }
+            let states: Vec<String> = semantics
+                .states
+                .iter()
+                .map(|state| state.id.clone())
+                .collect();
+            let (observables, parameters) = observables(&definitions, &states);
+            let all_parameters = semantics.parameters.get_or_insert_with(Vec::new);
+            merge_parameters(all_parameters, parameters, &observables);
+            set_parameter_values(all_parameters, &definitions);
+            semantics.observables = if observables.is_empty() {
+                None
+            } else {
+                Some(observables)
+            };
+        }
+    }
+
+    /// Substitute the definitions of the observables into the other equations, e.g. for
+    /// incidence = βSI/N and N = S + I + R, dS/dt = -incidence becomes dS/dt = -βSI/(S + I + R).
+    /// The definitions themselves are kept, so that the observables still have them.
+    pub fn inline_observables(&self) -> GeneralizedAMR {
+        let definitions = definitions(&self.met);
+        let met = self
+            .met
+            .iter()
+            .map(|equation| match Definition::try_from(equation.clone()) {
+                Ok(_) => equation.clone(),
+                Err(_) => inline(equation, &definitions),
+            })
+            .collect();
+        GeneralizedAMR {
+            met,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+fn equation(lhs: &str, rhs: &str) -> String {
+    format!("<math>{lhs}<mo>=</mo>{rhs}</math>")
+}
+
+#[cfg(test)]
+fn derivative(var: &str) -> String {
+    format!("<mfrac><mrow><mi>d</mi><mi>{var}</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>")
+}
+
+#[cfg(test)]
+fn sir_with_population() -> Vec<ModelEquation> {
+    [
+        equation(
+            &derivative("S"),
+            "<mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>/</mo><mi>N</mi>",
+        ),
+        equation(
+            &derivative("I"),
+            "<mi>β</mi><mi>S</mi><mi>I</mi><mo>/</mo><mi>N</mi><mo>-</mo><mi>γ</mi><mi>I</mi>",
+        ),
+        equation(&derivative("R"), "<mi>γ</mi><mi>I</mi>"),
+        equation(
+            "<mi>N</mi>",
+            "<mi>S</mi><mo>+</mo><mi>I</mi><mo>+</mo><mi>R</mi>",
+        ),
+    ]
+    .iter()
+    .map(|input| input.parse().unwrap())
+    .collect()
+}
+
+#[test]
+fn test_model_equations() {
+    let equations = sir_with_population();
+    assert!(matches!(equations[0], ModelEquation::Ode(_)));
+    let ModelEquation::Definition(definition) = &equations[3] else {
+        panic!("Expected a definition")
+    };
+    assert_eq!(definition.lhs_var, "N");
+    let observable = definition.to_observable(&["R".to_string(), "S".to_string()]);
+    assert_eq!(
+        observable.states,
+        Some(vec!["R".to_string(), "S".to_string()])
+    );
+    assert_eq!(observable.expression.as_deref(), Some("((S+I)+R)"));
+
+    // An equation that is neither an ODE nor a definition is reported as a malformed ODE.
+    let err = equation("<mi>N</mi><mo>+</mo><mi>S</mi>", "<mi>I</mi>")
+        .parse::<ModelEquation>()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        equation("<mi>N</mi><mo>+</mo><mi>S</mi>", "<mi>I</mi>")
+            .parse::<HigherOrderODE>()
+            .unwrap_err()
+    );
+}
+
+#[test]
+fn test_petri_net_observables() {
+    let (odes, definitions) = partition(sir_with_population());
+    let odes = crate::parsers::higher_order_ode::reduce_to_first_order(odes);
+    let rates = |petri_net: &PetriNet| {
+        let ode = &petri_net.semantics.as_ref().unwrap().ode;
+        let parameters: Vec<String> = ode
+            .parameters
+            .iter()
+            .flatten()
+            .map(|parameter| parameter.id.clone())
+            .collect();
+        assert_eq!(parameters, vec!["I0", "R0", "S0", "β", "γ"]);
+        assert_eq!(ode.observables.as_ref().unwrap()[0].id, "N");
+        ode.rates
+            .iter()
+            .flatten()
+            .map(|rate| rate.expression.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let petri_net = PetriNet::with_observables(odes.clone(), &definitions, false);
+    assert_eq!(rates(&petri_net), vec!["(I*S*β)/N", "I*γ"]);
+    let petri_net = PetriNet::with_observables(odes, &definitions, true);
+    assert_eq!(rates(&petri_net), vec!["(I*S*β)/((I+R)+S)", "I*γ"]);
+}
+
+#[test]
+fn test_generalized_amr_observables() {
+    let met: Vec<MathExpressionTree> = [
+        equation(&derivative("S"), "<mo>-</mo><mi>ι</mi>"),
+        equation(&derivative("I"), "<mi>ι</mi><mo>-</mo><mi>γ</mi><mi>I</mi>"),
+        equation(
+            "<mi>ι</mi>",
+            "<mi>β</mi><mi>S</mi><mi>I</mi><mo>/</mo><mi>N</mi>",
+        ),
+        equation("<mi>N</mi>", "<mi>S</mi><mo>+</mo><mi>I</mi>"),
+    ]
+    .iter()
+    .map(|input| input.parse().unwrap())
+    .collect();
+    let gamr = GeneralizedAMR::from(met);
+    let semantics = gamr.semantics.as_ref().unwrap();
+    let observables: Vec<&str> = semantics
+        .observables
+        .iter()
+        .flatten()
+        .map(|observable| observable.id.as_str())
+        .collect();
+    assert_eq!(observables, vec!["ι", "N"]);
+    let parameters: Vec<&str> = semantics
+        .parameters
+        .iter()
+        .flatten()
+        .map(|parameter| parameter.id.as_str())
+        .collect();
+    assert_eq!(parameters, vec!["β", "γ"]);
+
+    let inlined = gamr.inline_observables();
+    assert_eq!(inlined.met.len(), 4);
+    assert_eq!(
+        inlined.met[0].to_string(),
+        "(= (D(1, t) S) (- (* (* β S) (/ I (+ S I)))))"
+    );
+    assert_eq!(inlined.semantics, gamr.semantics);
+
+    let renaming = std::collections::HashMap::from([("N".to_string(), "P".to_string())]);
+    let renamed = gamr.rename(&renaming);
+    let observable = &renamed.semantics.unwrap().observables.unwrap()[0];
+    assert_eq!(observable.expression.as_deref(), Some("((β*S)*(I/P))"));
+}
+
+#[test]
+fn test_values() {
+    use crate::{
+        acset::Distribution,
+        simulation::{OdeSystem, SimulationConfig},
+    };
+    let mut equations = sir_with_population();
+    for (lhs, rhs) in [
+        ("<mi>β</mi>", "<mn>0.3</mn>"),
+        ("<mi>γ</mi>", "<mn>1</mn><mo>/</mo><mn>10</mn>"),
+        ("<mi>S</mi><mo>(</mo><mn>0</mn><mo>)</mo>", "<mn>990</mn>"),
+        ("<mi>I</mi><mo>(</mo><mn>0</mn><mo>)</mo>", "<mn>10</mn>"),
+        ("<mi>R</mi><mo>(</mo><mn>0</mn><mo>)</mo>", "<mn>0</mn>"),
+    ] {
+        equations.push(equation(lhs, rhs).parse().unwrap());
+    }
+    let (odes, definitions) = partition(equations);
+    assert!(definitions[3].initial);
+    let odes = crate::parsers::higher_order_ode::reduce_to_first_order(odes);
+    let mut petri_net = PetriNet::with_observables(odes, &definitions, true);
+
+    let ode = &mut petri_net.semantics.as_mut().unwrap().ode;
+    let values: Vec<(&str, f64)> = ode
+        .parameters
+        .iter()
+        .flatten()
+        .map(|parameter| (parameter.id.as_str(), parameter.value.unwrap().into()))
+        .collect();
+    assert_eq!(values, vec![("β", 0.3), ("γ", 0.1)]);
+    let initials: Vec<(&str, &str)> = ode
+        .initials
+        .iter()
+        .flatten()
+        .map(|initial| (initial.target.as_str(), initial.expression.as_str()))
+        .collect();
+    assert_eq!(initials, vec![("S", "990"), ("I", "10"), ("R", "0")]);
+    assert_eq!(ode.observables.as_ref().unwrap().len(), 1);
+
+    ode.parameters.as_mut().unwrap()[0].distribution =
+        Some(Distribution::normal(0.3, 0.05).bounded(Some(0.0), None));
+    let json = serde_json::to_value(&ode.parameters).unwrap();
+    assert_eq!(
+        json[0],
+        serde_json::json!({
+            "id": "β",
+            "name": "β",
+            "value": 0.3,
+            "distribution": {
+                "type": "Normal1",
+                "parameters": {"minimum": 0.0, "mean": 0.3, "stdev": 0.05}
+            }
+        })
+    );
+    assert_eq!(petri_net.validate(), vec![]);
+
+    // The values given by the equations suffice for a simulation.
+    let config: SimulationConfig =
+        serde_json::from_value(serde_json::json!({"end_time": 10.0, "step": 1.0})).unwrap();
+    let trajectory = OdeSystem::try_from(&petri_net)
+        .unwrap()
+        .simulate(&config)
+        .unwrap();
+    assert_eq!(trajectory.states.len(), 3);
+}
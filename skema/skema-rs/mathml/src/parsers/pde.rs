@@ -0,0 +1,322 @@
+//! Partial differential equations that evolve a field in time, e.g. ∂u/∂t = D ∂²u/∂x² + f(u),
+//! with the metadata needed to describe PDE-based models such as the Halfar glacier model.
+
+use crate::{
+    ast::{
+        operator::{Derivative, DerivativeNotation, Operator},
+        Ci, MathExpression, Mi,
+    },
+    parsers::{
+        generic_mathml::ParseDiagnostic,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+    },
+};
+use derive_new::new;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, str::FromStr};
+use utoipa::ToSchema;
+
+/// Spatial differential operators that can act on the fields of a PDE.
+#[derive(
+    Debug,
+    Ord,
+    PartialOrd,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    Hash,
+    Deserialize,
+    Serialize,
+    ToSchema,
+    JsonSchema,
+)]
+pub enum SpatialOperator {
+    Gradient,
+    Divergence,
+    Laplacian,
+}
+
+/// Partial differential equation with a single time derivative of a field on the LHS.
+#[derive(Debug, Ord, PartialOrd, PartialEq, Eq, Clone, Hash, new)]
+pub struct PDE {
+    /// The field on the LHS of the equation that is being differentiated.
+    pub lhs_var: Ci,
+
+    /// The independent variable of the time derivative on the LHS.
+    pub with_respect_to: Ci,
+
+    /// The order of the time derivative on the LHS.
+    pub order: u8,
+
+    /// The spatial independent variables of the field, each with the highest order of the partial
+    /// derivatives with respect to it that appear in the equation. Variables the field is only
+    /// declared to be a function of have order 0.
+    pub spatial_vars: Vec<(Ci, u8)>,
+
+    /// The spatial operators appearing on the RHS, in order of first appearance.
+    pub spatial_operators: Vec<SpatialOperator>,
+
+    /// An expression tree corresponding to the RHS of the PDE.
+    pub rhs: MathExpressionTree,
+}
+
+impl PDE {
+    /// The PDE as an equation whose LHS is the time derivative of the field.
+    pub fn to_met(&self) -> MathExpressionTree {
+        Cons(
+            Operator::Equals,
+            vec![
+                Cons(
+                    Operator::Derivative(Derivative::new(
+                        self.order,
+                        1,
+                        self.with_respect_to.clone(),
+                        DerivativeNotation::LeibnizPartialStandard,
+                    )),
+                    vec![Atom(MathExpression::Ci(self.lhs_var.clone()))],
+                ),
+                self.rhs.clone(),
+            ],
+        )
+    }
+
+    /// The identifiers on the RHS other than the field and its independent variables, i.e. the
+    /// parameters of the PDE.
+    pub fn parameters(&self) -> Vec<String> {
+        let mut identifiers = Vec::new();
+        collect_identifiers(&self.rhs, &mut identifiers);
+        let mut independent = vec![self.lhs_var.to_string(), self.with_respect_to.to_string()];
+        independent.extend(self.spatial_vars.iter().map(|(var, _)| var.to_string()));
+        identifiers.retain(|identifier| !independent.contains(identifier));
+        identifiers.sort();
+        identifiers.dedup();
+        identifiers
+    }
+
+    /// Record the spatial derivatives and operators applied on an expression of the RHS.
+    fn record_spatial_structure(&mut self, met: &MathExpressionTree) {
+        if let Cons(op, args) = met {
+            match op {
+                Operator::Derivative(Derivative {
+                    order, bound_var, ..
+                }) if bound_var.to_string() != self.with_respect_to.to_string() => {
+                    self.record_spatial_var(bound_var, *order)
+                }
+                Operator::Gradient(_) => self.record_spatial_operator(SpatialOperator::Gradient),
+                Operator::Div => self.record_spatial_operator(SpatialOperator::Divergence),
+                Operator::Laplacian => self.record_spatial_operator(SpatialOperator::Laplacian),
+                _ => {}
+            }
+            for arg in args {
+                self.record_spatial_structure(arg);
+            }
+        }
+    }
+
+    fn record_spatial_var(&mut self, var: &Ci, order: u8) {
+        match self
+            .spatial_vars
+            .iter_mut()
+            .find(|(spatial_var, _)| spatial_var.to_string() == var.to_string())
+        {
+            Some((_, max_order)) => *max_order = (*max_order).max(order),
+            None => self.spatial_vars.push((var.clone(), order)),
+        }
+    }
+
+    fn record_spatial_operator(&mut self, operator: SpatialOperator) {
+        if !self.spatial_operators.contains(&operator) {
+            self.spatial_operators.push(operator);
+        }
+    }
+}
+
+/// Collect the names of the identifiers in an expression, in order of appearance.
+fn collect_identifiers(met: &MathExpressionTree, identifiers: &mut Vec<String>) {
+    match met {
+        Atom(MathExpression::Ci(x)) => identifiers.push(x.content.to_string()),
+        Atom(MathExpression::Mi(Mi(x))) => identifiers.push(x.clone()),
+        Atom(_) => {}
+        Cons(_, args) => {
+            for arg in args {
+                collect_identifiers(arg, identifiers);
+            }
+        }
+    }
+}
+
+impl TryFrom<MathExpressionTree> for PDE {
+    type Error = ParseDiagnostic;
+
+    /// Interpret an equation of the form ∂ⁿu/∂tⁿ = rhs as a PDE.
+    fn try_from(met: MathExpressionTree) -> Result<Self, Self::Error> {
+        if let Cons(Operator::Equals, mut args) = met {
+            if args.len() == 2 {
+                let rhs = args.pop().unwrap();
+                if let Some(Cons(
+                    Operator::Derivative(Derivative {
+                        order,
+                        bound_var,
+                        notation:
+                            DerivativeNotation::LeibnizPartialStandard
+                            | DerivativeNotation::LeibnizPartialCompact,
+                        ..
+                    }),
+                    mut lhs,
+                )) = args.pop()
+                {
+                    if let (1, Some(Atom(MathExpression::Ci(lhs_var)))) = (lhs.len(), lhs.pop()) {
+                        let mut pde = PDE::new(lhs_var, bound_var, order, vec![], vec![], rhs);
+                        // Variables the field is declared to be a function of, e.g. u(x, t).
+                        for var in pde.lhs_var.func_of.clone().unwrap_or_default() {
+                            let name = var.to_string();
+                            if !name.is_empty() && name != pde.with_respect_to.to_string() {
+                                pde.record_spatial_var(&var, 0);
+                            }
+                        }
+                        let rhs = pde.rhs.clone();
+                        pde.record_spatial_structure(&rhs);
+                        return Ok(pde);
+                    }
+                }
+            }
+        }
+        Err(ParseDiagnostic::new(
+            "Expected a PDE of the form ∂u/∂t = f(u)".to_string(),
+        ))
+    }
+}
+
+impl FromStr for PDE {
+    type Err = ParseDiagnostic;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PDE::try_from(s.parse::<MathExpressionTree>()?)
+    }
+}
+
+#[test]
+fn test_reaction_diffusion() {
+    let input = "
+    <math>
+        <mfrac><mrow><mi>∂</mi><mi>u</mi></mrow><mrow><mi>∂</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo>
+        <mi>D</mi>
+        <mfrac>
+            <mrow><msup><mi>∂</mi><mn>2</mn></msup><mi>u</mi></mrow>
+            <mrow><mi>∂</mi><msup><mi>x</mi><mn>2</mn></msup></mrow>
+        </mfrac>
+        <mo>+</mo>
+        <mi>r</mi><mi>u</mi>
+    </math>
+    ";
+    let pde = input.parse::<PDE>().unwrap();
+    assert_eq!(pde.lhs_var.to_string(), "u");
+    assert_eq!(pde.with_respect_to.to_string(), "t");
+    assert_eq!(pde.order, 1);
+    let spatial_vars: Vec<(String, u8)> = pde
+        .spatial_vars
+        .iter()
+        .map(|(var, order)| (var.to_string(), *order))
+        .collect();
+    assert_eq!(spatial_vars, vec![("x".to_string(), 2)]);
+    assert!(pde.spatial_operators.is_empty());
+    assert_eq!(pde.parameters(), vec!["D", "r"]);
+    assert_eq!(
+        pde.to_met().to_string(),
+        "(= (PD(1, t) u) (+ (* D (PD(2, x) u)) (* r u)))"
+    );
+}
+
+#[test]
+fn test_spatial_operators() {
+    let input = "
+    <math>
+        <msub><mi>∂</mi><mi>t</mi></msub><mi>u</mi>
+        <mo>=</mo>
+        <mo>∇</mo><mo>⋅</mo><mo>(</mo><mi>D</mi><mo>∇</mo><mi>u</mi><mo>)</mo>
+        <mo>+</mo>
+        <mi>k</mi><msup><mo>∇</mo><mn>2</mn></msup><mi>u</mi>
+    </math>
+    ";
+    let pde = input.parse::<PDE>().unwrap();
+    assert_eq!(
+        pde.spatial_operators,
+        vec![
+            SpatialOperator::Divergence,
+            SpatialOperator::Gradient,
+            SpatialOperator::Laplacian
+        ]
+    );
+    assert_eq!(pde.parameters(), vec!["D", "k"]);
+}
+
+#[test]
+fn test_declared_spatial_vars() {
+    let input = "
+    <math>
+        <mfrac>
+            <mrow><mi>∂</mi><mi>u</mi><mo>(</mo><mi>x</mi><mo>,</mo><mi>t</mi><mo>)</mo></mrow>
+            <mrow><mi>∂</mi><mi>t</mi></mrow>
+        </mfrac>
+        <mo>=</mo>
+        <mo>-</mo><mi>c</mi>
+        <mfrac><mrow><mi>∂</mi><mi>u</mi></mrow><mrow><mi>∂</mi><mi>x</mi></mrow></mfrac>
+    </math>
+    ";
+    let pde = input.parse::<PDE>().unwrap();
+    let spatial_vars: Vec<(String, u8)> = pde
+        .spatial_vars
+        .iter()
+        .map(|(var, order)| (var.to_string(), *order))
+        .collect();
+    assert_eq!(spatial_vars, vec![("x".to_string(), 1)]);
+}
+
+#[test]
+fn test_ode_is_not_a_pde() {
+    let input = "
+    <math>
+        <mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo>
+        <mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi>
+    </math>
+    ";
+    assert!(input.parse::<PDE>().is_err());
+}
+
+#[test]
+fn test_pde_to_generalized_amr() {
+    use crate::acset::GeneralizedAMR;
+    let input = "
+    <math>
+        <mfrac><mrow><mi>∂</mi><mi>H</mi></mrow><mrow><mi>∂</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo>
+        <mo>∇</mo><mo>⋅</mo><mo>(</mo><mi>Γ</mi><msup><mi>H</mi><mi>n</mi></msup><mo>∇</mo><mi>H</mi><mo>)</mo>
+    </math>
+    ";
+    let gamr = GeneralizedAMR::from(vec![input.parse::<PDE>().unwrap()]);
+    let semantics = gamr.semantics.unwrap();
+    let states: Vec<String> = semantics
+        .states
+        .iter()
+        .map(|state| state.id.clone())
+        .collect();
+    assert_eq!(states, vec!["H"]);
+    let parameters: Vec<String> = semantics
+        .parameters
+        .unwrap()
+        .iter()
+        .map(|parameter| parameter.id.clone())
+        .collect();
+    assert_eq!(parameters, vec!["n", "Γ"]);
+    let pdes = semantics.pdes.unwrap();
+    assert_eq!(pdes[0].state, "H");
+    assert_eq!(pdes[0].time.id, "t");
+    assert_eq!(
+        pdes[0].spatial_operators,
+        vec![SpatialOperator::Divergence, SpatialOperator::Gradient]
+    );
+}
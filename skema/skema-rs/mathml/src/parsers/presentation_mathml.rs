@@ -0,0 +1,323 @@
+//! Printing of math expression trees and ODEs as presentation MathML, for rendering equations
+//! that were not extracted from MathML in the first place, e.g. those reconstructed from an AMR.
+//! The output is read back by the presentation MathML parsers of this crate.
+
+use crate::{
+    ast::{
+        operator::{Derivative, Logarithm, LogarithmNotation, Operator},
+        MathExpression, Mi,
+    },
+    parsers::{
+        first_order_ode::FirstOrderODE,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+    },
+};
+
+const RELATION: u8 = 1;
+const SUM: u8 = 2;
+const PRODUCT: u8 = 3;
+const POWER: u8 = 4;
+const ATOM: u8 = 5;
+
+/// Invisible times, which renders as juxtaposition but keeps adjacent numbers apart.
+const TIMES: &str = "<mo>\u{2062}</mo>";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn mo(op: &str) -> String {
+    format!("<mo>{}</mo>", escape(op))
+}
+
+fn mrow(content: String) -> String {
+    format!("<mrow>{content}</mrow>")
+}
+
+fn parenthesized(content: String) -> String {
+    mrow(format!("{}{}{}", mo("("), content, mo(")")))
+}
+
+fn atom(atom: &MathExpression) -> String {
+    match atom {
+        MathExpression::Mn(x) => format!("<mn>{}</mn>", escape(x.trim())),
+        MathExpression::Mi(Mi(x)) => format!("<mi>{}</mi>", escape(x.trim())),
+        MathExpression::Ci(ci) => self::atom(&ci.content),
+        MathExpression::Msub(base, sub) => {
+            format!("<msub>{}{}</msub>", self::atom(base), self::atom(sub))
+        }
+        MathExpression::Mrow(row) => mrow(row.0.iter().map(self::atom).collect()),
+        MathExpression::Mtext(x) => format!("<mtext>{}</mtext>", escape(x)),
+        _ => format!("<mi>{}</mi>", escape(&atom.to_string())),
+    }
+}
+
+/// Print an expression, parenthesized if it binds looser than the given precedence.
+fn wrap(met: &MathExpressionTree, precedence: u8) -> String {
+    let (printed, p) = print(met);
+    if p < precedence {
+        parenthesized(printed)
+    } else {
+        printed
+    }
+}
+
+fn call(function: &str, args: &[MathExpressionTree]) -> (String, u8) {
+    let args: Vec<String> = args.iter().map(|arg| print(arg).0).collect();
+    let printed = format!(
+        "<mi>{}</mi>{}{}{}",
+        escape(function),
+        mo("("),
+        args.join(&mo(",")),
+        mo(")")
+    );
+    (mrow(printed), ATOM)
+}
+
+fn sum(args: &[MathExpressionTree]) -> (String, u8) {
+    let mut printed = String::new();
+    for (i, arg) in args.iter().enumerate() {
+        match arg {
+            Cons(Operator::Subtract, negated) if negated.len() == 1 && i > 0 => {
+                printed.push_str(&mo("−"));
+                printed.push_str(&wrap(&negated[0], PRODUCT));
+            }
+            _ if i > 0 => {
+                printed.push_str(&mo("+"));
+                printed.push_str(&wrap(arg, SUM));
+            }
+            _ => printed.push_str(&wrap(arg, SUM)),
+        }
+    }
+    (printed, SUM)
+}
+
+fn relation(op: &Operator, args: &[MathExpressionTree]) -> (String, u8) {
+    let symbol = match op {
+        Operator::Equals => "=",
+        Operator::NotEqual => "≠",
+        Operator::Less => "<",
+        Operator::Greater => ">",
+        Operator::LessEqual => "≤",
+        Operator::GreaterEqual => "≥",
+        _ => unreachable!(),
+    };
+    let printed = format!(
+        "{}{}{}",
+        wrap(&args[0], RELATION),
+        mo(symbol),
+        wrap(&args[1], RELATION + 1)
+    );
+    (printed, RELATION)
+}
+
+fn piecewise(pieces: &[MathExpressionTree]) -> (String, u8) {
+    let cell = |content: String| format!("<mtd>{content}</mtd>");
+    let rows: Vec<String> = pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Cons(Operator::Piece, args) if args.len() == 2 => {
+                Some(cell(print(&args[0]).0) + &cell(print(&args[1]).0))
+            }
+            Cons(Operator::Otherwise, args) if args.len() == 1 => {
+                Some(cell(print(&args[0]).0) + &cell("<mtext>otherwise</mtext>".to_string()))
+            }
+            _ => None,
+        })
+        .map(|row| format!("<mtr>{row}</mtr>"))
+        .collect();
+    let printed = format!("{}<mtable>{}</mtable>", mo("{"), rows.concat());
+    (mrow(printed), ATOM)
+}
+
+/// Leibniz notation, e.g. dS/dt, or d/dt (S + I) for derivatives of compound expressions.
+fn derivative(derivative: &Derivative, arg: &MathExpressionTree) -> (String, u8) {
+    let power = |base: String| match derivative.order {
+        1 => base,
+        order => format!("<msup>{base}<mn>{order}</mn></msup>"),
+    };
+    let var = atom(&MathExpression::Ci(derivative.bound_var.clone()));
+    let denominator = mrow(format!("<mi>d</mi>{}", power(var)));
+    let printed = match arg {
+        Atom(_) => format!(
+            "<mfrac>{}{}</mfrac>",
+            mrow(format!(
+                "{}{}",
+                power("<mi>d</mi>".to_string()),
+                print(arg).0
+            )),
+            denominator
+        ),
+        _ => format!(
+            "<mfrac>{}{}</mfrac>{}",
+            power("<mi>d</mi>".to_string()),
+            denominator,
+            parenthesized(print(arg).0)
+        ),
+    };
+    (printed, ATOM)
+}
+
+fn function(op: &Operator) -> Option<&'static str> {
+    let function = match op {
+        Operator::Exp => "exp",
+        Operator::Logarithm(Logarithm {
+            notation: LogarithmNotation::Ln,
+        }) => "ln",
+        Operator::Logarithm(Logarithm {
+            notation: LogarithmNotation::Log,
+        }) => "log",
+        Operator::Sin => "sin",
+        Operator::Cos => "cos",
+        Operator::Tan => "tan",
+        Operator::Sec => "sec",
+        Operator::Csc => "csc",
+        Operator::Cot => "cot",
+        Operator::Arcsin => "arcsin",
+        Operator::Arccos => "arccos",
+        Operator::Arctan => "arctan",
+        Operator::Arcsec => "arcsec",
+        Operator::Arccsc => "arccsc",
+        Operator::Arccot => "arccot",
+        Operator::Min => "min",
+        _ => return None,
+    };
+    Some(function)
+}
+
+fn print(met: &MathExpressionTree) -> (String, u8) {
+    let (op, args) = match met {
+        Atom(x) => return (atom(x), ATOM),
+        Cons(op, args) => (op, args.as_slice()),
+    };
+    match op {
+        Operator::Add => sum(args),
+        Operator::Subtract if args.len() == 1 => {
+            let operand = match &args[0] {
+                Cons(Operator::Subtract, negated) if negated.len() == 1 => {
+                    parenthesized(print(&args[0]).0)
+                }
+                arg => wrap(arg, PRODUCT),
+            };
+            (format!("{}{}", mo("−"), operand), PRODUCT)
+        }
+        Operator::Subtract if args.len() == 2 => (
+            format!(
+                "{}{}{}",
+                wrap(&args[0], SUM),
+                mo("−"),
+                wrap(&args[1], PRODUCT)
+            ),
+            SUM,
+        ),
+        Operator::Multiply => {
+            let factors: Vec<String> = args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| wrap(arg, if i == 0 { PRODUCT } else { PRODUCT + 1 }))
+                .collect();
+            (factors.join(TIMES), PRODUCT)
+        }
+        Operator::Divide if args.len() == 2 => (
+            format!(
+                "<mfrac>{}{}</mfrac>",
+                mrow(print(&args[0]).0),
+                mrow(print(&args[1]).0)
+            ),
+            ATOM,
+        ),
+        Operator::Power if args.len() == 2 => (
+            format!(
+                "<msup>{}{}</msup>",
+                wrap(&args[0], ATOM),
+                mrow(print(&args[1]).0)
+            ),
+            POWER,
+        ),
+        Operator::Sqrt if args.len() == 1 => {
+            (format!("<msqrt>{}</msqrt>", print(&args[0]).0), ATOM)
+        }
+        Operator::Abs if args.len() == 1 => (
+            mrow(format!("{}{}{}", mo("|"), print(&args[0]).0, mo("|"))),
+            ATOM,
+        ),
+        Operator::Factorial if args.len() == 1 => {
+            (format!("{}{}", wrap(&args[0], ATOM), mo("!")), ATOM)
+        }
+        Operator::Equals
+        | Operator::NotEqual
+        | Operator::Less
+        | Operator::LessEqual
+        | Operator::Greater
+        | Operator::GreaterEqual
+            if args.len() == 2 =>
+        {
+            relation(op, args)
+        }
+        Operator::Piecewise => piecewise(args),
+        Operator::Derivative(d) if args.len() == 1 => derivative(d, &args[0]),
+        _ => match function(op) {
+            Some(function) => call(function, args),
+            None => call(&op.to_string(), args),
+        },
+    }
+}
+
+impl MathExpressionTree {
+    /// Print the expression as the content of a presentation MathML `<math>` element.
+    pub fn to_pmml(&self) -> String {
+        print(self).0
+    }
+}
+
+impl FirstOrderODE {
+    /// Print the ODE as a presentation MathML document, e.g. dS/dt = −β S I.
+    pub fn to_pmml(&self) -> String {
+        format!("<math>{}</math>", self.to_met().to_pmml())
+    }
+}
+
+#[cfg(test)]
+fn round_trip(input: &str) -> (String, String) {
+    let met = format!("<math>{input}</math>")
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    let printed = met.to_pmml();
+    let parsed = format!("<math>{printed}</math>")
+        .parse::<MathExpressionTree>()
+        .unwrap();
+    (met.to_string(), parsed.to_string())
+}
+
+#[test]
+fn test_round_trip() {
+    for input in [
+        "<mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi>",
+        "<mo>-</mo><mi>β</mi><mo>(</mo><mi>S</mi><mo>+</mo><mi>I</mi><mo>)</mo>",
+        "<mi>a</mi><mo>-</mo><mo>(</mo><mi>b</mi><mo>-</mo><mi>c</mi><mo>)</mo>",
+        "<mn>2</mn><mn>3</mn><mi>x</mi>",
+        "<mfrac><mrow><mi>β</mi><mi>S</mi><mi>I</mi></mrow><mi>N</mi></mfrac>",
+        "<msup><mrow><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo></mrow><mn>2</mn></msup>",
+        "<msqrt><mi>x</mi></msqrt><mo>+</mo><mrow><mi>exp</mi><mo>(</mo><mo>-</mo><mi>k</mi><mi>t</mi><mo>)</mo></mrow>",
+        "<msub><mi>β</mi><mi>h</mi></msub><msub><mi>S</mi><mi>h</mi></msub>",
+        "<mo>{</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>t</mi><mo>&lt;</mo><mn>5</mn></mtd></mtr><mtr><mtd><mi>b</mi></mtd><mtd><mtext>otherwise</mtext></mtd></mtr></mtable>",
+    ] {
+        let (expected, actual) = round_trip(input);
+        assert_eq!(expected, actual, "{}", input);
+    }
+}
+
+#[test]
+fn test_ode() {
+    let ode = "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap();
+    assert_eq!(
+        ode.to_pmml(),
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>−</mo><mi>β</mi><mo>\u{2062}</mo><mi>S</mi><mo>\u{2062}</mo><mi>I</mi></math>"
+    );
+    let parsed = ode.to_pmml().parse::<FirstOrderODE>().unwrap();
+    assert!(parsed.equivalent(&ode));
+}
@@ -0,0 +1,1074 @@
+//! Printers of math expression trees and ODE systems as SymPy (Python) source, Julia expressions
+//! for ModelingToolkit, readable Unicode math and numeric Python and Rust code.
+//!
+//! Operators that have no counterpart in SymPy, Julia or Unicode, e.g. the Laplacian, are
+//! printed as calls of undefined functions of the same name. Numeric Python and Rust code has to
+//! run as is, so printing such operators, e.g. derivatives, in those syntaxes is an error.
+
+use crate::{
+    ast::{
+        operator::{Derivative, Logarithm, LogarithmNotation, Operator},
+        MathExpression, Mi,
+    },
+    parsers::{
+        first_order_ode::FirstOrderODE,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+        substitution::identifiers,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, fmt};
+
+/// Target syntax of a printer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Syntax {
+    /// Python source using `import sympy as sp`
+    SymPy,
+    /// Julia expressions for ModelingToolkit's `@equations`
+    Julia,
+    /// Plain Unicode math, e.g. dS/dt = −β·S·I
+    Unicode,
+    /// Numeric Python using `import math` and, for matrices, `import numpy as np`
+    Python,
+    /// Numeric Rust on `f64`
+    Rust,
+}
+
+/// Syntax of a complete model definition printed by [`print_system`]. Runnable Python and Rust
+/// right hand side functions of a system are generated by [`crate::codegen`] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelSyntax {
+    SymPy,
+    Julia,
+    Unicode,
+}
+
+impl From<ModelSyntax> for Syntax {
+    fn from(syntax: ModelSyntax) -> Syntax {
+        match syntax {
+            ModelSyntax::SymPy => Syntax::SymPy,
+            ModelSyntax::Julia => Syntax::Julia,
+            ModelSyntax::Unicode => Syntax::Unicode,
+        }
+    }
+}
+
+/// Error raised when an expression contains an operator that has no counterpart in the target
+/// syntax, e.g. a derivative in numeric Python.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintError {
+    pub operator: Operator,
+    pub syntax: Syntax,
+}
+
+impl fmt::Display for PrintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The operator `{}` cannot be printed as {:?} code",
+            self.operator, self.syntax
+        )
+    }
+}
+
+impl std::error::Error for PrintError {}
+
+// Precedence of the printed expressions, from loosest to tightest binding.
+const RELATION: u8 = 1;
+const SUM: u8 = 2;
+const PRODUCT: u8 = 3;
+const POWER: u8 = 4;
+const ATOM: u8 = 5;
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield", "sp", "math", "np",
+];
+
+const JULIA_KEYWORDS: &[&str] = &[
+    "abstract",
+    "baremodule",
+    "begin",
+    "break",
+    "catch",
+    "const",
+    "continue",
+    "do",
+    "else",
+    "elseif",
+    "end",
+    "export",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "global",
+    "if",
+    "import",
+    "in",
+    "isa",
+    "let",
+    "local",
+    "macro",
+    "module",
+    "mutable",
+    "quote",
+    "return",
+    "struct",
+    "true",
+    "try",
+    "using",
+    "where",
+    "while",
+    "Dt",
+];
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try", "std", "f64",
+];
+
+fn subscript(c: char) -> Option<char> {
+    let subscripts = "₀₁₂₃₄₅₆₇₈₉ₐₑₕᵢⱼₖₗₘₙₒₚᵣₛₜᵤᵥₓ";
+    "0123456789aehijklmnoprstuvx"
+        .chars()
+        .position(|x| x == c)
+        .and_then(|i| subscripts.chars().nth(i))
+}
+
+fn superscript(c: char) -> Option<char> {
+    let superscripts = "⁰¹²³⁴⁵⁶⁷⁸⁹⁻";
+    "0123456789-"
+        .chars()
+        .position(|x| x == c)
+        .and_then(|i| superscripts.chars().nth(i))
+}
+
+/// The name of an identifier in the target syntax, e.g. β_{h} as β_h, or as βₕ in Unicode.
+pub fn identifier_name(name: &str, syntax: Syntax) -> String {
+    let plain: String = name
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '{' | '}'))
+        .collect();
+    match syntax {
+        Syntax::Unicode => match plain.split_once('_') {
+            Some((base, sub)) if !sub.is_empty() => {
+                match sub.chars().map(subscript).collect::<Option<String>>() {
+                    Some(sub) => format!("{base}{sub}"),
+                    None => plain,
+                }
+            }
+            _ => plain,
+        },
+        _ => {
+            let mut identifier: String = plain
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+                identifier.insert(0, '_');
+            }
+            let keywords = match syntax {
+                Syntax::Julia => JULIA_KEYWORDS,
+                Syntax::Rust => RUST_KEYWORDS,
+                _ => PYTHON_KEYWORDS,
+            };
+            if keywords.contains(&identifier.as_str()) {
+                identifier.push('_');
+            }
+            identifier
+        }
+    }
+}
+
+fn is_integer(met: &MathExpressionTree) -> bool {
+    matches!(met, Atom(MathExpression::Mn(x)) if x.trim().parse::<i64>().is_ok())
+}
+
+fn one() -> MathExpressionTree {
+    Atom(MathExpression::Mn("1".to_string()))
+}
+
+/// Reciprocal trigonometric functions in terms of the ones of Python's `math` module and Rust's
+/// `f64`, e.g. sec(x) as 1/cos(x) and arcsec(x) as arccos(1/x).
+fn reciprocal(op: &Operator, arg: &MathExpressionTree) -> Option<MathExpressionTree> {
+    let (function, of_reciprocal) = match op {
+        Operator::Sec => (Operator::Cos, false),
+        Operator::Csc => (Operator::Sin, false),
+        Operator::Cot => (Operator::Tan, false),
+        Operator::Arcsec => (Operator::Arccos, true),
+        Operator::Arccsc => (Operator::Arcsin, true),
+        Operator::Arccot => (Operator::Arctan, true),
+        _ => return None,
+    };
+    Some(if of_reciprocal {
+        Cons(
+            function,
+            vec![Cons(Operator::Divide, vec![one(), arg.clone()])],
+        )
+    } else {
+        Cons(
+            Operator::Divide,
+            vec![one(), Cons(function, vec![arg.clone()])],
+        )
+    })
+}
+
+struct Printer<'a> {
+    syntax: Syntax,
+    /// States of an ODE system, which SymPy needs written as functions of time, e.g. S(t)
+    functions: &'a [String],
+    time: &'a str,
+    /// The first operator without a counterpart in the numeric syntaxes, if any was printed
+    unsupported: RefCell<Option<Operator>>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(syntax: Syntax) -> Printer<'a> {
+        Printer {
+            syntax,
+            functions: &[],
+            time: "t",
+            unsupported: RefCell::new(None),
+        }
+    }
+
+    /// Print a call of an undefined function named after an operator that has no counterpart
+    /// in the target syntax, recording the operator if the syntax is a numeric one.
+    fn undefined(&self, op: &Operator, args: &[MathExpressionTree]) -> (String, u8) {
+        let name = identifier_name(&op.to_string(), Syntax::SymPy);
+        match self.syntax {
+            Syntax::SymPy => self.call(&format!("sp.Function(\"{name}\")"), args),
+            Syntax::Python | Syntax::Rust => {
+                self.unsupported
+                    .borrow_mut()
+                    .get_or_insert_with(|| op.clone());
+                self.call(&name, args)
+            }
+            _ => self.call(&name, args),
+        }
+    }
+
+    /// Print an expression, failing if it contains an operator that was printed as an undefined
+    /// function in a numeric syntax.
+    fn print_checked(&self, met: &MathExpressionTree) -> Result<String, PrintError> {
+        let printed = self.print(met).0;
+        match self.unsupported.borrow().clone() {
+            Some(operator) => Err(PrintError {
+                operator,
+                syntax: self.syntax,
+            }),
+            None => Ok(printed),
+        }
+    }
+
+    fn name(&self, name: &str) -> String {
+        identifier_name(name, self.syntax)
+    }
+
+    fn minus(&self) -> &'static str {
+        match self.syntax {
+            Syntax::Unicode => "−",
+            _ => "-",
+        }
+    }
+
+    /// Print an expression, parenthesized if it binds looser than the given precedence.
+    fn wrap(&self, met: &MathExpressionTree, precedence: u8) -> String {
+        let (printed, p) = self.print(met);
+        if p < precedence {
+            format!("({printed})")
+        } else {
+            printed
+        }
+    }
+
+    fn call(&self, function: &str, args: &[MathExpressionTree]) -> (String, u8) {
+        let args: Vec<String> = args.iter().map(|arg| self.print(arg).0).collect();
+        (format!("{}({})", function, args.join(", ")), ATOM)
+    }
+
+    /// Receiver of a Rust method call, with the type of numeric literals spelled out.
+    fn receiver(&self, met: &MathExpressionTree) -> String {
+        match met {
+            Atom(MathExpression::Mn(_)) => {
+                let literal = format!("{}_f64", self.print(met).0);
+                if literal.starts_with('-') {
+                    format!("({literal})")
+                } else {
+                    literal
+                }
+            }
+            _ => self.wrap(met, ATOM),
+        }
+    }
+
+    /// Rust method call on the first argument, e.g. x.powf(y).
+    fn method(&self, method: &str, args: &[MathExpressionTree]) -> (String, u8) {
+        let rest: Vec<String> = args[1..].iter().map(|arg| self.print(arg).0).collect();
+        (
+            format!(
+                "{}.{}({})",
+                self.receiver(&args[0]),
+                method,
+                rest.join(", ")
+            ),
+            ATOM,
+        )
+    }
+
+    fn atom(&self, atom: &MathExpression) -> String {
+        let name = match atom {
+            MathExpression::Mn(x)
+                if self.syntax == Syntax::Rust && is_integer(&Atom(atom.clone())) =>
+            {
+                return format!("{}.0", x.trim())
+            }
+            MathExpression::Mn(x) => return x.trim().to_string(),
+            MathExpression::Ci(ci) => ci.content.to_string(),
+            MathExpression::Mi(Mi(x)) => x.trim().to_string(),
+            _ => atom.to_string(),
+        };
+        match (name.as_str(), self.syntax) {
+            ("π" | "pi", Syntax::SymPy) => "sp.pi".to_string(),
+            ("π" | "pi", Syntax::Python) => "math.pi".to_string(),
+            ("π" | "pi", Syntax::Rust) => "std::f64::consts::PI".to_string(),
+            ("π" | "pi", _) => "π".to_string(),
+            _ if self.syntax == Syntax::SymPy && self.functions.contains(&name) => {
+                format!("{}({})", self.name(&name), self.name(self.time))
+            }
+            _ => self.name(&name),
+        }
+    }
+
+    fn negation(&self, arg: &MathExpressionTree) -> (String, u8) {
+        let operand = match arg {
+            Cons(Operator::Subtract, args) if args.len() == 1 => format!("({})", self.print(arg).0),
+            _ => self.wrap(arg, PRODUCT),
+        };
+        (format!("{}{}", self.minus(), operand), PRODUCT)
+    }
+
+    fn sum(&self, args: &[MathExpressionTree]) -> (String, u8) {
+        let mut printed = String::new();
+        for (i, arg) in args.iter().enumerate() {
+            match arg {
+                Cons(Operator::Subtract, negated) if negated.len() == 1 && i > 0 => printed
+                    .push_str(&format!(
+                        " {} {}",
+                        self.minus(),
+                        self.wrap(&negated[0], PRODUCT)
+                    )),
+                _ if i > 0 => printed.push_str(&format!(" + {}", self.wrap(arg, SUM))),
+                _ => printed.push_str(&self.wrap(arg, SUM)),
+            }
+        }
+        (printed, SUM)
+    }
+
+    fn binary(
+        &self,
+        op: &str,
+        args: &[MathExpressionTree],
+        precedence: u8,
+        right_associative: bool,
+    ) -> (String, u8) {
+        let (lhs, rhs) = if right_associative {
+            (precedence + 1, precedence)
+        } else {
+            (precedence, precedence + 1)
+        };
+        let printed = format!(
+            "{}{}{}",
+            self.wrap(&args[0], lhs),
+            op,
+            self.wrap(&args[1], rhs)
+        );
+        (printed, precedence)
+    }
+
+    fn product(&self, op: &str, args: &[MathExpressionTree]) -> (String, u8) {
+        let factors: Vec<String> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| self.wrap(arg, if i == 0 { PRODUCT } else { PRODUCT + 1 }))
+            .collect();
+        (factors.join(op), PRODUCT)
+    }
+
+    fn power(&self, args: &[MathExpressionTree]) -> (String, u8) {
+        match self.syntax {
+            Syntax::Unicode if is_integer(&args[1]) => {
+                let exponent: String = args[1]
+                    .to_string()
+                    .trim()
+                    .chars()
+                    .filter_map(superscript)
+                    .collect();
+                (format!("{}{}", self.wrap(&args[0], ATOM), exponent), POWER)
+            }
+            Syntax::SymPy | Syntax::Python => self.binary("**", args, POWER, true),
+            Syntax::Rust if is_integer(&args[1]) => (
+                format!(
+                    "{}.powi({})",
+                    self.receiver(&args[0]),
+                    args[1].to_string().trim()
+                ),
+                ATOM,
+            ),
+            Syntax::Rust => self.method("powf", args),
+            _ => self.binary("^", args, POWER, true),
+        }
+    }
+
+    fn relation(&self, op: &Operator, args: &[MathExpressionTree]) -> (String, u8) {
+        let symbol = match (op, self.syntax) {
+            (Operator::Equals, Syntax::SymPy) => return self.call("sp.Eq", args),
+            (Operator::NotEqual, Syntax::SymPy) => return self.call("sp.Ne", args),
+            (Operator::Equals, Syntax::Unicode) => " = ",
+            (Operator::Equals, _) => " == ",
+            (Operator::NotEqual, Syntax::Unicode) => " ≠ ",
+            (Operator::NotEqual, _) => " != ",
+            (Operator::Less, _) => " < ",
+            (Operator::Greater, _) => " > ",
+            (Operator::LessEqual, Syntax::Unicode) => " ≤ ",
+            (Operator::LessEqual, _) => " <= ",
+            (Operator::GreaterEqual, Syntax::Unicode) => " ≥ ",
+            (Operator::GreaterEqual, _) => " >= ",
+            _ => unreachable!(),
+        };
+        self.binary(symbol, args, RELATION, false)
+    }
+
+    fn piecewise(&self, pieces: &[MathExpressionTree]) -> (String, u8) {
+        let mut branches = vec![];
+        let mut otherwise = None;
+        for piece in pieces {
+            match piece {
+                Cons(Operator::Piece, args) if args.len() == 2 => {
+                    branches.push((&args[0], &args[1]))
+                }
+                Cons(Operator::Otherwise, args) if args.len() == 1 => otherwise = Some(&args[0]),
+                _ => {}
+            }
+        }
+        match self.syntax {
+            Syntax::SymPy => {
+                let mut printed: Vec<String> = branches
+                    .iter()
+                    .map(|(value, condition)| {
+                        format!("({}, {})", self.print(value).0, self.print(condition).0)
+                    })
+                    .collect();
+                if let Some(value) = otherwise {
+                    printed.push(format!("({}, True)", self.print(value).0));
+                }
+                (format!("sp.Piecewise({})", printed.join(", ")), ATOM)
+            }
+            Syntax::Julia => {
+                let fallback = otherwise.map_or("NaN".to_string(), |value| self.print(value).0);
+                let printed = branches
+                    .iter()
+                    .rev()
+                    .fold(fallback, |rest, (value, condition)| {
+                        format!(
+                            "ifelse({}, {}, {})",
+                            self.print(condition).0,
+                            self.print(value).0,
+                            rest
+                        )
+                    });
+                (printed, ATOM)
+            }
+            Syntax::Unicode => {
+                let mut printed: Vec<String> = branches
+                    .iter()
+                    .map(|(value, condition)| {
+                        format!("{} if {}", self.print(value).0, self.print(condition).0)
+                    })
+                    .collect();
+                if let Some(value) = otherwise {
+                    printed.push(format!("{} otherwise", self.print(value).0));
+                }
+                (format!("{{{}}}", printed.join("; ")), ATOM)
+            }
+            Syntax::Python | Syntax::Rust => {
+                let fallback = match (otherwise, self.syntax) {
+                    (Some(value), _) => self.print(value).0,
+                    (None, Syntax::Python) => "math.nan".to_string(),
+                    (None, _) => "f64::NAN".to_string(),
+                };
+                let printed = branches
+                    .iter()
+                    .rev()
+                    .fold(fallback, |rest, (value, condition)| {
+                        let (value, condition) = (self.print(value).0, self.print(condition).0);
+                        match self.syntax {
+                            Syntax::Python => format!("({value} if {condition} else {rest})"),
+                            _ => format!("(if {condition} {{ {value} }} else {{ {rest} }})"),
+                        }
+                    });
+                (printed, ATOM)
+            }
+        }
+    }
+
+    fn derivative(&self, derivative: &Derivative, arg: &MathExpressionTree) -> (String, u8) {
+        let var = self.name(&derivative.bound_var.to_string());
+        let order = derivative.order;
+        match self.syntax {
+            Syntax::SymPy if order == 1 => (
+                format!("sp.Derivative({}, {})", self.print(arg).0, var),
+                ATOM,
+            ),
+            Syntax::SymPy => (
+                format!("sp.Derivative({}, ({}, {}))", self.print(arg).0, var, order),
+                ATOM,
+            ),
+            Syntax::Julia if order == 1 => (
+                format!("Differential({})({})", var, self.print(arg).0),
+                ATOM,
+            ),
+            Syntax::Julia => (
+                format!("(Differential({})^{})({})", var, order, self.print(arg).0),
+                ATOM,
+            ),
+            Syntax::Python | Syntax::Rust => self.undefined(
+                &Operator::Derivative(derivative.clone()),
+                std::slice::from_ref(arg),
+            ),
+            Syntax::Unicode => {
+                let power: String = if order == 1 {
+                    String::new()
+                } else {
+                    order.to_string().chars().filter_map(superscript).collect()
+                };
+                match arg {
+                    Atom(_) => (
+                        format!("d{}{}/d{}{}", power, self.print(arg).0, var, power),
+                        ATOM,
+                    ),
+                    _ => (
+                        format!("d{}/d{}{}({})", power, var, power, self.print(arg).0),
+                        ATOM,
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Name of a function in the target syntax, or of the `f64` method in Rust.
+    fn function(&self, op: &Operator) -> Option<&'static str> {
+        let (sympy, julia, unicode, python, rust) = match op {
+            Operator::Exp => ("sp.exp", "exp", "exp", "math.exp", "exp"),
+            Operator::Logarithm(Logarithm {
+                notation: LogarithmNotation::Ln,
+            }) => ("sp.log", "log", "ln", "math.log", "ln"),
+            Operator::Sin => ("sp.sin", "sin", "sin", "math.sin", "sin"),
+            Operator::Cos => ("sp.cos", "cos", "cos", "math.cos", "cos"),
+            Operator::Tan => ("sp.tan", "tan", "tan", "math.tan", "tan"),
+            Operator::Sec => ("sp.sec", "sec", "sec", "", ""),
+            Operator::Csc => ("sp.csc", "csc", "csc", "", ""),
+            Operator::Cot => ("sp.cot", "cot", "cot", "", ""),
+            Operator::Arcsin => ("sp.asin", "asin", "arcsin", "math.asin", "asin"),
+            Operator::Arccos => ("sp.acos", "acos", "arccos", "math.acos", "acos"),
+            Operator::Arctan => ("sp.atan", "atan", "arctan", "math.atan", "atan"),
+            Operator::Arcsec => ("sp.asec", "asec", "arcsec", "", ""),
+            Operator::Arccsc => ("sp.acsc", "acsc", "arccsc", "", ""),
+            Operator::Arccot => ("sp.acot", "acot", "arccot", "", ""),
+            Operator::Sqrt => ("sp.sqrt", "sqrt", "√", "math.sqrt", "sqrt"),
+            Operator::Abs => ("sp.Abs", "abs", "abs", "abs", "abs"),
+            Operator::Factorial => (
+                "sp.factorial",
+                "factorial",
+                "factorial",
+                "math.factorial",
+                "",
+            ),
+            Operator::Min => ("sp.Min", "min", "min", "min", "min"),
+            _ => return None,
+        };
+        let function = match self.syntax {
+            Syntax::SymPy => sympy,
+            Syntax::Julia => julia,
+            Syntax::Unicode => unicode,
+            Syntax::Python => python,
+            Syntax::Rust => rust,
+        };
+        Some(function).filter(|function| !function.is_empty())
+    }
+
+    fn matrix(&self, args: &[MathExpressionTree]) -> (String, u8) {
+        let rows: Vec<Vec<String>> = args
+            .iter()
+            .map(|row| match row {
+                Cons(Operator::MatrixRow, entries) => {
+                    entries.iter().map(|entry| self.print(entry).0).collect()
+                }
+                _ => vec![self.print(row).0],
+            })
+            .collect();
+        let printed = match self.syntax {
+            Syntax::SymPy => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| format!("[{}]", row.join(", ")))
+                    .collect();
+                format!("sp.Matrix([{}])", rows.join(", "))
+            }
+            Syntax::Julia => {
+                let rows: Vec<String> = rows.iter().map(|row| row.join(" ")).collect();
+                format!("[{}]", rows.join("; "))
+            }
+            Syntax::Python => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| format!("[{}]", row.join(", ")))
+                    .collect();
+                format!("np.array([{}])", rows.join(", "))
+            }
+            Syntax::Rust => return self.undefined(&Operator::Matrix, args),
+            Syntax::Unicode => {
+                let rows: Vec<String> = rows.iter().map(|row| row.join(", ")).collect();
+                format!("[{}]", rows.join("; "))
+            }
+        };
+        (printed, ATOM)
+    }
+
+    fn print(&self, met: &MathExpressionTree) -> (String, u8) {
+        let (op, args) = match met {
+            Atom(atom) => return (self.atom(atom), ATOM),
+            Cons(op, args) => (op, args.as_slice()),
+        };
+        let syntax = self.syntax;
+        match op {
+            Operator::Add => self.sum(args),
+            Operator::Subtract if args.len() == 1 => self.negation(&args[0]),
+            Operator::Subtract if args.len() == 2 => {
+                self.binary(&format!(" {} ", self.minus()), args, SUM, false)
+            }
+            Operator::Multiply => {
+                self.product(if syntax == Syntax::Unicode { "·" } else { "*" }, args)
+            }
+            Operator::Sec
+            | Operator::Csc
+            | Operator::Cot
+            | Operator::Arcsec
+            | Operator::Arccsc
+            | Operator::Arccot
+                if matches!(syntax, Syntax::Python | Syntax::Rust) && args.len() == 1 =>
+            {
+                self.print(&reciprocal(op, &args[0]).unwrap())
+            }
+            Operator::Divide if syntax == Syntax::SymPy && args.iter().all(is_integer) => {
+                self.call("sp.Rational", args)
+            }
+            Operator::Divide if args.len() == 2 => self.binary("/", args, PRODUCT, false),
+            Operator::Power if args.len() == 2 => self.power(args),
+            Operator::Equals
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessEqual
+            | Operator::Greater
+            | Operator::GreaterEqual
+                if args.len() == 2 =>
+            {
+                self.relation(op, args)
+            }
+            Operator::Piecewise => self.piecewise(args),
+            Operator::Derivative(derivative) if args.len() == 1 => {
+                self.derivative(derivative, &args[0])
+            }
+            Operator::Logarithm(Logarithm {
+                notation: LogarithmNotation::Log,
+            }) => match syntax {
+                Syntax::SymPy => (format!("sp.log({}, 10)", self.print(&args[0]).0), ATOM),
+                Syntax::Julia => self.call("log10", args),
+                Syntax::Unicode => self.call("log", args),
+                Syntax::Python => self.call("math.log10", args),
+                Syntax::Rust => self.method("log10", args),
+            },
+            Operator::Logarithm(Logarithm {
+                notation: LogarithmNotation::LogBase(base),
+            }) => {
+                let base = self.atom(base);
+                match syntax {
+                    Syntax::SymPy => (
+                        format!("sp.log({}, {})", self.print(&args[0]).0, base),
+                        ATOM,
+                    ),
+                    Syntax::Julia => (format!("log({}, {})", base, self.print(&args[0]).0), ATOM),
+                    Syntax::Unicode => self.call(&format!("log_{base}"), args),
+                    Syntax::Python => (
+                        format!("math.log({}, {})", self.print(&args[0]).0, base),
+                        ATOM,
+                    ),
+                    Syntax::Rust => (format!("{}.log({})", self.receiver(&args[0]), base), ATOM),
+                }
+            }
+            Operator::Sqrt if syntax == Syntax::Unicode => {
+                (format!("√{}", self.wrap(&args[0], ATOM)), ATOM)
+            }
+            Operator::Abs if syntax == Syntax::Unicode => {
+                (format!("|{}|", self.print(&args[0]).0), ATOM)
+            }
+            Operator::Factorial if syntax == Syntax::Unicode => {
+                (format!("{}!", self.wrap(&args[0], ATOM)), ATOM)
+            }
+            Operator::Min if syntax == Syntax::Rust && !args.is_empty() => {
+                let (first, rest) = args.split_first().unwrap();
+                let printed = rest.iter().fold(self.receiver(first), |min, arg| {
+                    format!("{}.min({})", min, self.print(arg).0)
+                });
+                (printed, ATOM)
+            }
+            Operator::Matrix => self.matrix(args),
+            Operator::Transpose => match syntax {
+                Syntax::SymPy => (format!("{}.T", self.wrap(&args[0], ATOM)), ATOM),
+                Syntax::Julia => self.call("transpose", args),
+                Syntax::Unicode => (format!("{}ᵀ", self.wrap(&args[0], ATOM)), ATOM),
+                Syntax::Python => (format!("{}.T", self.wrap(&args[0], ATOM)), ATOM),
+                Syntax::Rust => self.undefined(op, args),
+            },
+            Operator::Inverse => match syntax {
+                Syntax::SymPy => (format!("{}.inv()", self.wrap(&args[0], ATOM)), ATOM),
+                Syntax::Julia => self.call("inv", args),
+                Syntax::Unicode => (format!("{}⁻¹", self.wrap(&args[0], ATOM)), ATOM),
+                Syntax::Python => self.call("np.linalg.inv", args),
+                Syntax::Rust => self.undefined(op, args),
+            },
+            Operator::MatMul => match syntax {
+                Syntax::SymPy | Syntax::Python => self.product(" @ ", args),
+                Syntax::Rust => self.undefined(op, args),
+                Syntax::Julia => self.product("*", args),
+                Syntax::Unicode => self.product("·", args),
+            },
+            Operator::Hadamard => match syntax {
+                Syntax::SymPy => self.call("sp.matrix_multiply_elementwise", args),
+                Syntax::Julia => self.product(" .* ", args),
+                Syntax::Unicode => self.product(" ⊙ ", args),
+                Syntax::Python => self.product("*", args),
+                Syntax::Rust => self.undefined(op, args),
+            },
+            Operator::Index if syntax == Syntax::Rust => self.undefined(op, args),
+            Operator::Index if !args.is_empty() => {
+                let indices: Vec<String> = args[1..].iter().map(|arg| self.print(arg).0).collect();
+                (
+                    format!("{}[{}]", self.wrap(&args[0], ATOM), indices.join(", ")),
+                    ATOM,
+                )
+            }
+            _ => match self.function(op) {
+                Some(function) if syntax == Syntax::Rust && args.len() == 1 => {
+                    self.method(function, args)
+                }
+                Some(function) => self.call(function, args),
+                None => self.undefined(op, args),
+            },
+        }
+    }
+}
+
+impl MathExpressionTree {
+    /// Print the expression in the given syntax, failing for operators that numeric Python or
+    /// Rust code has no counterpart of.
+    pub fn print(&self, syntax: Syntax) -> Result<String, PrintError> {
+        Printer::new(syntax).print_checked(self)
+    }
+
+    /// SymPy (Python) source for the expression, assuming `import sympy as sp` and symbols
+    /// named after its identifiers.
+    pub fn to_sympy(&self) -> String {
+        Printer::new(Syntax::SymPy).print(self).0
+    }
+
+    /// Julia expression for the expression, e.g. for ModelingToolkit.
+    pub fn to_julia(&self) -> String {
+        Printer::new(Syntax::Julia).print(self).0
+    }
+
+    /// Readable Unicode math for the expression, e.g. −β·S·I.
+    pub fn to_unicode(&self) -> String {
+        Printer::new(Syntax::Unicode).print(self).0
+    }
+}
+
+/// The parameters of an ODE system: the identifiers of the right hand sides that are neither
+/// states nor the independent variable, in order of first appearance.
+pub(crate) fn system_parameters<'a>(
+    states: &[String],
+    time: &str,
+    rhs: impl IntoIterator<Item = &'a MathExpressionTree>,
+) -> Vec<String> {
+    let mut names = vec![];
+    for met in rhs {
+        identifiers(met, &mut names);
+    }
+    names
+        .into_iter()
+        .filter(|name| {
+            !states.contains(name) && name != time && !matches!(name.as_str(), "π" | "pi")
+        })
+        .collect()
+}
+
+/// The states, parameters and independent variable of an ODE system.
+fn system_variables(odes: &[FirstOrderODE]) -> (Vec<String>, Vec<String>, String) {
+    let states: Vec<String> = odes.iter().map(|ode| ode.lhs_var.to_string()).collect();
+    let time = odes
+        .first()
+        .map_or("t".to_string(), |ode| ode.with_respect_to.to_string());
+    let parameters = system_parameters(&states, &time, odes.iter().map(|ode| &ode.rhs));
+    (states, parameters, time)
+}
+
+/// Print a system of ODEs as a complete model definition in the given syntax: a SymPy script
+/// defining `equations`, a ModelingToolkit `ODESystem` named `model`, or a readable summary of
+/// its states, parameters and equations.
+pub fn print_system(odes: &[FirstOrderODE], syntax: ModelSyntax) -> String {
+    let (states, parameters, time) = system_variables(odes);
+    let printer = Printer {
+        functions: &states,
+        time: &time,
+        ..Printer::new(syntax.into())
+    };
+    let t = printer.name(&time);
+    let names = |names: &[String], separator: &str| -> String {
+        names
+            .iter()
+            .map(|name| printer.name(name))
+            .collect::<Vec<String>>()
+            .join(separator)
+    };
+    let mut lines = vec![];
+    match syntax {
+        ModelSyntax::SymPy => {
+            lines.push("import sympy as sp".to_string());
+            lines.push(String::new());
+            lines.push(format!("{t} = sp.Symbol(\"{t}\")"));
+            if !states.is_empty() {
+                lines.push(format!(
+                    "{} = sp.symbols(\"{}\", cls=sp.Function)",
+                    names(&states, ", "),
+                    names(&states, " ")
+                ));
+            }
+            if !parameters.is_empty() {
+                lines.push(format!(
+                    "{} = sp.symbols(\"{}\")",
+                    names(&parameters, ", "),
+                    names(&parameters, " ")
+                ));
+            }
+            lines.push(String::new());
+            lines.push("equations = [".to_string());
+            for ode in odes {
+                lines.push(format!(
+                    "    sp.Eq(sp.Derivative({}({t}), {t}), {}),",
+                    printer.name(&ode.lhs_var.to_string()),
+                    printer.print(&ode.rhs).0
+                ));
+            }
+            lines.push("]".to_string());
+        }
+        ModelSyntax::Julia => {
+            lines.push("using ModelingToolkit".to_string());
+            lines.push(String::new());
+            lines.push(format!("@independent_variables {t}"));
+            if !parameters.is_empty() {
+                lines.push(format!("@parameters {}", names(&parameters, " ")));
+            }
+            if !states.is_empty() {
+                let states: Vec<String> = states
+                    .iter()
+                    .map(|state| format!("{}({t})", printer.name(state)))
+                    .collect();
+                lines.push(format!("@variables {}", states.join(" ")));
+            }
+            lines.push(format!("Dt = Differential({t})"));
+            lines.push(String::new());
+            lines.push("equations = [".to_string());
+            for ode in odes {
+                lines.push(format!(
+                    "    Dt({}) ~ {},",
+                    printer.name(&ode.lhs_var.to_string()),
+                    printer.print(&ode.rhs).0
+                ));
+            }
+            lines.push("]".to_string());
+            lines.push(String::new());
+            lines.push(format!("@named model = ODESystem(equations, {t})"));
+        }
+        ModelSyntax::Unicode => {
+            lines.push(format!("States: {}", names(&states, ", ")));
+            lines.push(format!("Parameters: {}", names(&parameters, ", ")));
+            lines.push("Equations:".to_string());
+            for ode in odes {
+                lines.push(format!(
+                    "  d{}/d{t} = {}",
+                    printer.name(&ode.lhs_var.to_string()),
+                    printer.print(&ode.rhs).0
+                ));
+            }
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+fn met(input: &str) -> MathExpressionTree {
+    format!("<math>{input}</math>")
+        .parse::<MathExpressionTree>()
+        .unwrap()
+}
+
+#[cfg(test)]
+fn sir() -> Vec<FirstOrderODE> {
+    [
+        "<mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi>",
+        "<mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi>",
+        "<mi>γ</mi><mi>I</mi>",
+    ]
+    .iter()
+    .zip(["S", "I", "R"])
+    .map(|(rhs, state)| {
+        format!("<math><mfrac><mrow><mi>d</mi><mi>{state}</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo>{rhs}</math>")
+            .parse::<FirstOrderODE>()
+            .unwrap()
+    })
+    .collect()
+}
+
+#[test]
+fn test_precedence() {
+    let expression = met("<mi>a</mi><mo>-</mo><mo>(</mo><mi>b</mi><mo>-</mo><mi>c</mi><mo>)</mo><mo>*</mo><msup><mrow><mo>(</mo><mo>-</mo><mi>x</mi><mo>)</mo></mrow><mn>2</mn></msup>");
+    assert_eq!(expression.to_sympy(), "a - (b - c)*(-x)**2");
+    assert_eq!(expression.to_julia(), "a - (b - c)*(-x)^2");
+    assert_eq!(expression.to_unicode(), "a − (b − c)·(−x)²");
+
+    let fraction = met("<mfrac><mn>1</mn><mn>2</mn></mfrac><mi>k</mi><mo>/</mo><mo>(</mo><mi>a</mi><mi>b</mi><mo>)</mo>");
+    assert_eq!(fraction.to_sympy(), "sp.Rational(1, 2)*(k/(a*b))");
+    assert_eq!(fraction.to_julia(), "1/2*(k/(a*b))");
+}
+
+#[test]
+fn test_functions_and_identifiers() {
+    let expression = met("<msub><mi>β</mi><mn>0</mn></msub><mrow><mi>exp</mi><mo>(</mo><mo>-</mo><mi>λ</mi><mi>t</mi><mo>)</mo></mrow><mo>+</mo><msqrt><mi>x</mi></msqrt>");
+    assert_eq!(expression.to_sympy(), "β_0*sp.exp(-λ*t) + sp.sqrt(x)");
+    assert_eq!(expression.to_julia(), "β_0*exp(-λ*t) + sqrt(x)");
+    assert_eq!(expression.to_unicode(), "β₀·exp(−λ·t) + √x");
+    assert_eq!(identifier_name("lambda", Syntax::SymPy), "lambda_");
+    assert_eq!(identifier_name("end", Syntax::Julia), "end_");
+    assert_eq!(identifier_name("S_{h}", Syntax::Unicode), "Sₕ");
+}
+
+#[test]
+fn test_piecewise() {
+    let ode = "
+    <math>
+        <mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>
+        <mo>=</mo>
+        <mrow>
+            <mo>{</mo>
+            <mtable>
+                <mtr><mtd><msub><mi>β</mi><mn>1</mn></msub></mtd><mtd><mi>t</mi><mo>&lt;</mo><msub><mi>t</mi><mn>0</mn></msub></mtd></mtr>
+                <mtr><mtd><msub><mi>β</mi><mn>2</mn></msub></mtd><mtd><mtext>otherwise</mtext></mtd></mtr>
+            </mtable>
+        </mrow>
+    </math>
+    "
+    .parse::<FirstOrderODE>()
+    .unwrap();
+    assert_eq!(
+        ode.rhs.to_sympy(),
+        "sp.Piecewise((β_1, t < t_0), (β_2, True))"
+    );
+    assert_eq!(ode.rhs.to_julia(), "ifelse(t < t_0, β_1, β_2)");
+    assert_eq!(ode.rhs.to_unicode(), "{β₁ if t < t₀; β₂ otherwise}");
+}
+
+#[test]
+fn test_systems() {
+    let odes = sir();
+    assert_eq!(
+        print_system(&odes, ModelSyntax::SymPy),
+        "import sympy as sp
+
+t = sp.Symbol(\"t\")
+S, I, R = sp.symbols(\"S I R\", cls=sp.Function)
+β, γ = sp.symbols(\"β γ\")
+
+equations = [
+    sp.Eq(sp.Derivative(S(t), t), -β*S(t)*I(t)),
+    sp.Eq(sp.Derivative(I(t), t), β*S(t)*I(t) - γ*I(t)),
+    sp.Eq(sp.Derivative(R(t), t), γ*I(t)),
+]
+"
+    );
+    assert_eq!(
+        print_system(&odes, ModelSyntax::Julia),
+        "using ModelingToolkit
+
+@independent_variables t
+@parameters β γ
+@variables S(t) I(t) R(t)
+Dt = Differential(t)
+
+equations = [
+    Dt(S) ~ -β*S*I,
+    Dt(I) ~ β*S*I - γ*I,
+    Dt(R) ~ γ*I,
+]
+
+@named model = ODESystem(equations, t)
+"
+    );
+    assert_eq!(
+        print_system(&odes, ModelSyntax::Unicode),
+        "States: S, I, R
+Parameters: β, γ
+Equations:
+  dS/dt = −β·S·I
+  dI/dt = β·S·I − γ·I
+  dR/dt = γ·I
+"
+    );
+}
+
+#[test]
+fn test_unsupported_operators() {
+    let expression = met("<mi>B</mi><mo>⊙</mo><msup><mi>C</mi><mo>⊤</mo></msup>");
+    assert_eq!(expression.print(Syntax::Python).unwrap(), "B*C.T");
+    let err = expression.print(Syntax::Rust).unwrap_err();
+    assert_eq!(err.operator, Operator::Hadamard);
+    assert_eq!(
+        err.to_string(),
+        "The operator `⊙` cannot be printed as Rust code"
+    );
+
+    let expression = met("<mi>k</mi><mfrac><mrow><mi>d</mi><mi>x</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac>");
+    assert!(matches!(
+        expression.print(Syntax::Python),
+        Err(PrintError {
+            operator: Operator::Derivative(_),
+            syntax: Syntax::Python,
+        })
+    ));
+    assert_eq!(expression.to_sympy(), "k*sp.Derivative(x, t)");
+}
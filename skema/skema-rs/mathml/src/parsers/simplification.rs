@@ -0,0 +1,589 @@
+//! Symbolic simplification of math expression trees, so that equivalent equations extracted
+//! from different sources (e.g. code and papers) normalize to the same form.
+
+use crate::{
+    ast::{
+        operator::{Logarithm, LogarithmNotation, Operator},
+        MathExpression,
+    },
+    parsers::math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+};
+use std::convert::TryFrom;
+
+/// Numeric constant, kept as an exact fraction for as long as possible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Rational(i64, i64),
+    Real(f64),
+}
+
+use Number::{Rational, Real};
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Number {
+    /// Construct a fraction in lowest terms with a positive denominator.
+    fn rational(numerator: i64, denominator: i64) -> Number {
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        Rational(numerator / divisor, denominator / divisor)
+    }
+
+    fn parse(s: &str) -> Option<Number> {
+        let s = s.trim();
+        match s.parse::<i64>() {
+            Ok(integer) => Some(Rational(integer, 1)),
+            Err(_) => s.parse::<f64>().ok().filter(|x| x.is_finite()).map(Real),
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Rational(numerator, denominator) => numerator as f64 / denominator as f64,
+            Real(x) => x,
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        self.to_f64() == 0.0
+    }
+
+    pub fn is_one(self) -> bool {
+        self.to_f64() == 1.0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.to_f64() < 0.0
+    }
+
+    pub(crate) fn as_integer(self) -> Option<i64> {
+        match self {
+            Rational(integer, 1) => Some(integer),
+            _ => None,
+        }
+    }
+
+    fn add(self, other: Number) -> Number {
+        if let (Rational(a, b), Rational(c, d)) = (self, other) {
+            if let (Some(x), Some(y), Some(denominator)) =
+                (a.checked_mul(d), c.checked_mul(b), b.checked_mul(d))
+            {
+                if let Some(numerator) = x.checked_add(y) {
+                    return Number::rational(numerator, denominator);
+                }
+            }
+        }
+        Real(self.to_f64() + other.to_f64())
+    }
+
+    fn mul(self, other: Number) -> Number {
+        if let (Rational(a, b), Rational(c, d)) = (self, other) {
+            if let (Some(numerator), Some(denominator)) = (a.checked_mul(c), b.checked_mul(d)) {
+                return Number::rational(numerator, denominator);
+            }
+        }
+        Real(self.to_f64() * other.to_f64())
+    }
+
+    fn neg(self) -> Number {
+        match self {
+            Rational(numerator, denominator) => Rational(-numerator, denominator),
+            Real(x) => Real(-x),
+        }
+    }
+
+    fn abs(self) -> Number {
+        if self.is_negative() {
+            self.neg()
+        } else {
+            self
+        }
+    }
+
+    /// The reciprocal of the number, or None for zero.
+    fn recip(self) -> Option<Number> {
+        match self {
+            _ if self.is_zero() => None,
+            Rational(numerator, denominator) => Some(Number::rational(denominator, numerator)),
+            Real(x) => Some(Real(1.0 / x)),
+        }
+    }
+
+    /// Raise the number to an integer power, or None if the result is undefined.
+    pub(crate) fn powi(self, exponent: i64) -> Option<Number> {
+        let base = if exponent < 0 { self.recip()? } else { self };
+        let exponent = u32::try_from(exponent.unsigned_abs()).ok()?;
+        if let Rational(numerator, denominator) = base {
+            if let (Some(numerator), Some(denominator)) = (
+                numerator.checked_pow(exponent),
+                denominator.checked_pow(exponent),
+            ) {
+                return Some(Rational(numerator, denominator));
+            }
+        }
+        Some(Real(base.to_f64().powf(exponent as f64)))
+    }
+
+    /// The square root of the number, if it can be computed without losing exactness.
+    fn sqrt(self) -> Option<Number> {
+        let exact_root = |x: i64| {
+            let root = (x as f64).sqrt().round() as i64;
+            (root.checked_mul(root) == Some(x)).then_some(root)
+        };
+        match self {
+            Rational(numerator, denominator) if numerator >= 0 => {
+                Some(Rational(exact_root(numerator)?, exact_root(denominator)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Read a numeric constant from an expression such as 2, -3 or 1/2.
+pub(crate) fn number(met: &MathExpressionTree) -> Option<Number> {
+    match met {
+        Atom(MathExpression::Mn(x)) => Number::parse(x),
+        Cons(Operator::Subtract, args) if args.len() == 1 => number(&args[0]).map(Number::neg),
+        Cons(Operator::Divide, args) if args.len() == 2 => {
+            Some(number(&args[0])?.mul(number(&args[1])?.recip()?))
+        }
+        _ => None,
+    }
+}
+
+/// Expression for a numeric constant, with negative numbers written with a unary minus.
+pub(crate) fn number_expression(n: Number) -> MathExpressionTree {
+    if n.is_negative() {
+        return Cons(Operator::Subtract, vec![number_expression(n.neg())]);
+    }
+    match n {
+        Rational(numerator, 1) => Atom(MathExpression::Mn(numerator.to_string())),
+        Rational(numerator, denominator) => Cons(
+            Operator::Divide,
+            vec![
+                Atom(MathExpression::Mn(numerator.to_string())),
+                Atom(MathExpression::Mn(denominator.to_string())),
+            ],
+        ),
+        Real(x) => Atom(MathExpression::Mn(x.to_string())),
+    }
+}
+
+/// A product of a numeric coefficient and powers of non-numeric factors.
+#[derive(Debug, Clone)]
+pub(crate) struct Term {
+    pub(crate) coefficient: Number,
+    /// Pairs of (base, exponent), with at most one pair per base.
+    pub(crate) factors: Vec<(MathExpressionTree, MathExpressionTree)>,
+}
+
+impl Term {
+    pub(crate) fn constant(coefficient: Number) -> Term {
+        Term {
+            coefficient,
+            factors: vec![],
+        }
+    }
+
+    pub(crate) fn factor(base: MathExpressionTree, exponent: MathExpressionTree) -> Term {
+        Term {
+            coefficient: Rational(1, 1),
+            factors: vec![(base, exponent)],
+        }
+    }
+
+    /// Identifies terms that differ only by their coefficient.
+    fn key(&self) -> String {
+        let mut factors: Vec<String> = self
+            .factors
+            .iter()
+            .map(|(base, exponent)| format!("{base}^{exponent}"))
+            .collect();
+        factors.sort();
+        factors.join(" ")
+    }
+
+    pub(crate) fn neg(mut self) -> Term {
+        self.coefficient = self.coefficient.neg();
+        self
+    }
+
+    /// Multiply two terms, adding the exponents of factors with a common base.
+    pub(crate) fn mul(mut self, other: Term) -> Term {
+        self.coefficient = self.coefficient.mul(other.coefficient);
+        for (base, exponent) in other.factors {
+            let key = base.to_string();
+            match self.factors.iter_mut().find(|(b, _)| b.to_string() == key) {
+                Some((_, e)) => {
+                    *e = Cons(Operator::Add, vec![e.clone(), exponent]).simplify();
+                }
+                None => self.factors.push((base, exponent)),
+            }
+        }
+        self.normalize()
+    }
+
+    /// Raise the term to an integer power, or None if the result is undefined.
+    pub(crate) fn powi(self, exponent: i64) -> Option<Term> {
+        let coefficient = self.coefficient.powi(exponent)?;
+        let n = number_expression(Rational(exponent, 1));
+        let factors = self
+            .factors
+            .into_iter()
+            .map(|(base, e)| {
+                (
+                    base,
+                    Cons(Operator::Multiply, vec![e, n.clone()]).simplify(),
+                )
+            })
+            .collect();
+        Some(
+            Term {
+                coefficient,
+                factors,
+            }
+            .normalize(),
+        )
+    }
+
+    /// Fold numeric powers into the coefficient and drop factors raised to the power zero. The
+    /// remaining factors keep the order in which they first appeared.
+    fn normalize(mut self) -> Term {
+        let mut factors = vec![];
+        for (base, exponent) in self.factors {
+            let e = number(&exponent);
+            if e.is_some_and(Number::is_zero) {
+                continue;
+            }
+            let folded = number(&base)
+                .zip(e.and_then(Number::as_integer))
+                .and_then(|(b, e)| b.powi(e));
+            match folded {
+                Some(n) => self.coefficient = self.coefficient.mul(n),
+                None => factors.push((base, exponent)),
+            }
+        }
+        self.factors = factors;
+        self
+    }
+
+    /// Interpret a (simplified) expression as a single term.
+    fn from_expression(met: &MathExpressionTree) -> Term {
+        let one = || Atom(MathExpression::Mn("1".to_string()));
+        if let Some(n) = number(met) {
+            return Term::constant(n);
+        }
+        match met {
+            Cons(Operator::Multiply, args) => args
+                .iter()
+                .map(Term::from_expression)
+                .fold(Term::constant(Rational(1, 1)), Term::mul),
+            Cons(Operator::Subtract, args) if args.len() == 1 => {
+                Term::from_expression(&args[0]).neg()
+            }
+            Cons(Operator::Divide, args) if args.len() == 2 => {
+                let denominator = Term::from_expression(&args[1]);
+                match denominator.clone().powi(-1) {
+                    Some(reciprocal) => Term::from_expression(&args[0]).mul(reciprocal),
+                    None => Term::from_expression(&args[0]).mul(Term::factor(
+                        args[1].clone(),
+                        number_expression(Rational(-1, 1)),
+                    )),
+                }
+            }
+            Cons(Operator::Power, args) if args.len() == 2 => {
+                match number(&args[1]).and_then(Number::as_integer) {
+                    Some(exponent) => Term::from_expression(&args[0])
+                        .powi(exponent)
+                        .unwrap_or_else(|| Term::factor(args[0].clone(), args[1].clone())),
+                    None => Term::factor(args[0].clone(), args[1].clone()),
+                }
+            }
+            _ => Term::factor(met.clone(), one()),
+        }
+    }
+
+    /// Rebuild the term as an expression with the given way of multiplying factors, returned
+    /// with its sign separated out.
+    pub(crate) fn to_expression(
+        &self,
+        product: fn(Vec<MathExpressionTree>) -> MathExpressionTree,
+    ) -> (bool, MathExpressionTree) {
+        let mut numerator = vec![];
+        let mut denominator = vec![];
+        match self.coefficient.abs() {
+            Rational(p, q) => {
+                if p != 1 {
+                    numerator.push(number_expression(Rational(p, 1)));
+                }
+                if q != 1 {
+                    denominator.push(number_expression(Rational(q, 1)));
+                }
+            }
+            c => {
+                if !c.is_one() {
+                    numerator.push(number_expression(c));
+                }
+            }
+        }
+        for (base, exponent) in &self.factors {
+            match number(exponent) {
+                Some(e) if e.is_negative() => denominator.push(power(base, e.neg())),
+                Some(e) => numerator.push(power(base, e)),
+                None => numerator.push(Cons(Operator::Power, vec![base.clone(), exponent.clone()])),
+            }
+        }
+        let numerator = product(numerator);
+        let expression = if denominator.is_empty() {
+            numerator
+        } else {
+            Cons(Operator::Divide, vec![numerator, product(denominator)])
+        };
+        (self.coefficient.is_negative(), expression)
+    }
+}
+
+fn power(base: &MathExpressionTree, exponent: Number) -> MathExpressionTree {
+    if exponent.is_one() {
+        base.clone()
+    } else {
+        Cons(
+            Operator::Power,
+            vec![base.clone(), number_expression(exponent)],
+        )
+    }
+}
+
+/// Left-nested binary product, in the shape produced by the Pratt parser.
+fn product(factors: Vec<MathExpressionTree>) -> MathExpressionTree {
+    factors
+        .into_iter()
+        .reduce(|lhs, rhs| Cons(Operator::Multiply, vec![lhs, rhs]))
+        .unwrap_or_else(|| Atom(MathExpression::Mn("1".to_string())))
+}
+
+/// Interpret a (simplified) expression as a sum of terms.
+fn terms(met: &MathExpressionTree, negate: bool, terms_so_far: &mut Vec<Term>) {
+    match met {
+        Cons(Operator::Add, args) => {
+            for arg in args {
+                terms(arg, negate, terms_so_far);
+            }
+        }
+        Cons(Operator::Subtract, args) if args.len() == 1 => terms(&args[0], !negate, terms_so_far),
+        Cons(Operator::Subtract, args) if args.len() == 2 => {
+            terms(&args[0], negate, terms_so_far);
+            terms(&args[1], !negate, terms_so_far);
+        }
+        _ => {
+            let term = Term::from_expression(met);
+            terms_so_far.push(if negate { term.neg() } else { term });
+        }
+    }
+}
+
+/// Add up like terms, keeping the order in which terms first appeared and dropping those that
+/// cancel out.
+pub(crate) fn collect(terms: Vec<Term>) -> Vec<Term> {
+    let mut collected: Vec<(String, Term)> = vec![];
+    for term in terms {
+        let key = term.key();
+        match collected.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, t)) => t.coefficient = t.coefficient.add(term.coefficient),
+            None => collected.push((key, term)),
+        }
+    }
+    collected
+        .into_iter()
+        .map(|(_, term)| term)
+        .filter(|term| !term.coefficient.is_zero())
+        .collect()
+}
+
+/// Collect like terms and rebuild the sum as a left-nested chain of additions and subtractions,
+/// with terms in a canonical order and constants last.
+fn sum(met: &MathExpressionTree) -> MathExpressionTree {
+    let mut all_terms = vec![];
+    terms(met, false, &mut all_terms);
+    let mut collected: Vec<(String, Term)> = collect(all_terms)
+        .into_iter()
+        .map(|mut term| {
+            term.factors
+                .sort_by_cached_key(|(base, _)| base.to_string());
+            (term.key(), term)
+        })
+        .collect();
+    collected
+        .sort_by(|(a, t1), (b, t2)| (t1.factors.is_empty(), a).cmp(&(t2.factors.is_empty(), b)));
+    let mut result: Option<MathExpressionTree> = None;
+    for (_, term) in collected {
+        let (negative, expression) = term.to_expression(product);
+        result = Some(match (result, negative) {
+            (None, false) => expression,
+            (None, true) => Cons(Operator::Subtract, vec![expression]),
+            (Some(lhs), false) => Cons(Operator::Add, vec![lhs, expression]),
+            (Some(lhs), true) => Cons(Operator::Subtract, vec![lhs, expression]),
+        });
+    }
+    result.unwrap_or_else(|| Atom(MathExpression::Mn("0".to_string())))
+}
+
+/// Evaluate functions at the arguments for which they have exact values.
+fn simplify_function(op: Operator, args: Vec<MathExpressionTree>) -> MathExpressionTree {
+    let integer = |n: i64| Atom(MathExpression::Mn(n.to_string()));
+    let arg = match args.as_slice() {
+        [arg] => arg,
+        _ => return Cons(op, args),
+    };
+    match (&op, number(arg)) {
+        (Operator::Exp | Operator::Cos, Some(n)) if n.is_zero() => integer(1),
+        (
+            Operator::Sin | Operator::Tan | Operator::Arcsin | Operator::Arctan | Operator::Sqrt,
+            Some(n),
+        ) if n.is_zero() => integer(0),
+        (
+            Operator::Logarithm(Logarithm {
+                notation: LogarithmNotation::Ln | LogarithmNotation::Log,
+            }),
+            Some(n),
+        ) if n.is_one() => integer(0),
+        (Operator::Sqrt, Some(n)) => match n.sqrt() {
+            Some(root) => number_expression(root),
+            None => Cons(op, args),
+        },
+        (Operator::Abs, Some(n)) => number_expression(n.abs()),
+        (
+            Operator::Logarithm(Logarithm {
+                notation: LogarithmNotation::Ln,
+            }),
+            None,
+        ) => match arg {
+            Cons(Operator::Exp, exponent) if exponent.len() == 1 => exponent[0].clone(),
+            _ => Cons(op, args),
+        },
+        _ => Cons(op, args),
+    }
+}
+
+impl MathExpressionTree {
+    /// Simplify the expression by folding constants, eliminating additive and multiplicative
+    /// identities and double negations, collecting like terms, merging powers of a common base
+    /// and ordering the operands of sums and products canonically. Products of sums are not
+    /// expanded.
+    pub fn simplify(&self) -> MathExpressionTree {
+        match self {
+            Atom(_) => self.clone(),
+            Cons(op, args) => {
+                let args: Vec<MathExpressionTree> = args.iter().map(|arg| arg.simplify()).collect();
+                match op {
+                    Operator::Add
+                    | Operator::Subtract
+                    | Operator::Multiply
+                    | Operator::Divide
+                    | Operator::Power => sum(&Cons(op.clone(), args)),
+                    _ => simplify_function(op.clone(), args),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn simplified(input: &str) -> String {
+    input
+        .parse::<MathExpressionTree>()
+        .unwrap()
+        .simplify()
+        .to_string()
+}
+
+#[test]
+fn test_constant_folding() {
+    assert_eq!(
+        simplified("<math><mn>2</mn><mo>+</mo><mn>3</mn><mo>*</mo><mn>4</mn></math>"),
+        "14"
+    );
+    assert_eq!(
+        simplified("<math><mfrac><mn>6</mn><mn>4</mn></mfrac></math>"),
+        "(/ 3 2)"
+    );
+    assert_eq!(
+        simplified("<math><msup><mn>2</mn><mn>3</mn></msup><mo>-</mo><mn>0.5</mn></math>"),
+        "7.5"
+    );
+    assert_eq!(
+        simplified("<math><msup><mi>e</mi><mn>0</mn></msup><mo>+</mo><mrow><mi>cos</mi><mo>(</mo><mn>0</mn><mo>)</mo></mrow></math>"),
+        "2"
+    );
+}
+
+#[test]
+fn test_identities_and_negation() {
+    assert_eq!(
+        simplified("<math><mi>x</mi><mo>+</mo><mn>0</mn><mo>*</mo><mi>y</mi></math>"),
+        "x"
+    );
+    assert_eq!(
+        simplified("<math><mn>1</mn><mo>*</mo><msup><mi>x</mi><mn>1</mn></msup></math>"),
+        "x"
+    );
+    assert_eq!(
+        simplified("<math><mo>-</mo><mo>(</mo><mo>-</mo><mi>x</mi><mo>)</mo></math>"),
+        "x"
+    );
+    assert_eq!(
+        simplified("<math><mi>x</mi><mo>-</mo><mi>x</mi></math>"),
+        "0"
+    );
+}
+
+#[test]
+fn test_like_terms_and_powers() {
+    assert_eq!(
+        simplified(
+            "<math><mi>x</mi><mo>+</mo><mn>2</mn><mi>x</mi><mo>-</mo><mi>y</mi><mo>+</mo><mi>y</mi></math>"
+        ),
+        "(* 3 x)"
+    );
+    assert_eq!(
+        simplified(
+            "<math><mfrac><mrow><mi>x</mi><msup><mi>x</mi><mn>2</mn></msup></mrow><mi>x</mi></mfrac></math>"
+        ),
+        "(^ x 2)"
+    );
+    assert_eq!(
+        simplified(
+            "<math><msup><mi>x</mi><mi>a</mi></msup><msup><mi>x</mi><mi>b</mi></msup></math>"
+        ),
+        "(^ x (+ a b))"
+    );
+    assert_eq!(
+        simplified("<math><mfrac><mrow><mn>2</mn><mi>x</mi></mrow><mrow><mn>4</mn><mi>y</mi></mrow></mfrac></math>"),
+        "(/ x (* 2 y))"
+    );
+}
+
+#[test]
+fn test_code_and_paper_forms_agree() {
+    // As extracted from code, with a unary minus and nested products.
+    let code = MathExpressionTree::from_cmml(
+        "<apply><plus/>
+            <apply><times/>
+                <apply><times/><apply><minus/><ci>β</ci></apply><ci>I</ci></apply>
+                <ci>S</ci>
+            </apply>
+            <apply><times/><ci>γ</ci><ci>I</ci></apply>
+        </apply>",
+    )
+    .unwrap();
+    // As extracted from a paper.
+    let paper =
+        simplified("<math><mi>γ</mi><mi>I</mi><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>");
+    assert_eq!(code.simplify().to_string(), paper);
+    assert_eq!(paper, "(+ (- (* (* I S) β)) (* I γ))");
+}
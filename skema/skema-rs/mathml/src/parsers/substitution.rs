@@ -0,0 +1,348 @@
+//! Substitution and renaming of identifiers in math expression trees, ODEs and generalized AMRs,
+//! e.g. for aligning the names used by models from different sources or for inlining a
+//! definition such as N = S + I + R.
+//!
+//! Identifiers are matched by name regardless of how their subscripts are written, so that
+//! `S_{h}`, `S_h` and `<msub><mi>S</mi><mi>h</mi></msub>` all refer to the same variable,
+//! whether they appear as `Ci` or `Mi` elements.
+
+use crate::{
+    acset::{GeneralizedAMR, Parameter},
+    ast::{
+        operator::{Derivative, Operator},
+        Ci, MathExpression, Mi,
+    },
+    parsers::{
+        first_order_ode::FirstOrderODE,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+    },
+};
+use std::collections::HashMap;
+
+/// Key under which identifiers are matched.
+pub(crate) fn key(name: &str) -> String {
+    name.chars().filter(|c| !matches!(c, '{' | '}')).collect()
+}
+
+/// The name of an identifier, or None for numbers, text and other atoms.
+pub(crate) fn identifier(atom: &MathExpression) -> Option<String> {
+    match atom {
+        MathExpression::Ci(ci) => Some(ci.content.to_string()),
+        MathExpression::Mi(Mi(name)) => Some(name.clone()),
+        MathExpression::Msub(..) => Some(atom.to_string()),
+        _ => None,
+    }
+}
+
+fn is_identifier(atom: &MathExpression, var: &str) -> bool {
+    identifier(atom).is_some_and(|name| key(&name) == key(var))
+}
+
+/// Renaming with its keys normalized for matching.
+struct Renaming(HashMap<String, String>);
+
+impl Renaming {
+    fn new(map: &HashMap<String, String>) -> Renaming {
+        Renaming(
+            map.iter()
+                .map(|(from, to)| (key(from), to.clone()))
+                .collect(),
+        )
+    }
+
+    fn get(&self, name: &str) -> Option<&String> {
+        self.0.get(&key(name))
+    }
+
+    fn name(&self, name: &str) -> String {
+        self.get(name).cloned().unwrap_or_else(|| name.to_string())
+    }
+
+    fn ci(&self, ci: &Ci) -> Ci {
+        let content = match self.get(&ci.content.to_string()) {
+            Some(name) => Box::new(MathExpression::Mi(Mi(name.clone()))),
+            None => ci.content.clone(),
+        };
+        Ci {
+            content,
+            func_of: ci
+                .func_of
+                .as_ref()
+                .map(|args| args.iter().map(|arg| self.ci(arg)).collect()),
+            ..ci.clone()
+        }
+    }
+
+    fn atom(&self, atom: &MathExpression) -> MathExpression {
+        match atom {
+            MathExpression::Ci(ci) => MathExpression::Ci(self.ci(ci)),
+            _ => match identifier(atom).and_then(|name| self.get(&name)) {
+                Some(name) => MathExpression::Mi(Mi(name.clone())),
+                None => atom.clone(),
+            },
+        }
+    }
+
+    fn operator(&self, op: &Operator) -> Operator {
+        match op {
+            Operator::Derivative(derivative) => Operator::Derivative(Derivative {
+                bound_var: self.ci(&derivative.bound_var),
+                ..derivative.clone()
+            }),
+            _ => op.clone(),
+        }
+    }
+
+    fn met(&self, met: &MathExpressionTree) -> MathExpressionTree {
+        match met {
+            Atom(atom) => Atom(self.atom(atom)),
+            Cons(op, args) => Cons(
+                self.operator(op),
+                args.iter().map(|arg| self.met(arg)).collect(),
+            ),
+        }
+    }
+}
+
+/// Collect the names of the identifiers in an expression, in order of first appearance.
+pub(crate) fn identifiers(met: &MathExpressionTree, names: &mut Vec<String>) {
+    match met {
+        Atom(atom) => {
+            if let Some(name) = identifier(atom) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        Cons(_, args) => {
+            for arg in args {
+                identifiers(arg, names);
+            }
+        }
+    }
+}
+
+impl MathExpressionTree {
+    /// Replace every occurrence of the variable with an expression.
+    pub fn substitute(&self, var: &str, replacement: &MathExpressionTree) -> MathExpressionTree {
+        match self {
+            Atom(atom) if is_identifier(atom, var) => replacement.clone(),
+            Atom(_) => self.clone(),
+            Cons(op, args) => Cons(
+                op.clone(),
+                args.iter()
+                    .map(|arg| arg.substitute(var, replacement))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Rename identifiers, including the variables of derivatives and the arguments of
+    /// functions, e.g. S(t).
+    pub fn rename(&self, map: &HashMap<String, String>) -> MathExpressionTree {
+        Renaming::new(map).met(self)
+    }
+}
+
+impl FirstOrderODE {
+    /// Replace every occurrence of the variable in the RHS with an expression.
+    pub fn substitute(&self, var: &str, replacement: &MathExpressionTree) -> FirstOrderODE {
+        FirstOrderODE {
+            rhs: self.rhs.substitute(var, replacement),
+            ..self.clone()
+        }
+    }
+
+    /// Rename identifiers on both sides of the equation.
+    pub fn rename(&self, map: &HashMap<String, String>) -> FirstOrderODE {
+        let renaming = Renaming::new(map);
+        FirstOrderODE {
+            lhs_var: renaming.ci(&self.lhs_var),
+            func_of: self.func_of.iter().map(|ci| renaming.ci(ci)).collect(),
+            with_respect_to: renaming.ci(&self.with_respect_to),
+            rhs: renaming.met(&self.rhs),
+        }
+    }
+}
+
+/// Whether the equation is the definition of the variable, e.g. N = S + I + R for N.
+fn defines(equation: &MathExpressionTree, var: &str) -> bool {
+    match equation {
+        Cons(Operator::Equals, args) => matches!(&args[0], Atom(atom) if is_identifier(atom, var)),
+        _ => false,
+    }
+}
+
+impl GeneralizedAMR {
+    /// Replace every occurrence of the variable with an expression, dropping the equation that
+    /// defines the variable, if any. The variable is removed from the parameters, and identifiers
+    /// of the replacement that are neither states nor parameters are added to them.
+    pub fn substitute(&self, var: &str, replacement: &MathExpressionTree) -> GeneralizedAMR {
+        let met = self
+            .met
+            .iter()
+            .filter(|equation| !defines(equation, var))
+            .map(|equation| equation.substitute(var, replacement))
+            .collect();
+        let semantics = self.semantics.clone().map(|mut semantics| {
+            let mut parameters = semantics.parameters.unwrap_or_default();
+            parameters.retain(|parameter| key(&parameter.id) != key(var));
+            let mut names = vec![];
+            identifiers(replacement, &mut names);
+            for name in names {
+                let known = semantics
+                    .states
+                    .iter()
+                    .any(|state| key(&state.id) == key(&name))
+                    || parameters
+                        .iter()
+                        .any(|parameter| key(&parameter.id) == key(&name));
+                if !known {
+                    parameters.push(Parameter {
+                        id: name.clone(),
+                        name: Some(name),
+                        ..Default::default()
+                    });
+                }
+            }
+            semantics.parameters = Some(parameters);
+            semantics
+        });
+        let mut gamr = GeneralizedAMR {
+            met,
+            semantics,
+            ..self.clone()
+        };
+        gamr.set_observables();
+        gamr
+    }
+
+    /// Rename identifiers in the equations, states, parameters, observables and PDE semantics.
+    pub fn rename(&self, map: &HashMap<String, String>) -> GeneralizedAMR {
+        let renaming = Renaming::new(map);
+        let semantics = self.semantics.clone().map(|mut semantics| {
+            semantics.states = semantics
+                .states
+                .into_iter()
+                .map(|mut state| {
+                    state.id = renaming.name(&state.id);
+                    state.name = renaming.name(&state.name);
+                    state
+                })
+                .collect();
+            for parameter in semantics.parameters.iter_mut().flatten() {
+                parameter.id = renaming.name(&parameter.id);
+                parameter.name = parameter.name.as_ref().map(|name| renaming.name(name));
+            }
+            for pde in semantics.pdes.iter_mut().flatten() {
+                pde.state = renaming.name(&pde.state);
+                pde.time.id = renaming.name(&pde.time.id);
+                for variable in pde.spatial.iter_mut() {
+                    variable.id = renaming.name(&variable.id);
+                }
+            }
+            semantics
+        });
+        let mut gamr = GeneralizedAMR {
+            met: self
+                .met
+                .iter()
+                .map(|equation| renaming.met(equation))
+                .collect(),
+            semantics,
+            ..self.clone()
+        };
+        gamr.set_observables();
+        gamr
+    }
+}
+
+#[cfg(test)]
+fn met(input: &str) -> MathExpressionTree {
+    format!("<math>{input}</math>")
+        .parse::<MathExpressionTree>()
+        .unwrap()
+}
+
+#[test]
+fn test_substitute() {
+    let total = met("<mi>S</mi><mo>+</mo><mi>I</mi><mo>+</mo><mi>R</mi>");
+    let force = met("<mfrac><mrow><mi>β</mi><mi>S</mi><mi>I</mi></mrow><mi>N</mi></mfrac>");
+    assert_eq!(
+        force.substitute("N", &total).to_string(),
+        "(/ (* (* β S) I) (+ (+ S I) R))"
+    );
+    // Variables that do not occur are left alone.
+    assert_eq!(force.substitute("M", &total), force);
+    // Subscripts match whether or not they are delimited with braces.
+    let subscripted = met("<msub><mi>β</mi><mi>h</mi></msub><msub><mi>S</mi><mi>h</mi></msub>");
+    assert_eq!(
+        subscripted
+            .substitute("S_h", &met("<mi>x</mi>"))
+            .to_string(),
+        "(* β_{h} x)"
+    );
+}
+
+#[test]
+fn test_rename() {
+    let map: HashMap<String, String> = [("beta", "β"), ("S_{h}", "S_h")]
+        .iter()
+        .map(|(from, to)| (from.to_string(), to.to_string()))
+        .collect();
+    let renamed = met("<mi>beta</mi><msub><mi>S</mi><mi>h</mi></msub><mi>I</mi>").rename(&map);
+    assert_eq!(renamed.to_string(), "(* (* β S_h) I)");
+    assert_eq!(
+        renamed.to_cmml(),
+        "<apply><times/><apply><times/><ci>β</ci><ci>S_h</ci></apply><ci>I</ci></apply>"
+    );
+
+    let ode = "<math><mfrac><mrow><mi>d</mi><msub><mi>S</mi><mi>h</mi></msub></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>beta</mi><msub><mi>S</mi><mi>h</mi></msub></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap()
+        .rename(&map);
+    assert_eq!(ode.lhs_var.to_string(), "S_h");
+    assert_eq!(ode.rhs.to_string(), "(* (- β) S_h)");
+}
+
+#[test]
+fn test_generalized_amr() {
+    let gamr = GeneralizedAMR::from(vec![
+        met("<mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>λ</mi><mi>S</mi>"),
+        met("<mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>λ</mi><mi>S</mi>"),
+    ]);
+    let parameters = |gamr: &GeneralizedAMR| -> Vec<String> {
+        let mut ids: Vec<String> = gamr
+            .semantics
+            .as_ref()
+            .unwrap()
+            .parameters
+            .iter()
+            .flatten()
+            .map(|parameter| parameter.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(parameters(&gamr), vec!["λ"]);
+
+    // Inline the force of infection.
+    let inlined = gamr.substitute("λ", &met("<mi>β</mi><mi>I</mi>"));
+    assert_eq!(parameters(&inlined), vec!["β"]);
+    assert_eq!(inlined.met[1].to_string(), "(= (D(1, t) I) (* (* β I) S))");
+
+    let map: HashMap<String, String> = [("S".to_string(), "Sus".to_string())].into();
+    let renamed = inlined.rename(&map);
+    let states: Vec<String> = renamed
+        .semantics
+        .unwrap()
+        .states
+        .iter()
+        .map(|state| state.id.clone())
+        .collect();
+    assert_eq!(states, vec!["I", "Sus"]);
+    assert_eq!(
+        renamed.met[0].to_string(),
+        "(= (D(1, t) Sus) (* (- (* β I)) Sus))"
+    );
+}
@@ -0,0 +1,283 @@
+//! Reconstruction of the ODEs of Petri net and RegNet AMRs, the inverse of their construction
+//! from ODEs, e.g. for round-trip tests or for viewing an AMR as readable equations.
+
+use crate::{
+    acset::{PetriNet, RegNet},
+    ast::{operator::Operator, Ci, MathExpression, Mi},
+    parsers::{
+        first_order_ode::FirstOrderODE,
+        generic_mathml::ParseDiagnostic,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+    },
+};
+use std::fmt;
+
+/// Error raised when the ODEs of a model cannot be reconstructed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReconstructionError {
+    /// A transition of a Petri net or an edge of a RegNet without a rate.
+    MissingRate(String),
+    /// A rate of a Petri net whose expression cannot be parsed.
+    InvalidRate(String, ParseDiagnostic),
+}
+
+impl fmt::Display for ReconstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconstructionError::MissingRate(x) => write!(f, "Transition `{x}` has no rate"),
+            ReconstructionError::InvalidRate(x, err) => {
+                write!(f, "The rate of transition `{x}` cannot be parsed: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReconstructionError {}
+
+fn ci(name: &str) -> Ci {
+    Ci::new(
+        None,
+        Box::new(MathExpression::Mi(Mi(name.to_string()))),
+        None,
+        None,
+    )
+}
+
+fn identifier(name: &str) -> MathExpressionTree {
+    Atom(MathExpression::Ci(ci(name)))
+}
+
+/// First order ODEs with the given derivatives of the states.
+fn odes(
+    time: &str,
+    states: Vec<String>,
+    derivatives: Vec<Vec<MathExpressionTree>>,
+) -> Vec<FirstOrderODE> {
+    states
+        .iter()
+        .zip(derivatives)
+        .map(|(state, terms)| FirstOrderODE {
+            lhs_var: ci(state),
+            func_of: vec![],
+            with_respect_to: ci(time),
+            rhs: sum(terms),
+        })
+        .collect()
+}
+
+impl PetriNet {
+    /// Reconstruct the ODEs of the Petri net, in the order of its states, using the rates of its
+    /// semantics if it has any and mass action kinetics with the rate constants of its
+    /// transitions otherwise. Rates written with a leading minus sign, as produced for sinks, are
+    /// read as the magnitude of the flux.
+    pub fn to_odes(&self) -> Result<Vec<FirstOrderODE>, ReconstructionError> {
+        let ode = self.semantics.as_ref().map(|semantics| &semantics.ode);
+        let rates = ode.and_then(|ode| ode.rates.as_ref());
+        let states: Vec<String> = self
+            .model
+            .states
+            .iter()
+            .map(|state| state.id.clone())
+            .collect();
+        let mut derivatives: Vec<Vec<MathExpressionTree>> = vec![vec![]; states.len()];
+        for transition in self.model.transitions.iter() {
+            let inputs = transition.input.clone().unwrap_or_default();
+            let outputs = transition.output.clone().unwrap_or_default();
+            let rate = match rates {
+                Some(rates) => {
+                    let rate = rates
+                        .iter()
+                        .find(|rate| rate.target == transition.id)
+                        .and_then(|rate| rate.expression_mathml.as_ref())
+                        .ok_or_else(|| ReconstructionError::MissingRate(transition.id.clone()))?;
+                    let rate = MathExpressionTree::from_cmml(rate).map_err(|err| {
+                        ReconstructionError::InvalidRate(transition.id.clone(), err)
+                    })?;
+                    flux(rate)
+                }
+                None => {
+                    let rate_constant = transition
+                        .properties
+                        .as_ref()
+                        .and_then(|properties| properties.rate_constant.as_ref())
+                        .ok_or_else(|| ReconstructionError::MissingRate(transition.id.clone()))?;
+                    Cons(
+                        Operator::Multiply,
+                        std::iter::once(constant(rate_constant))
+                            .chain(inputs.iter().map(|input| identifier(input)))
+                            .collect(),
+                    )
+                }
+            };
+            for (i, state) in states.iter().enumerate() {
+                let produced = outputs.iter().filter(|x| *x == state).count() as i64;
+                let consumed = inputs.iter().filter(|x| *x == state).count() as i64;
+                let net = produced - consumed;
+                match net {
+                    0 => {}
+                    1 => derivatives[i].push(rate.clone()),
+                    -1 => derivatives[i].push(Cons(Operator::Subtract, vec![rate.clone()])),
+                    _ => derivatives[i].push(Cons(
+                        Operator::Multiply,
+                        vec![Atom(MathExpression::Mn(net.to_string())), rate.clone()],
+                    )),
+                }
+            }
+        }
+
+        let time = ode
+            .and_then(|ode| ode.time.as_ref())
+            .map_or("t", |time| time.id.as_str());
+        Ok(odes(time, states, derivatives))
+    }
+}
+
+impl RegNet {
+    /// Reconstruct the ODEs of the regulatory network, in the order of its vertices. Each vertex
+    /// with a rate constant grows, or decays if its sign is negative, in proportion to itself,
+    /// and each edge promotes or inhibits its target in proportion to the product of its source
+    /// and target, with the rate constant of the edge or the parameter it is named after, e.g.
+    /// dR/dt = α R − β F R for the prey of Lotka-Volterra.
+    pub fn to_odes(&self) -> Result<Vec<FirstOrderODE>, ReconstructionError> {
+        let signed = |sign: Option<bool>, term: MathExpressionTree| match sign {
+            Some(false) => Cons(Operator::Subtract, vec![term]),
+            _ => term,
+        };
+        let states: Vec<String> = self
+            .model
+            .vertices
+            .iter()
+            .map(|vertex| vertex.id.clone())
+            .collect();
+        let mut derivatives: Vec<Vec<MathExpressionTree>> = self
+            .model
+            .vertices
+            .iter()
+            .map(|vertex| {
+                vertex
+                    .rate_constant
+                    .iter()
+                    .map(|rate_constant| {
+                        let term = Cons(
+                            Operator::Multiply,
+                            vec![constant(rate_constant), identifier(&vertex.id)],
+                        );
+                        signed(vertex.sign, term)
+                    })
+                    .collect()
+            })
+            .collect();
+        for edge in self.model.edges.iter() {
+            let (source, target) = match (&edge.source, &edge.target) {
+                (Some(source), Some(target)) => (source, target),
+                _ => continue,
+            };
+            let rate_constant = edge
+                .properties
+                .as_ref()
+                .map(|properties| {
+                    properties
+                        .rate_constant
+                        .as_ref()
+                        .unwrap_or(&properties.name)
+                })
+                .ok_or_else(|| ReconstructionError::MissingRate(edge.id.clone()))?;
+            let term = Cons(
+                Operator::Multiply,
+                vec![
+                    constant(rate_constant),
+                    identifier(source),
+                    identifier(target),
+                ],
+            );
+            if let Some(i) = states.iter().position(|state| state == target) {
+                derivatives[i].push(signed(edge.sign, term));
+            }
+        }
+
+        Ok(odes("t", states, derivatives))
+    }
+}
+
+/// A rate constant, which is either a number or the name of a parameter.
+fn constant(rate_constant: &str) -> MathExpressionTree {
+    match rate_constant.parse::<f64>() {
+        Ok(_) => Atom(MathExpression::Mn(rate_constant.to_string())),
+        Err(_) => identifier(rate_constant),
+    }
+}
+
+/// The sum of the terms of a derivative.
+fn sum(mut terms: Vec<MathExpressionTree>) -> MathExpressionTree {
+    match terms.len() {
+        0 => Atom(MathExpression::Mn("0".to_string())),
+        1 => terms.pop().unwrap(),
+        _ => Cons(Operator::Add, terms),
+    }
+}
+
+/// The magnitude of a rate, unwrapping the unary plus and minus that the rates of extracted
+/// Petri nets are wrapped in.
+fn flux(rate: MathExpressionTree) -> MathExpressionTree {
+    match rate {
+        Cons(Operator::Add | Operator::Subtract, mut args) if args.len() == 1 => {
+            flux(args.pop().unwrap())
+        }
+        _ => rate,
+    }
+}
+
+#[cfg(test)]
+fn sir() -> Vec<FirstOrderODE> {
+    [
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>",
+    ]
+    .iter()
+    .map(|input| input.parse::<FirstOrderODE>().unwrap())
+    .collect()
+}
+
+#[test]
+fn test_reconstruct_odes() {
+    // Equations → Petri net → equations, in the order of the states of the Petri net.
+    let odes = sir();
+    let reconstructed = PetriNet::from(odes.clone()).to_odes().unwrap();
+    assert_eq!(reconstructed.len(), 3);
+    for ode in odes.iter() {
+        assert!(
+            reconstructed.iter().any(|other| other.equivalent(ode)),
+            "{}",
+            ode.rhs
+        );
+    }
+    assert!(reconstructed[0]
+        .to_met()
+        .to_latex()
+        .contains("\\frac{d I}{dt}"));
+
+    // Equations → RegNet → equations for Lotka-Volterra.
+    let odes: Vec<FirstOrderODE> = [
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>α</mi><mi>R</mi><mo>-</mo><mi>β</mi><mi>R</mi><mi>F</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>F</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>δ</mi><mi>R</mi><mi>F</mi><mo>-</mo><mi>γ</mi><mi>F</mi></math>",
+    ]
+    .iter()
+    .map(|input| input.parse().unwrap())
+    .collect();
+    let reconstructed = RegNet::from(odes.clone()).to_odes().unwrap();
+    assert_eq!(reconstructed.len(), 2);
+    for ode in odes.iter() {
+        assert!(
+            reconstructed.iter().any(|other| other.equivalent(ode)),
+            "{}",
+            ode.rhs
+        );
+    }
+
+    // The presentation MathML of reconstructed equations parses back to the same equations.
+    for ode in reconstructed.iter() {
+        let parsed = ode.to_pmml().parse::<FirstOrderODE>().unwrap();
+        assert!(parsed.equivalent(ode), "{}", ode.to_pmml());
+    }
+}
@@ -0,0 +1,641 @@
+//! Numerical simulation of extracted models, for sanity-checking their dynamics without shipping
+//! them to another tool.
+
+use crate::{
+    acset::{Parameter, PetriNet, RegNet},
+    parsers::{
+        evaluation::{CompiledExpression, EvalError},
+        first_order_ode::FirstOrderODE,
+        math_expression_tree::MathExpressionTree,
+    },
+    reconstruction::ReconstructionError,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, convert::TryFrom, fmt};
+use utoipa::ToSchema;
+
+/// Numerical integration method.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Hash, Deserialize, Serialize, ToSchema, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    /// Classical Runge-Kutta method with a fixed step size.
+    #[default]
+    Rk4,
+    /// Dormand-Prince Runge-Kutta method with an adaptive step size.
+    Rk45,
+}
+
+fn default_tolerance() -> f64 {
+    1e-6
+}
+
+fn default_max_points() -> usize {
+    100_000
+}
+
+/// The time span, integration method and parameter and initial values of a simulation.
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize, ToSchema, JsonSchema)]
+pub struct SimulationConfig {
+    #[serde(default)]
+    pub start_time: f64,
+    pub end_time: f64,
+    /// Interval between the reported time points. For RK4 this is also the step size; for RK45
+    /// it is the largest step size.
+    pub step: f64,
+    #[serde(default)]
+    pub method: Method,
+    /// Tolerance of the local error of each step, relative to the magnitude of the states, for
+    /// RK45.
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f64,
+    /// Parameter values, overriding the values given by the model.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, f64>,
+    /// Initial values of the states, overriding the values given by the model.
+    #[serde(default)]
+    pub initials: BTreeMap<String, f64>,
+    /// Largest number of time points to report. A time span and step that would give more are
+    /// refused, since every point is kept in memory. Requests cannot raise it.
+    #[serde(skip_deserializing, default = "default_max_points")]
+    pub max_points: usize,
+}
+
+/// The values of a state at the time points of a trajectory.
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize, ToSchema, JsonSchema)]
+pub struct StateTrajectory {
+    pub id: String,
+    pub values: Vec<f64>,
+}
+
+/// The result of a simulation.
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize, ToSchema, JsonSchema)]
+pub struct Trajectory {
+    pub time: Vec<f64>,
+    pub states: Vec<StateTrajectory>,
+}
+
+impl Trajectory {
+    /// The trajectory as CSV, with a column for the time followed by a column per state.
+    pub fn to_csv(&self, time: &str) -> String {
+        let mut csv = String::new();
+        let header: Vec<&str> = std::iter::once(time)
+            .chain(self.states.iter().map(|state| state.id.as_str()))
+            .collect();
+        csv.push_str(&header.join(","));
+        csv.push('\n');
+        for (i, t) in self.time.iter().enumerate() {
+            let row: Vec<String> = std::iter::once(*t)
+                .chain(self.states.iter().map(|state| state.values[i]))
+                .map(|x| x.to_string())
+                .collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Error raised when a model cannot be simulated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    /// A rate or derivative that cannot be evaluated, e.g. because a parameter has no value.
+    Eval(EvalError),
+    /// A state without an initial value.
+    MissingInitial(String),
+    /// A model whose ODEs cannot be reconstructed.
+    Reconstruction(ReconstructionError),
+    /// An invalid time span, step size or tolerance.
+    InvalidConfig(String),
+    /// The step size of RK45 became too small to meet the tolerance, e.g. for a stiff system.
+    StepSizeTooSmall(f64),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::Eval(err) => write!(f, "{err}"),
+            SimulationError::MissingInitial(x) => write!(f, "No initial value was given for `{x}`"),
+            SimulationError::Reconstruction(err) => write!(f, "{err}"),
+            SimulationError::InvalidConfig(x) => write!(f, "{x}"),
+            SimulationError::StepSizeTooSmall(t) => write!(
+                f,
+                "The step size became too small to meet the tolerance at time {t}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl From<EvalError> for SimulationError {
+    fn from(err: EvalError) -> Self {
+        SimulationError::Eval(err)
+    }
+}
+
+impl From<ReconstructionError> for SimulationError {
+    fn from(err: ReconstructionError) -> Self {
+        SimulationError::Reconstruction(err)
+    }
+}
+
+/// A system of ODEs dx/dt = f(t, x) ready for numerical integration.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OdeSystem {
+    /// The independent variable.
+    pub time: String,
+    pub states: Vec<String>,
+    /// The derivative of each state.
+    pub derivatives: Vec<MathExpressionTree>,
+    /// Parameter values given by the model.
+    pub parameters: BTreeMap<String, f64>,
+    /// Initial values given by the model.
+    pub initials: BTreeMap<String, f64>,
+}
+
+impl From<&[FirstOrderODE]> for OdeSystem {
+    fn from(odes: &[FirstOrderODE]) -> Self {
+        OdeSystem {
+            time: odes
+                .first()
+                .map(|ode| ode.with_respect_to.to_string())
+                .unwrap_or_else(|| "t".to_string()),
+            states: odes.iter().map(|ode| ode.lhs_var.to_string()).collect(),
+            derivatives: odes.iter().map(|ode| ode.rhs.clone()).collect(),
+            parameters: BTreeMap::new(),
+            initials: BTreeMap::new(),
+        }
+    }
+}
+
+impl TryFrom<&PetriNet> for OdeSystem {
+    type Error = SimulationError;
+
+    /// The ODEs of a Petri net as reconstructed by [`PetriNet::to_odes`], with the parameter and
+    /// initial values of its semantics.
+    fn try_from(petri_net: &PetriNet) -> Result<Self, Self::Error> {
+        let ode = petri_net.semantics.as_ref().map(|semantics| &semantics.ode);
+        let parameters = parameter_values(ode.and_then(|ode| ode.parameters.as_ref()));
+        let mut initials = BTreeMap::new();
+        for initial in ode
+            .and_then(|ode| ode.initials.as_ref())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(value) = initial_value(&initial.expression, &parameters) {
+                initials.insert(initial.target.clone(), value);
+            }
+        }
+        let mut system = OdeSystem::from(petri_net.to_odes()?.as_slice());
+        if let Some(time) = ode.and_then(|ode| ode.time.as_ref()) {
+            system.time = time.id.clone();
+        }
+        system.parameters = parameters;
+        system.initials = initials;
+        Ok(system)
+    }
+}
+
+impl TryFrom<&RegNet> for OdeSystem {
+    type Error = SimulationError;
+
+    /// The ODEs of a regulatory network as reconstructed by [`RegNet::to_odes`], with the values
+    /// of its parameters and the initial values of its vertices.
+    fn try_from(regnet: &RegNet) -> Result<Self, Self::Error> {
+        let parameters = parameter_values(regnet.model.parameters.as_ref());
+        let mut initials = BTreeMap::new();
+        for vertex in regnet.model.vertices.iter() {
+            if let Some(value) = vertex
+                .initial
+                .as_ref()
+                .and_then(|initial| initial_value(initial, &parameters))
+            {
+                initials.insert(vertex.id.clone(), value);
+            }
+        }
+        let mut system = OdeSystem::from(regnet.to_odes()?.as_slice());
+        system.parameters = parameters;
+        system.initials = initials;
+        Ok(system)
+    }
+}
+
+/// The values of the parameters that have one.
+fn parameter_values(parameters: Option<&Vec<Parameter>>) -> BTreeMap<String, f64> {
+    parameters
+        .into_iter()
+        .flatten()
+        .filter_map(|parameter| Some((parameter.id.clone(), parameter.value?.into())))
+        .collect()
+}
+
+/// An initial value given as a number or as the name of a parameter.
+fn initial_value(expression: &str, parameters: &BTreeMap<String, f64>) -> Option<f64> {
+    let expression = expression.trim();
+    expression
+        .parse::<f64>()
+        .ok()
+        .or_else(|| parameters.get(expression).copied())
+}
+
+/// The compiled right hand side of an ODE system, evaluated on a buffer holding the time, the
+/// states and the parameters, in that order.
+struct CompiledSystem {
+    derivatives: Vec<CompiledExpression>,
+    buffer: Vec<f64>,
+}
+
+impl CompiledSystem {
+    fn eval(&mut self, t: f64, x: &[f64], dx: &mut [f64]) {
+        self.buffer[0] = t;
+        self.buffer[1..=x.len()].copy_from_slice(x);
+        for (dx_i, derivative) in dx.iter_mut().zip(self.derivatives.iter()) {
+            *dx_i = derivative(&self.buffer);
+        }
+    }
+}
+
+/// x + h Σ aᵢ kᵢ
+fn combine(x: &[f64], h: f64, terms: &[(f64, &[f64])]) -> Vec<f64> {
+    (0..x.len())
+        .map(|i| x[i] + h * terms.iter().map(|(a, k)| a * k[i]).sum::<f64>())
+        .collect()
+}
+
+fn rk4_step(system: &mut CompiledSystem, t: f64, x: &[f64], h: f64) -> Vec<f64> {
+    let n = x.len();
+    let (mut k1, mut k2, mut k3, mut k4) = (vec![0.0; n], vec![0.0; n], vec![0.0; n], vec![0.0; n]);
+    system.eval(t, x, &mut k1);
+    system.eval(t + h / 2.0, &combine(x, h, &[(0.5, &k1)]), &mut k2);
+    system.eval(t + h / 2.0, &combine(x, h, &[(0.5, &k2)]), &mut k3);
+    system.eval(t + h, &combine(x, h, &[(1.0, &k3)]), &mut k4);
+    combine(
+        x,
+        h,
+        &[
+            (1.0 / 6.0, &k1),
+            (1.0 / 3.0, &k2),
+            (1.0 / 3.0, &k3),
+            (1.0 / 6.0, &k4),
+        ],
+    )
+}
+
+/// A Dormand-Prince step, returning the fifth order solution and the estimated local error.
+fn rk45_step(system: &mut CompiledSystem, t: f64, x: &[f64], h: f64) -> (Vec<f64>, Vec<f64>) {
+    const C: [f64; 6] = [1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+    const A: [&[f64]; 6] = [
+        &[1.0 / 5.0],
+        &[3.0 / 40.0, 9.0 / 40.0],
+        &[44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0],
+        &[
+            19372.0 / 6561.0,
+            -25360.0 / 2187.0,
+            64448.0 / 6561.0,
+            -212.0 / 729.0,
+        ],
+        &[
+            9017.0 / 3168.0,
+            -355.0 / 33.0,
+            46732.0 / 5247.0,
+            49.0 / 176.0,
+            -5103.0 / 18656.0,
+        ],
+        &[
+            35.0 / 384.0,
+            0.0,
+            500.0 / 1113.0,
+            125.0 / 192.0,
+            -2187.0 / 6784.0,
+            11.0 / 84.0,
+        ],
+    ];
+    // Difference between the weights of the fifth and fourth order solutions.
+    const E: [f64; 7] = [
+        35.0 / 384.0 - 5179.0 / 57600.0,
+        0.0,
+        500.0 / 1113.0 - 7571.0 / 16695.0,
+        125.0 / 192.0 - 393.0 / 640.0,
+        -2187.0 / 6784.0 + 92097.0 / 339200.0,
+        11.0 / 84.0 - 187.0 / 2100.0,
+        -1.0 / 40.0,
+    ];
+    let mut k = vec![vec![0.0; x.len()]; 7];
+    system.eval(t, x, &mut k[0]);
+    for stage in 0..6 {
+        let terms: Vec<(f64, &[f64])> = A[stage]
+            .iter()
+            .zip(k.iter())
+            .map(|(a, k)| (*a, k.as_slice()))
+            .collect();
+        let x_stage = combine(x, h, &terms);
+        system.eval(t + C[stage] * h, &x_stage, &mut k[stage + 1]);
+        if stage == 5 {
+            // The last stage is evaluated at the fifth order solution.
+            let error: Vec<f64> = (0..x.len())
+                .map(|i| h * E.iter().zip(k.iter()).map(|(e, k)| e * k[i]).sum::<f64>())
+                .collect();
+            return (x_stage, error);
+        }
+    }
+    unreachable!()
+}
+
+impl OdeSystem {
+    /// Integrate the system over the time span of the configuration.
+    pub fn simulate(&self, config: &SimulationConfig) -> Result<Trajectory, SimulationError> {
+        let span = config.end_time - config.start_time;
+        if !(span.is_finite() && span > 0.0) {
+            return Err(SimulationError::InvalidConfig(
+                "The end time must be after the start time".to_string(),
+            ));
+        }
+        if !(config.step.is_finite() && config.step > 0.0) {
+            return Err(SimulationError::InvalidConfig(
+                "The step must be positive".to_string(),
+            ));
+        }
+        if config.method == Method::Rk45
+            && !(config.tolerance.is_finite() && config.tolerance > 0.0)
+        {
+            return Err(SimulationError::InvalidConfig(
+                "The tolerance must be positive".to_string(),
+            ));
+        }
+
+        let mut parameters = self.parameters.clone();
+        parameters.extend(config.parameters.clone());
+        let x0 = self
+            .states
+            .iter()
+            .map(|state| {
+                config
+                    .initials
+                    .get(state)
+                    .or_else(|| self.initials.get(state))
+                    .copied()
+                    .ok_or_else(|| SimulationError::MissingInitial(state.clone()))
+            })
+            .collect::<Result<Vec<f64>, SimulationError>>()?;
+
+        let variables: Vec<String> = std::iter::once(self.time.clone())
+            .chain(self.states.iter().cloned())
+            .chain(parameters.keys().cloned())
+            .collect();
+        let mut buffer = vec![0.0; variables.len()];
+        buffer[1 + self.states.len()..]
+            .copy_from_slice(&parameters.values().copied().collect::<Vec<f64>>());
+        let mut system = CompiledSystem {
+            derivatives: self
+                .derivatives
+                .iter()
+                .map(|derivative| derivative.compile(&variables))
+                .collect::<Result<_, EvalError>>()?,
+            buffer,
+        };
+
+        // The reported time points are evenly spaced, except possibly for the last one.
+        let steps = (span / config.step - 1e-9).ceil();
+        if steps >= config.max_points as f64 {
+            return Err(SimulationError::InvalidConfig(format!(
+                "The time span and step give more than {} time points",
+                config.max_points
+            )));
+        }
+        let steps = steps as usize;
+        let times: Vec<f64> = (0..=steps)
+            .map(|i| (config.start_time + i as f64 * config.step).min(config.end_time))
+            .collect();
+        let mut values = vec![x0.clone()];
+        let mut x = x0;
+        let mut h = config.step;
+        for window in times.windows(2) {
+            let (mut t, next) = (window[0], window[1]);
+            match config.method {
+                Method::Rk4 => x = rk4_step(&mut system, t, &x, next - t),
+                Method::Rk45 => {
+                    while t < next {
+                        let h_step = h.min(next - t);
+                        let (x_new, error) = rk45_step(&mut system, t, &x, h_step);
+                        let error = error
+                            .iter()
+                            .zip(x.iter().zip(x_new.iter()))
+                            .map(|(e, (x, x_new))| {
+                                e.abs() / (config.tolerance * (1.0 + x.abs().max(x_new.abs())))
+                            })
+                            .fold(0.0, f64::max);
+                        if error <= 1.0 {
+                            t = if next - t - h_step <= 1e-12 * next.abs().max(1.0) {
+                                next
+                            } else {
+                                t + h_step
+                            };
+                            x = x_new;
+                        }
+                        let factor = if error.is_nan() {
+                            0.2
+                        } else {
+                            (0.9 * error.powf(-0.2)).clamp(0.2, 5.0)
+                        };
+                        h = (h_step * factor).min(config.step);
+                        if h < 1e-12 * t.abs().max(1.0) {
+                            return Err(SimulationError::StepSizeTooSmall(t));
+                        }
+                    }
+                }
+            }
+            values.push(x.clone());
+        }
+
+        Ok(Trajectory {
+            time: times,
+            states: self
+                .states
+                .iter()
+                .enumerate()
+                .map(|(i, state)| StateTrajectory {
+                    id: state.clone(),
+                    values: values.iter().map(|x| x[i]).collect(),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+fn config(
+    method: Method,
+    parameters: &[(&str, f64)],
+    initials: &[(&str, f64)],
+) -> SimulationConfig {
+    let map = |pairs: &[(&str, f64)]| {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect()
+    };
+    SimulationConfig {
+        start_time: 0.0,
+        end_time: 2.0,
+        step: 0.1,
+        method,
+        tolerance: 1e-8,
+        parameters: map(parameters),
+        initials: map(initials),
+        max_points: default_max_points(),
+    }
+}
+
+#[cfg(test)]
+fn sir() -> Vec<FirstOrderODE> {
+    [
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>",
+    ]
+    .iter()
+    .map(|input| input.parse::<FirstOrderODE>().unwrap())
+    .collect()
+}
+
+#[test]
+fn test_exponential_decay() {
+    let odes = vec![
+        "<math><mfrac><mrow><mi>d</mi><mi>x</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>k</mi><mi>x</mi></math>"
+            .parse::<FirstOrderODE>()
+            .unwrap(),
+    ];
+    let system = OdeSystem::from(odes.as_slice());
+    for method in [Method::Rk4, Method::Rk45] {
+        let trajectory = system
+            .simulate(&config(method, &[("k", 0.5)], &[("x", 1.0)]))
+            .unwrap();
+        assert_eq!(trajectory.time.len(), 21);
+        assert_eq!(*trajectory.time.last().unwrap(), 2.0);
+        let x = trajectory.states[0].values.last().unwrap();
+        assert!(
+            (x - (-1.0f64).exp()).abs() < 1e-7,
+            "{}",
+            format!("{method:?}: {x}")
+        );
+    }
+}
+
+#[test]
+fn test_simulate_petri_net() {
+    let odes = sir();
+    let petri_net = PetriNet::from(odes.clone());
+    let system = OdeSystem::try_from(&petri_net).unwrap();
+    let config = config(
+        Method::Rk45,
+        &[("β", 0.002), ("γ", 0.1)],
+        &[("S", 990.0), ("I", 10.0), ("R", 0.0)],
+    );
+    let from_petri_net = system.simulate(&config).unwrap();
+    let from_odes = OdeSystem::from(odes.as_slice()).simulate(&config).unwrap();
+    for state in from_odes.states.iter() {
+        let other = from_petri_net
+            .states
+            .iter()
+            .find(|other| other.id == state.id)
+            .unwrap();
+        for (x, y) in state.values.iter().zip(other.values.iter()) {
+            assert!((x - y).abs() < 1e-6);
+        }
+    }
+    // The total population is conserved.
+    let total: f64 = from_petri_net
+        .states
+        .iter()
+        .map(|state| state.values.last().unwrap())
+        .sum();
+    assert!((total - 1000.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_simulation_errors() {
+    let system = OdeSystem::from(sir().as_slice());
+    assert_eq!(
+        system.simulate(&config(
+            Method::Rk4,
+            &[("β", 0.002), ("γ", 0.1)],
+            &[("S", 990.0)]
+        )),
+        Err(SimulationError::MissingInitial("I".to_string()))
+    );
+    assert_eq!(
+        system.simulate(&config(
+            Method::Rk4,
+            &[("β", 0.002)],
+            &[("S", 990.0), ("I", 10.0), ("R", 0.0)]
+        )),
+        Err(SimulationError::Eval(EvalError::UnboundIdentifier(
+            "γ".to_string()
+        )))
+    );
+
+    // The number of time points is bounded
+    let initials = [("S", 990.0), ("I", 10.0), ("R", 0.0)];
+    let mut huge = config(Method::Rk4, &[("β", 0.002), ("γ", 0.1)], &initials);
+    huge.end_time = 1e12;
+    assert!(matches!(
+        system.simulate(&huge),
+        Err(SimulationError::InvalidConfig(_))
+    ));
+    let mut tiny = config(Method::Rk45, &[("β", 0.002), ("γ", 0.1)], &initials);
+    tiny.step = 1e-12;
+    assert!(matches!(
+        system.simulate(&tiny),
+        Err(SimulationError::InvalidConfig(_))
+    ));
+    let mut limited = config(Method::Rk4, &[("β", 0.002), ("γ", 0.1)], &initials);
+    limited.max_points = 21;
+    assert_eq!(system.simulate(&limited).unwrap().time.len(), 21);
+    limited.max_points = 20;
+    assert!(system.simulate(&limited).is_err());
+    let requested: SimulationConfig = serde_json::from_value(serde_json::json!({
+        "end_time": 1000.0,
+        "step": 1e-9,
+        "max_points": 1e12,
+        "parameters": {"β": 0.002, "γ": 0.1},
+        "initials": {"S": 990.0, "I": 10.0, "R": 0.0}
+    }))
+    .unwrap();
+    assert_eq!(requested.max_points, default_max_points());
+    assert!(matches!(
+        system.simulate(&requested),
+        Err(SimulationError::InvalidConfig(_))
+    ));
+
+    // Extracted models have no initial values until they are given
+    let petri_net = PetriNet::from(sir());
+    assert!(matches!(
+        OdeSystem::try_from(&petri_net).unwrap().simulate(&config(
+            Method::Rk4,
+            &[("β", 0.002), ("γ", 0.1)],
+            &[]
+        )),
+        Err(SimulationError::MissingInitial(_))
+    ));
+}
+
+#[test]
+fn test_trajectory_csv() {
+    let trajectory = Trajectory {
+        time: vec![0.0, 0.5],
+        states: vec![
+            StateTrajectory {
+                id: "S".to_string(),
+                values: vec![1.0, 0.75],
+            },
+            StateTrajectory {
+                id: "I".to_string(),
+                values: vec![0.0, 0.25],
+            },
+        ],
+    };
+    assert_eq!(trajectory.to_csv("t"), "t,S,I\n0,1,0\n0.5,0.75,0.25\n");
+}
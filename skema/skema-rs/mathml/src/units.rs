@@ -0,0 +1,1007 @@
+//! Units of measurement and dimensional analysis of ODE systems.
+//!
+//! Units are products of powers of base units, parsed from expressions such as `1/day`,
+//! `persons` or `m^2 s^-1`. They are propagated through the right hand side of each ODE to check
+//! that every term has the units of the derivative, and to infer the units of parameters that
+//! have none, e.g. β in dS/dt = -β*S*I must be in 1/(day*person) if S and I are in persons and t
+//! is in days.
+//!
+//! Only dimensions are tracked: numeric factors are ignored, and different units of the same
+//! quantity, e.g. days and weeks, are distinct base units.
+
+use crate::{
+    acset::{Metadata, PetriNet, RegNet, Semantics, StockFlow, Time, Units},
+    ast::{operator::Operator, Ci, MathExpression, Mi},
+    parsers::{
+        expansion::Monomial,
+        first_order_ode::FirstOrderODE,
+        math_expression_tree::MathExpressionTree::{self, Atom, Cons},
+        simplification::{number, Number},
+        substitution::{identifier, identifiers, key},
+    },
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use utoipa::ToSchema;
+
+/// Other names of base units, mostly plurals.
+const ALIASES: &[(&str, &str)] = &[
+    ("persons", "person"),
+    ("people", "person"),
+    ("individual", "person"),
+    ("individuals", "person"),
+    ("seconds", "second"),
+    ("minutes", "minute"),
+    ("hours", "hour"),
+    ("days", "day"),
+    ("weeks", "week"),
+    ("months", "month"),
+    ("years", "year"),
+];
+
+/// Names of the unit of dimensionless quantities.
+const DIMENSIONLESS: &[&str] = &["1", "dimensionless", "unitless"];
+
+/// Base units with their exponents.
+type Factors<'a> = Vec<(&'a String, i64)>;
+
+/// A product of powers of base units, e.g. m^2/s.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Unit {
+    /// Exponents of the base units, none of which are zero.
+    pub exponents: BTreeMap<String, i64>,
+}
+
+impl Unit {
+    pub fn dimensionless() -> Unit {
+        Unit::default()
+    }
+
+    /// The base unit of the given name, e.g. person for persons.
+    pub fn base(name: &str) -> Unit {
+        let name = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map_or(name, |(_, base)| base);
+        if DIMENSIONLESS.contains(&name) {
+            return Unit::dimensionless();
+        }
+        Unit {
+            exponents: BTreeMap::from([(name.to_string(), 1)]),
+        }
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.exponents.is_empty()
+    }
+
+    /// The product of two units, failing if an exponent overflows.
+    pub fn mul(&self, other: &Unit) -> Result<Unit, UnitError> {
+        let mut exponents = self.exponents.clone();
+        for (name, exponent) in other.exponents.iter() {
+            let sum = exponents.entry(name.clone()).or_insert(0);
+            *sum = sum
+                .checked_add(*exponent)
+                .ok_or_else(|| overflow(format!("{self}*{other}"), name))?;
+        }
+        exponents.retain(|_, exponent| *exponent != 0);
+        Ok(Unit { exponents })
+    }
+
+    pub fn div(&self, other: &Unit) -> Result<Unit, UnitError> {
+        self.mul(&other.powi(-1)?)
+    }
+
+    /// The nth power of a unit, failing if an exponent overflows.
+    pub fn powi(&self, n: i64) -> Result<Unit, UnitError> {
+        let mut exponents = BTreeMap::new();
+        for (name, exponent) in self.exponents.iter() {
+            let power = exponent
+                .checked_mul(n)
+                .ok_or_else(|| overflow(format!("({self})^{n}"), name))?;
+            if power != 0 {
+                exponents.insert(name.clone(), power);
+            }
+        }
+        Ok(Unit { exponents })
+    }
+
+    /// The nth root, if every exponent is divisible by n.
+    pub fn root(&self, n: i64) -> Option<Unit> {
+        let exponents = self
+            .exponents
+            .iter()
+            .map(|(name, exponent)| {
+                let root = exponent.checked_div(n)?;
+                (root.checked_mul(n)? == *exponent).then(|| (name.clone(), root))
+            })
+            .collect::<Option<_>>()?;
+        Some(Unit { exponents })
+    }
+
+    /// The unit as an expression, e.g. for its content MathML.
+    pub fn to_expression(&self) -> MathExpressionTree {
+        let factor = |name: &String, exponent: i64| {
+            let base = Atom(MathExpression::Ci(Ci::new(
+                None,
+                Box::new(MathExpression::Mi(Mi(name.clone()))),
+                None,
+                None,
+            )));
+            match exponent {
+                1 => base,
+                _ => Cons(
+                    Operator::Power,
+                    vec![base, Atom(MathExpression::Mn(exponent.to_string()))],
+                ),
+            }
+        };
+        let product = |mut factors: Vec<MathExpressionTree>| match factors.len() {
+            0 => Atom(MathExpression::Mn("1".to_string())),
+            1 => factors.pop().unwrap(),
+            _ => Cons(Operator::Multiply, factors),
+        };
+        let (numerator, denominator) = self.split();
+        let numerator = product(numerator.iter().map(|(x, e)| factor(x, *e)).collect());
+        match denominator.is_empty() {
+            true => numerator,
+            false => Cons(
+                Operator::Divide,
+                vec![
+                    numerator,
+                    product(denominator.iter().map(|(x, e)| factor(x, *e)).collect()),
+                ],
+            ),
+        }
+    }
+
+    /// The base units with positive and negated negative exponents.
+    fn split(&self) -> (Factors<'_>, Factors<'_>) {
+        let numerator = self
+            .exponents
+            .iter()
+            .filter(|(_, exponent)| **exponent > 0)
+            .map(|(name, exponent)| (name, *exponent))
+            .collect();
+        let denominator = self
+            .exponents
+            .iter()
+            .filter(|(_, exponent)| **exponent < 0)
+            .map(|(name, exponent)| (name, -exponent))
+            .collect();
+        (numerator, denominator)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let product = |factors: &[(&String, i64)]| -> String {
+            let factors: Vec<String> = factors
+                .iter()
+                .map(|(name, exponent)| match exponent {
+                    1 => name.to_string(),
+                    _ => format!("{name}^{exponent}"),
+                })
+                .collect();
+            factors.join("*")
+        };
+        let (numerator, denominator) = self.split();
+        let numerator = match numerator.is_empty() {
+            true => "1".to_string(),
+            false => product(&numerator),
+        };
+        match denominator.len() {
+            0 => write!(f, "{numerator}"),
+            1 => write!(f, "{}/{}", numerator, product(&denominator)),
+            _ => write!(f, "{}/({})", numerator, product(&denominator)),
+        }
+    }
+}
+
+/// Error raised when a unit expression cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitError {
+    pub expression: String,
+    pub message: String,
+}
+
+impl fmt::Display for UnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid units `{}`: {}", self.expression, self.message)
+    }
+}
+
+impl std::error::Error for UnitError {}
+
+fn overflow(expression: String, name: &str) -> UnitError {
+    UnitError {
+        expression,
+        message: format!("the exponent of {name} overflows"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Number(String),
+    Times,
+    Divide,
+    Power,
+    Minus,
+    LParen,
+    RParen,
+    /// Exponent written with superscripts, e.g. ⁻¹
+    Superscript(i64),
+}
+
+const SUPERSCRIPTS: &str = "⁰¹²³⁴⁵⁶⁷⁸⁹";
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => {}
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                tokens.push(Token::Power);
+            }
+            '*' | '·' | '⋅' | '×' => tokens.push(Token::Times),
+            '/' => tokens.push(Token::Divide),
+            '^' => tokens.push(Token::Power),
+            '-' | '−' => tokens.push(Token::Minus),
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '⁻' | '⁰' | '¹' | '²' | '³' | '⁴' | '⁵' | '⁶' | '⁷' | '⁸' | '⁹' => {
+                let mut superscripts = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !SUPERSCRIPTS.contains(c) {
+                        break;
+                    }
+                    superscripts.push(c);
+                    chars.next();
+                }
+                let exponent: String = superscripts
+                    .chars()
+                    .map(|c| match SUPERSCRIPTS.chars().position(|x| x == c) {
+                        Some(digit) => char::from_digit(digit as u32, 10).unwrap(),
+                        None => '-',
+                    })
+                    .collect();
+                let exponent = exponent
+                    .parse()
+                    .map_err(|_| format!("invalid exponent `{superscripts}`"))?;
+                tokens.push(Token::Superscript(exponent));
+            }
+            _ if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Number(number));
+            }
+            _ if c.is_alphabetic() || matches!(c, '_' | '%' | '°') => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphabetic() || c.is_ascii_digit() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Name(name));
+            }
+            _ => return Err(format!("unexpected character `{c}`")),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parser of unit expressions, in which juxtaposition binds tighter than `*` and `/`, so that
+/// `1/person day` is 1/(person*day).
+struct UnitParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl UnitParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {expected:?}, found {token:?}")),
+            None => Err(format!("expected {expected:?} at the end")),
+        }
+    }
+
+    fn product(&mut self) -> Result<Unit, String> {
+        let mut unit = self.group()?;
+        loop {
+            match self.peek() {
+                Some(Token::Times) => {
+                    self.next();
+                    unit = unit.mul(&self.group()?).map_err(|err| err.message)?;
+                }
+                Some(Token::Divide) => {
+                    self.next();
+                    unit = unit.div(&self.group()?).map_err(|err| err.message)?;
+                }
+                _ => return Ok(unit),
+            }
+        }
+    }
+
+    fn group(&mut self) -> Result<Unit, String> {
+        let mut unit = self.factor()?;
+        while matches!(
+            self.peek(),
+            Some(Token::Name(_) | Token::Number(_) | Token::LParen)
+        ) {
+            unit = unit.mul(&self.factor()?).map_err(|err| err.message)?;
+        }
+        Ok(unit)
+    }
+
+    fn factor(&mut self) -> Result<Unit, String> {
+        let unit = match self.next() {
+            Some(Token::Name(name)) => Unit::base(&name),
+            // Units are compared by dimension only, so a scale factor other than 1 would be lost
+            // Units are compared by dimension only, so a scale factor other than 1 would be lost
+            Some(Token::Number(number)) if number.parse::<f64>() == Ok(1.0) => {
+                Unit::dimensionless()
+            }
+            Some(Token::Number(number)) => {
+                return Err(format!("scale factor `{number}` is not supported"))
+            }
+            Some(Token::LParen) => {
+                let unit = self.product()?;
+                self.expect(Token::RParen)?;
+                unit
+            }
+            Some(token) => return Err(format!("unexpected {token:?}")),
+            None => return Err("unexpected end".to_string()),
+        };
+        let exponent = match self.peek() {
+            Some(Token::Power) => {
+                self.next();
+                self.exponent()?
+            }
+            Some(Token::Superscript(exponent)) => {
+                let exponent = *exponent;
+                self.next();
+                exponent
+            }
+            _ => return Ok(unit),
+        };
+        unit.powi(exponent).map_err(|err| err.message)
+    }
+
+    fn exponent(&mut self) -> Result<i64, String> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let exponent = self.exponent()?;
+                self.expect(Token::RParen)?;
+                Ok(exponent)
+            }
+            Some(Token::Minus) => Ok(-self.exponent()?),
+            Some(Token::Number(exponent)) => exponent
+                .parse()
+                .map_err(|_| format!("exponent `{exponent}` is not an integer")),
+            Some(token) => Err(format!("expected an exponent, found {token:?}")),
+            None => Err("expected an exponent at the end".to_string()),
+        }
+    }
+}
+
+impl FromStr for Unit {
+    type Err = UnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |message: String| UnitError {
+            expression: s.to_string(),
+            message,
+        };
+        let tokens = tokenize(s).map_err(error)?;
+        if tokens.is_empty() {
+            return Ok(Unit::dimensionless());
+        }
+        let mut parser = UnitParser {
+            tokens,
+            position: 0,
+        };
+        let unit = parser.product().map_err(error)?;
+        match parser.peek() {
+            None => Ok(unit),
+            Some(token) => Err(error(format!("unexpected {token:?}"))),
+        }
+    }
+}
+
+impl From<&Unit> for Units {
+    fn from(unit: &Unit) -> Units {
+        Units {
+            expression: unit.to_string(),
+            expression_mathml: unit.to_expression().to_cmml(),
+        }
+    }
+}
+
+/// Inconsistent or unknown units in an equation of an ODE system.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, JsonSchema)]
+pub struct UnitWarning {
+    /// Index of the equation
+    pub equation: usize,
+    /// Index of the term of the expanded right hand side, if the warning is about a single term
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub term: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for UnitWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.term {
+            Some(term) => write!(
+                f,
+                "Equation {}, term {}: {}",
+                self.equation, term, self.message
+            ),
+            None => write!(f, "Equation {}: {}", self.equation, self.message),
+        }
+    }
+}
+
+/// The result of the dimensional analysis of an ODE system.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UnitAnalysis {
+    /// The independent variable.
+    pub time: String,
+    /// Units of the states, parameters and independent variable, given or inferred, by name
+    /// without braces, e.g. S_h for S_{h}.
+    pub units: BTreeMap<String, Unit>,
+    /// Parameters whose units were inferred from the equations, in order of inference.
+    pub inferred: Vec<String>,
+    pub warnings: Vec<UnitWarning>,
+}
+
+/// Propagation of units through an expression, collecting the inconsistencies found on the way.
+struct Propagation<'a> {
+    units: &'a BTreeMap<String, Unit>,
+    issues: Vec<String>,
+}
+
+impl<'a> Propagation<'a> {
+    fn new(units: &'a BTreeMap<String, Unit>) -> Propagation<'a> {
+        Propagation {
+            units,
+            issues: vec![],
+        }
+    }
+
+    fn issue(&mut self, issue: String) {
+        if !self.issues.contains(&issue) {
+            self.issues.push(issue);
+        }
+    }
+
+    /// A unit that was computed with checked arithmetic, reporting an overflow as an issue.
+    fn checked(&mut self, unit: Result<Unit, UnitError>) -> Option<Unit> {
+        unit.map_err(|err| self.issue(err.to_string())).ok()
+    }
+
+    /// The units of the operands, which must agree.
+    fn same(&mut self, args: &[MathExpressionTree]) -> Option<Unit> {
+        let mut first: Option<(&MathExpressionTree, Unit)> = None;
+        for arg in args {
+            let unit = match self.units(arg) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            match &first {
+                None => first = Some((arg, unit)),
+                Some((other, other_unit)) if *other_unit != unit => {
+                    let issue = format!(
+                        "{} has units {} but {} has units {}",
+                        other.to_unicode(),
+                        other_unit,
+                        arg.to_unicode(),
+                        unit
+                    );
+                    self.issue(issue);
+                }
+                _ => {}
+            }
+        }
+        first.map(|(_, unit)| unit)
+    }
+
+    /// Check that an argument of a function, e.g. of exp, is dimensionless.
+    fn dimensionless(&mut self, arg: &MathExpressionTree, within: &MathExpressionTree) {
+        if let Some(unit) = self.units(arg) {
+            if !unit.is_dimensionless() {
+                let issue = format!(
+                    "{} in {} must be dimensionless but has units {}",
+                    arg.to_unicode(),
+                    within.to_unicode(),
+                    unit
+                );
+                self.issue(issue);
+            }
+        }
+    }
+
+    fn power(
+        &mut self,
+        met: &MathExpressionTree,
+        base: &MathExpressionTree,
+        exponent: &MathExpressionTree,
+    ) -> Option<Unit> {
+        match number(exponent) {
+            Some(Number::Rational(p, q)) => {
+                let unit = self.units(base)?;
+                let power = self.checked(unit.powi(p))?.root(q);
+                if power.is_none() {
+                    let issue = format!(
+                        "units {} of {} cannot be raised to the power {}",
+                        unit,
+                        base.to_unicode(),
+                        exponent.to_unicode()
+                    );
+                    self.issue(issue);
+                }
+                power
+            }
+            _ => {
+                self.dimensionless(exponent, met);
+                self.dimensionless(base, met);
+                Some(Unit::dimensionless())
+            }
+        }
+    }
+
+    fn units(&mut self, met: &MathExpressionTree) -> Option<Unit> {
+        let (op, args) = match met {
+            Atom(MathExpression::Mn(_)) => return Some(Unit::dimensionless()),
+            Atom(atom) => {
+                let name = key(&identifier(atom)?);
+                return match name.as_str() {
+                    "π" | "pi" => Some(Unit::dimensionless()),
+                    _ => self.units.get(&name).cloned(),
+                };
+            }
+            Cons(op, args) => (op, args.as_slice()),
+        };
+        match op {
+            Operator::Add | Operator::Subtract | Operator::Min => self.same(args),
+            Operator::Multiply => {
+                let units: Vec<Option<Unit>> = args.iter().map(|arg| self.units(arg)).collect();
+                units
+                    .into_iter()
+                    .try_fold(Unit::dimensionless(), |product, unit| {
+                        self.checked(product.mul(&unit?))
+                    })
+            }
+            Operator::Divide if args.len() == 2 => {
+                let numerator = self.units(&args[0]);
+                let denominator = self.units(&args[1]);
+                self.checked(numerator?.div(&denominator?))
+            }
+            Operator::Power if args.len() == 2 => self.power(met, &args[0], &args[1]),
+            Operator::Sqrt if args.len() == 1 => {
+                let unit = self.units(&args[0])?;
+                let root = unit.root(2);
+                if root.is_none() {
+                    let issue = format!(
+                        "units {} of {} have no square root",
+                        unit,
+                        args[0].to_unicode()
+                    );
+                    self.issue(issue);
+                }
+                root
+            }
+            Operator::Abs if args.len() == 1 => self.units(&args[0]),
+            Operator::Exp
+            | Operator::Logarithm(_)
+            | Operator::Factorial
+            | Operator::Sin
+            | Operator::Cos
+            | Operator::Tan
+            | Operator::Sec
+            | Operator::Csc
+            | Operator::Cot
+            | Operator::Arcsin
+            | Operator::Arccos
+            | Operator::Arctan
+            | Operator::Arcsec
+            | Operator::Arccsc
+            | Operator::Arccot => {
+                for arg in args {
+                    self.dimensionless(arg, met);
+                }
+                Some(Unit::dimensionless())
+            }
+            Operator::Derivative(derivative) if args.len() == 1 => {
+                let unit = self.units(&args[0]);
+                let variable = self.units.get(&key(&derivative.bound_var.to_string()));
+                self.checked(unit?.div(variable?))
+            }
+            Operator::Piecewise => {
+                let mut values = vec![];
+                for piece in args {
+                    match piece {
+                        Cons(Operator::Piece, piece_args) if piece_args.len() == 2 => {
+                            values.push(piece_args[0].clone());
+                            self.units(&piece_args[1]);
+                        }
+                        Cons(Operator::Otherwise, piece_args) if piece_args.len() == 1 => {
+                            values.push(piece_args[0].clone())
+                        }
+                        _ => {}
+                    }
+                }
+                self.same(&values)
+            }
+            Operator::Equals
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessEqual
+            | Operator::Greater
+            | Operator::GreaterEqual => {
+                self.same(args);
+                None
+            }
+            _ => {
+                for arg in args {
+                    self.units(arg);
+                }
+                None
+            }
+        }
+    }
+}
+
+/// The units of the derivative of an ODE's state, if known.
+fn derivative_units(units: &BTreeMap<String, Unit>, ode: &FirstOrderODE) -> Option<Unit> {
+    let state = units.get(&key(&ode.lhs_var.to_string()))?;
+    let time = units.get(&key(&ode.with_respect_to.to_string()))?;
+    state.div(time).ok()
+}
+
+/// The units of the only factor of a term whose units are unknown, if it is a parameter raised
+/// to an integer power, such that the term has the given units.
+fn infer(
+    units: &BTreeMap<String, Unit>,
+    excluded: &[String],
+    target: &Unit,
+    monomial: &Monomial,
+) -> Option<(String, Unit)> {
+    let mut known = Unit::dimensionless();
+    let mut unknown = None;
+    for (base, exponent) in monomial.factors.iter() {
+        let factor = Cons(Operator::Power, vec![base.clone(), exponent.clone()]);
+        if let Some(unit) = Propagation::new(units).units(&factor) {
+            known = known.mul(&unit).ok()?;
+            continue;
+        }
+        let name = match base {
+            Atom(atom) => key(&identifier(atom)?),
+            _ => return None,
+        };
+        let power = number(exponent)
+            .and_then(Number::as_integer)
+            .filter(|power| *power != 0)?;
+        if unknown.is_some() || excluded.contains(&name) || units.contains_key(&name) {
+            return None;
+        }
+        unknown = Some((name, power));
+    }
+    let (name, power) = unknown?;
+    Some((name, target.div(&known).ok()?.root(power)?))
+}
+
+/// Check the units of an ODE system given the units of some of its identifiers, e.g. persons
+/// for S, I and R and days for t. The units of parameters are inferred from the terms in which
+/// all other units are known, and every term of the expanded right hand sides is checked against
+/// the units of its derivative.
+pub fn check_units(odes: &[FirstOrderODE], given: &BTreeMap<String, Unit>) -> UnitAnalysis {
+    let mut units: BTreeMap<String, Unit> = given
+        .iter()
+        .map(|(name, unit)| (key(name), unit.clone()))
+        .collect();
+    let time = odes
+        .first()
+        .map_or("t".to_string(), |ode| key(&ode.with_respect_to.to_string()));
+    // States and the independent variable are never inferred.
+    let mut excluded: Vec<String> = odes
+        .iter()
+        .map(|ode| key(&ode.lhs_var.to_string()))
+        .collect();
+    excluded.push(time.clone());
+    let monomials: Vec<Vec<Monomial>> = odes.iter().map(|ode| ode.rhs.monomials()).collect();
+
+    let mut inferred = vec![];
+    loop {
+        let mut progress = false;
+        for (ode, terms) in odes.iter().zip(monomials.iter()) {
+            let target = match derivative_units(&units, ode) {
+                Some(target) => target,
+                None => continue,
+            };
+            for monomial in terms {
+                if let Some((name, unit)) = infer(&units, &excluded, &target, monomial) {
+                    units.insert(name.clone(), unit);
+                    inferred.push(name);
+                    progress = true;
+                }
+            }
+        }
+        if !progress {
+            break;
+        }
+    }
+
+    let mut warnings = vec![];
+    let mut unknown = vec![];
+    for (equation, (ode, terms)) in odes.iter().zip(monomials.iter()).enumerate() {
+        let target = derivative_units(&units, ode);
+        for (term, monomial) in terms.iter().enumerate() {
+            let mut propagation = Propagation::new(&units);
+            let unit = propagation.units(&monomial.magnitude());
+            let mut issues = propagation.issues;
+            if let (Some(unit), Some(target)) = (unit, &target) {
+                if unit != *target {
+                    issues.push(format!(
+                        "{} has units {} but d{}/d{} has units {}",
+                        monomial.to_expression().to_unicode(),
+                        unit,
+                        ode.lhs_var,
+                        ode.with_respect_to,
+                        target
+                    ));
+                }
+            }
+            warnings.extend(issues.into_iter().map(|message| UnitWarning {
+                equation,
+                term: Some(term),
+                message,
+            }));
+        }
+        let mut names = vec![ode.lhs_var.to_string(), ode.with_respect_to.to_string()];
+        identifiers(&ode.rhs, &mut names);
+        for name in names.iter().map(|name| key(name)) {
+            if !units.contains_key(&name)
+                && !unknown.contains(&name)
+                && !matches!(name.as_str(), "π" | "pi")
+            {
+                warnings.push(UnitWarning {
+                    equation,
+                    term: None,
+                    message: format!("The units of {name} are unknown"),
+                });
+                unknown.push(name);
+            }
+        }
+    }
+
+    UnitAnalysis {
+        time,
+        units,
+        inferred,
+        warnings,
+    }
+}
+
+/// Add warnings to the metadata of a model.
+fn add_warnings(metadata: &mut Option<Metadata>, warnings: &[UnitWarning]) {
+    if warnings.is_empty() {
+        return;
+    }
+    metadata
+        .get_or_insert_with(Metadata::default)
+        .warnings
+        .extend(warnings.iter().map(|warning| warning.to_string()));
+}
+
+/// Set the units of the parameters and independent variable of ODE semantics.
+fn set_semantics_units(semantics: Option<&mut Semantics>, analysis: &UnitAnalysis) {
+    let units = |name: &str| analysis.units.get(&key(name)).map(Units::from);
+    if let Some(semantics) = semantics {
+        for parameter in semantics.ode.parameters.iter_mut().flatten() {
+            parameter.units = units(&parameter.id).or_else(|| parameter.units.take());
+        }
+        if let Some(time_units) = units(&analysis.time) {
+            let time = semantics.ode.time.get_or_insert_with(|| Time {
+                id: analysis.time.clone(),
+                units: None,
+            });
+            time.units = Some(time_units);
+        }
+    }
+}
+
+impl PetriNet {
+    /// Set the units of the states, parameters and independent variable from a dimensional
+    /// analysis, and add its warnings to the metadata.
+    pub fn set_units(&mut self, analysis: &UnitAnalysis) {
+        let units = |name: &str| analysis.units.get(&key(name)).map(Units::from);
+        self.model.states = std::mem::take(&mut self.model.states)
+            .into_iter()
+            .map(|mut state| {
+                state.units = units(&state.id).or(state.units);
+                state
+            })
+            .collect();
+        set_semantics_units(self.semantics.as_mut(), analysis);
+        add_warnings(&mut self.metadata, &analysis.warnings);
+    }
+}
+
+impl StockFlow {
+    /// Set the units of the stocks, parameters and independent variable from a dimensional
+    /// analysis, and add its warnings to the metadata.
+    pub fn set_units(&mut self, analysis: &UnitAnalysis) {
+        let units = |name: &str| analysis.units.get(&key(name)).map(Units::from);
+        self.model.stocks = std::mem::take(&mut self.model.stocks)
+            .into_iter()
+            .map(|mut stock| {
+                stock.units = units(&stock.id).or(stock.units);
+                stock
+            })
+            .collect();
+        set_semantics_units(self.semantics.as_mut(), analysis);
+        add_warnings(&mut self.metadata, &analysis.warnings);
+    }
+}
+
+impl RegNet {
+    /// Set the units of the parameters from a dimensional analysis, and add its warnings to the
+    /// metadata.
+    pub fn set_units(&mut self, analysis: &UnitAnalysis) {
+        for parameter in self.model.parameters.iter_mut().flatten() {
+            if let Some(unit) = analysis.units.get(&key(&parameter.id)) {
+                parameter.units = Some(Units::from(unit));
+            }
+        }
+        add_warnings(&mut self.metadata, &analysis.warnings);
+    }
+}
+
+#[cfg(test)]
+fn sir() -> Vec<FirstOrderODE> {
+    [
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi><mo>-</mo><mi>S</mi></math>",
+    ]
+    .iter()
+    .map(|input| input.parse::<FirstOrderODE>().unwrap())
+    .collect()
+}
+
+#[cfg(test)]
+fn given(units: &[(&str, &str)]) -> BTreeMap<String, Unit> {
+    units
+        .iter()
+        .map(|(name, unit)| (name.to_string(), unit.parse().unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_parse_units() {
+    let unit = |s: &str| s.parse::<Unit>().unwrap().to_string();
+    assert_eq!(unit("1/day"), "1/day");
+    assert_eq!(unit("persons"), "person");
+    assert_eq!(unit("m^2 s^-1"), "m^2/s");
+    assert_eq!(unit("m² s⁻¹"), "m^2/s");
+    assert_eq!(unit("1/person day"), "1/(day*person)");
+    assert_eq!(unit("kg*m/s**2"), "kg*m/s^2");
+    assert_eq!(unit("day/day"), "1");
+    assert_eq!(unit(""), "1");
+    assert!("m^x".parse::<Unit>().is_err());
+    assert!("(m".parse::<Unit>().is_err());
+    assert_eq!(
+        "2 m".parse::<Unit>().unwrap_err().to_string(),
+        "Invalid units `2 m`: scale factor `2` is not supported"
+    );
+    assert!("1000 m".parse::<Unit>().is_err());
+    assert_eq!(unit("1.0 m"), "m");
+    assert_eq!(
+        "m^9223372036854775807 m"
+            .parse::<Unit>()
+            .unwrap_err()
+            .to_string(),
+        "Invalid units `m^9223372036854775807 m`: the exponent of m overflows"
+    );
+    assert!("(m^4611686018427387904)^2".parse::<Unit>().is_err());
+    let unit = "m^4611686018427387904".parse::<Unit>().unwrap();
+    assert!(unit.mul(&unit).is_err());
+    assert_eq!(unit.root(2).unwrap().to_string(), "m^2305843009213693952");
+    assert!(unit.root(3).is_none());
+    assert_eq!(
+        "1/(person*day)".parse::<Unit>().unwrap(),
+        "day^-1 persons^-1".parse().unwrap()
+    );
+}
+
+#[test]
+fn test_infer_and_check() {
+    let analysis = check_units(
+        &sir(),
+        &given(&[
+            ("S", "persons"),
+            ("I", "persons"),
+            ("R", "persons"),
+            ("t", "days"),
+        ]),
+    );
+    assert_eq!(analysis.inferred, vec!["β", "γ"]);
+    assert_eq!(analysis.units["β"].to_string(), "1/(day*person)");
+    assert_eq!(analysis.units["γ"].to_string(), "1/day");
+    // The last term of dR/dt is missing a rate.
+    assert_eq!(
+        analysis.warnings,
+        vec![UnitWarning {
+            equation: 2,
+            term: Some(1),
+            message: "−S has units person but dR/dt has units person/day".to_string()
+        }]
+    );
+}
+
+#[test]
+fn test_inconsistent_subexpressions() {
+    let ode = "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>k</mi><mi>S</mi><mrow><mi>exp</mi><mo>(</mo><mo>-</mo><mi>t</mi><mo>)</mo></mrow></math>"
+        .parse::<FirstOrderODE>()
+        .unwrap();
+    let analysis = check_units(&[ode], &given(&[("S", "persons"), ("t", "day")]));
+    assert_eq!(analysis.units["k"].to_string(), "1/day");
+    assert_eq!(analysis.warnings.len(), 1);
+    assert_eq!(
+        analysis.warnings[0].to_string(),
+        "Equation 0, term 0: −t in exp(−t) must be dimensionless but has units day"
+    );
+
+    let mut petri_net = PetriNet::from(sir());
+    petri_net.set_units(&check_units(&sir(), &given(&[("S", "persons")])));
+    let state = petri_net.model.states.iter().find(|s| s.id == "S").unwrap();
+    assert_eq!(state.units.as_ref().unwrap().expression, "person");
+    // Without units for t, nothing can be inferred.
+    assert!(petri_net
+        .metadata
+        .unwrap()
+        .warnings
+        .contains(&"Equation 0: The units of t are unknown".to_string()));
+
+    // Without any units, every identifier is reported.
+    let analysis = check_units(&sir(), &BTreeMap::new());
+    assert_eq!(analysis.warnings.len(), 6);
+    assert_eq!(
+        analysis.warnings[0].to_string(),
+        "Equation 0: The units of S are unknown"
+    );
+
+    // Overflowing units are reported instead of panicking.
+    let huge = "m^4611686018427387904";
+    let analysis = check_units(
+        &sir(),
+        &given(&[("S", huge), ("I", huge), ("β", "1/day"), ("t", "day")]),
+    );
+    assert!(analysis
+        .warnings
+        .iter()
+        .any(|warning| warning.message.ends_with("the exponent of m overflows")));
+}
@@ -0,0 +1,270 @@
+//! Validation of AMRs against the TA4 JSON schemas from
+//! https://github.com/DARPA-ASKEM/Model-Representations, which are bundled in `schemas/` so that
+//! validation works offline. Violations are reported with the JSON path of the offending value,
+//! e.g. `$.model.transitions[0].input`.
+
+use crate::acset::{PetriNet, RegNet, StockFlow};
+use jsonschema::{paths::PathChunk, Draft, JSONSchema};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt, sync::OnceLock};
+use utoipa::ToSchema;
+
+/// A TA4 model representation schema.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Deserialize, Serialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AmrSchema {
+    PetriNet,
+    RegNet,
+    StockFlow,
+}
+
+impl AmrSchema {
+    /// The URL of the schema, for the `schema` field of AMR headers.
+    pub fn url(self) -> &'static str {
+        match self {
+            AmrSchema::PetriNet => "https://github.com/DARPA-ASKEM/Model-Representations/blob/main/petrinet/petrinet_schema.json",
+            AmrSchema::RegNet => "https://raw.githubusercontent.com/DARPA-ASKEM/Model-Representations/regnet_v0.1/regnet/regnet_schema.json",
+            AmrSchema::StockFlow => "https://raw.githubusercontent.com/DARPA-ASKEM/Model-Representations/stockflow_v0.1/stockflow/stockflow_schema.json",
+        }
+    }
+
+    fn document(self) -> &'static str {
+        match self {
+            AmrSchema::PetriNet => include_str!("../schemas/petrinet_schema.json"),
+            AmrSchema::RegNet => include_str!("../schemas/regnet_schema.json"),
+            AmrSchema::StockFlow => include_str!("../schemas/stockflow_schema.json"),
+        }
+    }
+
+    fn compiled(self) -> &'static JSONSchema {
+        static PETRI_NET: OnceLock<JSONSchema> = OnceLock::new();
+        static REGNET: OnceLock<JSONSchema> = OnceLock::new();
+        static STOCK_FLOW: OnceLock<JSONSchema> = OnceLock::new();
+        let cell = match self {
+            AmrSchema::PetriNet => &PETRI_NET,
+            AmrSchema::RegNet => &REGNET,
+            AmrSchema::StockFlow => &STOCK_FLOW,
+        };
+        cell.get_or_init(|| {
+            let document: Value =
+                serde_json::from_str(self.document()).expect("Bundled schemas are valid JSON");
+            JSONSchema::options()
+                .with_draft(Draft::Draft202012)
+                .compile(&document)
+                .expect("Bundled schemas are valid JSON schemas")
+        })
+    }
+
+    /// The schema an AMR claims to conform to, from the `schema_name` or `schema` of its header,
+    /// or else from the fields of its model, e.g. `states` and `transitions` for a Petri net.
+    pub fn detect(amr: &Value) -> Option<AmrSchema> {
+        let schemas = [AmrSchema::PetriNet, AmrSchema::RegNet, AmrSchema::StockFlow];
+        let header = &amr["header"];
+        if let Some(name) = header["schema_name"].as_str() {
+            let name = name.to_lowercase();
+            let schema = schemas.iter().find(|schema| schema.to_string() == name);
+            if schema.is_some() {
+                return schema.copied();
+            }
+        }
+        if let Some(url) = header["schema"].as_str() {
+            let schema = schemas
+                .iter()
+                .find(|schema| url.contains(&format!("{schema}_schema")));
+            if schema.is_some() {
+                return schema.copied();
+            }
+        }
+        let model = &amr["model"];
+        if model.get("transitions").is_some() {
+            Some(AmrSchema::PetriNet)
+        } else if model.get("edges").is_some() {
+            Some(AmrSchema::RegNet)
+        } else if model.get("flows").is_some() {
+            Some(AmrSchema::StockFlow)
+        } else {
+            None
+        }
+    }
+
+    /// Validate an AMR against the schema.
+    pub fn validate(self, amr: &Value) -> Vec<Violation> {
+        match self.compiled().validate(amr) {
+            Ok(()) => vec![],
+            Err(errors) => errors
+                .map(|error| Violation {
+                    path: json_path(error.instance_path.iter()),
+                    message: error.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for AmrSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmrSchema::PetriNet => write!(f, "petrinet"),
+            AmrSchema::RegNet => write!(f, "regnet"),
+            AmrSchema::StockFlow => write!(f, "stockflow"),
+        }
+    }
+}
+
+/// A JSON path, e.g. `$.model.states[0].id`, with property names that are not identifiers
+/// quoted, e.g. `$.metadata["$ref"]`.
+fn json_path<'a>(chunks: impl Iterator<Item = &'a PathChunk>) -> String {
+    let mut path = "$".to_string();
+    for chunk in chunks {
+        match chunk {
+            PathChunk::Property(name)
+                if !name.is_empty()
+                    && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+                    && !name.starts_with(|c: char| c.is_ascii_digit()) =>
+            {
+                path.push('.');
+                path.push_str(name);
+            }
+            PathChunk::Property(name) => path.push_str(&format!("[{name:?}]")),
+            PathChunk::Index(i) => path.push_str(&format!("[{i}]")),
+            PathChunk::Keyword(keyword) => path.push_str(&format!("[{keyword:?}]")),
+        }
+    }
+    path
+}
+
+/// A value of an AMR that does not conform to its schema.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize, ToSchema, JsonSchema)]
+pub struct Violation {
+    /// JSON path of the offending value, e.g. `$.model.transitions[0]`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Serialize a model and validate it against a schema.
+pub fn validate_model<T: Serialize>(model: &T, schema: AmrSchema) -> Vec<Violation> {
+    match serde_json::to_value(model) {
+        Ok(amr) => schema.validate(&amr),
+        Err(err) => vec![Violation {
+            path: "$".to_string(),
+            message: err.to_string(),
+        }],
+    }
+}
+
+impl PetriNet {
+    /// The ways in which the Petri net does not conform to the TA4 schema.
+    pub fn validate(&self) -> Vec<Violation> {
+        validate_model(self, AmrSchema::PetriNet)
+    }
+}
+
+impl RegNet {
+    /// The ways in which the RegNet does not conform to the TA4 schema.
+    pub fn validate(&self) -> Vec<Violation> {
+        validate_model(self, AmrSchema::RegNet)
+    }
+}
+
+impl StockFlow {
+    /// The ways in which the stock and flow model does not conform to the TA4 schema.
+    pub fn validate(&self) -> Vec<Violation> {
+        validate_model(self, AmrSchema::StockFlow)
+    }
+}
+
+#[cfg(test)]
+fn odes(inputs: &[&str]) -> Vec<crate::parsers::first_order_ode::FirstOrderODE> {
+    inputs.iter().map(|input| input.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_generated_amrs() {
+    let petri_net = PetriNet::from(odes(&[
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>",
+    ]));
+    assert_eq!(petri_net.header.schema, AmrSchema::PetriNet.url());
+    assert_eq!(petri_net.validate(), vec![]);
+
+    // Sinks, e.g. the deaths of predators, have empty outputs
+    let lotka_volterra = PetriNet::from(odes(&[
+        "<math><mfrac><mrow><mi>d</mi><mi>x</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>α</mi><mi>x</mi><mo>-</mo><mi>β</mi><mi>x</mi><mi>y</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>y</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>δ</mi><mi>x</mi><mi>y</mi><mo>-</mo><mi>γ</mi><mi>y</mi></math>",
+    ]));
+    assert!(lotka_volterra
+        .model
+        .transitions
+        .iter()
+        .any(|transition| transition.output == Some(vec![])));
+    assert_eq!(lotka_volterra.validate(), vec![]);
+
+    let regnet = RegNet::from(odes(&[
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>α</mi><mi>R</mi><mo>-</mo><mi>β</mi><mi>R</mi><mi>F</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>F</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>δ</mi><mi>R</mi><mi>F</mi><mo>-</mo><mi>γ</mi><mi>F</mi></math>",
+    ]));
+    assert_eq!(regnet.validate(), vec![]);
+
+    let stock_flow = StockFlow::from(odes(&[
+        "<math><mfrac><mrow><mi>d</mi><mi>S</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mo>-</mo><mi>β</mi><mi>S</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>I</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>β</mi><mi>S</mi><mi>I</mi><mo>-</mo><mi>γ</mi><mi>I</mi></math>",
+        "<math><mfrac><mrow><mi>d</mi><mi>R</mi></mrow><mrow><mi>d</mi><mi>t</mi></mrow></mfrac><mo>=</mo><mi>γ</mi><mi>I</mi></math>",
+    ]));
+    assert_eq!(
+        AmrSchema::detect(&serde_json::to_value(&stock_flow).unwrap()),
+        Some(AmrSchema::StockFlow)
+    );
+    assert_eq!(stock_flow.validate(), vec![]);
+}
+
+#[test]
+fn test_violations() {
+    let amr: Value = serde_json::from_str(
+        r#"{
+            "header": {"name": "SIR", "schema_name": "PetriNet", "description": ""},
+            "model": {
+                "states": [{"id": "S"}, {"name": "I"}],
+                "transitions": [{"id": "inf", "input": ["S", "I"]}]
+            },
+            "semantics": {"ode": {"parameters": [{"id": "β", "value": "0.1"}]}}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(AmrSchema::detect(&amr), Some(AmrSchema::PetriNet));
+    let mut paths: Vec<String> = AmrSchema::PetriNet
+        .validate(&amr)
+        .into_iter()
+        .map(|violation| violation.path)
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            "$.header",
+            "$.model.states[1]",
+            "$.model.transitions[0]",
+            "$.semantics.ode.parameters[0].value",
+        ]
+    );
+
+    // Without a header, the schema is detected from the fields of the model.
+    let amr = serde_json::json!({"model": {"vertices": [], "edges": []}});
+    assert_eq!(AmrSchema::detect(&amr), Some(AmrSchema::RegNet));
+    let violations = AmrSchema::RegNet.validate(&amr);
+    assert_eq!(violations.len(), 2);
+    assert!(
+        violations
+            .iter()
+            .any(|violation| violation.to_string()
+                == "$.model: \"parameters\" is a required property")
+    );
+}
@@ -52,6 +52,14 @@ async fn main() -> std::io::Result<()> {
             skema::services::mathml::get_decapodes,
             skema::services::mathml::get_met,
             skema::services::mathml::get_gamr,
+            skema::services::mathml::substitute,
+            skema::services::latex::get_latex_met,
+            skema::services::latex::get_latex_amr,
+            skema::services::latex::get_latex_decapodes,
+            skema::services::simulation::simulate,
+            skema::services::codegen::codegen,
+            skema::services::amr::equations,
+            skema::services::amr::validate,
             gromet::get_model_ids,
             gromet::post_model,
             gromet::delete_model,
@@ -59,8 +67,10 @@ async fn main() -> std::io::Result<()> {
             gromet::get_named_opis,
             gromet::get_named_ports,
             gromet::get_model_RN,
+            gromet::get_model_SF,
             gromet::model2PN,
             gromet::model2RN,
+            gromet::model2SF,
             gromet::model2MET,
             gromet::model2GAMR,
             ping,
@@ -71,14 +81,26 @@ async fn main() -> std::io::Result<()> {
                 mathml::parsers::math_expression_tree::MathExpressionTree,
                 mathml::ast::MathExpression,
                 mathml::ast::operator::Operator,
+                mathml::parsers::generic_mathml::ParseDiagnostic,
+                skema::services::mathml::EquationError,
+                skema::services::mathml::Substitution,
+                skema::services::mathml::SubstitutionRequest,
                 mathml::parsers::decapodes_serialization::DecapodesCollection,
                 mathml::parsers::decapodes_serialization::WiringDiagram,
                 mathml::acset::GeneralizedAMR,
                 mathml::acset::GeneralSemantics,
                 mathml::acset::AMRmathml,
+                mathml::acset::MathMLEquations,
+                mathml::acset::AMRlatex,
                 mathml::acset::RegNet,
                 mathml::acset::ModelRegNet,
                 mathml::acset::ModelPetriNet,
+                mathml::acset::StockFlow,
+                mathml::acset::ModelStockFlow,
+                mathml::acset::Stock,
+                mathml::acset::Flow,
+                mathml::acset::Auxiliary,
+                mathml::acset::Link,
                 mathml::acset::PetriNet,
                 mathml::acset::State,
                 mathml::acset::Transition,
@@ -88,6 +110,8 @@ async fn main() -> std::io::Result<()> {
                 mathml::acset::Properties,
                 mathml::acset::Parameter,
                 mathml::acset::Distribution,
+                mathml::acset::DistributionParameters,
+                mathml::acset::Float,
                 mathml::acset::RegState,
                 mathml::acset::RegTransition,
                 mathml::acset::Units,
@@ -96,6 +120,21 @@ async fn main() -> std::io::Result<()> {
                 mathml::acset::Ode,
                 mathml::acset::Observable,
                 mathml::acset::Time,
+                mathml::simulation::SimulationConfig,
+                mathml::simulation::Method,
+                mathml::simulation::Trajectory,
+                mathml::simulation::StateTrajectory,
+                skema::services::simulation::SimulationRequest,
+                skema::services::simulation::SimulationModel,
+                skema::services::simulation::TrajectoryFormat,
+                mathml::codegen::Language,
+                mathml::codegen::GeneratedCode,
+                skema::services::codegen::CodegenRequest,
+                skema::services::amr::AmrModel,
+                skema::services::amr::AmrEquations,
+                skema::services::amr::ValidationReport,
+                mathml::validation::AmrSchema,
+                mathml::validation::Violation,
                 skema::Attribute,
                 skema::FunctionNet,
                 skema::FunctionType,
@@ -151,9 +190,19 @@ async fn main() -> std::io::Result<()> {
             .service(skema::services::mathml::get_decapodes)
             .service(skema::services::mathml::get_met)
             .service(skema::services::mathml::get_gamr)
+            .service(skema::services::mathml::substitute)
+            .service(skema::services::latex::get_latex_met)
+            .service(skema::services::latex::get_latex_amr)
+            .service(skema::services::latex::get_latex_decapodes)
+            .service(skema::services::simulation::simulate)
+            .service(skema::services::codegen::codegen)
+            .service(skema::services::amr::equations)
+            .service(skema::services::amr::validate)
             .service(gromet::get_model_RN)
+            .service(gromet::get_model_SF)
             .service(gromet::model2PN)
             .service(gromet::model2RN)
+            .service(gromet::model2SF)
             .service(gromet::model2MET)
             .service(gromet::model2GAMR)
             .service(ping)
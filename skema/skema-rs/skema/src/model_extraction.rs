@@ -15,6 +15,7 @@ use mathml::ast::Type::Function;
 use mathml::ast::{MathExpression, Mi};
 use mathml::parsers::first_order_ode::{flatten_mults, FirstOrderODE};
 use mathml::parsers::math_expression_tree::MathExpressionTree;
+use mathml::parsers::observables::Definition;
 
 use neo4rs;
 use neo4rs::{query, Error};
@@ -62,7 +63,20 @@ pub struct ModelEdge {
  */
 #[allow(non_snake_case)]
 pub async fn module_id2mathml_MET_ast(module_id: i64, config: Config) -> Vec<FirstOrderODE> {
+    module_id2mathml_model(module_id, config).await.0
+}
+
+/**
+ * This is the same as module_id2mathml_MET_ast, but also returns the definitions of the names
+ * assigned numeric literals in the core dynamics, e.g. beta = 0.3, which carry the values of the
+ * parameters and initial conditions of the model.
+ */
+pub async fn module_id2mathml_model(
+    module_id: i64,
+    config: Config,
+) -> (Vec<FirstOrderODE>, Vec<Definition>) {
     let mut core_dynamics_ast = Vec::<FirstOrderODE>::new();
+    let mut definitions = Vec::<Definition>::new();
 
     let core_id = find_pn_dynamics(module_id, config.clone()).await;
 
@@ -84,12 +98,12 @@ pub async fn module_id2mathml_MET_ast(module_id: i64, config: Config) -> Vec<Fir
         };
         core_dynamics_ast.push(fo_eq);
     } else {
-        core_dynamics_ast = subgrapg2_core_dyn_MET_ast(core_id[0], config.clone())
+        (core_dynamics_ast, definitions) = subgrapg2_core_dyn_model(core_id[0], config.clone())
             .await
             .unwrap();
     }
 
-    core_dynamics_ast
+    (core_dynamics_ast, definitions)
 }
 
 /**
@@ -168,6 +182,17 @@ pub async fn subgrapg2_core_dyn_MET_ast(
     root_node_id: i64,
     config: Config,
 ) -> Result<Vec<FirstOrderODE>, Error> {
+    Ok(subgrapg2_core_dyn_model(root_node_id, config).await?.0)
+}
+
+/**
+ * This extracts the FirstOrderODE's of the core dynamics along with the definitions of the names
+ * assigned numeric literals, which are the expressions without any primitives.
+ */
+pub async fn subgrapg2_core_dyn_model(
+    root_node_id: i64,
+    config: Config,
+) -> Result<(Vec<FirstOrderODE>, Vec<Definition>), Error> {
     // get the petgraph of the subgraph
     let graph = subgraph2petgraph(root_node_id, config.clone()).await;
 
@@ -180,6 +205,7 @@ pub async fn subgrapg2_core_dyn_MET_ast(
     }
 
     let mut core_dynamics = Vec::<FirstOrderODE>::new();
+    let mut definitions = Vec::<Definition>::new();
 
     // initialize vector to collect all expression wiring graphs
     for i in 0..expression_nodes.len() {
@@ -216,10 +242,47 @@ pub async fn subgrapg2_core_dyn_MET_ast(
             } else {
                 core_dynamics.push(tree_2_MET_ast(expr, root_node[0]).unwrap());
             }
+        } else if prim_counter == 0 {
+            if let Some(definition) = literal_definition(&sub_w) {
+                definitions.push(definition);
+            }
         }
     }
 
-    Ok(core_dynamics)
+    // a literal assigned to a state of the dynamics is its initial condition
+    let states: Vec<String> = core_dynamics
+        .iter()
+        .map(|ode| ode.lhs_var.to_string())
+        .collect();
+    for definition in definitions.iter_mut() {
+        definition.initial = states.contains(&definition.lhs_var);
+    }
+
+    Ok((core_dynamics, definitions))
+}
+
+/// This gets the definition of a named output port wired to a numeric literal, e.g. beta = 0.3,
+/// from the wires only subgraph of an expression. The literal may be wired through un-named ports.
+fn literal_definition(graph: &petgraph::Graph<ModelNode, ModelEdge>) -> Option<Definition> {
+    let opos: Vec<NodeIndex> = graph
+        .node_indices()
+        .filter(|node| graph[*node].label == *"Opo")
+        .collect();
+    if opos.len() != 1 {
+        return None;
+    }
+    let opo = opos[0];
+    let lhs_var = graph[opo].name.clone()?;
+    let mut dfs = Dfs::new(graph, opo);
+    let literal =
+        std::iter::from_fn(|| dfs.next(graph)).find(|node| graph[*node].label == *"Literal")?;
+    let value = graph[literal].value.as_ref()?.value.replace('\"', "");
+    value.parse::<f64>().ok()?;
+    Some(Definition {
+        lhs_var,
+        initial: false,
+        rhs: MathExpressionTree::Atom(MathExpression::Mn(value)),
+    })
 }
 
 /**
@@ -306,14 +369,19 @@ pub fn get_args_MET(
             // asummption it is atomic
             if graph[node].label.clone() == *"Literal" {
                 let temp_string = graph[node].value.clone().unwrap().value.replace('\"', "");
-                let arg2 = MathExpression::Mi(Mi(temp_string.clone()));
-                let ci_arg = Ci {
-                    r#type: Some(mathml::ast::Type::Real),
-                    content: Box::new(arg2),
-                    func_of: None,
-                    notation: None,
-                };
-                args.push(MathExpressionTree::Atom(MathExpression::Ci(ci_arg.clone())));
+                // numeric literals are kept as numbers so they are not taken for parameters
+                if temp_string.parse::<f64>().is_ok() {
+                    args.push(MathExpressionTree::Atom(MathExpression::Mn(temp_string)));
+                } else {
+                    let arg2 = MathExpression::Mi(Mi(temp_string.clone()));
+                    let ci_arg = Ci {
+                        r#type: Some(mathml::ast::Type::Real),
+                        content: Box::new(arg2),
+                        func_of: None,
+                        notation: None,
+                    };
+                    args.push(MathExpressionTree::Atom(MathExpression::Ci(ci_arg.clone())));
+                }
             } else {
                 let temp_string = graph[node].name.as_ref().unwrap().clone();
                 let arg2 = MathExpression::Mi(Mi(temp_string.clone()));
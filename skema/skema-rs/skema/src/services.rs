@@ -1,2 +1,6 @@
+pub mod amr;
+pub mod codegen;
 pub mod gromet;
+pub mod latex;
 pub mod mathml;
+pub mod simulation;
@@ -0,0 +1,146 @@
+use actix_web::{put, web, HttpResponse};
+use mathml::{
+    acset::{PetriNet, RegNet},
+    parsers::{first_order_ode::FirstOrderODE, math_expression_tree::MathExpressionTree},
+    validation::{validate_model, AmrSchema, Violation},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use utoipa;
+use utoipa::{IntoParams, ToSchema};
+
+/// Opt-in strict mode of the endpoints that return AMRs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Strictness {
+    /// Refuse to return a model that does not conform to its TA4 schema, and respond with status
+    /// 422 and the violations instead
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// How algebraic definitions among the equations of a model, e.g. N = S + I + R, enter the AMR.
+/// Either way, they are included as its observables.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ObservableOptions {
+    /// Substitute the definitions into the rates, instead of referring to the observables by
+    /// name
+    #[serde(default)]
+    pub inline_observables: bool,
+}
+
+/// Respond with a model, unless strict mode is on and the model does not conform to its schema,
+/// in which case respond with the violations.
+pub(crate) fn model_response<T: Serialize>(
+    model: &T,
+    schema: AmrSchema,
+    strictness: Strictness,
+) -> HttpResponse {
+    if strictness.strict {
+        let violations = validate_model(model, schema);
+        if !violations.is_empty() {
+            return HttpResponse::UnprocessableEntity().json(violations);
+        }
+    }
+    HttpResponse::Ok().json(model)
+}
+
+/// The result of validating an AMR against its schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ValidationReport {
+    pub schema: AmrSchema,
+    pub valid: bool,
+    pub violations: Vec<Violation>,
+}
+
+/// Validate a Petri net, RegNet or stock and flow AMR against its TA4 JSON schema, which is
+/// determined from its header or, failing that, from the fields of its model. Violations are
+/// reported with the JSON path of the offending value.
+#[utoipa::path(
+    request_body(content = String, description = "AMR JSON", content_type = "application/json"),
+    responses(
+        (
+            status = 200,
+            body = ValidationReport
+        ),
+        (
+            status = 400,
+            description = "The schema of the AMR cannot be determined"
+        )
+    )
+)]
+#[put("/amr/validate")]
+pub async fn validate(payload: web::Json<Value>) -> HttpResponse {
+    match AmrSchema::detect(&payload) {
+        Some(schema) => {
+            let violations = schema.validate(&payload);
+            HttpResponse::Ok().json(web::Json(ValidationReport {
+                schema,
+                valid: violations.is_empty(),
+                violations,
+            }))
+        }
+        None => HttpResponse::BadRequest()
+            .content_type("text/plain")
+            .body("The AMR is neither a Petri net, a RegNet nor a stock and flow model."),
+    }
+}
+
+/// AMR to reconstruct the equations of, either a Petri net or a RegNet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum AmrModel {
+    PetriNet(PetriNet),
+    RegNet(RegNet),
+}
+
+/// Equations reconstructed from an AMR, one per state, in each of the supported formats.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AmrEquations {
+    pub met: Vec<MathExpressionTree>,
+    pub latex: Vec<String>,
+    /// Presentation MathML documents.
+    pub mathml: Vec<String>,
+}
+
+impl From<&[FirstOrderODE]> for AmrEquations {
+    fn from(odes: &[FirstOrderODE]) -> Self {
+        let met: Vec<MathExpressionTree> = odes.iter().map(FirstOrderODE::to_met).collect();
+        AmrEquations {
+            latex: met.iter().map(MathExpressionTree::to_latex).collect(),
+            mathml: odes.iter().map(FirstOrderODE::to_pmml).collect(),
+            met,
+        }
+    }
+}
+
+/// Reconstruct the ODEs of a Petri net AMR, from the rates of its semantics or by mass action,
+/// or of a RegNet AMR, from the rate constants and signs of its vertices and edges.
+#[utoipa::path(
+    request_body = AmrModel,
+    responses(
+        (
+            status = 200,
+            body = AmrEquations
+        ),
+        (
+            status = 400,
+            description = "The AMR has a transition without a rate, or a rate that cannot be parsed"
+        )
+    )
+)]
+#[put("/amr/equations")]
+pub async fn equations(payload: web::Json<AmrModel>) -> HttpResponse {
+    let odes = match &*payload {
+        AmrModel::PetriNet(petri_net) => petri_net.to_odes(),
+        AmrModel::RegNet(regnet) => regnet.to_odes(),
+    };
+    match odes {
+        Ok(odes) => HttpResponse::Ok().json(web::Json(AmrEquations::from(odes.as_slice()))),
+        Err(err) => HttpResponse::BadRequest()
+            .content_type("text/plain")
+            .body(err.to_string()),
+    }
+}
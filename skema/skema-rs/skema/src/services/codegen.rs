@@ -0,0 +1,54 @@
+use crate::services::{
+    mathml::EquationError,
+    simulation::{ode_system, SimulationModel},
+};
+use actix_web::{put, web, HttpResponse};
+use mathml::{codegen::Language, parsers::generic_mathml::ParseDiagnostic};
+use serde::{Deserialize, Serialize};
+
+use utoipa;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CodegenRequest {
+    /// Petri net AMR or array of MathML strings with one ODE each
+    pub model: SimulationModel,
+    #[serde(default)]
+    pub language: Language,
+}
+
+/// Generate the right hand side function of a Petri net AMR or a system of MathML ODEs in Python
+/// (for scipy.integrate.solve_ivp), Rust or Julia, along with the order of its states and
+/// parameters.
+#[utoipa::path(
+    request_body = CodegenRequest,
+    responses(
+        (
+            status = 200,
+            body = GeneratedCode
+        ),
+        (
+            status = 400,
+            description = "The model cannot be parsed",
+            body = EquationError
+        ),
+        (
+            status = 422,
+            description = "The model contains an operator that has no counterpart in the language, e.g. a derivative",
+            body = EquationError
+        )
+    )
+)]
+#[put("/codegen")]
+pub async fn codegen(payload: web::Json<CodegenRequest>) -> HttpResponse {
+    match ode_system(&payload.model) {
+        Ok(system) => match system.generate(payload.language) {
+            Ok(generated) => HttpResponse::Ok().json(web::Json(generated)),
+            Err(err) => HttpResponse::UnprocessableEntity().json(EquationError {
+                equation: None,
+                error: ParseDiagnostic::new(err.to_string()),
+            }),
+        },
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
+}
@@ -1,17 +1,23 @@
 //! REST API endpoints related to CRUD operations and other queries on GroMEt objects.
 use crate::config::Config;
 use crate::database::{parse_gromet_queries, run_queries};
-use crate::model_extraction::module_id2mathml_MET_ast;
+use crate::model_extraction::{module_id2mathml_MET_ast, module_id2mathml_model};
+use crate::services::{
+    amr::{ObservableOptions, Strictness},
+    mathml::amr_response,
+};
 use crate::ModuleCollection;
 use actix_web::web::ServiceConfig;
 use actix_web::{delete, get, post, put, web, HttpResponse};
-use mathml::acset::{GeneralizedAMR, PetriNet, RegNet};
+use mathml::acset::{GeneralizedAMR, PetriNet, RegNet, StockFlow};
 
 use mathml::ast::{self, MathExpression};
+use mathml::parsers::first_order_ode::FirstOrderODE;
 use mathml::parsers::math_expression_tree::MathExpressionTree;
+use mathml::parsers::observables::Definition;
 use neo4rs;
 use neo4rs::{query, Error, Node};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use mathml::ast::operator::DerivativeNotation;
@@ -28,25 +34,17 @@ pub fn configure() -> impl FnOnce(&mut ServiceConfig) {
             .service(get_model_ids);
     }
 }
-#[allow(non_snake_case)]
-pub async fn model_to_RN(gromet: ModuleCollection, config: Config) -> Result<RegNet, Error> {
-    let module_id = push_model_to_db(gromet, config.clone()).await; // pushes model to db and gets id
-    let ref_module_id1 = module_id.as_ref();
-    let ref_module_id2 = module_id.as_ref();
-    let mathml_ast = module_id2mathml_MET_ast(*ref_module_id1.unwrap(), config.clone()).await; // turns model into mathml ast equations
-    let _del_response = delete_module(*ref_module_id2.unwrap(), config.clone()).await; // deletes model from db
-    Ok(RegNet::from(mathml_ast))
-}
 
-// this is updated to mathexpressiontrees
-#[allow(non_snake_case)]
-pub async fn model_to_PN(gromet: ModuleCollection, config: Config) -> Result<PetriNet, Error> {
-    let module_id = push_model_to_db(gromet, config.clone()).await; // pushes model to db and gets id
-    let ref_module_id1 = module_id.as_ref();
-    let ref_module_id2 = module_id.as_ref();
-    let mathml_ast = module_id2mathml_MET_ast(*ref_module_id1.unwrap(), config.clone()).await; // turns model into mathml ast equations
-    let _del_response = delete_module(*ref_module_id2.unwrap(), config.clone()).await; // deletes model from db
-    Ok(PetriNet::from(mathml_ast))
+/// Push a model to the database, extract its ODEs and the definitions of its parameters,
+/// initial conditions and observables, and delete it again.
+pub async fn extract_model(
+    gromet: ModuleCollection,
+    config: Config,
+) -> Result<(Vec<FirstOrderODE>, Vec<Definition>), Error> {
+    let module_id = push_model_to_db(gromet, config.clone()).await?; // pushes model to db and gets id
+    let model = module_id2mathml_model(module_id, config.clone()).await; // turns model into mathml ast equations
+    delete_module(module_id, config).await?; // deletes model from db
+    Ok(model)
 }
 
 pub async fn push_model_to_db(gromet: ModuleCollection, config: Config) -> Result<i64, Error> {
@@ -261,32 +259,97 @@ pub async fn get_named_opis(path: web::Path<i64>, config: web::Data<Config>) ->
 /// This retrieves a RegNet AMR based on model id.
 #[allow(non_snake_case)]
 #[utoipa::path(
+    params(Strictness),
     responses(
         (
             status = 200, description = "Successfully retrieved RN AMR",
             body = RegNet
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
         )
     )
 )]
 #[get("/models/{id}/RN")]
-pub async fn get_model_RN(path: web::Path<i64>, config: web::Data<Config>) -> HttpResponse {
+pub async fn get_model_RN(
+    path: web::Path<i64>,
+    config: web::Data<Config>,
+    strictness: web::Query<Strictness>,
+) -> HttpResponse {
+    let config1 = Config {
+        db_host: config.db_host.clone(),
+        db_port: config.db_port,
+        db_protocol: config.db_protocol.clone(),
+    };
+    let (mathml_ast, definitions) = module_id2mathml_model(path.into_inner(), config1).await;
+    amr_response(
+        "regnet",
+        mathml_ast,
+        &definitions,
+        ObservableOptions::default(),
+        &BTreeMap::new(),
+        *strictness,
+    )
+}
+
+/// This retrieves a stock and flow AMR based on model id.
+#[allow(non_snake_case)]
+#[utoipa::path(
+    params(Strictness),
+    responses(
+        (
+            status = 200, description = "Successfully retrieved SF AMR",
+            body = StockFlow
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
+        )
+    )
+)]
+#[get("/models/{id}/SF")]
+pub async fn get_model_SF(
+    path: web::Path<i64>,
+    config: web::Data<Config>,
+    strictness: web::Query<Strictness>,
+) -> HttpResponse {
     let config1 = Config {
         db_host: config.db_host.clone(),
         db_port: config.db_port,
         db_protocol: config.db_protocol.clone(),
     };
-    let mathml_ast = module_id2mathml_MET_ast(path.into_inner(), config1).await;
-    HttpResponse::Ok().json(web::Json(RegNet::from(mathml_ast)))
+    let (mathml_ast, definitions) = module_id2mathml_model(path.into_inner(), config1).await;
+    amr_response(
+        "stockflow",
+        mathml_ast,
+        &definitions,
+        ObservableOptions::default(),
+        &BTreeMap::new(),
+        *strictness,
+    )
 }
 
 /// This returns a PetriNet AMR from a gromet.
 #[allow(non_snake_case)]
 #[utoipa::path(
+    params(Strictness),
     request_body = ModuleCollection,
     responses(
         (
             status = 200, description = "Successfully retrieved PN AMR",
-            body = ModelPetriNet
+            body = PetriNet
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
+        ),
+        (
+            status = 500,
+            description = "The model cannot be pushed to or deleted from the database"
         )
     )
 )]
@@ -294,25 +357,46 @@ pub async fn get_model_RN(path: web::Path<i64>, config: web::Data<Config>) -> Ht
 pub async fn model2PN(
     payload: web::Json<ModuleCollection>,
     config: web::Data<Config>,
+    strictness: web::Query<Strictness>,
 ) -> HttpResponse {
     let config1 = Config {
         db_host: config.db_host.clone(),
         db_port: config.db_port,
         db_protocol: config.db_protocol.clone(),
     };
-    HttpResponse::Ok().json(web::Json(
-        model_to_PN(payload.into_inner(), config1).await.unwrap(),
-    ))
+    match extract_model(payload.into_inner(), config1).await {
+        Ok((mathml_ast, definitions)) => amr_response(
+            "petrinet",
+            mathml_ast,
+            &definitions,
+            ObservableOptions::default(),
+            &BTreeMap::new(),
+            *strictness,
+        ),
+        Err(err) => HttpResponse::InternalServerError()
+            .content_type("text/plain")
+            .body(err.to_string()),
+    }
 }
 
 /// This returns a RegNet AMR from a gromet.
 #[allow(non_snake_case)]
 #[utoipa::path(
+    params(Strictness),
     request_body = ModuleCollection,
     responses(
         (
             status = 200, description = "Successfully retrieved RN AMR",
-            body = ModelRegNet
+            body = RegNet
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
+        ),
+        (
+            status = 500,
+            description = "The model cannot be pushed to or deleted from the database"
         )
     )
 )]
@@ -320,15 +404,73 @@ pub async fn model2PN(
 pub async fn model2RN(
     payload: web::Json<ModuleCollection>,
     config: web::Data<Config>,
+    strictness: web::Query<Strictness>,
 ) -> HttpResponse {
     let config1 = Config {
         db_host: config.db_host.clone(),
         db_port: config.db_port,
         db_protocol: config.db_protocol.clone(),
     };
-    HttpResponse::Ok().json(web::Json(
-        model_to_RN(payload.into_inner(), config1).await.unwrap(),
-    ))
+    match extract_model(payload.into_inner(), config1).await {
+        Ok((mathml_ast, definitions)) => amr_response(
+            "regnet",
+            mathml_ast,
+            &definitions,
+            ObservableOptions::default(),
+            &BTreeMap::new(),
+            *strictness,
+        ),
+        Err(err) => HttpResponse::InternalServerError()
+            .content_type("text/plain")
+            .body(err.to_string()),
+    }
+}
+
+/// This returns a stock and flow AMR from a gromet.
+#[allow(non_snake_case)]
+#[utoipa::path(
+    params(Strictness),
+    request_body = ModuleCollection,
+    responses(
+        (
+            status = 200, description = "Successfully retrieved SF AMR",
+            body = StockFlow
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
+        ),
+        (
+            status = 500,
+            description = "The model cannot be pushed to or deleted from the database"
+        )
+    )
+)]
+#[put("/models/SF")]
+pub async fn model2SF(
+    payload: web::Json<ModuleCollection>,
+    config: web::Data<Config>,
+    strictness: web::Query<Strictness>,
+) -> HttpResponse {
+    let config1 = Config {
+        db_host: config.db_host.clone(),
+        db_port: config.db_port,
+        db_protocol: config.db_protocol.clone(),
+    };
+    match extract_model(payload.into_inner(), config1).await {
+        Ok((mathml_ast, definitions)) => amr_response(
+            "stockflow",
+            mathml_ast,
+            &definitions,
+            ObservableOptions::default(),
+            &BTreeMap::new(),
+            *strictness,
+        ),
+        Err(err) => HttpResponse::InternalServerError()
+            .content_type("text/plain")
+            .body(err.to_string()),
+    }
 }
 
 /// This returns a MET vector from a gromet.
@@ -0,0 +1,97 @@
+use crate::services::{
+    amr::{ObservableOptions, Strictness},
+    mathml::{
+        amr_response, decapodes_collection, flatten_odes, parse_equations_with, EquationError,
+    },
+};
+use actix_web::{put, web, HttpResponse};
+use mathml::{
+    acset::AMRlatex,
+    parsers::{first_order_ode::FirstOrderODE, math_expression_tree::MathExpressionTree},
+    validation::Violation,
+};
+
+use utoipa;
+
+/// Return a JSON representation of a METCollection from an array of LaTeX strings.
+#[utoipa::path(
+    request_body = Vec<String>,
+    responses(
+        (
+            status = 200,
+            body = Vec<MathExpressionTree>
+        ),
+        (
+            status = 400,
+            body = EquationError
+        )
+    )
+)]
+#[put("/latex/met")]
+pub async fn get_latex_met(payload: web::Json<Vec<String>>) -> HttpResponse {
+    match parse_equations_with(&payload, MathExpressionTree::from_latex) {
+        Ok(met_vec) => HttpResponse::Ok().json(web::Json(met_vec)),
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
+}
+
+/// Return a JSON representation of a DecapodeCollection from an array of LaTeX strings.
+#[utoipa::path(
+    request_body = Vec<String>,
+    responses(
+        (
+            status = 200,
+            body = DecapodesCollection
+        ),
+        (
+            status = 400,
+            body = EquationError
+        )
+    )
+)]
+#[put("/latex/decapodes")]
+pub async fn get_latex_decapodes(payload: web::Json<Vec<String>>) -> HttpResponse {
+    match parse_equations_with(&payload, MathExpressionTree::from_latex) {
+        Ok(met_vec) => HttpResponse::Ok().json(web::Json(decapodes_collection(&met_vec))),
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
+}
+
+/// Return a JSON representation of an AMR constructed from an array of LaTeX strings and a string
+/// for the AMR subtype.
+#[utoipa::path(
+    params(Strictness),
+    request_body = AMRlatex,
+    responses(
+        (
+            status = 200,
+            body = EitherBody<PetriNet, RegNet>,
+        ),
+        (
+            status = 400,
+            body = EquationError
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
+        )
+    )
+)]
+#[put("/latex/amr")]
+pub async fn get_latex_amr(
+    payload: web::Json<AMRlatex>,
+    strictness: web::Query<Strictness>,
+) -> HttpResponse {
+    match parse_equations_with(&payload.latex, FirstOrderODE::from_latex) {
+        Ok(odes) => amr_response(
+            &payload.model,
+            flatten_odes(odes),
+            &[],
+            ObservableOptions::default(),
+            &payload.units,
+            *strictness,
+        ),
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
+}
@@ -1,21 +1,202 @@
+use crate::services::amr::{model_response, ObservableOptions, Strictness};
 use actix_web::{post, put, web, HttpResponse};
 use mathml::parsers::decapodes_serialization::{
     to_wiring_diagram, DecapodesCollection, WiringDiagram,
 };
+use mathml::parsers::equation_system::split_system;
 use mathml::parsers::first_order_ode::flatten_mults;
-use mathml::parsers::generic_mathml::math;
+use mathml::parsers::generic_mathml::{math, ParseDiagnostic};
+use mathml::parsers::higher_order_ode::reduce_to_first_order;
 use mathml::parsers::math_expression_tree::MathExpressionTree;
-use mathml::parsers::math_expression_tree::{
-    preprocess_mathml_for_to_latex, replace_unicode_with_symbols,
-};
 use mathml::{
-    acset::{AMRmathml, GeneralizedAMR, PetriNet, RegNet},
+    acset::{AMRmathml, GeneralizedAMR, MathMLEquations, PetriNet, RegNet, StockFlow},
     expression::get_code_exp_graphs,
-    parsers::first_order_ode::{first_order_ode, FirstOrderODE},
+    parsers::first_order_ode::FirstOrderODE,
+    parsers::observables::{inline_definitions, partition, Definition, ModelEquation},
+    units::{check_units, Unit, UnitError},
+    validation::{AmrSchema, Violation},
 };
 use petgraph::dot::{Config, Dot};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
 
 use utoipa;
+use utoipa::ToSchema;
+
+/// Machine-readable diagnostic returned (with status 400) when an equation in a request cannot be
+/// parsed, or a model cannot be simulated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct EquationError {
+    /// Index of the offending equation in the request payload, if the error concerns one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equation: Option<usize>,
+    pub error: ParseDiagnostic,
+}
+
+/// Parse every equation in a batch with `parse`, stopping at the first one that fails.
+pub(crate) fn parse_equations_with<T, F>(
+    equations: &[String],
+    parse: F,
+) -> Result<Vec<T>, EquationError>
+where
+    F: Fn(&str) -> Result<T, ParseDiagnostic>,
+{
+    equations
+        .iter()
+        .enumerate()
+        .map(|(equation, x)| {
+            parse(x.as_str()).map_err(|error| EquationError {
+                equation: Some(equation),
+                error,
+            })
+        })
+        .collect()
+}
+
+/// Parse every equation in a batch of MathML strings, stopping at the first one that fails.
+fn parse_equations<T>(equations: &[String]) -> Result<Vec<T>, EquationError>
+where
+    T: FromStr<Err = ParseDiagnostic>,
+{
+    parse_equations_with(equations, |x| x.parse::<T>())
+}
+
+/// Replacement of an identifier with an expression.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Substitution {
+    /// Name of the identifier, e.g. N or S_{h}
+    pub var: String,
+    /// Presentation MathML of the expression replacing it
+    pub replacement: String,
+}
+
+/// Equations in which to substitute and rename identifiers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SubstitutionRequest {
+    /// Presentation MathML strings with one equation each
+    pub mathml: Vec<String>,
+    /// Substitutions, applied in order
+    #[serde(default)]
+    pub substitutions: Vec<Substitution>,
+    /// New names of identifiers, applied after the substitutions
+    #[serde(default)]
+    pub renames: HashMap<String, String>,
+}
+
+/// Flatten the multiplications in the right hand sides of a batch of ODEs.
+pub(crate) fn flatten_odes(mut odes: Vec<FirstOrderODE>) -> Vec<FirstOrderODE> {
+    for ode in odes.iter_mut() {
+        ode.rhs = flatten_mults(ode.rhs.clone());
+    }
+    odes
+}
+
+/// Reduce the ODEs among the equations of a model to a first order system and flatten their
+/// multiplications, and split off the definitions of observables.
+fn split_model(equations: Vec<ModelEquation>) -> (Vec<FirstOrderODE>, Vec<Definition>) {
+    let (odes, definitions) = partition(equations);
+    (flatten_odes(reduce_to_first_order(odes)), definitions)
+}
+
+/// Parse ODEs and definitions of observables, e.g. N = S + I + R, from a batch of MathML strings.
+/// The ODEs are reduced to a first order system and their multiplications flattened.
+fn parse_model(
+    equations: &[String],
+) -> Result<(Vec<FirstOrderODE>, Vec<Definition>), EquationError> {
+    parse_equations::<ModelEquation>(equations).map(split_model)
+}
+
+/// Parse ODEs and definitions of observables from a single MathML document containing a system of
+/// equations, as for [`parse_model`]. Diagnostics refer to the equation's position in the system.
+fn parse_system_model(
+    document: &str,
+) -> Result<(Vec<FirstOrderODE>, Vec<Definition>), EquationError> {
+    split_system(document)
+        .map_err(|error| EquationError {
+            equation: Some(0),
+            error,
+        })?
+        .iter()
+        .enumerate()
+        .map(|(equation, x)| {
+            x.parse().map_err(|error| EquationError {
+                equation: Some(equation),
+                error,
+            })
+        })
+        .collect::<Result<Vec<ModelEquation>, _>>()
+        .map(split_model)
+}
+
+/// Parse ODEs from a batch of MathML strings, reduce them to a first order system and flatten
+/// their multiplications. Definitions of observables among the equations are substituted into
+/// the ODEs.
+pub(crate) fn parse_odes(equations: &[String]) -> Result<Vec<FirstOrderODE>, EquationError> {
+    parse_model(equations).map(|(odes, definitions)| inline_definitions(&odes, &definitions))
+}
+
+/// Construct the AMR of the requested model type from a set of ODEs and definitions of
+/// observables, which are substituted into the ODEs of RegNets and stock and flow models, along
+/// with values of parameters and initial conditions, e.g. β = 0.3 and S(0) = 990. The units
+/// given for some of its identifiers are propagated to infer the units of the others, which are
+/// set on the AMR, and warnings about inconsistent or unknown units are added to its metadata.
+/// In strict mode, an AMR that does not conform to its schema is refused.
+pub(crate) fn amr_response(
+    model_type: &str,
+    odes: Vec<FirstOrderODE>,
+    definitions: &[Definition],
+    observables: ObservableOptions,
+    units: &BTreeMap<String, String>,
+    strictness: Strictness,
+) -> HttpResponse {
+    let units = units
+        .iter()
+        .map(|(name, unit)| Ok((name.clone(), unit.parse::<Unit>()?)))
+        .collect::<Result<BTreeMap<_, _>, UnitError>>();
+    let analysis = match units {
+        Ok(units) => check_units(&odes, &units),
+        Err(err) => {
+            return HttpResponse::BadRequest().json(EquationError {
+                equation: None,
+                error: ParseDiagnostic::new(err.to_string()).with_token(err.expression),
+            })
+        }
+    };
+    if model_type == "regnet" {
+        let mut regnet = RegNet::from(inline_definitions(&odes, definitions));
+        regnet.set_values(definitions);
+        regnet.set_units(&analysis);
+        model_response(&regnet, AmrSchema::RegNet, strictness)
+    } else if model_type == "petrinet" {
+        let mut petri_net =
+            PetriNet::with_observables(odes, definitions, observables.inline_observables);
+        petri_net.set_units(&analysis);
+        model_response(&petri_net, AmrSchema::PetriNet, strictness)
+    } else if model_type == "stockflow" {
+        let mut stock_flow = StockFlow::from(inline_definitions(&odes, definitions));
+        stock_flow.set_values(definitions);
+        stock_flow.set_units(&analysis);
+        model_response(&stock_flow, AmrSchema::StockFlow, strictness)
+    } else {
+        HttpResponse::BadRequest()
+            .content_type("text/plain")
+            .body("Please specify a valid model.")
+    }
+}
+
+/// Construct a DecapodesCollection from a set of math expression trees.
+pub(crate) fn decapodes_collection(met_vec: &[MathExpressionTree]) -> DecapodesCollection {
+    let mut deca_vec = Vec::<WiringDiagram>::new();
+    for term in met_vec.iter() {
+        deca_vec.push(to_wiring_diagram(term));
+    }
+    DecapodesCollection {
+        decapodes: deca_vec,
+    }
+}
 
 /// Parse MathML and return a DOT representation of the abstract syntax tree (AST)
 #[utoipa::path(
@@ -50,16 +231,23 @@ pub async fn get_ast_graph(payload: String) -> String {
         (
             status = 200,
             body = String
+        ),
+        (
+            status = 400,
+            body = EquationError
         )
     )
 )]
 #[put("/mathml/math-exp-graph")]
-pub async fn get_math_exp_graph(payload: String) -> String {
-    let contents = payload;
-    let exp = contents.parse::<MathExpressionTree>().unwrap();
-    let g = exp.to_graph();
-    let dot_representation = Dot::new(&g);
-    dot_representation.to_string()
+pub async fn get_math_exp_graph(payload: String) -> HttpResponse {
+    match parse_equations::<MathExpressionTree>(&[payload]) {
+        Ok(exps) => {
+            let g = exps[0].to_graph();
+            let dot_representation = Dot::new(&g);
+            HttpResponse::Ok().body(dot_representation.to_string())
+        }
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
 }
 
 /// Parse a MathML representation of the code implementation and return a DOT representation of the math
@@ -85,19 +273,24 @@ pub async fn get_code_exp_graph_set(payload: web::Json<Vec<MathExpressionTree>>)
 /// return the corresponding LaTeX representation
 #[utoipa::path(
     request_body = String,
-        responses(
+    responses(
         (
             status = 200,
             body = String
+        ),
+        (
+            status = 400,
+            body = EquationError
         )
     )
 )]
 #[post("/mathml/latex")]
-pub async fn get_latex(payload: String) -> String {
-    let modified_input1 = &replace_unicode_with_symbols(&payload).to_string();
-    let modified_input2 = &preprocess_mathml_for_to_latex(modified_input1).to_string();
-    let exp = modified_input2.parse::<MathExpressionTree>().unwrap();
-    exp.to_latex()
+pub async fn get_latex(payload: String) -> HttpResponse {
+    // the parser normalizes the payload itself, so diagnostics refer to what the user sent
+    match parse_equations::<MathExpressionTree>(&[payload]) {
+        Ok(exps) => HttpResponse::Ok().body(exps[0].to_latex()),
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
 }
 
 /// Parse presentation MathML and return a content MathML representation. Currently limited to
@@ -108,13 +301,19 @@ pub async fn get_latex(payload: String) -> String {
         (
             status = 200,
             body = String
+        ),
+        (
+            status = 400,
+            body = EquationError
         )
     )
 )]
 #[put("/mathml/content-mathml")]
-pub async fn get_content_mathml(payload: String) -> String {
-    let ode = payload.parse::<FirstOrderODE>().unwrap();
-    ode.to_cmml()
+pub async fn get_content_mathml(payload: String) -> HttpResponse {
+    match parse_equations::<FirstOrderODE>(&[payload]) {
+        Ok(odes) => HttpResponse::Ok().body(odes[0].to_cmml()),
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
 }
 
 /// Return a JSON representation of a DecapodeCollection, which should be the foundation of a DecapodeCollection AMR, from
@@ -125,23 +324,19 @@ pub async fn get_content_mathml(payload: String) -> String {
         (
             status = 200,
             body = DecapodeCollection
+        ),
+        (
+            status = 400,
+            body = EquationError
         )
     )
 )]
 #[put("/mathml/decapodes")]
 pub async fn get_decapodes(payload: web::Json<Vec<String>>) -> HttpResponse {
-    let met_vec: Vec<MathExpressionTree> = payload
-        .iter()
-        .map(|x| x.parse::<MathExpressionTree>().unwrap())
-        .collect();
-    let mut deca_vec = Vec::<WiringDiagram>::new();
-    for term in met_vec.iter() {
-        deca_vec.push(to_wiring_diagram(term));
+    match parse_equations::<MathExpressionTree>(&payload) {
+        Ok(met_vec) => HttpResponse::Ok().json(web::Json(decapodes_collection(&met_vec))),
+        Err(err) => HttpResponse::BadRequest().json(err),
     }
-    let decapodes_collection = DecapodesCollection {
-        decapodes: deca_vec.clone(),
-    };
-    HttpResponse::Ok().json(web::Json(decapodes_collection))
 }
 
 /// Return a JSON representation of a METCollection from
@@ -152,144 +347,232 @@ responses(
 (
 status = 200,
 body = Vec<MathExpressionTree>
+),
+(
+status = 400,
+body = EquationError
 )
 )
 )]
 #[put("/mathml/met")]
 pub async fn get_met(payload: web::Json<Vec<String>>) -> HttpResponse {
-    let met_vec: Vec<MathExpressionTree> = payload
+    match parse_equations::<MathExpressionTree>(&payload) {
+        Ok(met_vec) => HttpResponse::Ok().json(web::Json(met_vec)),
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
+}
+
+/// Substitute expressions for identifiers and rename identifiers in an array of MathML strings,
+/// returning the resulting math expression trees.
+#[utoipa::path(
+    request_body = SubstitutionRequest,
+    responses(
+        (
+            status = 200,
+            body = Vec<MathExpressionTree>
+        ),
+        (
+            status = 400,
+            body = EquationError
+        )
+    )
+)]
+#[put("/mathml/substitute")]
+pub async fn substitute(payload: web::Json<SubstitutionRequest>) -> HttpResponse {
+    let mut met_vec = match parse_equations::<MathExpressionTree>(&payload.mathml) {
+        Ok(met_vec) => met_vec,
+        Err(err) => return HttpResponse::BadRequest().json(err),
+    };
+    for substitution in payload.substitutions.iter() {
+        let replacement = match substitution.replacement.parse::<MathExpressionTree>() {
+            Ok(replacement) => replacement,
+            Err(err) => {
+                return HttpResponse::BadRequest().json(EquationError {
+                    equation: None,
+                    error: ParseDiagnostic {
+                        message: format!(
+                            "Invalid replacement for {}: {}",
+                            substitution.var, err.message
+                        ),
+                        ..err
+                    },
+                })
+            }
+        };
+        met_vec = met_vec
+            .iter()
+            .map(|met| met.substitute(&substitution.var, &replacement))
+            .collect();
+    }
+    let met_vec: Vec<MathExpressionTree> = met_vec
         .iter()
-        .map(|x| x.parse::<MathExpressionTree>().unwrap())
+        .map(|met| met.rename(&payload.renames))
         .collect();
     HttpResponse::Ok().json(web::Json(met_vec))
 }
 
 /// Return a JSON of a Generalized AMR from
-/// an array of MET strings.
+/// an array of MET strings. Algebraic equations, e.g. N = S + I + R, define its observables.
 #[utoipa::path(
+    params(ObservableOptions),
     request_body = Vec<String>,
     responses(
     (
     status = 200,
     body = GeneralizedAMR
+    ),
+    (
+    status = 400,
+    body = EquationError
     )
     )
     )]
 #[put("/mathml/g-amr")]
-pub async fn get_gamr(payload: web::Json<Vec<String>>) -> HttpResponse {
-    let met_vec: Vec<MathExpressionTree> = payload
-        .iter()
-        .map(|x| x.parse::<MathExpressionTree>().unwrap())
-        .collect();
-    HttpResponse::Ok().json(web::Json(GeneralizedAMR::from(met_vec)))
+pub async fn get_gamr(
+    payload: web::Json<Vec<String>>,
+    observables: web::Query<ObservableOptions>,
+) -> HttpResponse {
+    match parse_equations::<MathExpressionTree>(&payload) {
+        Ok(met_vec) => {
+            let gamr = GeneralizedAMR::from(met_vec);
+            if observables.inline_observables {
+                HttpResponse::Ok().json(web::Json(gamr.inline_observables()))
+            } else {
+                HttpResponse::Ok().json(web::Json(gamr))
+            }
+        }
+        Err(err) => HttpResponse::BadRequest().json(err),
+    }
 }
 
 /// Return a JSON representation of a PetriNet ModelRep constructed from an array of MathML strings.
+/// Algebraic equations, e.g. N = S + I + R, define its observables, and equations such as
+/// β = 0.3 and S(0) = 990 the values of its parameters and initial conditions.
 #[utoipa::path(
+    params(Strictness, ObservableOptions),
     request_body = Vec<String>,
     responses(
         (
             status = 200,
             body = PetriNet
+        ),
+        (
+            status = 400,
+            body = EquationError
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
         )
     )
 )]
 #[put("/mathml/petrinet")]
-pub async fn get_acset(payload: web::Json<Vec<String>>) -> HttpResponse {
-    let asts_result: Result<Vec<_>, _> = payload
-        .iter()
-        .map(|x| first_order_ode(x.as_str().into()))
-        .collect();
-
-    match asts_result {
-        Ok(asts) => {
-            let mut flattened_asts = Vec::<FirstOrderODE>::new();
-            for (_, mut eq) in asts {
-                eq.rhs = flatten_mults(eq.rhs.clone());
-                flattened_asts.push(eq.clone());
-            }
-            HttpResponse::Ok().json(web::Json(PetriNet::from(flattened_asts)))
-        }
-        Err(err) => HttpResponse::BadRequest()
-            .content_type("text/plain")
-            .body(err.to_string()),
+pub async fn get_acset(
+    payload: web::Json<Vec<String>>,
+    strictness: web::Query<Strictness>,
+    observables: web::Query<ObservableOptions>,
+) -> HttpResponse {
+    match parse_model(&payload) {
+        Ok((flattened_asts, definitions)) => amr_response(
+            "petrinet",
+            flattened_asts,
+            &definitions,
+            *observables,
+            &BTreeMap::new(),
+            *strictness,
+        ),
+        Err(err) => HttpResponse::BadRequest().json(err),
     }
 }
 
 /// Return a JSON representation of a RegNet ModelRep constructed from an array of MathML strings.
+/// Algebraic equations, e.g. N = S + I + R, are substituted into the ODEs, and equations such as
+/// β = 0.3 and S(0) = 990 give the values of its parameters and initial conditions.
 #[utoipa::path(
+    params(Strictness),
     request_body = Vec<String>,
     responses(
         (
             status = 200,
             body = RegNet
+        ),
+        (
+            status = 400,
+            body = EquationError
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
         )
     )
 )]
 #[put("/mathml/regnet")]
-pub async fn get_regnet(payload: web::Json<Vec<String>>) -> HttpResponse {
-    let asts_result: Result<Vec<_>, _> = payload
-        .iter()
-        .map(|x| first_order_ode(x.as_str().into()))
-        .collect();
-
-    match asts_result {
-        Ok(asts) => {
-            let mut flattened_asts = Vec::<FirstOrderODE>::new();
-            for (_, mut eq) in asts {
-                eq.rhs = flatten_mults(eq.rhs.clone());
-                flattened_asts.push(eq.clone());
-            }
-            HttpResponse::Ok().json(web::Json(RegNet::from(flattened_asts)))
-        }
-        Err(err) => HttpResponse::BadRequest()
-            .content_type("text/plain")
-            .body(err.to_string()),
+pub async fn get_regnet(
+    payload: web::Json<Vec<String>>,
+    strictness: web::Query<Strictness>,
+) -> HttpResponse {
+    match parse_model(&payload) {
+        Ok((flattened_asts, definitions)) => amr_response(
+            "regnet",
+            flattened_asts,
+            &definitions,
+            ObservableOptions::default(),
+            &BTreeMap::new(),
+            *strictness,
+        ),
+        Err(err) => HttpResponse::BadRequest().json(err),
     }
 }
 
-/// Return a JSON representation of an AMR constructed from an array of MathML strings and a string
-/// for the AMR subtype.
+/// Return a JSON representation of an AMR constructed from MathML and a string for the AMR
+/// subtype, `petrinet`, `regnet` or `stockflow`. The MathML is either an array of strings with one
+/// equation each, or a single string containing the whole system (e.g. one equation per row of an
+/// `<mtable>`). Algebraic equations, e.g. N = S + I + R, define the observables of a Petri net,
+/// and are substituted into the ODEs of the other models.
+///
+/// Optional units of states, parameters and the independent variable are propagated through the
+/// equations: the inferred units are set on the AMR and inconsistencies are reported as warnings
+/// in its metadata.
 #[utoipa::path(
+    params(Strictness, ObservableOptions),
     request_body = AMRmathml,
     responses(
         (
             status = 200,
             body = EitherBody<PetriNet, RegNet>,
+        ),
+        (
+            status = 400,
+            body = EquationError
+        ),
+        (
+            status = 422,
+            body = Vec<Violation>,
+            description = "In strict mode, the model does not conform to its schema"
         )
     )
 )]
 #[put("/mathml/amr")]
-pub async fn get_amr(payload: web::Json<AMRmathml>) -> HttpResponse {
-    let mt_asts: Result<Vec<_>, _> = payload
-        .mathml
-        .iter()
-        .map(|x| first_order_ode(x.as_str().into()))
-        .collect();
-
-    match mt_asts {
-        Ok(mt_asts) => {
-            let mut flattened_asts = Vec::<FirstOrderODE>::new();
-
-            for (_, mut eq) in mt_asts {
-                println!("pre-flattened RHS: {:?}", eq.rhs.to_string().clone());
-                eq.rhs = flatten_mults(eq.rhs.clone());
-                println!("once flattened RHS: {:?}", eq.rhs.to_string().clone());
-                flattened_asts.push(eq.clone());
-            }
-            let model_type = payload.model.clone();
-            if model_type == *"regnet" {
-                HttpResponse::Ok().json(web::Json(RegNet::from(flattened_asts)))
-            } else if model_type == *"petrinet" {
-                HttpResponse::Ok().json(web::Json(PetriNet::from(flattened_asts)))
-            } else {
-                HttpResponse::BadRequest()
-                    .content_type("text/plain")
-                    .body("Please specify a valid model.")
-            }
-        }
-        Err(err) => HttpResponse::BadRequest()
-            .content_type("text/plain")
-            .body(err.to_string()),
+pub async fn get_amr(
+    payload: web::Json<AMRmathml>,
+    strictness: web::Query<Strictness>,
+    observables: web::Query<ObservableOptions>,
+) -> HttpResponse {
+    let model = match &payload.mathml {
+        MathMLEquations::System(document) => parse_system_model(document),
+        MathMLEquations::Equations(equations) => parse_model(equations),
+    };
+    match model {
+        Ok((flattened_asts, definitions)) => amr_response(
+            &payload.model,
+            flattened_asts,
+            &definitions,
+            *observables,
+            &payload.units,
+            *strictness,
+        ),
+        Err(err) => HttpResponse::BadRequest().json(err),
     }
 }
@@ -0,0 +1,92 @@
+use crate::services::mathml::{parse_odes, EquationError};
+use actix_web::{put, web, HttpResponse};
+use mathml::{
+    acset::PetriNet,
+    parsers::generic_mathml::ParseDiagnostic,
+    simulation::{OdeSystem, SimulationConfig, SimulationError},
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use utoipa;
+use utoipa::ToSchema;
+
+/// Model to simulate, given either as a Petri net AMR or as an array of MathML strings with one
+/// ODE each.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum SimulationModel {
+    PetriNet(PetriNet),
+    Equations(Vec<String>),
+}
+
+/// The ODE system of a model to simulate or generate code for.
+pub(crate) fn ode_system(model: &SimulationModel) -> Result<OdeSystem, EquationError> {
+    match model {
+        SimulationModel::PetriNet(petri_net) => Ok(OdeSystem::try_from(petri_net)?),
+        SimulationModel::Equations(equations) => {
+            parse_odes(equations).map(|odes| OdeSystem::from(odes.as_slice()))
+        }
+    }
+}
+
+/// A model that cannot be simulated is reported like an equation that cannot be parsed, without
+/// the index of an equation.
+impl From<SimulationError> for EquationError {
+    fn from(err: SimulationError) -> Self {
+        EquationError {
+            equation: None,
+            error: ParseDiagnostic::new(err.to_string()),
+        }
+    }
+}
+
+/// Format of the trajectory returned by a simulation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TrajectoryFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SimulationRequest {
+    pub model: SimulationModel,
+    pub config: SimulationConfig,
+    #[serde(default)]
+    pub format: TrajectoryFormat,
+}
+
+/// Simulate a Petri net AMR or a system of MathML ODEs with RK4 or RK45, returning its trajectory
+/// as JSON or CSV.
+#[utoipa::path(
+    request_body = SimulationRequest,
+    responses(
+        (
+            status = 200,
+            body = Trajectory
+        ),
+        (
+            status = 400,
+            description = "The model cannot be parsed or simulated",
+            body = EquationError
+        )
+    )
+)]
+#[put("/simulate")]
+pub async fn simulate(payload: web::Json<SimulationRequest>) -> HttpResponse {
+    let system = match ode_system(&payload.model) {
+        Ok(system) => system,
+        Err(err) => return HttpResponse::BadRequest().json(err),
+    };
+    match system.simulate(&payload.config) {
+        Ok(trajectory) => match payload.format {
+            TrajectoryFormat::Json => HttpResponse::Ok().json(web::Json(trajectory)),
+            TrajectoryFormat::Csv => HttpResponse::Ok()
+                .content_type("text/csv")
+                .body(trajectory.to_csv(&system.time)),
+        },
+        Err(err) => HttpResponse::BadRequest().json(EquationError::from(err)),
+    }
+}
@@ -14,3 +14,32 @@ async fn test_get_amr() {
     let response = test::call_service(&app, request).await;
     assert!(response.status().is_success());
 }
+
+#[actix_web::test]
+async fn test_get_amr_from_system() {
+    let app = test::init_service(App::new().service(get_amr)).await;
+    let payload = fs::read_to_string("tests/data/get_amr_system_payload.json").unwrap();
+    let request = test::TestRequest::put()
+        .uri("/mathml/amr")
+        .insert_header(ContentType::json())
+        .set_payload(payload)
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert!(response.status().is_success());
+}
+
+#[actix_web::test]
+async fn test_get_amr_strict_with_sink() {
+    let app = test::init_service(App::new().service(get_amr)).await;
+    // Lotka-Volterra, whose predators die out without prey
+    let mut payload: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string("tests/data/get_amr_payload.json").unwrap())
+            .unwrap();
+    payload["model"] = "petrinet".into();
+    let request = test::TestRequest::put()
+        .uri("/mathml/amr?strict=true")
+        .set_json(payload)
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert!(response.status().is_success());
+}